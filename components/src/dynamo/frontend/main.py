@@ -130,6 +130,12 @@ def parse_args():
         default=1.0,
         help="KV Router: Weight for overlap score in worker selection. Higher values prioritize KV cache reuse.",
     )
+    parser.add_argument(
+        "--kv-decode-load-weight",
+        type=float,
+        default=1.0,
+        help="KV Router: Weight for outstanding decode load in worker selection. Higher values prioritize balancing decode load.",
+    )
     parser.add_argument(
         "--router-temperature",
         type=float,
@@ -271,6 +277,7 @@ async def async_main():
             router_snapshot_threshold=flags.router_snapshot_threshold,
             router_reset_states=flags.router_reset_states,
             router_track_active_blocks=flags.router_track_active_blocks,
+            decode_load_weight=flags.kv_decode_load_weight,
         )
     elif flags.router_mode == "random":
         router_mode = RouterMode.Random
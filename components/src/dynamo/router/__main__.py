@@ -172,6 +172,13 @@ def parse_args():
         help="KV Router: Weight for overlap score in worker selection. Higher values prioritize KV cache reuse (default: 1.0)",
     )
 
+    parser.add_argument(
+        "--kv-decode-load-weight",
+        type=float,
+        default=1.0,
+        help="KV Router: Weight for outstanding decode load in worker selection. Higher values prioritize balancing decode load (default: 1.0)",
+    )
+
     parser.add_argument(
         "--router-temperature",
         type=float,
@@ -239,6 +246,7 @@ async def worker(runtime: DistributedRuntime):
     logger.debug(
         f"Configuration: endpoint={args.endpoint}, block_size={args.block_size}, "
         f"overlap_score_weight={args.kv_overlap_score_weight}, "
+        f"decode_load_weight={args.kv_decode_load_weight}, "
         f"router_temperature={args.router_temperature}, "
         f"use_kv_events={args.use_kv_events}, "
         f"router_replica_sync={args.router_replica_sync}, "
@@ -255,6 +263,7 @@ async def worker(runtime: DistributedRuntime):
         router_snapshot_threshold=args.router_snapshot_threshold,
         router_reset_states=args.router_reset_states,
         router_track_active_blocks=args.router_track_active_blocks,
+        decode_load_weight=args.kv_decode_load_weight,
     )
 
     # Create service component - use "router" as component name
@@ -434,14 +434,18 @@ impl KvIndexer {
             // Pass None for snapshot_tx and get_workers_tx to skip snapshot handling in Python bindings
             llm_rs::kv_router::subscriber::start_kv_router_background(
                 component.inner.clone(),
-                consumer_uuid.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
                 inner.event_sender(),
                 inner.remove_worker_sender(),
                 None,
                 None,
                 cancellation_token,
-                None,
-                true,
+                llm_rs::kv_router::subscriber::KvRouterBackgroundConfig::builder()
+                    .consumer_uuid(
+                        consumer_uuid.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+                    )
+                    .reset_states(true)
+                    .build()
+                    .map_err(to_pyerr)?,
             )
             .await
             .map_err(to_pyerr)?;
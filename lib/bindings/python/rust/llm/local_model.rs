@@ -44,6 +44,34 @@ impl ModelRuntimeConfig {
         self.inner.reasoning_parser = reasoning_parser;
     }
 
+    #[setter]
+    fn set_max_context_length(&mut self, max_context_length: u64) {
+        self.inner.max_context_length = Some(max_context_length);
+    }
+
+    #[setter]
+    fn set_max_requests_per_second(&mut self, max_requests_per_second: u32) {
+        self.inner.max_requests_per_second = Some(max_requests_per_second);
+    }
+
+    #[setter]
+    fn set_prefill_tokens_per_second(&mut self, prefill_tokens_per_second: u64) {
+        self.inner.prefill_tokens_per_second = Some(prefill_tokens_per_second);
+    }
+
+    #[setter]
+    fn set_spec_decode_acceptance_rate(&mut self, spec_decode_acceptance_rate: f64) {
+        self.inner.spec_decode_acceptance_rate = Some(spec_decode_acceptance_rate);
+    }
+
+    #[setter]
+    fn set_disaggregation_role(&mut self, disaggregation_role: &str) -> PyResult<()> {
+        self.inner.disaggregation_role =
+            serde_json::from_value(serde_json::Value::String(disaggregation_role.to_string()))
+                .map_err(to_pyerr)?;
+        Ok(())
+    }
+
     #[setter]
     fn set_data_parallel_size(&mut self, data_parallel_size: u32) {
         self.inner.data_parallel_size = data_parallel_size;
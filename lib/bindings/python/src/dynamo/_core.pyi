@@ -10,6 +10,7 @@ from typing import (
     List,
     Optional,
     Tuple,
+    Union,
 )
 
 from ._prometheus_names import prometheus_names
@@ -1303,7 +1304,7 @@ class KvPushRouter:
     async def get_potential_loads(
         self,
         token_ids: List[int],
-    ) -> List[Dict[str, int]]:
+    ) -> List[Dict[str, Union[int, float]]]:
         """
         Get potential prefill and decode loads for all workers.
 
@@ -1316,6 +1317,8 @@ class KvPushRouter:
                 - dp_rank: The data parallel rank
                 - potential_prefill_tokens: Number of tokens that would need prefill
                 - potential_decode_blocks: Number of blocks currently in decode phase
+                - eviction_risk: Estimated risk in [0, 1] that the request evicts blocks
+                  of the active requests of the worker
 
         Note:
             Each (worker_id, dp_rank) pair is returned as a separate entry.
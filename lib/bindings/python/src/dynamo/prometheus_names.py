@@ -35,6 +35,13 @@ class distributed_runtime:
     UPTIME_SECONDS = "uptime_seconds"
 
 
+class egress_router:
+    """Egress router Prometheus metric names"""
+
+    # Total number of response frames received after the final frame of a stream
+    POST_COMPLETION_FRAMES_TOTAL = "post_completion_frames_total"
+
+
 class frontend_service:
     """Frontend service metrics (LLM HTTP service)"""
 
@@ -88,6 +95,32 @@ class kvbm_connector:
 class kvrouter:
     # Number of KV cache events applied to the index (including status)
     KV_CACHE_EVENTS_APPLIED = "kv_cache_events_applied"
+    # Number of active requests tracked by the router's scheduler
+    TRACKED_REQUESTS = "tracked_requests"
+    # Number of tracked requests evicted because the tracked requests cap was exceeded
+    TRACKED_REQUESTS_EVICTED = "tracked_requests_evicted"
+    # Relative spread of the worker logits of the last routing decision
+    LOGIT_SPREAD = "logit_spread"
+    # Number of decision windows in which the worker logits barely differed
+    DEGENERATE_ROUTING_WINDOWS = "degenerate_routing_windows"
+    # Number of blocks in the radix tree of the indexer, summed over workers
+    INDEXER_TREE_BLOCKS = "indexer_tree_blocks"
+    # Number of blocks evicted to keep the radix tree under its size bound
+    INDEXER_TREE_BLOCKS_EVICTED = "indexer_tree_blocks_evicted"
+    # Number of KV events waiting to be applied by the indexer
+    INDEXER_EVENT_QUEUE_DEPTH = "indexer_event_queue_depth"
+    # Number of KV events dropped because the indexer could not keep up
+    INDEXER_EVENTS_DROPPED = "indexer_events_dropped"
+    # Number of attempts to acquire a router lock, by lock and result
+    LOCK_ACQUISITION_ATTEMPTS = "lock_acquisition_attempts"
+    # Total time a router lock was held by this router
+    LOCK_HOLD_SECONDS = "lock_hold_seconds"
+    # Duration of the last hold of a router lock by this router
+    LOCK_LAST_HOLD_SECONDS = "lock_last_hold_seconds"
+    # Whether routing is degraded to load only because the scheduler queue is deep (0 or 1)
+    ROUTING_DEGRADED = "routing_degraded"
+    # Number of requests routed without overlap data because routing was degraded
+    DEGRADED_ROUTING_REQUESTS = "degraded_routing_requests"
 
 
 class kvstats:
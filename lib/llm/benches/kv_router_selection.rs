@@ -0,0 +1,77 @@
+// SPDX-FileCopyrightText: Copyright (c) 2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use dynamo_llm::kv_router::indexer::OverlapScores;
+use dynamo_llm::kv_router::protocols::{WorkerId, WorkerWithDpRank};
+use dynamo_llm::kv_router::scheduler::{DefaultWorkerSelector, SchedulingRequest};
+use dynamo_llm::kv_router::{KvRouterConfig, WorkerSelector};
+use dynamo_llm::local_model::runtime_config::ModelRuntimeConfig;
+
+const BLOCK_SIZE: u32 = 16;
+
+/// Input Sequence Length of the routed request
+const ISL_TOKENS: usize = 4096;
+
+/// Workers caching a prefix of the request, with decreasing overlaps
+const OVERLAPPING_WORKERS: usize = 32;
+
+/// A request against `num_workers` loaded workers, a few of which cache part of its prefix
+fn request(num_workers: usize) -> SchedulingRequest {
+    let request_blocks = ISL_TOKENS / BLOCK_SIZE as usize;
+    let mut overlaps = OverlapScores::new();
+    for worker_id in 0..OVERLAPPING_WORKERS {
+        let worker = WorkerWithDpRank::from_worker_id((worker_id * 7 % num_workers) as WorkerId);
+        overlaps
+            .scores
+            .insert(worker, (request_blocks - worker_id * 4) as u32);
+    }
+
+    let mut request = SchedulingRequest::new(ISL_TOKENS, overlaps);
+    for worker_id in 0..num_workers {
+        let worker = WorkerWithDpRank::from_worker_id(worker_id as WorkerId);
+        let overlap = request.overlaps.scores.get(&worker).copied().unwrap_or(0) as usize;
+        request
+            .decode_blocks
+            .insert(worker, 1000 + worker_id % 200 + request_blocks - overlap);
+        request
+            .prefill_tokens
+            .insert(worker, ISL_TOKENS - overlap * BLOCK_SIZE as usize);
+    }
+    request
+}
+
+/// `cargo bench --bench kv_router_selection` to run it
+pub fn select_worker(c: &mut Criterion) {
+    let mut group = c.benchmark_group("select-worker-group");
+    for num_workers in [128, 1024, 4096] {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = (0..num_workers)
+            .map(|worker_id| (worker_id as WorkerId, None))
+            .collect();
+        let request = request(num_workers);
+
+        for (name, router_max_candidates) in
+            [("all-workers", None), ("max-candidates-64", Some(64))]
+        {
+            let selector = DefaultWorkerSelector::new(Some(KvRouterConfig {
+                router_max_candidates,
+                ..Default::default()
+            }));
+            group.bench_with_input(BenchmarkId::new(name, num_workers), &num_workers, |b, _| {
+                b.iter(|| {
+                    selector
+                        .select_worker(black_box(&workers), black_box(&request), BLOCK_SIZE)
+                        .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, select_worker);
+criterion_main!(benches);
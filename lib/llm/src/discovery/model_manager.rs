@@ -9,9 +9,8 @@ use std::{
 use parking_lot::{Mutex, RwLock};
 
 use dynamo_runtime::component::Component;
-use dynamo_runtime::prelude::DistributedRuntimeProvider;
 
-use crate::{discovery::KV_ROUTERS_ROOT_PATH, model_card::ModelDeploymentCard};
+use crate::model_card::ModelDeploymentCard;
 use crate::{
     kv_router::KvRouter,
     types::generic::tensor::TensorStreamingEngine,
@@ -260,39 +259,21 @@ impl ModelManager {
         kv_router_config: Option<KvRouterConfig>,
     ) -> anyhow::Result<Arc<KvRouter>> {
         if let Some(kv_chooser) = self.get_kv_chooser(model_name) {
-            // Check if the existing router has a different block size
+            // The router tracks the load of all the workers of a model with a single block size,
+            // so a worker with another block size would be silently mis-accounted
             if kv_chooser.block_size() != kv_cache_block_size {
-                tracing::warn!(
-                    model_name = %model_name,
-                    existing_block_size = %kv_chooser.block_size(),
-                    requested_block_size = %kv_cache_block_size,
-                    "KV Router block size mismatch! Model is requesting a different kv_cache_block_size than the existing router. \
-                     This will cause routing to fail silently. Consider using the same block size or restarting the router."
+                anyhow::bail!(
+                    "KV Router block size mismatch for model {model_name}: the worker uses a \
+                     kv_cache_block_size of {kv_cache_block_size} but the router was created with \
+                     {}. All the workers of a model must use the same block size.",
+                    kv_chooser.block_size()
                 );
             }
             return Ok(kv_chooser);
         }
 
-        // Create new KV router with etcd registration
-        let etcd_client = component
-            .drt()
-            .etcd_client()
-            .ok_or_else(|| anyhow::anyhow!("KV routing requires etcd (dynamic mode)"))?;
+        // Create new KV router, which registers its UUID in etcd
         let router_uuid = uuid::Uuid::new_v4();
-        let router_key = format!(
-            "{}/{}/{}",
-            KV_ROUTERS_ROOT_PATH,
-            component.path(),
-            router_uuid
-        );
-        etcd_client
-            .kv_create(
-                &router_key,
-                serde_json::to_vec_pretty(&kv_router_config.unwrap_or_default())?,
-                None, // use primary lease
-            )
-            .await?;
-
         let selector = Box::new(DefaultWorkerSelector::new(kv_router_config));
         let chooser = KvRouter::new(
             component.clone(),
@@ -29,7 +29,7 @@
 //!
 
 use axum::response::sse::Event;
-use dynamo_runtime::engine::AsyncEngineContext;
+use dynamo_runtime::engine::{AsyncEngineContext, CancellationReason};
 use futures::{Stream, StreamExt};
 use std::sync::Arc;
 
@@ -136,7 +136,7 @@ async fn connection_monitor(
             if let Some(metrics) = &metrics {
                 metrics.inc_client_disconnect();
             }
-            engine_context.kill();
+            engine_context.kill_with_reason(CancellationReason::ClientDisconnected);
         }
         Ok(ConnectionStatus::ClosedGracefully) => {
             tracing::trace!("Connection closed gracefully");
@@ -150,7 +150,7 @@ async fn connection_monitor(
             if let Some(metrics) = &metrics {
                 metrics.inc_client_disconnect();
             }
-            engine_context.kill();
+            engine_context.kill_with_reason(CancellationReason::ClientDisconnected);
         }
         Ok(ConnectionStatus::ClosedGracefully) => {
             tracing::trace!("Stream closed gracefully");
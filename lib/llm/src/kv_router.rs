@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -20,35 +20,64 @@ use dynamo_runtime::{
 use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 
+pub mod admission;
 pub mod approx;
+pub mod config_document;
 pub mod indexer;
+pub mod journal;
 pub mod metrics_aggregator;
+pub mod policy;
 pub mod protocols;
 pub mod publisher;
 pub mod recorder;
+pub mod replay;
 pub mod scheduler;
 pub mod scoring;
 pub mod sequence;
+pub mod shutdown;
+pub mod snapshot;
 pub mod subscriber;
 
 use crate::{
     kv_router::{
+        admission::AdmissionPolicy,
         approx::ApproxKvIndexer,
         indexer::{
-            KvIndexer, KvIndexerInterface, KvRouterError, OverlapScores, RouterEvent,
-            compute_block_hash_for_seq, compute_seq_hash_for_block,
+            DEFAULT_EVENT_CHANNEL_CAPACITY, EventBackpressurePolicy, KvIndexer, KvIndexerInterface,
+            KvRouterError, OverlapScores, RouterEvent, compute_block_hash_for_seq_with,
+            compute_seq_hash_for_block_with, start_event_shedding,
         },
+        journal::ReservationJournal,
+        policy::{AppliedPolicy, RoutingPolicy, RoutingPolicyBroadcast},
         protocols::{
             LocalBlockHash, RouterRequest, RouterResponse, WorkerSelectionResult, WorkerWithDpRank,
         },
-        scheduler::{KvScheduler, KvSchedulerError, PotentialLoad, SchedulingRequest},
+        recorder::start_event_tee,
+        scheduler::{
+            ClusterUtilization, DecisionLatencyMetrics, HitRateBucket, KvScheduler,
+            KvSchedulerConfig, KvSchedulerError, LoadShedding, PotentialLoad, ProvisionalSchedule,
+            PublishBreaker, SchedulerState, SchedulingPhase, SchedulingRequest,
+        },
         scoring::ProcessedEndpoints,
-        subscriber::start_kv_router_background,
+        sequence::{ActiveStateSnapshot, ImportReport},
+        shutdown::ShutdownSequence,
+        snapshot::SnapshotFormat,
+        subscriber::{
+            ConsumerReport, EffectiveSnapshotThreshold, KvRouterBackgroundConfig, RouterIdentity,
+            WorkerEventCounters, WorkerEventStats, claim_router_uuid, consumer_report,
+            parse_extra_event_subjects, parse_worker_filter, release_router_uuid,
+            start_kv_router_background,
+        },
     },
     local_model::runtime_config::ModelRuntimeConfig,
     model_card::{self, ModelDeploymentCard},
     preprocessor::PreprocessedRequest,
     protocols::common::llm_backend::LLMEngineOutput,
+    recorder::RecorderRotation,
+    tokens::{
+        SequenceHash,
+        hasher::{SequenceHasher, Xxh3SequenceHasher},
+    },
 };
 
 // [gluo TODO] shouldn't need to be public
@@ -60,8 +89,46 @@ pub const KV_METRICS_ENDPOINT: &str = "load_metrics";
 // for metric publishing (push-based)
 pub const KV_EVENT_SUBJECT: &str = "kv_events";
 pub const KV_HIT_RATE_SUBJECT: &str = "kv-hit-rate";
+pub const KV_SHADOW_SELECTION_SUBJECT: &str = "kv-shadow-selection";
+pub const KV_WORKER_MEMBERSHIP_SUBJECT: &str = "kv-worker-membership";
 pub const KV_METRICS_SUBJECT: &str = "kv_metrics";
 
+/// Comma-separated subjects, besides [`KV_EVENT_SUBJECT`], on which some workers of the
+/// component publish KV events, e.g. when engines of different kinds serve the same model
+pub const KV_EXTRA_EVENT_SUBJECTS_ENV: &str = "DYN_KV_EXTRA_EVENT_SUBJECTS";
+
+/// Comma-separated worker ids whose KV events are the only ones indexed, e.g. for a lightweight
+/// router debugging a few workers. Such a router takes no part in snapshots.
+pub const KV_EVENT_WORKER_FILTER_ENV: &str = "DYN_KV_EVENT_WORKER_IDS";
+
+/// JSONL file to record every KV event the router feeds its indexer to, snapshot included, for
+/// replay debugging. Unset by default, since recording copies every event.
+pub const KV_EVENT_TEE_PATH_ENV: &str = "DYN_KV_EVENT_TEE_PATH";
+/// Size in bytes after which the KV event recording moves on to a new file (default: 100 MB)
+pub const KV_EVENT_TEE_MAX_BYTES_ENV: &str = "DYN_KV_EVENT_TEE_MAX_BYTES";
+/// Seconds after which the KV event recording moves on to a new file (default: unset)
+pub const KV_EVENT_TEE_ROTATE_SECS_ENV: &str = "DYN_KV_EVENT_TEE_ROTATE_SECS";
+/// Number of KV event recording files kept (default: 10)
+pub const KV_EVENT_TEE_MAX_FILES_ENV: &str = "DYN_KV_EVENT_TEE_MAX_FILES";
+
+/// Rotation of the KV event recording, from the `DYN_KV_EVENT_TEE_*` variables
+fn event_tee_rotation_from_env() -> RecorderRotation {
+    fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+        let value = std::env::var(name).ok()?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            tracing::warn!("Ignoring invalid {name}={value}");
+        }
+        parsed
+    }
+    RecorderRotation {
+        max_lines: None,
+        max_bytes: Some(var(KV_EVENT_TEE_MAX_BYTES_ENV).unwrap_or(100_000_000)),
+        max_age: var(KV_EVENT_TEE_ROTATE_SECS_ENV).map(Duration::from_secs_f64),
+        max_files: Some(var(KV_EVENT_TEE_MAX_FILES_ENV).unwrap_or(10)),
+    }
+}
+
 // for inter-router comms
 pub const PREFILL_SUBJECT: &str = "prefill_events";
 pub const ACTIVE_SEQUENCES_SUBJECT: &str = "active_sequences_events";
@@ -71,6 +138,7 @@ pub const RADIX_STATE_BUCKET: &str = "radix-bucket";
 pub const RADIX_STATE_FILE: &str = "radix-state";
 pub const ROUTER_SNAPSHOT_LOCK: &str = "router-snapshot-lock";
 pub const ROUTER_CLEANUP_LOCK: &str = "router-cleanup-lock";
+pub const ROUTER_SNAPSHOT_TIMESTAMP: &str = "router-snapshot-timestamp";
 
 /// A trait that users can implement to define custom selection logic
 pub trait WorkerSelector {
@@ -80,6 +148,30 @@ pub trait WorkerSelector {
         request: &SchedulingRequest,
         block_size: u32,
     ) -> Result<WorkerSelectionResult, KvSchedulerError>;
+
+    /// Rank candidate workers from best to worst. Used for speculative dispatch to several workers.
+    /// The default implementation only returns the result of [`WorkerSelector::select_worker`].
+    fn rank_workers(
+        &self,
+        workers: &HashMap<protocols::WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Result<Vec<WorkerSelectionResult>, KvSchedulerError> {
+        Ok(vec![self.select_worker(workers, request, block_size)?])
+    }
+
+    /// Replace the config of the selector with one broadcast to every router replica, see
+    /// [`policy`]. Selectors which are not configured by [`KvRouterConfig`] ignore it.
+    fn apply_config(&self, _config: &KvRouterConfig) {}
+
+    /// Track the routing set, called with every worker whenever it changes rather than with the
+    /// candidates of a decision, which may leave some out. Stateless selectors ignore it.
+    fn observe_workers(&self, _workers: &HashMap<protocols::WorkerId, Option<ModelRuntimeConfig>>) {
+    }
+
+    /// Forget any state the selector derived from the load accounting, which
+    /// [`KvScheduler::reset_state`] is clearing. Stateless selectors ignore it.
+    fn reset_load_state(&self) {}
 }
 
 /// Override configuration for router settings that can be specified per-request
@@ -90,10 +182,151 @@ pub struct RouterConfigOverride {
 
     #[builder(default)]
     pub router_temperature: Option<f64>,
+
+    #[builder(default)]
+    pub decode_load_weight: Option<f64>,
+
+    #[builder(default)]
+    pub router_pressure_overlap_scale: Option<f64>,
+
+    #[builder(default)]
+    pub router_pressure_exponent: Option<f64>,
+
+    #[builder(default)]
+    pub router_normalize_objectives: Option<bool>,
+
+    /// `u32::MAX` disables the exclusion for this request
+    #[builder(default)]
+    pub router_exclude_zero_overlap_above: Option<u32>,
+
+    /// `usize::MAX` scores every worker for this request
+    #[builder(default)]
+    pub router_max_candidates: Option<usize>,
+
+    #[builder(default)]
+    pub router_consistent_hash_fallback: Option<bool>,
+
+    /// An exponent of 0 counts every overlapping block equally for this request
+    #[builder(default)]
+    pub router_overlap_recency_exponent: Option<f64>,
+
+    #[builder(default)]
+    pub router_overlap_token_efficiency: Option<f64>,
+
+    #[builder(default)]
+    pub router_eviction_risk_weight: Option<f64>,
+
+    /// Weight of the latest decode loads in the moving averages this request updates, which are
+    /// shared with the other requests
+    #[builder(default)]
+    pub router_load_smoothing: Option<f64>,
+
+    /// Can enable the warmup boost for this request even if the router config disables it
+    #[builder(default)]
+    pub router_warmup_secs: Option<f64>,
+
+    #[builder(default)]
+    pub router_warmup_boost: Option<f64>,
+}
+
+impl RouterConfigOverride {
+    /// `config` with every field set in this override replaced
+    pub fn apply(&self, config: &KvRouterConfig) -> KvRouterConfig {
+        KvRouterConfig {
+            overlap_score_weight: self
+                .overlap_score_weight
+                .unwrap_or(config.overlap_score_weight),
+            router_temperature: self.router_temperature.unwrap_or(config.router_temperature),
+            decode_load_weight: self.decode_load_weight.unwrap_or(config.decode_load_weight),
+            router_pressure_overlap_scale: self
+                .router_pressure_overlap_scale
+                .unwrap_or(config.router_pressure_overlap_scale),
+            router_pressure_exponent: self
+                .router_pressure_exponent
+                .unwrap_or(config.router_pressure_exponent),
+            router_normalize_objectives: self
+                .router_normalize_objectives
+                .unwrap_or(config.router_normalize_objectives),
+            router_exclude_zero_overlap_above: self
+                .router_exclude_zero_overlap_above
+                .or(config.router_exclude_zero_overlap_above),
+            router_max_candidates: self.router_max_candidates.or(config.router_max_candidates),
+            router_consistent_hash_fallback: self
+                .router_consistent_hash_fallback
+                .unwrap_or(config.router_consistent_hash_fallback),
+            router_overlap_recency_exponent: self
+                .router_overlap_recency_exponent
+                .or(config.router_overlap_recency_exponent),
+            router_overlap_token_efficiency: self
+                .router_overlap_token_efficiency
+                .unwrap_or(config.router_overlap_token_efficiency),
+            router_eviction_risk_weight: self
+                .router_eviction_risk_weight
+                .unwrap_or(config.router_eviction_risk_weight),
+            router_load_smoothing: self
+                .router_load_smoothing
+                .unwrap_or(config.router_load_smoothing),
+            router_warmup_secs: self.router_warmup_secs.unwrap_or(config.router_warmup_secs),
+            router_warmup_boost: self
+                .router_warmup_boost
+                .unwrap_or(config.router_warmup_boost),
+            ..*config
+        }
+    }
+}
+
+/// How the router treats workers which do not report `total_kv_blocks` in their runtime config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownCapacityPolicy {
+    /// Route to them without any capacity limit, leaving them out of capacity-aware features
+    /// such as cache pressure
+    #[default]
+    Unlimited,
+    /// Assume they have this many KV blocks
+    DefaultCapacity(u64),
+    /// Do not route to them while any worker reports its capacity
+    Exclude,
+}
+
+impl UnknownCapacityPolicy {
+    /// The capacity assumed for a worker which reported `reported` blocks, if any
+    pub fn capacity(self, reported: Option<u64>) -> Option<u64> {
+        match (reported, self) {
+            (Some(blocks), _) => Some(blocks),
+            (None, UnknownCapacityPolicy::DefaultCapacity(blocks)) => Some(blocks),
+            (None, _) => None,
+        }
+    }
+}
+
+/// How the router behaves when no worker has published a [`ModelRuntimeConfig`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingRuntimeConfigsPolicy {
+    /// Route anyway in a degraded mode: every worker is assumed to have a single dp rank and
+    /// unknown KV capacity, so capacity-aware features such as cache pressure are disabled
+    #[default]
+    Degraded,
+    /// Refuse to route until at least one worker publishes its runtime config
+    FailFast,
+}
+
+/// How the router picks a worker for a request whose prefix is cached on no worker
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColdPrefixPolicy {
+    /// Route by load, as for any other request
+    #[default]
+    LoadOnly,
+    /// Route by consistent hashing on the first block of the prompt, so that repeated cold
+    /// prompts sharing a prefix land on the same worker and build up locality over time
+    PrefixAffinity,
 }
 
 /// KV Router configuration parameters
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KvRouterConfig {
     pub overlap_score_weight: f64,
 
@@ -111,6 +344,212 @@ pub struct KvRouterConfig {
 
     /// Whether to reset the router state on startup (default: false)
     pub router_reset_states: bool,
+
+    /// Maximum time in seconds without a successful snapshot (cluster-wide) while the stream is
+    /// above the snapshot threshold before an error is raised. If None, the watchdog is disabled.
+    pub router_snapshot_staleness_secs: Option<u64>,
+
+    /// When set, the snapshot threshold adapts to the growth rate of the event stream: a snapshot
+    /// is taken once the stream would exceed `router_snapshot_threshold` within this many seconds
+    /// at its recent growth rate, so bursts snapshot sooner while quiet periods keep the
+    /// configured threshold (default: None, fixed threshold)
+    pub router_snapshot_adaptive_horizon_secs: Option<f64>,
+
+    /// Whether a failed slot reservation fails (or re-selects) the request instead of being
+    /// logged and ignored (default: false)
+    pub router_strict_slot_tracking: bool,
+
+    /// Whether to route by consistent hashing on the first sequence hash when no overlap data is
+    /// available (indexer unavailable or empty), preserving approximate cache locality at the cost
+    /// of ignoring load for those requests. Requires `router_track_active_blocks` (default: false)
+    pub router_consistent_hash_fallback: bool,
+
+    /// Half-life in seconds of the cache affinity towards the worker which last served a prefix.
+    /// While a prefix sees no new requests, that worker's overlap credit decays, so a long-lived
+    /// but low-rate session is re-balanced by load instead of staying pinned to one worker.
+    /// Requires `router_track_active_blocks` (default: None, no decay)
+    pub router_affinity_decay_secs: Option<f64>,
+
+    /// Maximum number of active requests tracked by the scheduler across all workers. When
+    /// exceeded, the oldest requests are evicted as if freed, bounding memory if `free` is never
+    /// called for some requests (default: None, unbounded)
+    pub router_max_tracked_requests: Option<usize>,
+
+    /// How strongly cluster cache pressure (potential decode blocks over total KV capacity of the
+    /// workers reporting it) scales up `overlap_score_weight`: the effective weight is
+    /// `overlap_score_weight * (1 + scale * pressure^exponent)`. Locality matters more when caches
+    /// are full, load balancing when they are idle (default: 0.0, no scaling)
+    pub router_pressure_overlap_scale: f64,
+
+    /// Exponent shaping the pressure curve of `router_pressure_overlap_scale` (default: 1.0)
+    pub router_pressure_exponent: f64,
+
+    /// What to do when routing while no worker has published a runtime config
+    /// (default: route in degraded mode)
+    pub router_missing_runtime_configs: MissingRuntimeConfigsPolicy,
+
+    /// How much outstanding decode load penalizes a worker in the logit, independently of
+    /// `overlap_score_weight` which weighs the prefill (non-overlapping) blocks (default: 1.0)
+    pub decode_load_weight: f64,
+
+    /// Whether to normalize the prefill and decode objectives to [0, 1] by their maximum across
+    /// workers before weighting them, so the weights trade off objectives on different scales.
+    /// The default reproduces the unnormalized formula (default: false)
+    pub router_normalize_objectives: bool,
+
+    /// When the best overlap across workers exceeds this many blocks, workers with no overlap at
+    /// all are excluded from selection, making prefix-cache routing a hard rule rather than a
+    /// soft bias for workloads where re-prefill is expensive (default: None, never excluded)
+    pub router_exclude_zero_overlap_above: Option<u32>,
+
+    /// When set, overlapping blocks are weighted by their position in the request instead of
+    /// counting equally: the block at position `i` of `n` is credited
+    /// `(exponent + 1) * ((i + 0.5) / n)^exponent` blocks. A full prefix hit keeps its credit,
+    /// while overlap limited to the leading blocks, such as a system prompt every request shares,
+    /// is discounted (default: None, every block counts as 1)
+    pub router_overlap_recency_exponent: Option<f64>,
+
+    /// Maximum number of workers scored per decision. When set, only the workers with the highest
+    /// overlap (topped up with random workers if too few have any) plus a few random ones for
+    /// exploration are scored, bounding the per-request cost in very large clusters
+    /// (default: None, every worker is scored)
+    pub router_max_candidates: Option<usize>,
+
+    /// Whether to journal every reservation in etcd so that the reservations of a router which
+    /// crashed before dispatching or freeing its requests are freed by the next router to start.
+    /// Costs an etcd write per scheduled and freed request (default: false)
+    pub router_reservation_journal: bool,
+
+    /// When set, KV hit rate events are accumulated per worker over windows of this many seconds
+    /// and published as a single aggregated event per worker per window, instead of one event per
+    /// request (default: None, one event per request)
+    pub router_hit_rate_window_secs: Option<f64>,
+
+    /// Maximum number of requests per second dispatched to any single worker, enforced with a
+    /// token bucket per worker. Workers may override it with `max_requests_per_second` in their
+    /// runtime config (default: None, unlimited)
+    pub router_worker_max_rps: Option<f64>,
+
+    /// Whether to follow the routing policy broadcast to all router replicas of the component,
+    /// so that a config published by any replica replaces the selector config of every one.
+    /// Costs an etcd watch per router (default: false)
+    pub router_policy_broadcast: bool,
+
+    /// Maximum time in milliseconds to wait for the indexer to answer an overlap query. Past it,
+    /// the request is routed without overlap data instead of waiting for a slow indexer
+    /// (default: None, wait indefinitely)
+    pub router_overlap_query_timeout_ms: Option<u64>,
+
+    /// Maximum number of blocks, summed over workers, held by the radix tree of the indexer.
+    /// Past it, the least recently matched blocks are evicted, lowering the overlap scores of
+    /// cold prefixes instead of growing without bound (default: None, unbounded)
+    pub router_max_tree_blocks: Option<usize>,
+
+    /// How workers which do not report their KV capacity are treated: without limit, with a
+    /// default capacity, or excluded from routing while other workers report theirs
+    /// (default: without limit)
+    pub router_unknown_capacity: UnknownCapacityPolicy,
+
+    /// Format in which this router uploads radix tree snapshots. Routers read snapshots in any
+    /// format, so replicas may differ while the setting is rolled out (default: JSON)
+    pub router_snapshot_format: SnapshotFormat,
+
+    /// Smoothing factor of the exponential moving average of each worker's decode load used in
+    /// the logit, in (0, 1]: the weight of the latest load against the running average. Lower
+    /// values make routing follow sustained load trends rather than instantaneous spikes, reducing
+    /// flapping between workers (default: 1.0, no smoothing)
+    pub router_load_smoothing: f64,
+
+    /// Interval in seconds between two load snapshots pushed to the subscribers of
+    /// `subscribe_loads`. A significant load change is pushed without waiting for the interval
+    /// (default: 1.0)
+    pub router_load_update_interval_secs: f64,
+
+    /// Scheduler queue depth above which requests are routed by load only, skipping the overlap
+    /// query, so that an overloaded router drains its backlog faster. Full-quality routing
+    /// resumes once the depth falls back to half the threshold (default: None, never degraded)
+    pub router_degraded_queue_depth: Option<usize>,
+
+    /// Scheduler queue depth, the high-water mark, at or above which incoming requests of a
+    /// priority below `router_shed_min_priority` are rejected with `AllWorkersBusy` instead of
+    /// being queued, so that interactive traffic keeps being served during an overload at the
+    /// expense of batch traffic (default: None, never shed)
+    pub router_shed_queue_depth: Option<usize>,
+
+    /// Requests of at least this priority are never shed by `router_shed_queue_depth`
+    /// (default: 1, i.e. requests without a priority are shed)
+    pub router_shed_min_priority: i32,
+
+    /// Each priority level below `router_shed_min_priority - 1` lowers the queue depth at which
+    /// requests are shed by this many requests, so the lowest priorities are shed first
+    /// (default: 0, every shed priority at the high-water mark)
+    pub router_shed_priority_step: usize,
+
+    /// How requests overlapping no worker's cache are routed: by load, or by prefix affinity to
+    /// build up cache locality. Prefix affinity requires `router_track_active_blocks`
+    /// (default: load only)
+    pub router_cold_prefix_policy: ColdPrefixPolicy,
+
+    /// Maximum number of leading blocks of a request looked up in the indexer, bounding the cost
+    /// of the overlap query for very long prompts. A worker caching more than this many blocks
+    /// of the request is credited with this many, so beyond the cap the prefill cost is
+    /// overestimated and workers caching the prefix to different depths no longer differ
+    /// (default: None, every block is looked up)
+    pub router_max_overlap_blocks: Option<usize>,
+
+    /// Seconds during which a worker which joined after the router started is favored, so that
+    /// its cold cache gets traffic to warm up rather than staying unattractive under low load
+    /// (default: 0.0, no warmup)
+    pub router_warmup_secs: f64,
+
+    /// Fraction in [0, 1] by which the logit of a worker is lowered when it joins, decaying
+    /// linearly to no boost over `router_warmup_secs` (default: 0.5)
+    pub router_warmup_boost: f64,
+
+    /// Maximum number of consumed KV events waiting to be applied by the indexer. A larger queue
+    /// absorbs longer bursts of events (default: 2048)
+    pub router_event_channel_capacity: usize,
+
+    /// What happens to the consumed KV events while the queue of the indexer is full: wait for
+    /// the indexer, which stops consuming the stream, or drop the oldest or the incoming events,
+    /// keeping the stream flowing at the cost of a less accurate radix tree (default: block)
+    pub router_event_backpressure: EventBackpressurePolicy,
+
+    /// Prefill tokens saved per token of a cached block, in [0, 1]. The default maps every token
+    /// of an overlapping block to a saved prefill token; lower it for engines which re-compute
+    /// part of a cached prefix, e.g. its last partial block, so the cost function doesn't
+    /// over-credit cache hits (default: 1.0)
+    pub router_overlap_token_efficiency: f64,
+
+    /// Seed of the random generator of worker selection, covering softmax sampling, tie-breaking
+    /// and the exploration candidates, so that the same requests against the same state yield
+    /// the same routing decisions, e.g. for reproducible benchmarks (default: None, unseeded)
+    pub router_seed: Option<u64>,
+
+    /// Seconds a removed worker is held out of routing, even if it reappears in the instance set
+    /// meanwhile, so that a flapping worker has time to stabilize and register its cache again
+    /// (default: 0.0, re-admitted right away)
+    pub router_worker_rejoin_cooldown_secs: f64,
+
+    /// Weight of the eviction risk of a worker in its logit. The risk is the share of the new
+    /// blocks of the request which only fit by evicting blocks of the active requests of the
+    /// worker, and the penalty scales it by the blocks to prefill, steering large cold requests
+    /// away from nearly full workers (default: 0.0, no penalty)
+    pub router_eviction_risk_weight: f64,
+
+    /// Consecutive failures to publish the per-request KV hit rate events after which publishing
+    /// pauses for `router_hit_rate_publish_cooldown_secs`, e.g. during a NATS outage, instead of
+    /// delaying and logging every decision (default: 5; None never pauses)
+    pub router_hit_rate_publish_failures: Option<u32>,
+
+    /// Seconds the KV hit rate events stay unpublished once publishing pauses, before a single
+    /// publish probes whether it recovered (default: 30.0)
+    pub router_hit_rate_publish_cooldown_secs: f64,
+
+    /// Seconds of waiting in the scheduler queue worth one priority level. While the scheduler
+    /// is gated, e.g. by `router_worker_max_rps`, the requests which waited longest then move
+    /// ahead of newer ones of higher priority instead of starving (default: None, no aging)
+    pub router_queue_aging_secs: Option<f64>,
 }
 
 impl Default for KvRouterConfig {
@@ -123,6 +562,47 @@ impl Default for KvRouterConfig {
             router_track_active_blocks: true,
             router_snapshot_threshold: Some(1000000),
             router_reset_states: false,
+            router_snapshot_staleness_secs: Some(600),
+            router_snapshot_adaptive_horizon_secs: None,
+            router_strict_slot_tracking: false,
+            router_consistent_hash_fallback: false,
+            router_affinity_decay_secs: None,
+            router_max_tracked_requests: None,
+            router_pressure_overlap_scale: 0.0,
+            router_pressure_exponent: 1.0,
+            router_missing_runtime_configs: MissingRuntimeConfigsPolicy::Degraded,
+            decode_load_weight: 1.0,
+            router_normalize_objectives: false,
+            router_exclude_zero_overlap_above: None,
+            router_overlap_recency_exponent: None,
+            router_max_candidates: None,
+            router_reservation_journal: false,
+            router_hit_rate_window_secs: None,
+            router_worker_max_rps: None,
+            router_policy_broadcast: false,
+            router_overlap_query_timeout_ms: None,
+            router_max_tree_blocks: None,
+            router_unknown_capacity: UnknownCapacityPolicy::Unlimited,
+            router_snapshot_format: SnapshotFormat::Json,
+            router_load_smoothing: 1.0,
+            router_load_update_interval_secs: 1.0,
+            router_degraded_queue_depth: None,
+            router_shed_queue_depth: None,
+            router_shed_min_priority: 1,
+            router_shed_priority_step: 0,
+            router_cold_prefix_policy: ColdPrefixPolicy::LoadOnly,
+            router_max_overlap_blocks: None,
+            router_warmup_secs: 0.0,
+            router_warmup_boost: 0.5,
+            router_event_channel_capacity: DEFAULT_EVENT_CHANNEL_CAPACITY,
+            router_event_backpressure: EventBackpressurePolicy::Block,
+            router_overlap_token_efficiency: 1.0,
+            router_seed: None,
+            router_worker_rejoin_cooldown_secs: 0.0,
+            router_eviction_risk_weight: 0.0,
+            router_hit_rate_publish_failures: Some(5),
+            router_hit_rate_publish_cooldown_secs: 30.0,
+            router_queue_aging_secs: None,
         }
     }
 }
@@ -151,6 +631,7 @@ impl KvRouterConfig {
             router_snapshot_threshold: router_snapshot_threshold
                 .unwrap_or(default.router_snapshot_threshold),
             router_reset_states: router_reset_states.unwrap_or(default.router_reset_states),
+            ..default
         }
     }
 }
@@ -186,6 +667,25 @@ impl Indexer {
         }
     }
 
+    async fn get_workers(&self) -> Result<Vec<protocols::WorkerId>, KvRouterError> {
+        match self {
+            Indexer::KvIndexer(indexer) => indexer.get_workers().await,
+            Indexer::ApproxKvIndexer(indexer) => indexer.get_workers().await,
+            Indexer::None => Ok(Vec::new()),
+        }
+    }
+
+    async fn evict_sequence(
+        &self,
+        block_hash: protocols::ExternalSequenceBlockHash,
+    ) -> Result<usize, KvRouterError> {
+        match self {
+            Indexer::KvIndexer(indexer) => indexer.evict_sequence(block_hash).await,
+            // Predicted blocks expire on their own after the TTL
+            Indexer::ApproxKvIndexer(_) | Indexer::None => Ok(0),
+        }
+    }
+
     async fn dump_events(&self) -> Result<Vec<RouterEvent>, KvRouterError> {
         match self {
             Indexer::KvIndexer(indexer) => indexer.dump_events().await,
@@ -211,9 +711,56 @@ pub struct KvRouter {
 
     kv_router_config: KvRouterConfig,
 
+    sequence_hasher: Arc<dyn SequenceHasher>,
+
+    component: Component,
+
+    identity: RouterIdentity,
+
+    event_counters: WorkerEventCounters,
+
+    effective_snapshot_threshold: EffectiveSnapshotThreshold,
+
+    policy_broadcast: Option<RoutingPolicyBroadcast>,
+
+    applied_policy: AppliedPolicy,
+
     cancellation_token: tokio_util::sync::CancellationToken,
 }
 
+/// Options of [`KvRouter::new_with_options`]
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct KvRouterOptions {
+    /// Selects the worker of every request (default: [`scheduler::DefaultWorkerSelector`])
+    #[builder(default)]
+    pub selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+
+    /// Also runs on every request, publishing how its choice compares to the one of `selector`
+    /// which actually routes, see [`scheduler::ShadowSelectionEvent`] (default: none)
+    #[builder(default)]
+    pub shadow_selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+
+    /// Approves, denies or re-prioritizes every request before it is scheduled, see
+    /// [`admission`] (default: none)
+    #[builder(default)]
+    pub admission_policy: Option<Arc<dyn AdmissionPolicy>>,
+
+    /// Hashes the request tokens. Workers must publish KV events computed with a hasher of the
+    /// same algorithm id (default: [`Xxh3SequenceHasher`])
+    #[builder(default = "Arc::new(Xxh3SequenceHasher::default())")]
+    pub sequence_hasher: Arc<dyn SequenceHasher>,
+
+    #[builder(default)]
+    pub kv_router_config: Option<KvRouterConfig>,
+}
+
+impl KvRouterOptions {
+    pub fn builder() -> KvRouterOptionsBuilder {
+        KvRouterOptionsBuilder::default()
+    }
+}
+
 impl KvRouter {
     pub async fn new(
         component: Component,
@@ -222,6 +769,31 @@ impl KvRouter {
         kv_router_config: Option<KvRouterConfig>,
         consumer_uuid: String,
     ) -> Result<Self> {
+        Self::new_with_options(
+            component,
+            block_size,
+            consumer_uuid,
+            KvRouterOptions::builder()
+                .selector(selector)
+                .kv_router_config(kv_router_config)
+                .build()?,
+        )
+        .await
+    }
+
+    pub async fn new_with_options(
+        component: Component,
+        block_size: u32,
+        consumer_uuid: String,
+        options: KvRouterOptions,
+    ) -> Result<Self> {
+        let KvRouterOptions {
+            selector,
+            shadow_selector,
+            admission_policy,
+            sequence_hasher,
+            kv_router_config,
+        } = options;
         let kv_router_config = kv_router_config.unwrap_or_default();
 
         let cancellation_token = component
@@ -229,6 +801,8 @@ impl KvRouter {
             .primary_lease()
             .expect("Cannot KV route static workers")
             .primary_token();
+        // The tasks stop in order once the primary token is cancelled, see [`shutdown`]
+        let shutdown = ShutdownSequence::new();
 
         let generate_endpoint = component.endpoint("generate");
         let client = generate_endpoint.client().await?;
@@ -247,52 +821,176 @@ impl KvRouter {
             .etcd_client()
             .expect("Cannot KV route without etcd client");
 
+        // Fail fast if another live router uses this UUID, and release it when this one stops
+        claim_router_uuid(
+            &etcd_client,
+            &component,
+            &consumer_uuid,
+            serde_json::to_vec_pretty(&kv_router_config)?,
+        )
+        .await?;
+        {
+            let etcd_client = etcd_client.clone();
+            let component = component.clone();
+            let consumer_uuid = consumer_uuid.clone();
+            let cancellation_token = shutdown.indexer();
+            tokio::spawn(async move {
+                cancellation_token.cancelled().await;
+                if let Err(e) = release_router_uuid(&etcd_client, &component, &consumer_uuid).await
+                {
+                    tracing::warn!("Failed to release router UUID {consumer_uuid}: {e:?}");
+                }
+            });
+        }
+
         let runtime_configs_watcher = watch_prefix_with_extraction(
-            etcd_client,
+            etcd_client.clone(),
             model_card::ROOT_PATH,
             key_extractors::lease_id,
-            |card: ModelDeploymentCard| Some(card.runtime_config),
-            cancellation_token.clone(),
+            |card: ModelDeploymentCard| {
+                let mut runtime_config = card.runtime_config;
+                // Fall back to the card's context length so requests which cannot fit can be
+                // rejected by the scheduler
+                if runtime_config.max_context_length.is_none() && card.context_length > 0 {
+                    runtime_config.max_context_length = Some(card.context_length as u64);
+                }
+                Some(runtime_config)
+            },
+            shutdown.indexer(),
         )
         .await?;
         let runtime_configs_rx = runtime_configs_watcher.receiver();
 
+        // When dropping events, they queue up ahead of the indexer instead of in its channel
+        let indexer_event_capacity = match kv_router_config.router_event_backpressure {
+            EventBackpressurePolicy::Block => kv_router_config.router_event_channel_capacity.max(1),
+            _ => 1,
+        };
         let indexer = if kv_router_config.overlap_score_weight == 0.0 {
             // When overlap_score_weight is zero, we don't need to track prefixes
             Indexer::None
         } else if kv_router_config.use_kv_events {
             let kv_indexer_metrics = indexer::KvIndexerMetrics::from_component(&component);
-            Indexer::KvIndexer(KvIndexer::new(
-                cancellation_token.clone(),
+            Indexer::KvIndexer(KvIndexer::new_with_sequence_hasher(
+                shutdown.indexer(),
+                None,
                 block_size,
                 kv_indexer_metrics,
+                sequence_hasher.clone(),
+                kv_router_config.router_max_tree_blocks,
+                indexer_event_capacity,
             ))
         } else {
             // hard code 120 seconds for now
-            Indexer::ApproxKvIndexer(ApproxKvIndexer::new(
-                cancellation_token.clone(),
+            Indexer::ApproxKvIndexer(ApproxKvIndexer::new_with_sequence_hasher(
+                shutdown.indexer(),
                 block_size,
                 Duration::from_secs(120),
+                sequence_hasher.clone(),
             ))
         };
 
+        let identity = RouterIdentity::new(&component, &consumer_uuid);
+
+        let load_shedding = kv_router_config
+            .router_shed_queue_depth
+            .map(|high_water_mark| LoadShedding {
+                high_water_mark,
+                min_priority: kv_router_config.router_shed_min_priority,
+                priority_step: kv_router_config.router_shed_priority_step,
+            });
         let scheduler = KvScheduler::start(
             component.clone(),
             block_size,
             instances_rx,
             runtime_configs_rx,
             selector,
-            kv_router_config.router_replica_sync,
-            consumer_uuid.clone(),
+            shadow_selector,
+            KvSchedulerConfig::builder()
+                .router_uuid(consumer_uuid.clone())
+                .replica_sync(kv_router_config.router_replica_sync)
+                .strict_slot_tracking(kv_router_config.router_strict_slot_tracking)
+                .affinity_half_life(
+                    kv_router_config
+                        .router_affinity_decay_secs
+                        .map(Duration::from_secs_f64),
+                )
+                .max_tracked_requests(kv_router_config.router_max_tracked_requests)
+                .journal(kv_router_config.router_reservation_journal.then(|| {
+                    ReservationJournal::new(etcd_client.clone(), &component, &consumer_uuid)
+                }))
+                .hit_rate_window(
+                    kv_router_config
+                        .router_hit_rate_window_secs
+                        .map(Duration::from_secs_f64),
+                )
+                .hit_rate_publish_breaker(kv_router_config.router_hit_rate_publish_failures.map(
+                    |failure_threshold| PublishBreaker {
+                        failure_threshold,
+                        cooldown: Duration::from_secs_f64(
+                            kv_router_config.router_hit_rate_publish_cooldown_secs,
+                        ),
+                    },
+                ))
+                .worker_max_rps(kv_router_config.router_worker_max_rps)
+                .rejoin_cooldown(
+                    (kv_router_config.router_worker_rejoin_cooldown_secs > 0.0).then(|| {
+                        Duration::from_secs_f64(kv_router_config.router_worker_rejoin_cooldown_secs)
+                    }),
+                )
+                .admission_policy(admission_policy)
+                .load_update_interval(Duration::from_secs_f64(
+                    kv_router_config.router_load_update_interval_secs,
+                ))
+                .degraded_queue_depth(kv_router_config.router_degraded_queue_depth)
+                .load_shedding(load_shedding)
+                .queue_aging(
+                    kv_router_config
+                        .router_queue_aging_secs
+                        .map(Duration::from_secs_f64),
+                )
+                .intake_token(Some(shutdown.intake()))
+                .cancellation_token(Some(shutdown.scheduler()))
+                .build()?,
         )
         .await?;
+        {
+            let shutdown = shutdown.clone();
+            let cancellation_token = cancellation_token.clone();
+            let queue_depth = scheduler.queue_depth_probe();
+            tokio::spawn(async move { shutdown.run(cancellation_token, queue_depth).await });
+        }
 
         // Start unified background process if using KvIndexer
+        let event_counters = WorkerEventCounters::default();
+        let effective_snapshot_threshold = EffectiveSnapshotThreshold::default();
         if let Indexer::KvIndexer(ref kv_indexer) = indexer {
+            let event_sender = match std::env::var(KV_EVENT_TEE_PATH_ENV) {
+                Ok(path) if !path.is_empty() => {
+                    tracing::info!("Recording the KV events consumed by the router to {path}");
+                    start_event_tee(
+                        kv_indexer.event_sender(),
+                        path,
+                        event_tee_rotation_from_env(),
+                        shutdown.indexer(),
+                    )
+                    .await?
+                }
+                _ => kv_indexer.event_sender(),
+            };
+            let event_sender = match kv_router_config.router_event_backpressure {
+                EventBackpressurePolicy::Block => event_sender,
+                policy => start_event_shedding(
+                    event_sender,
+                    kv_router_config.router_event_channel_capacity.max(1),
+                    policy,
+                    indexer::KvIndexerMetrics::from_component(&component),
+                    shutdown.indexer(),
+                ),
+            };
             start_kv_router_background(
                 component.clone(),
-                consumer_uuid,
-                kv_indexer.event_sender(),
+                event_sender,
                 kv_indexer.remove_worker_sender(),
                 kv_router_config
                     .router_snapshot_threshold
@@ -300,11 +998,44 @@ impl KvRouter {
                 kv_router_config
                     .router_snapshot_threshold
                     .map(|_| kv_indexer.snapshot_event_sender()),
-                cancellation_token.clone(),
-                kv_router_config.router_snapshot_threshold,
-                kv_router_config.router_reset_states,
+                shutdown.subscriber(),
+                KvRouterBackgroundConfig::builder()
+                    .consumer_uuid(consumer_uuid)
+                    .snapshot_threshold(kv_router_config.router_snapshot_threshold)
+                    .reset_states(kv_router_config.router_reset_states)
+                    .snapshot_staleness_secs(kv_router_config.router_snapshot_staleness_secs)
+                    .adaptive_snapshot_horizon_secs(
+                        kv_router_config.router_snapshot_adaptive_horizon_secs,
+                    )
+                    .snapshot_format(kv_router_config.router_snapshot_format)
+                    .event_counters(event_counters.clone())
+                    .effective_snapshot_threshold(effective_snapshot_threshold.clone())
+                    .extra_event_subjects(parse_extra_event_subjects(
+                        &std::env::var(KV_EXTRA_EVENT_SUBJECTS_ENV).unwrap_or_default(),
+                    ))
+                    .worker_filter(parse_worker_filter(
+                        &std::env::var(KV_EVENT_WORKER_FILTER_ENV).unwrap_or_default(),
+                    )?)
+                    .stopped(Some(shutdown.subscriber_stopped()))
+                    .build()?,
             )
             .await?;
+        } else {
+            shutdown.subscriber_stopped().cancel();
+        }
+
+        let policy_broadcast = kv_router_config
+            .router_policy_broadcast
+            .then(|| RoutingPolicyBroadcast::new(etcd_client.clone(), &component));
+        let applied_policy = AppliedPolicy::default();
+        if let Some(ref broadcast) = policy_broadcast {
+            broadcast
+                .watch(
+                    scheduler.selector(),
+                    applied_policy.clone(),
+                    shutdown.scheduler(),
+                )
+                .await?;
         }
 
         tracing::info!("KV Routing initialized");
@@ -313,10 +1044,61 @@ impl KvRouter {
             scheduler,
             block_size,
             kv_router_config,
+            sequence_hasher,
+            component,
+            identity,
+            event_counters,
+            effective_snapshot_threshold,
+            policy_broadcast,
+            applied_policy,
             cancellation_token,
         })
     }
 
+    /// Query the indexer for the overlap of each worker. If the query does not complete within
+    /// `router_overlap_query_timeout_ms`, route without overlap data, by load alone (or by
+    /// consistent hashing when enabled), rather than holding up the request. The query is
+    /// skipped altogether while routing is degraded by a deep queue, and limited to the leading
+    /// `router_max_overlap_blocks` blocks.
+    async fn find_overlaps(
+        &self,
+        mut block_hashes: Vec<LocalBlockHash>,
+    ) -> Result<OverlapScores, KvRouterError> {
+        if self.scheduler.routing_degraded() {
+            return Ok(OverlapScores::new());
+        }
+        if let Some(max_blocks) = self.kv_router_config.router_max_overlap_blocks {
+            block_hashes.truncate(max_blocks);
+        }
+        let started = std::time::Instant::now();
+        let overlaps = match self.kv_router_config.router_overlap_query_timeout_ms {
+            None => self.indexer.find_matches(block_hashes).await,
+            Some(timeout_ms) => {
+                let timeout = Duration::from_millis(timeout_ms);
+                match tokio::time::timeout(timeout, self.indexer.find_matches(block_hashes)).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        tracing::warn!(
+                            "Overlap query timed out after {timeout:?}, routing without overlap data"
+                        );
+                        Ok(OverlapScores::new())
+                    }
+                }
+            }
+        };
+        let metrics = DecisionLatencyMetrics::get();
+        DecisionLatencyMetrics::observe(&metrics.overlap, started);
+        overlaps
+    }
+
+    fn block_hashes(&self, tokens: &[u32]) -> Vec<LocalBlockHash> {
+        compute_block_hash_for_seq_with(self.sequence_hasher.as_ref(), tokens, self.block_size)
+    }
+
+    fn sequence_hashes(&self, block_hashes: &[LocalBlockHash]) -> Vec<SequenceHash> {
+        compute_seq_hash_for_block_with(self.sequence_hasher.as_ref(), block_hashes)
+    }
+
     /// Give these tokens, find the worker with the best match in it's KV cache.
     /// Returns the best worker (with dp_rank) and overlap amount in number of blocks.
     /// Now also takes optional context_id for request tracking
@@ -326,6 +1108,95 @@ impl KvRouter {
         tokens: &[u32],
         router_config_override: Option<&RouterConfigOverride>,
         update_states: bool,
+    ) -> anyhow::Result<(WorkerWithDpRank, u32)> {
+        self.find_best_match_in_phase(
+            context_id,
+            tokens,
+            router_config_override,
+            update_states,
+            SchedulingPhase::Any,
+        )
+        .await
+    }
+
+    /// First stage of disaggregated serving: find the best prefill (or aggregated) worker for
+    /// these tokens and reserve the prefill on it.
+    pub async fn schedule_prefill(
+        &self,
+        context_id: &str,
+        tokens: &[u32],
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> anyhow::Result<(WorkerWithDpRank, u32)> {
+        self.find_best_match_in_phase(
+            Some(context_id),
+            tokens,
+            router_config_override,
+            true,
+            SchedulingPhase::Prefill,
+        )
+        .await
+    }
+
+    /// Second stage of disaggregated serving: once prefill is done, move the request's
+    /// reservation from the prefill worker to the best decode (or aggregated) worker.
+    pub async fn schedule_decode(
+        &self,
+        context_id: &str,
+        tokens: &[u32],
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> anyhow::Result<(WorkerWithDpRank, u32)> {
+        self.find_best_match_in_phase(
+            Some(context_id),
+            tokens,
+            router_config_override,
+            true,
+            SchedulingPhase::Decode,
+        )
+        .await
+    }
+
+    /// Reserve a provisional worker for these tokens from the last known loads and return it
+    /// without waiting for the scheduler, refining the choice in the background. See
+    /// [`ProvisionalSchedule`] for the consistency tradeoff.
+    pub async fn find_best_match_provisional(
+        &self,
+        context_id: &str,
+        tokens: &[u32],
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> anyhow::Result<ProvisionalSchedule> {
+        let block_hashes = self.block_hashes(tokens);
+        let overlap_scores = self.find_overlaps(block_hashes.clone()).await?;
+        let seq_hashes = self.sequence_hashes(&block_hashes);
+
+        let schedule = self
+            .scheduler
+            .schedule_provisional(
+                context_id.to_string(),
+                tokens.len(),
+                self.kv_router_config
+                    .router_track_active_blocks
+                    .then(|| seq_hashes.clone()),
+                overlap_scores,
+                router_config_override,
+            )
+            .await?;
+
+        // The approximate indexer learns the provisional placement
+        if let Indexer::ApproxKvIndexer(ref indexer) = self.indexer {
+            indexer
+                .process_routing_decision(schedule.provisional_worker(), block_hashes, seq_hashes)
+                .await?;
+        }
+        Ok(schedule)
+    }
+
+    async fn find_best_match_in_phase(
+        &self,
+        context_id: Option<&str>,
+        tokens: &[u32],
+        router_config_override: Option<&RouterConfigOverride>,
+        update_states: bool,
+        phase: SchedulingPhase,
     ) -> anyhow::Result<(WorkerWithDpRank, u32)> {
         // Validate that context_id is provided when update_states is true
         if update_states && context_id.is_none() {
@@ -334,10 +1205,19 @@ impl KvRouter {
 
         let isl_tokens = tokens.len();
 
-        let block_hashes = compute_block_hash_for_seq(tokens, self.block_size);
-        let seq_hashes = compute_seq_hash_for_block(&block_hashes);
+        let block_hashes = self.block_hashes(tokens);
+        let seq_hashes = self.sequence_hashes(&block_hashes);
 
-        let overlap_scores = self.indexer.find_matches(block_hashes.clone()).await?;
+        let overlap_scores = match self.find_overlaps(block_hashes.clone()).await {
+            Ok(overlap_scores) => overlap_scores,
+            Err(e) if self.kv_router_config.router_consistent_hash_fallback => {
+                tracing::warn!(
+                    "KV indexer unavailable, falling back to consistent-hash routing: {e:?}"
+                );
+                OverlapScores::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         // Determine who needs seq_hashes
         let approx_indexer_needs_it = matches!(self.indexer, Indexer::ApproxKvIndexer(_));
@@ -352,17 +1232,44 @@ impl KvRouter {
                 (false, false) => (None, None),
             };
 
-        let best_worker = self
-            .scheduler
-            .schedule(
-                context_id.map(|s| s.to_string()),
-                isl_tokens,
-                maybe_seq_hashes_2,
-                overlap_scores.clone(),
-                router_config_override,
-                update_states,
-            )
-            .await?;
+        let request_id = context_id.map(|s| s.to_string());
+        let overlaps = overlap_scores.clone();
+        let best_worker = match (phase, request_id) {
+            (SchedulingPhase::Prefill, Some(request_id)) => {
+                self.scheduler
+                    .schedule_prefill(
+                        request_id,
+                        isl_tokens,
+                        maybe_seq_hashes_2,
+                        overlaps,
+                        router_config_override,
+                    )
+                    .await?
+            }
+            (SchedulingPhase::Decode, Some(request_id)) => {
+                self.scheduler
+                    .schedule_decode(
+                        request_id,
+                        isl_tokens,
+                        maybe_seq_hashes_2,
+                        overlaps,
+                        router_config_override,
+                    )
+                    .await?
+            }
+            (_, request_id) => {
+                self.scheduler
+                    .schedule(
+                        request_id,
+                        isl_tokens,
+                        maybe_seq_hashes_2,
+                        overlaps,
+                        router_config_override,
+                        update_states,
+                    )
+                    .await?
+            }
+        };
 
         if let Indexer::ApproxKvIndexer(ref indexer) = self.indexer {
             indexer
@@ -379,6 +1286,41 @@ impl KvRouter {
         Ok((best_worker, overlap_amount))
     }
 
+    /// Find the `n` best workers for these tokens, ordered from best to worst, with their overlap
+    /// amounts in number of blocks. Nothing is reserved: the caller must call
+    /// [`KvRouter::add_request`] for the worker it ends up using.
+    pub async fn find_top_n_matches(
+        &self,
+        n: usize,
+        tokens: &[u32],
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> anyhow::Result<Vec<(WorkerWithDpRank, u32)>> {
+        let isl_tokens = tokens.len();
+        let block_hashes = self.block_hashes(tokens);
+        let overlap_scores = self.find_overlaps(block_hashes.clone()).await?;
+
+        let maybe_seq_hashes = self
+            .kv_router_config
+            .router_track_active_blocks
+            .then(|| self.sequence_hashes(&block_hashes));
+
+        let responses = self
+            .scheduler
+            .schedule_top_n(
+                n,
+                isl_tokens,
+                maybe_seq_hashes,
+                overlap_scores,
+                router_config_override,
+            )
+            .await?;
+
+        Ok(responses
+            .into_iter()
+            .map(|response| (response.best_worker, response.overlap_blocks))
+            .collect())
+    }
+
     pub async fn add_request(
         &self,
         request_id: String,
@@ -389,8 +1331,8 @@ impl KvRouter {
         let isl_tokens = tokens.len();
 
         let maybe_seq_hashes = self.kv_router_config.router_track_active_blocks.then(|| {
-            let block_hashes = compute_block_hash_for_seq(tokens, self.block_size);
-            compute_seq_hash_for_block(&block_hashes)
+            let block_hashes = self.block_hashes(tokens);
+            self.sequence_hashes(&block_hashes)
         });
 
         self.scheduler
@@ -404,14 +1346,19 @@ impl KvRouter {
             .await;
     }
 
-    pub async fn mark_prefill_completed(&self, request_id: &str) -> Result<()> {
+    pub async fn mark_prefill_completed(&self, request_id: &str) -> Result<(), KvSchedulerError> {
         self.scheduler.mark_prefill_completed(request_id).await
     }
 
-    pub async fn free(&self, request_id: &str) -> Result<()> {
+    pub async fn free(&self, request_id: &str) -> Result<(), KvSchedulerError> {
         self.scheduler.free(request_id).await
     }
 
+    /// Cancel a request still waiting to be scheduled, e.g. because its client disconnected
+    pub fn cancel(&self, request_id: &str) {
+        self.scheduler.cancel(request_id)
+    }
+
     pub fn block_size(&self) -> u32 {
         self.block_size
     }
@@ -419,12 +1366,12 @@ impl KvRouter {
     /// Get potential prefill and decode loads for all workers
     pub async fn get_potential_loads(&self, tokens: &[u32]) -> Result<Vec<PotentialLoad>> {
         let isl_tokens = tokens.len();
-        let block_hashes = compute_block_hash_for_seq(tokens, self.block_size);
-        let overlap_scores = self.indexer.find_matches(block_hashes).await?;
+        let block_hashes = self.block_hashes(tokens);
+        let overlap_scores = self.find_overlaps(block_hashes).await?;
 
         let maybe_seq_hashes = self.kv_router_config.router_track_active_blocks.then(|| {
-            let block_hashes = compute_block_hash_for_seq(tokens, self.block_size);
-            compute_seq_hash_for_block(&block_hashes)
+            let block_hashes = self.block_hashes(tokens);
+            self.sequence_hashes(&block_hashes)
         });
 
         Ok(self
@@ -433,10 +1380,135 @@ impl KvRouter {
             .await)
     }
 
+    /// Overlap in blocks of these tokens with the cache of each worker, the best across its dp
+    /// ranks, e.g. to decide whether to wait for a warm worker. Read-only: unlike routing, nothing
+    /// is scheduled nor reserved, and the cost function is not evaluated.
+    pub async fn query_overlap(
+        &self,
+        tokens: &[u32],
+    ) -> Result<HashMap<protocols::WorkerId, u32>, KvRouterError> {
+        let block_hashes = self.block_hashes(tokens);
+        let overlap_scores = self.indexer.find_matches(block_hashes).await?;
+        Ok(overlap_scores.worker_scores())
+    }
+
+    /// Stream of the potential loads of all workers, pushed periodically and on every significant
+    /// change, e.g. to drive an autoscaler. A subscriber which falls behind skips the snapshots
+    /// it missed rather than blocking the scheduler.
+    pub fn subscribe_loads(&self) -> impl futures::Stream<Item = Vec<PotentialLoad>> + use<> {
+        self.scheduler.subscribe_loads()
+    }
+
     /// Dump all events from the indexer
     pub async fn dump_events(&self) -> Result<Vec<RouterEvent>, KvRouterError> {
         self.indexer.dump_events().await
     }
+
+    /// Evict a sequence known to be stale, e.g. after a model reload, and the blocks stored after
+    /// it, from the indexer of this router without waiting for the workers' removal events.
+    /// Returns the number of evicted blocks. Other router replicas keep the sequence.
+    pub async fn evict_sequence(
+        &self,
+        block_hash: protocols::ExternalSequenceBlockHash,
+    ) -> Result<usize, KvRouterError> {
+        let evicted = self.indexer.evict_sequence(block_hash).await?;
+        tracing::info!("Evicted {evicted} blocks of sequence {block_hash:?} from the indexer");
+        Ok(evicted)
+    }
+
+    /// Workers the indexer currently holds blocks for, to compare against the registered
+    /// instances when diagnosing stale workers. Empty when no indexer is used.
+    pub async fn indexer_workers(&self) -> Result<HashSet<protocols::WorkerId>, KvRouterError> {
+        Ok(self.indexer.get_workers().await?.into_iter().collect())
+    }
+
+    /// Fraction of the cluster's total KV cache in use, with a per-worker breakdown
+    pub async fn cluster_utilization(&self) -> ClusterUtilization {
+        self.scheduler.cluster_utilization().await
+    }
+
+    /// Every request the router believes is in flight, with its assigned worker. An external
+    /// reconciler can compare this against the workers' actual active requests and [`Self::free`]
+    /// the entries the router is still tracking by mistake.
+    pub fn active_requests(&self) -> Vec<(String, protocols::WorkerId)> {
+        self.scheduler.active_requests()
+    }
+
+    /// Every request in flight with its worker, blocks and outstanding prefill, for a router
+    /// taking over from this one to [`Self::import_active_state`]. This complements the radix
+    /// tree snapshot, which holds the cache state rather than the in-flight requests.
+    pub async fn export_active_state(&self) -> ActiveStateSnapshot {
+        self.scheduler.export_active_state().await
+    }
+
+    /// Reset only the load accounting of the router, e.g. after detecting that it drifted, while
+    /// keeping the radix tree and its cache locality, unlike `router_reset_states`. Returns the
+    /// number of tracked requests forgotten; see [`KvScheduler::reset_state`].
+    pub fn reset_scheduler_state(&self) -> usize {
+        self.scheduler.reset_state()
+    }
+
+    /// Continue the load accounting of the router which exported `snapshot`
+    pub async fn import_active_state(&self, snapshot: ActiveStateSnapshot) -> Result<ImportReport> {
+        self.scheduler.import_active_state(snapshot).await
+    }
+
+    /// Cluster and per-worker hit rates over the last few minutes, e.g. to compare locality
+    /// before and after a routing config change
+    pub fn hit_rate_history(&self) -> Vec<HitRateBucket> {
+        self.scheduler.hit_rate_history()
+    }
+
+    /// Full scheduler state as a single serializable snapshot, to attach to support requests
+    pub async fn dump_state(&self) -> SchedulerState {
+        self.scheduler.dump_state().await
+    }
+
+    /// The UUID, NATS consumer, stream and bucket names of this router
+    pub fn identity(&self) -> &RouterIdentity {
+        &self.identity
+    }
+
+    /// List the active NATS consumers against the active routers of this component, to help
+    /// diagnose orphaned consumers
+    pub async fn consumer_report(&self) -> Result<ConsumerReport> {
+        consumer_report(&self.component).await
+    }
+
+    /// Per-worker counts and rates of the KV events consumed by this router. Empty when using
+    /// approximate routing, which does not consume KV events.
+    pub fn worker_event_stats(&self) -> Vec<WorkerEventStats> {
+        self.event_counters.snapshot()
+    }
+
+    /// Stream size above which this router currently snapshots, which follows the stream growth
+    /// rate in adaptive mode. None when snapshots are disabled or not handled by this router.
+    pub fn effective_snapshot_threshold(&self) -> Option<u64> {
+        self.effective_snapshot_threshold.get()
+    }
+
+    /// Publish `config` to every router replica of this component following the routing policy
+    /// broadcast, including this one. Fails unless `router_policy_broadcast` is enabled.
+    pub async fn broadcast_config(&self, config: KvRouterConfig) -> Result<RoutingPolicy> {
+        let Some(ref broadcast) = self.policy_broadcast else {
+            anyhow::bail!("Routing policy broadcast is disabled (router_policy_broadcast)");
+        };
+        broadcast.publish(config).await
+    }
+
+    /// The broadcast routing policy this router applies, None if it routes with its local config
+    pub fn routing_policy(&self) -> Option<RoutingPolicy> {
+        self.applied_policy.get()
+    }
+
+    /// Replace the selector of the scheduler at runtime, see [`KvScheduler::set_selector`]. The
+    /// broadcast routing policy this router applies, if any, is applied to `selector` first.
+    pub fn set_selector(&self, selector: Box<dyn WorkerSelector + Send + Sync>) {
+        if let Some(policy) = self.applied_policy.get() {
+            selector.apply_config(&policy.config);
+        }
+        self.scheduler.set_selector(selector);
+    }
 }
 
 // NOTE: KVRouter works like a PushRouter,
@@ -468,6 +1540,9 @@ impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Er
             RouterRequest::MarkFree => RouterResponse::FreeMarked {
                 success: self.free(&context_id).await.is_ok(),
             },
+            RouterRequest::ResetSchedulerState => RouterResponse::SchedulerStateReset {
+                forgotten_requests: self.reset_scheduler_state(),
+            },
         };
 
         let response = Annotated::from_data(response);
@@ -506,7 +1581,11 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
     ///    - DOES update router states to track this request (unless query_instance_id is also set)
     ///    - Bypasses the normal KV matching logic
     ///
-    /// 3. **If neither are set (default behavior)**:
+    /// 3. **If `bypass_routing` is set in the request**, and no backend instance is:
+    ///    - Routes to the next worker in round robin, without computing overlaps
+    ///    - Does NOT update any router local states
+    ///
+    /// 4. **If none are set (default behavior)**:
     ///    - Finds the best worker based on KV cache overlap
     ///    - Updates router states to track the request
     ///    - Routes to the selected worker
@@ -520,6 +1599,20 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
         match self.inner.client.instance_source.as_ref() {
             InstanceSource::Static => self.inner.r#static(request).await,
             InstanceSource::Dynamic(_) => {
+                // Send requests bypassing routing, e.g. health checks, straight to a worker
+                if request.bypass_routing && request.backend_instance_id.is_none() {
+                    let worker = self.chooser.scheduler.round_robin_worker().await?;
+                    tracing::debug!(
+                        "Bypassing KV routing, sending request to worker_id={} dp_rank={}",
+                        worker.worker_id,
+                        worker.dp_rank
+                    );
+                    let (mut backend_input, context) = request.into_parts();
+                    backend_input.dp_rank = Some(worker.dp_rank);
+                    let request = context.map(|_| backend_input);
+                    return self.inner.direct(request, worker.worker_id).await;
+                }
+
                 // Extract context ID for request tracking
                 let context_id = request.context().id().to_string();
 
@@ -538,9 +1631,8 @@ impl AsyncEngine<SingleIn<PreprocessedRequest>, ManyOut<Annotated<LLMEngineOutpu
                     }
 
                     // Compute actual overlap blocks by querying the indexer
-                    let block_hashes =
-                        compute_block_hash_for_seq(&request.token_ids, self.chooser.block_size());
-                    let overlap_scores = self.chooser.indexer.find_matches(block_hashes).await?;
+                    let block_hashes = self.chooser.block_hashes(&request.token_ids);
+                    let overlap_scores = self.chooser.find_overlaps(block_hashes).await?;
                     let worker = WorkerWithDpRank::new(id, dp_rank);
                     let overlap_blocks = overlap_scores.scores.get(&worker).copied().unwrap_or(0);
 
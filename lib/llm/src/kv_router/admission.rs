@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Admission of requests before they are routed.
+//!
+//! An [`AdmissionPolicy`] lets an external policy engine, e.g. for quota enforcement or abuse
+//! detection, approve, deny or re-prioritize every request as the scheduler dequeues it. Denied
+//! requests fail with [`super::scheduler::KvSchedulerError::AdmissionDenied`]; re-prioritized
+//! ones are scheduled before the queued requests of lower priority. Without a policy every
+//! request is admitted and no policy is consulted.
+
+use async_trait::async_trait;
+
+use super::scheduler::SchedulingRequest;
+
+/// The verdict of an [`AdmissionPolicy`] on a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdmissionDecision {
+    Allow,
+    /// Refuse the request, with the reason reported to the caller
+    Deny(String),
+    /// Admit the request with this priority; higher priorities are scheduled first
+    Reprioritize(i32),
+}
+
+#[async_trait]
+pub trait AdmissionPolicy: Send + Sync {
+    /// Decide whether `request` may be routed. Called once per request, from the scheduler loop,
+    /// so a slow policy delays the requests queued behind it.
+    async fn admit(&self, request: &SchedulingRequest) -> AdmissionDecision;
+}
@@ -23,11 +23,14 @@ use tokio::sync::{mpsc, oneshot};
 use tokio::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
-use crate::tokens::{SequenceHash, TokenBlockSequence};
+use std::sync::Arc;
+
+use crate::tokens::SequenceHash;
+use crate::tokens::hasher::{SequenceHasher, Xxh3SequenceHasher};
 
 use crate::kv_router::indexer::{
     DumpRequest, KvIndexerInterface, KvRouterError, OverlapScores, RadixTree, RouterEvent,
-    compute_block_hash_for_seq,
+    compute_block_hash_for_seq_with, compute_seq_hash_for_block_with,
 };
 use crate::kv_router::protocols::{
     ExternalSequenceBlockHash, KvCacheEvent, KvCacheEventData, KvCacheRemoveData, KvCacheStoreData,
@@ -171,20 +174,40 @@ pub struct ApproxKvIndexer {
     route_tx: mpsc::Sender<RouterResult>,
     /// A sender for remove worker requests.
     remove_worker_tx: mpsc::Sender<WorkerId>,
+    /// A sender for get workers requests.
+    get_workers_tx: mpsc::Sender<super::indexer::GetWorkersRequest>,
     /// A sender for dump requests.
     dump_tx: mpsc::Sender<DumpRequest>,
     /// A handle to the background task managing the KV store.
     task: OnceLock<std::thread::JoinHandle<()>>,
     /// The size of the KV block this indexer can handle.
     kv_block_size: u32,
+    /// Hashes routed tokens into block and sequence hashes.
+    sequence_hasher: Arc<dyn SequenceHasher>,
 }
 
 impl ApproxKvIndexer {
     pub fn new(token: CancellationToken, kv_block_size: u32, ttl: Duration) -> Self {
+        Self::new_with_sequence_hasher(
+            token,
+            kv_block_size,
+            ttl,
+            Arc::new(Xxh3SequenceHasher::default()),
+        )
+    }
+
+    /// Create a new `ApproxKvIndexer` which hashes routed tokens with `sequence_hasher`.
+    pub fn new_with_sequence_hasher(
+        token: CancellationToken,
+        kv_block_size: u32,
+        ttl: Duration,
+        sequence_hasher: Arc<dyn SequenceHasher>,
+    ) -> Self {
+        let hasher_id = sequence_hasher.algorithm_id().into_owned();
         let (match_tx, mut match_rx) = mpsc::channel::<MatchRequest>(2048);
         let (route_tx, mut route_rx) = mpsc::channel::<RouterResult>(2048);
         let (remove_worker_tx, mut remove_worker_rx) = mpsc::channel::<WorkerId>(16);
-        let (_get_workers_tx, mut get_workers_rx) =
+        let (get_workers_tx, mut get_workers_rx) =
             mpsc::channel::<super::indexer::GetWorkersRequest>(16);
         let (dump_tx, mut dump_rx) = mpsc::channel::<DumpRequest>(16);
         let cancel_clone = token.clone();
@@ -196,7 +219,7 @@ impl ApproxKvIndexer {
                 .unwrap();
 
             runtime.block_on(async move {
-                let mut trie = RadixTree::new();
+                let mut trie = RadixTree::new().with_sequence_hasher_id(hasher_id);
                 // Use a reasonable threshold - can be made configurable if needed
                 let mut timer_manager: TimerManager<TimerEntry> = TimerManager::new(ttl, 50);
                 let mut event_id = 0;
@@ -296,9 +319,11 @@ impl ApproxKvIndexer {
             match_tx,
             route_tx,
             remove_worker_tx,
+            get_workers_tx,
             dump_tx,
             task: once,
             kv_block_size,
+            sequence_hasher,
         }
     }
 
@@ -306,6 +331,11 @@ impl ApproxKvIndexer {
         self.kv_block_size
     }
 
+    /// Workers with at least one unexpired block in the tree
+    pub async fn get_workers(&self) -> Result<Vec<WorkerId>, KvRouterError> {
+        super::indexer::request_workers(&self.get_workers_tx).await
+    }
+
     /// Core function to process a routing decision with pre-computed hashes
     pub async fn process_routing_decision(
         &self,
@@ -331,14 +361,13 @@ impl ApproxKvIndexer {
         tokens: &[u32],
         worker: WorkerWithDpRank,
     ) -> Result<(), KvRouterError> {
-        let local_hashes = compute_block_hash_for_seq(tokens, self.kv_block_size);
-
-        let sequence = TokenBlockSequence::new(tokens.into(), self.kv_block_size, None);
-        let sequence_hashes = sequence
-            .blocks()
-            .iter()
-            .map(|b| b.sequence_hash())
-            .collect::<Vec<_>>();
+        let local_hashes = compute_block_hash_for_seq_with(
+            self.sequence_hasher.as_ref(),
+            tokens,
+            self.kv_block_size,
+        );
+        let sequence_hashes =
+            compute_seq_hash_for_block_with(self.sequence_hasher.as_ref(), &local_hashes);
 
         self.process_routing_decision(worker, local_hashes, sequence_hashes)
             .await
@@ -374,7 +403,11 @@ impl KvIndexerInterface for ApproxKvIndexer {
         &self,
         tokens: &[u32],
     ) -> Result<OverlapScores, KvRouterError> {
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq_with(
+            self.sequence_hasher.as_ref(),
+            tokens,
+            self.kv_block_size,
+        );
         self.find_matches(sequence).await
     }
 
@@ -0,0 +1,266 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Versioned documents of the routing configuration.
+//!
+//! A [`KvRouterConfigDocument`] captures the [`KvRouterConfig`] of a router, and optionally the
+//! [`DisaggRouterConf`] holding the prompt length above which prefill is done remotely, so that
+//! a known-good configuration can be kept as code and applied to other routers. Documents are
+//! validated when loaded, so a NaN weight or a negative temperature is refused up front instead
+//! of producing meaningless routing decisions. Settings missing from a document keep their
+//! default, so documents written by older routers still load.
+
+use anyhow::{Result, ensure};
+use serde::{Deserialize, Serialize};
+
+use super::KvRouterConfig;
+use crate::disagg_router::DisaggRouterConf;
+
+/// Version of the document layout, refused by routers which only know older ones
+pub const CONFIG_DOCUMENT_SCHEMA_VERSION: u32 = 1;
+
+/// A routing configuration as exported by [`KvRouterConfig::to_document`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvRouterConfigDocument {
+    pub schema_version: u32,
+    pub kv_router: KvRouterConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disagg_router: Option<DisaggRouterConf>,
+}
+
+/// The part of a document read before the rest, so that a document of a newer schema is
+/// reported as such rather than as a parse error
+#[derive(Deserialize)]
+struct DocumentHeader {
+    schema_version: u32,
+}
+
+impl KvRouterConfigDocument {
+    pub fn new(kv_router: KvRouterConfig) -> Self {
+        Self {
+            schema_version: CONFIG_DOCUMENT_SCHEMA_VERSION,
+            kv_router,
+            disagg_router: None,
+        }
+    }
+
+    pub fn with_disagg_router(mut self, disagg_router: DisaggRouterConf) -> Self {
+        self.disagg_router = Some(disagg_router);
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.kv_router.validate()?;
+        if let Some(disagg_router) = &self.disagg_router {
+            ensure!(
+                disagg_router.max_local_prefill_length >= 0,
+                "max_local_prefill_length must be non-negative, got {}",
+                disagg_router.max_local_prefill_length
+            );
+        }
+        Ok(())
+    }
+
+    /// Serialize the document to pretty-printed JSON, refusing an invalid configuration
+    pub fn to_json(&self) -> Result<String> {
+        self.validate()?;
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Parse and validate a document written by [`KvRouterConfigDocument::to_json`]
+    pub fn from_json(document: &str) -> Result<Self> {
+        let header: DocumentHeader = serde_json::from_str(document)?;
+        ensure!(
+            header.schema_version <= CONFIG_DOCUMENT_SCHEMA_VERSION,
+            "Unsupported config document schema version {}, expected at most {CONFIG_DOCUMENT_SCHEMA_VERSION}",
+            header.schema_version
+        );
+        let document: Self = serde_json::from_str(document)?;
+        document.validate()?;
+        Ok(document)
+    }
+}
+
+impl KvRouterConfig {
+    /// Export the config as a versioned JSON document, see [`KvRouterConfigDocument`]
+    pub fn to_document(&self) -> Result<String> {
+        KvRouterConfigDocument::new(*self).to_json()
+    }
+
+    /// Load a config exported by [`KvRouterConfig::to_document`], failing if it is invalid
+    pub fn from_document(document: &str) -> Result<Self> {
+        Ok(KvRouterConfigDocument::from_json(document)?.kv_router)
+    }
+
+    /// Check for settings which would make routing meaningless, such as a NaN weight, a negative
+    /// temperature or a fraction outside of [0, 1]
+    pub fn validate(&self) -> Result<()> {
+        let non_negative = [
+            ("overlap_score_weight", self.overlap_score_weight),
+            ("decode_load_weight", self.decode_load_weight),
+            ("router_temperature", self.router_temperature),
+            (
+                "router_pressure_overlap_scale",
+                self.router_pressure_overlap_scale,
+            ),
+            ("router_warmup_secs", self.router_warmup_secs),
+            (
+                "router_worker_rejoin_cooldown_secs",
+                self.router_worker_rejoin_cooldown_secs,
+            ),
+            (
+                "router_eviction_risk_weight",
+                self.router_eviction_risk_weight,
+            ),
+            (
+                "router_hit_rate_publish_cooldown_secs",
+                self.router_hit_rate_publish_cooldown_secs,
+            ),
+        ];
+        for (name, value) in non_negative {
+            ensure!(
+                value.is_finite() && value >= 0.0,
+                "{name} must be finite and non-negative, got {value}"
+            );
+        }
+
+        let positive = [
+            (
+                "router_pressure_exponent",
+                Some(self.router_pressure_exponent),
+            ),
+            (
+                "router_load_update_interval_secs",
+                Some(self.router_load_update_interval_secs),
+            ),
+            (
+                "router_snapshot_adaptive_horizon_secs",
+                self.router_snapshot_adaptive_horizon_secs,
+            ),
+            (
+                "router_affinity_decay_secs",
+                self.router_affinity_decay_secs,
+            ),
+            (
+                "router_hit_rate_window_secs",
+                self.router_hit_rate_window_secs,
+            ),
+            ("router_worker_max_rps", self.router_worker_max_rps),
+            ("router_queue_aging_secs", self.router_queue_aging_secs),
+        ];
+        for (name, value) in positive {
+            if let Some(value) = value {
+                ensure!(
+                    value.is_finite() && value > 0.0,
+                    "{name} must be finite and positive, got {value}"
+                );
+            }
+        }
+
+        let fractions = [
+            ("router_warmup_boost", self.router_warmup_boost),
+            (
+                "router_overlap_token_efficiency",
+                self.router_overlap_token_efficiency,
+            ),
+        ];
+        for (name, value) in fractions {
+            ensure!(
+                (0.0..=1.0).contains(&value),
+                "{name} must be in [0, 1], got {value}"
+            );
+        }
+        ensure!(
+            self.router_load_smoothing > 0.0 && self.router_load_smoothing <= 1.0,
+            "router_load_smoothing must be in (0, 1], got {}",
+            self.router_load_smoothing
+        );
+
+        // Blocks are credited `(exponent + 1) * position^exponent`, which must stay positive
+        if let Some(exponent) = self.router_overlap_recency_exponent {
+            ensure!(
+                exponent.is_finite() && exponent > -1.0,
+                "router_overlap_recency_exponent must be finite and above -1, got {exponent}"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_document_round_trip() {
+        let config = KvRouterConfig {
+            overlap_score_weight: 2.0,
+            router_temperature: 0.5,
+            router_max_candidates: Some(4),
+            ..Default::default()
+        };
+        let loaded = KvRouterConfig::from_document(&config.to_document().unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_value(loaded).unwrap(),
+            serde_json::to_value(config).unwrap()
+        );
+
+        let document = KvRouterConfigDocument::new(config)
+            .with_disagg_router(DisaggRouterConf {
+                max_local_prefill_length: 512,
+            })
+            .to_json()
+            .unwrap();
+        let loaded = KvRouterConfigDocument::from_json(&document).unwrap();
+        assert_eq!(loaded.disagg_router.unwrap().max_local_prefill_length, 512);
+
+        // Settings missing from the document keep their default
+        let loaded = KvRouterConfig::from_document(
+            r#"{"schema_version": 1, "kv_router": {"router_temperature": 0.25}}"#,
+        )
+        .unwrap();
+        assert_eq!(loaded.router_temperature, 0.25);
+        assert_eq!(
+            loaded.overlap_score_weight,
+            KvRouterConfig::default().overlap_score_weight
+        );
+    }
+
+    #[test]
+    fn test_config_document_validation() {
+        let invalid = [
+            KvRouterConfig {
+                overlap_score_weight: f64::NAN,
+                ..Default::default()
+            },
+            KvRouterConfig {
+                router_temperature: -1.0,
+                ..Default::default()
+            },
+            KvRouterConfig {
+                router_load_smoothing: 0.0,
+                ..Default::default()
+            },
+            KvRouterConfig {
+                router_hit_rate_window_secs: Some(f64::INFINITY),
+                ..Default::default()
+            },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err(), "{config:?}");
+            assert!(config.to_document().is_err());
+        }
+        assert!(KvRouterConfig::default().validate().is_ok());
+
+        let negative_temperature =
+            r#"{"schema_version": 1, "kv_router": {"router_temperature": -0.5}}"#;
+        assert!(KvRouterConfig::from_document(negative_temperature).is_err());
+
+        let newer = format!(
+            r#"{{"schema_version": {}, "kv_router": {{}}}}"#,
+            CONFIG_DOCUMENT_SCHEMA_VERSION + 1
+        );
+        let error = KvRouterConfig::from_document(&newer).unwrap_err();
+        assert!(error.to_string().contains("schema version"), "{error}");
+    }
+}
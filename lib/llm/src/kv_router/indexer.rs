@@ -32,16 +32,16 @@
 //! This module provides a scalable and efficient way to manage and retrieve data blocks for LLM inference, leveraging a global KV cache to optimize performance.
 
 use async_trait::async_trait;
-use bytes::Bytes;
 use dynamo_runtime::{
     component::Component,
     metrics::{MetricsRegistry, prometheus_names::kvrouter},
 };
-use prometheus::{IntCounterVec, Opts};
+use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts};
 use serde::{Deserialize, Serialize};
 use std::{
-    cell::RefCell,
-    collections::{HashMap, VecDeque},
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
     iter,
     rc::Rc,
     sync::{Arc, OnceLock},
@@ -52,10 +52,13 @@ use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
 use xxhash_rust::xxh3;
 
-pub const XXH3_SEED: u64 = 1337;
+pub const XXH3_SEED: u64 = DEFAULT_SEQUENCE_HASHER_SEED;
 
 use crate::kv_router::protocols::*;
 use crate::tokens::SequenceHash;
+use crate::tokens::hasher::{
+    DEFAULT_SEQUENCE_HASHER, DEFAULT_SEQUENCE_HASHER_SEED, SequenceHasher, Xxh3SequenceHasher,
+};
 
 /// Errors that can occur in the KV Router.
 #[derive(Debug, thiserror::Error)]
@@ -78,6 +81,9 @@ pub enum KvCacheEventError {
 
     #[error("Failed to find block")]
     BlockNotFound,
+
+    #[error("Event was hashed with a different sequence hasher")]
+    HasherMismatch,
 }
 
 /// A shared reference to a [`RadixBlock`].
@@ -120,16 +126,19 @@ pub fn compute_block_hash(data: &[u8]) -> LocalBlockHash {
 ///
 /// A vector of `LocalBlockHash` representing the computed hashes for each chunk of tokens.
 pub fn compute_block_hash_for_seq(tokens: &[u32], kv_block_size: u32) -> Vec<LocalBlockHash> {
-    tokens
-        .chunks_exact(kv_block_size as usize) // Split into chunks of kv_block_size elements
-        .map(|chunk| {
-            let bytes: Vec<u8> = chunk
-                .iter()
-                .flat_map(|&num| num.to_le_bytes()) // Convert each i32 to its little-endian bytes
-                .collect();
+    compute_block_hash_for_seq_with(&DEFAULT_SEQUENCE_HASHER, tokens, kv_block_size)
+}
 
-            compute_block_hash(&Bytes::from(bytes)) // Convert the byte Vec to Bytes
-        })
+/// Compute the hash for a sequence of tokens using the given [`SequenceHasher`].
+pub fn compute_block_hash_for_seq_with(
+    hasher: &dyn SequenceHasher,
+    tokens: &[u32],
+    kv_block_size: u32,
+) -> Vec<LocalBlockHash> {
+    hasher
+        .block_hashes(tokens, kv_block_size as usize)
+        .into_iter()
+        .map(LocalBlockHash)
         .collect()
 }
 
@@ -147,24 +156,16 @@ pub fn compute_block_hash_for_seq(tokens: &[u32], kv_block_size: u32) -> Vec<Loc
 ///
 /// A vector of u64 values representing the sequence hashes for each block.
 pub fn compute_seq_hash_for_block(block_hashes: &[LocalBlockHash]) -> Vec<SequenceHash> {
-    if block_hashes.is_empty() {
-        return Vec::new();
-    }
-
-    let mut sequence_hashes = Vec::with_capacity(block_hashes.len());
-    sequence_hashes.push(block_hashes[0].0);
-
-    for i in 1..block_hashes.len() {
-        let parent_seq_hash = sequence_hashes[i - 1];
-        let current_block_hash = block_hashes[i].0;
-
-        let combined = [parent_seq_hash, current_block_hash];
-        let bytes: Vec<u8> = combined.iter().flat_map(|&num| num.to_le_bytes()).collect();
-        let seq_hash = compute_hash(&bytes);
-        sequence_hashes.push(seq_hash);
-    }
+    compute_seq_hash_for_block_with(&DEFAULT_SEQUENCE_HASHER, block_hashes)
+}
 
-    sequence_hashes
+/// Compute rolling sequence hashes for a vector of block hashes using the given [`SequenceHasher`].
+pub fn compute_seq_hash_for_block_with(
+    hasher: &dyn SequenceHasher,
+    block_hashes: &[LocalBlockHash],
+) -> Vec<SequenceHash> {
+    let block_hashes: Vec<u64> = block_hashes.iter().map(|h| h.0).collect();
+    hasher.sequence_hashes(&block_hashes)
 }
 
 /// A [`KvCacheEvent`] on a specific LLM worker denoted by [`WorkerId`].
@@ -174,6 +175,9 @@ pub struct RouterEvent {
     worker_id: WorkerId,
     /// The cache event associated with the worker.
     event: KvCacheEvent,
+    /// The [`SequenceHasher::algorithm_id`] used to compute the block hashes, if known.
+    #[serde(default)]
+    hasher_id: Option<String>,
 }
 
 impl RouterEvent {
@@ -188,7 +192,27 @@ impl RouterEvent {
     ///
     /// A new `RouterEvent`.
     pub fn new(worker_id: WorkerId, event: KvCacheEvent) -> Self {
-        Self { worker_id, event }
+        Self {
+            worker_id,
+            event,
+            hasher_id: None,
+        }
+    }
+
+    /// Tag the event with the algorithm id of the hasher that produced its block hashes.
+    pub fn with_hasher_id(mut self, hasher_id: impl Into<String>) -> Self {
+        self.hasher_id = Some(hasher_id.into());
+        self
+    }
+
+    /// The algorithm id of the hasher that produced this event, if it was tagged.
+    pub fn hasher_id(&self) -> Option<&str> {
+        self.hasher_id.as_deref()
+    }
+
+    /// The ID of the worker emitting the event.
+    pub fn worker_id(&self) -> WorkerId {
+        self.worker_id
     }
 }
 
@@ -202,6 +226,8 @@ struct RadixBlock {
     workers: HashMap<WorkerWithDpRank, ExternalSequenceBlockHash>,
     /// A buffer of times that this block was last traversed
     recent_uses: VecDeque<Instant>,
+    /// Tick of the tree clock at which this block was last stored or matched
+    last_hit: u64,
 }
 
 impl RadixBlock {
@@ -215,6 +241,7 @@ impl RadixBlock {
             children: HashMap::new(),
             workers: HashMap::new(),
             recent_uses: VecDeque::new(),
+            last_hit: 0,
         }
     }
 }
@@ -235,6 +262,17 @@ pub struct RadixTree {
     lookup: HashMap<WorkerWithDpRank, HashMap<ExternalSequenceBlockHash, SharedRadixBlock>>,
     /// The time buffer the radix tree should check when considering frequence of block accesses
     expiration_duration: Option<Duration>,
+    /// The hasher algorithm id events must match. Untagged events are always accepted.
+    sequence_hasher_id: Option<String>,
+    /// Workers already reported for publishing events with a mismatched hasher
+    hasher_mismatch_reported: HashSet<WorkerId>,
+    /// Maximum number of blocks (summed over workers) the tree holds before evicting the least
+    /// recently hit ones. None means unbounded.
+    max_blocks: Option<usize>,
+    /// Logical clock advanced by every store and match, ordering blocks by recency
+    clock: Cell<u64>,
+    /// Total number of blocks evicted to stay under `max_blocks`
+    evicted_blocks: u64,
 }
 
 impl Default for RadixTree {
@@ -254,9 +292,27 @@ impl RadixTree {
             root: Rc::new(RefCell::new(RadixBlock::new())),
             lookup: HashMap::new(),
             expiration_duration,
+            sequence_hasher_id: None,
+            hasher_mismatch_reported: HashSet::new(),
+            max_blocks: None,
+            clock: Cell::new(0),
+            evicted_blocks: 0,
         }
     }
 
+    /// Bound the tree to `max_blocks` blocks, summed over workers. Past it, the least recently
+    /// hit blocks are evicted leaf first, so cold subtrees go before the prefixes they extend.
+    pub fn with_max_blocks(mut self, max_blocks: Option<usize>) -> Self {
+        self.max_blocks = max_blocks;
+        self
+    }
+
+    /// Only accept events tagged with this hasher algorithm id (or untagged events).
+    pub fn with_sequence_hasher_id(mut self, hasher_id: impl Into<String>) -> Self {
+        self.sequence_hasher_id = Some(hasher_id.into());
+        self
+    }
+
     pub fn new() -> Self {
         Self::new_with_frequency(None)
     }
@@ -275,6 +331,7 @@ impl RadixTree {
         let mut scores = OverlapScores::new();
         let mut current = self.root.clone();
         let now = Instant::now();
+        let tick = self.tick();
 
         tracing::trace!(
             "RadixTree::find_matches: looking for sequence={:?}",
@@ -288,6 +345,7 @@ impl RadixTree {
             };
             if let Some(block) = next_block {
                 scores.update_scores(block.borrow().workers.keys());
+                block.borrow_mut().last_hit = tick;
 
                 if let Some(expiration_duration) = self.expiration_duration {
                     let mut block_mut = block.borrow_mut();
@@ -329,6 +387,21 @@ impl RadixTree {
     ///
     /// * `event` - The `RouterEvent` to apply.
     pub fn apply_event(&mut self, event: RouterEvent) -> Result<(), KvCacheEventError> {
+        if let (Some(expected), Some(actual)) = (&self.sequence_hasher_id, &event.hasher_id)
+            && expected != actual
+        {
+            if self.hasher_mismatch_reported.insert(event.worker_id) {
+                tracing::error!(
+                    worker_id = event.worker_id,
+                    expected_hasher = %expected,
+                    actual_hasher = %actual,
+                    "Dropping KV events from worker using a different sequence hasher; \
+                    its blocks will never match router requests"
+                );
+            }
+            return Err(KvCacheEventError::HasherMismatch);
+        }
+
         let (worker_id, kv_event) = (event.worker_id, event.event);
         let (id, op) = (kv_event.event_id, kv_event.data);
 
@@ -337,6 +410,7 @@ impl RadixTree {
 
         tracing::trace!(id, "RadixTree::apply_event: Store operation: {:?}", op);
 
+        let tick = self.tick();
         let worker_lookup = self.lookup.entry(worker).or_default();
 
         match op {
@@ -384,10 +458,11 @@ impl RadixTree {
                     };
 
                     // add our worker to the block with its external hash
-                    block
-                        .borrow_mut()
-                        .workers
-                        .insert(worker, block_id.block_hash);
+                    {
+                        let mut block_mut = block.borrow_mut();
+                        block_mut.workers.insert(worker, block_id.block_hash);
+                        block_mut.last_hit = tick;
+                    }
 
                     // add the block to the worker_id lookup table
                     worker_lookup.insert(block_id.block_hash, block.clone());
@@ -397,6 +472,7 @@ impl RadixTree {
 
                     current = block;
                 }
+                self.enforce_max_blocks();
                 Ok(())
             }
             KvCacheEventData::Removed(remove) => {
@@ -472,6 +548,123 @@ impl RadixTree {
         self.remove_or_clear_worker_blocks(worker_id, false);
     }
 
+    /// Evict the block with external hash `block_hash` from every worker holding it, along with
+    /// the blocks each of them stored after it, without waiting for the workers to emit removal
+    /// events, e.g. after a model reload invalidated their cache. Later matches stop before the
+    /// evicted block. Returns the number of blocks evicted, summed over workers.
+    pub fn evict_sequence(&mut self, block_hash: ExternalSequenceBlockHash) -> usize {
+        let mut evicted = 0;
+        for (worker, worker_lookup) in self.lookup.iter_mut() {
+            let Some(block) = worker_lookup.get(&block_hash).cloned() else {
+                continue;
+            };
+            let mut stack = vec![block];
+            while let Some(block) = stack.pop() {
+                let mut guard = block.borrow_mut();
+                let Some(external_hash) = guard.workers.remove(worker) else {
+                    continue;
+                };
+                worker_lookup.remove(&external_hash);
+                evicted += 1;
+                stack.extend(guard.children.values().cloned());
+                // if no workers are using this block, that is true for all children
+                if guard.workers.is_empty() {
+                    guard.children.clear();
+                }
+            }
+        }
+        evicted
+    }
+
+    /// Advance the logical clock, returning the new tick
+    fn tick(&self) -> u64 {
+        let tick = self.clock.get() + 1;
+        self.clock.set(tick);
+        tick
+    }
+
+    /// Number of blocks in the tree, summed over workers: a block cached by two workers counts
+    /// twice
+    pub fn num_blocks(&self) -> usize {
+        self.lookup.values().map(HashMap::len).sum()
+    }
+
+    /// Total number of blocks evicted to stay under the bound set by [`Self::with_max_blocks`]
+    pub fn evicted_blocks(&self) -> u64 {
+        self.evicted_blocks
+    }
+
+    /// Evict the least recently hit leaves until the tree is back to 90% of `max_blocks`, so that
+    /// evictions happen in batches rather than on every store. A block is always hit at least as
+    /// recently as its children, so evicting the coldest leaf first, and its parent once it has
+    /// become a leaf, removes whole cold subtrees before the prefixes other requests still match.
+    fn enforce_max_blocks(&mut self) {
+        let Some(max_blocks) = self.max_blocks else {
+            return;
+        };
+        let mut num_blocks = self.num_blocks();
+        if num_blocks <= max_blocks {
+            return;
+        }
+        let target = max_blocks - max_blocks / 10;
+        let evicted_before = self.evicted_blocks;
+
+        // Every block reachable from the root: (index of its parent, parent, key in the parent,
+        // block). The parent index is None for the children of the root.
+        let mut nodes: Vec<(
+            Option<usize>,
+            SharedRadixBlock,
+            LocalBlockHash,
+            SharedRadixBlock,
+        )> = Vec::new();
+        let mut stack = vec![(None, self.root.clone())];
+        while let Some((index, block)) = stack.pop() {
+            for (tokens_hash, child) in &block.borrow().children {
+                stack.push((Some(nodes.len()), child.clone()));
+                nodes.push((index, block.clone(), *tokens_hash, child.clone()));
+            }
+        }
+
+        let mut leaves: BinaryHeap<Reverse<(u64, usize)>> = nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, (.., block))| block.borrow().children.is_empty())
+            .map(|(index, (.., block))| Reverse((block.borrow().last_hit, index)))
+            .collect();
+
+        while num_blocks > target {
+            let Some(Reverse((_, index))) = leaves.pop() else {
+                break;
+            };
+            let (parent_index, parent, tokens_hash, leaf) = &nodes[index];
+            for (worker, block_hash) in &leaf.borrow().workers {
+                let Some(worker_lookup) = self.lookup.get_mut(worker) else {
+                    continue;
+                };
+                if worker_lookup
+                    .get(block_hash)
+                    .is_some_and(|block| Rc::ptr_eq(block, leaf))
+                {
+                    worker_lookup.remove(block_hash);
+                    num_blocks -= 1;
+                    self.evicted_blocks += 1;
+                }
+            }
+            parent.borrow_mut().children.remove(tokens_hash);
+
+            if let Some(parent_index) = *parent_index
+                && parent.borrow().children.is_empty()
+            {
+                leaves.push(Reverse((parent.borrow().last_hit, parent_index)));
+            }
+        }
+
+        tracing::debug!(
+            "Evicted {} blocks from the radix tree to stay under {max_blocks} blocks",
+            self.evicted_blocks - evicted_before
+        );
+    }
+
     pub fn clear_all_blocks(&mut self, worker_id: WorkerId) {
         self.remove_or_clear_worker_blocks(worker_id, true);
     }
@@ -537,6 +730,7 @@ impl RadixTree {
                         }),
                         dp_rank: worker_id.dp_rank,
                     },
+                    hasher_id: self.sequence_hasher_id.clone(),
                 };
                 events.push(event);
                 event_id += 1;
@@ -564,12 +758,29 @@ impl RadixTree {
 pub struct KvIndexerMetrics {
     /// Counter of events applied.
     pub kv_cache_events_applied: IntCounterVec,
+    /// Number of blocks in the radix tree, summed over workers.
+    pub tree_blocks: IntGauge,
+    /// Counter of blocks evicted to keep the radix tree under its size bound.
+    pub tree_blocks_evicted: IntCounter,
+    /// Number of KV events waiting to be applied by the indexer.
+    pub event_queue_depth: IntGauge,
+    /// Counter of KV events dropped because the indexer could not keep up.
+    pub events_dropped: IntCounter,
+}
+
+/// Tree size last reported by an indexer task. The metrics are updated by the difference, so
+/// that the trees of the shards of a sharded indexer add up.
+#[derive(Default)]
+struct TreeSizeReport {
+    blocks: usize,
+    evicted: u64,
 }
 
 /// Metric status labels.
 pub const METRIC_STATUS_OK: &str = "ok";
 pub const METRIC_STATUS_PARENT_NOT_FOUND: &str = "parent_block_not_found";
 pub const METRIC_STATUS_BLOCK_NOT_FOUND: &str = "block_not_found";
+pub const METRIC_STATUS_HASHER_MISMATCH: &str = "hasher_mismatch";
 
 /// Metric event labels.
 pub const METRIC_EVENT_STORED: &str = "stored";
@@ -579,9 +790,19 @@ pub const METRIC_EVENT_CLEARED: &str = "cleared";
 static KV_INDEXER_METRICS: OnceLock<Arc<KvIndexerMetrics>> = OnceLock::new();
 
 impl KvIndexerMetrics {
-    fn new(kv_cache_events_applied: IntCounterVec) -> Self {
+    fn new(
+        kv_cache_events_applied: IntCounterVec,
+        tree_blocks: IntGauge,
+        tree_blocks_evicted: IntCounter,
+        event_queue_depth: IntGauge,
+        events_dropped: IntCounter,
+    ) -> Self {
         Self {
             kv_cache_events_applied,
+            tree_blocks,
+            tree_blocks_evicted,
+            event_queue_depth,
+            events_dropped,
         }
     }
 
@@ -589,13 +810,40 @@ impl KvIndexerMetrics {
     /// KV_INDEXER_METRICS to avoid duplicate registration issues.
     pub fn from_component(component: &Component) -> Arc<Self> {
         KV_INDEXER_METRICS.get_or_init(|| {
-            match component.create_intcountervec(
-                kvrouter::KV_CACHE_EVENTS_APPLIED,
-                "Total number of KV cache events applied to index",
-                &["event_type", "status"],
-                &[],
-            ) {
-                Ok(kv_cache_events_applied) => Arc::new(Self::new(kv_cache_events_applied)),
+            let metrics = component
+                .create_intcountervec(
+                    kvrouter::KV_CACHE_EVENTS_APPLIED,
+                    "Total number of KV cache events applied to index",
+                    &["event_type", "status"],
+                    &[],
+                )
+                .and_then(|kv_cache_events_applied| {
+                    Ok(Self::new(
+                        kv_cache_events_applied,
+                        component.create_intgauge(
+                            kvrouter::INDEXER_TREE_BLOCKS,
+                            "Number of blocks in the radix tree of the indexer, summed over workers",
+                            &[],
+                        )?,
+                        component.create_intcounter(
+                            kvrouter::INDEXER_TREE_BLOCKS_EVICTED,
+                            "Total number of blocks evicted to keep the radix tree under its size bound",
+                            &[],
+                        )?,
+                        component.create_intgauge(
+                            kvrouter::INDEXER_EVENT_QUEUE_DEPTH,
+                            "Number of KV events waiting to be applied by the indexer",
+                            &[],
+                        )?,
+                        component.create_intcounter(
+                            kvrouter::INDEXER_EVENTS_DROPPED,
+                            "Total number of KV events dropped because the indexer could not keep up",
+                            &[],
+                        )?,
+                    ))
+                });
+            match metrics {
+                Ok(metrics) => Arc::new(metrics),
                 Err(e) => {
                     tracing::warn!("Failed to create kv indexer metrics from component: {}. Using unregistered metrics as fallback.", e);
                     Arc::new(Self::new_unregistered())
@@ -616,9 +864,38 @@ impl KvIndexerMetrics {
                 &["event_type", "status"],
             )
             .unwrap(),
+            tree_blocks: IntGauge::new(
+                kvrouter::INDEXER_TREE_BLOCKS,
+                "Number of blocks in the radix tree of the indexer, summed over workers",
+            )
+            .unwrap(),
+            tree_blocks_evicted: IntCounter::new(
+                kvrouter::INDEXER_TREE_BLOCKS_EVICTED,
+                "Total number of blocks evicted to keep the radix tree under its size bound",
+            )
+            .unwrap(),
+            event_queue_depth: IntGauge::new(
+                kvrouter::INDEXER_EVENT_QUEUE_DEPTH,
+                "Number of KV events waiting to be applied by the indexer",
+            )
+            .unwrap(),
+            events_dropped: IntCounter::new(
+                kvrouter::INDEXER_EVENTS_DROPPED,
+                "Total number of KV events dropped because the indexer could not keep up",
+            )
+            .unwrap(),
         }
     }
 
+    /// Update the tree size metrics with the changes of `trie` since `last`
+    fn report_tree_size(&self, trie: &RadixTree, last: &mut TreeSizeReport) {
+        let blocks = trie.num_blocks();
+        let evicted = trie.evicted_blocks();
+        self.tree_blocks.add(blocks as i64 - last.blocks as i64);
+        self.tree_blocks_evicted.inc_by(evicted - last.evicted);
+        *last = TreeSizeReport { blocks, evicted };
+    }
+
     pub fn get_event_type(event_data: &KvCacheEventData) -> &'static str {
         match event_data {
             KvCacheEventData::Stored(_) => METRIC_EVENT_STORED,
@@ -642,6 +919,7 @@ impl KvIndexerMetrics {
                 let error_label = match e {
                     KvCacheEventError::ParentBlockNotFound => METRIC_STATUS_PARENT_NOT_FOUND,
                     KvCacheEventError::BlockNotFound => METRIC_STATUS_BLOCK_NOT_FOUND,
+                    KvCacheEventError::HasherMismatch => METRIC_STATUS_HASHER_MISMATCH,
                 };
                 self.kv_cache_events_applied
                     .with_label_values(&[event_type, error_label])
@@ -652,6 +930,9 @@ impl KvIndexerMetrics {
 }
 
 /// Scores representing the overlap of workers (with their dp_rank).
+///
+/// Overlap is always matched from the start of the request, so a worker with a score of `n`
+/// holds exactly the blocks at positions `0..n`, see [`OverlapScores::overlap_positions`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OverlapScores {
     // map of worker (with dp_rank) to score
@@ -694,6 +975,40 @@ impl OverlapScores {
         }
     }
 
+    /// Merge the worker scores reported by another source, such as another indexer shard.
+    /// Workers known to a single source keep that score; workers scored by both, e.g. by two
+    /// shards which briefly disagree while the worker is moved between them, keep the best
+    /// locality either reports. A stale source may overstate the overlap by the blocks evicted
+    /// since, which only costs some re-prefill. Frequencies are left untouched.
+    pub fn merge_scores(&mut self, scores: HashMap<WorkerWithDpRank, u32>) {
+        for (worker, score) in scores {
+            self.scores
+                .entry(worker)
+                .and_modify(|current| *current = (*current).max(score))
+                .or_insert(score);
+        }
+    }
+
+    /// Best score of each worker across its dp ranks
+    pub fn worker_scores(&self) -> HashMap<WorkerId, u32> {
+        let mut scores: HashMap<WorkerId, u32> = HashMap::new();
+        for (worker, score) in &self.scores {
+            let best = scores.entry(worker.worker_id).or_default();
+            *best = (*best).max(*score);
+        }
+        scores
+    }
+
+    /// Positions within the request of the blocks a worker has cached
+    pub fn overlap_positions(&self, worker: &WorkerWithDpRank) -> std::ops::Range<usize> {
+        0..self.scores.get(worker).copied().unwrap_or(0) as usize
+    }
+
+    /// Overlap of a worker with each cached block counted as `weight(position)` instead of 1
+    pub fn weighted_score(&self, worker: &WorkerWithDpRank, weight: impl Fn(usize) -> f64) -> f64 {
+        self.overlap_positions(worker).map(weight).sum()
+    }
+
     /// Add an entry in the frequency list.
     pub fn add_frequency(&mut self, frequency: usize) {
         if frequency != 0 {
@@ -721,12 +1036,37 @@ pub struct DumpRequest {
     pub resp: oneshot::Sender<Vec<RouterEvent>>,
 }
 
+/// A request to evict a sequence from the tree, see [`RadixTree::evict_sequence`]
+pub struct EvictSequenceRequest {
+    pub block_hash: ExternalSequenceBlockHash,
+    /// Channel to send the number of evicted blocks
+    pub resp: oneshot::Sender<usize>,
+}
+
 /// A request to get all workers currently tracked
 pub struct GetWorkersRequest {
     /// Channel to send the worker IDs
     pub resp: oneshot::Sender<Vec<WorkerId>>,
 }
 
+/// Ask the indexer task behind `get_workers_tx` for the workers in its tree
+pub(crate) async fn request_workers(
+    get_workers_tx: &mpsc::Sender<GetWorkersRequest>,
+) -> Result<Vec<WorkerId>, KvRouterError> {
+    let (resp_tx, resp_rx) = oneshot::channel();
+    if let Err(e) = get_workers_tx
+        .send(GetWorkersRequest { resp: resp_tx })
+        .await
+    {
+        tracing::error!("Failed to send get workers request: {:?}", e);
+        return Err(KvRouterError::IndexerOffline);
+    }
+
+    resp_rx
+        .await
+        .map_err(|_| KvRouterError::IndexerDroppedRequest)
+}
+
 #[async_trait]
 pub trait KvIndexerInterface {
     /// Find matches for a given sequence of `LocalBlockHash`es.
@@ -782,6 +1122,90 @@ pub trait KvIndexerInterface {
     async fn dump_events(&self) -> Result<Vec<RouterEvent>, KvRouterError>;
 }
 
+/// Default number of KV events which may wait to be applied by the indexer
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 2048;
+
+/// Capacity of the channel feeding [`start_event_shedding`], which drains it without waiting
+/// on the indexer
+const EVENT_SHEDDING_INTAKE_CAPACITY: usize = 64;
+
+/// What happens to the KV events consumed by the router while the indexer's queue is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventBackpressurePolicy {
+    /// Wait for the indexer, which in turn stops consuming the event stream until it catches up.
+    /// No event is lost, but the radix tree lags further behind the workers.
+    #[default]
+    Block,
+    /// Drop the oldest queued event to make room, keeping the tree closest to the current state
+    /// of the workers at the cost of missing older stores and removals
+    DropOldest,
+    /// Drop the incoming event, counted in the dropped events metric
+    DropNewest,
+}
+
+/// Queue the events sent on the returned channel for `indexer_tx`, up to `capacity` of them,
+/// shedding events per `policy` once full rather than blocking the sender. Only meaningful for
+/// the dropping policies; under [`EventBackpressurePolicy::Block`] send to the indexer directly.
+pub fn start_event_shedding(
+    indexer_tx: mpsc::Sender<RouterEvent>,
+    capacity: usize,
+    policy: EventBackpressurePolicy,
+    metrics: Arc<KvIndexerMetrics>,
+    cancel: CancellationToken,
+) -> mpsc::Sender<RouterEvent> {
+    let (shed_tx, mut shed_rx) = mpsc::channel::<RouterEvent>(EVENT_SHEDDING_INTAKE_CAPACITY);
+
+    tokio::spawn(async move {
+        let mut pending: VecDeque<RouterEvent> = VecDeque::with_capacity(capacity);
+        let mut dropped: u64 = 0;
+        loop {
+            tokio::select! {
+                biased;
+
+                _ = cancel.cancelled() => break,
+
+                permit = indexer_tx.reserve(), if !pending.is_empty() => {
+                    let Ok(permit) = permit else { break };
+                    permit.send(pending.pop_front().expect("pending checked non-empty"));
+                    metrics.event_queue_depth.dec();
+                }
+
+                event = shed_rx.recv() => {
+                    let Some(event) = event else {
+                        // The senders are gone, hand the queued events over before stopping
+                        while let Some(event) = pending.pop_front() {
+                            metrics.event_queue_depth.dec();
+                            if indexer_tx.send(event).await.is_err() {
+                                break;
+                            }
+                        }
+                        break;
+                    };
+                    if pending.len() >= capacity {
+                        metrics.events_dropped.inc();
+                        dropped += 1;
+                        if dropped.is_power_of_two() {
+                            tracing::warn!(
+                                "KV indexer is falling behind, {dropped} events dropped ({policy:?})"
+                            );
+                        }
+                        if policy == EventBackpressurePolicy::DropNewest {
+                            continue;
+                        }
+                        pending.pop_front();
+                        metrics.event_queue_depth.dec();
+                    }
+                    pending.push_back(event);
+                    metrics.event_queue_depth.inc();
+                }
+            }
+        }
+        metrics.event_queue_depth.sub(pending.len() as i64);
+    });
+    shed_tx
+}
+
 /// The KV Indexer, managing the KV store and handling events and match requests.
 pub struct KvIndexer {
     /// A `CancellationToken` for managing shutdown.
@@ -796,10 +1220,14 @@ pub struct KvIndexer {
     get_workers_tx: mpsc::Sender<GetWorkersRequest>,
     /// A sender for dump requests.
     dump_tx: mpsc::Sender<DumpRequest>,
+    /// A sender for sequence eviction requests.
+    evict_sequence_tx: mpsc::Sender<EvictSequenceRequest>,
     /// A handle to the background task managing the KV store.
     task: OnceLock<std::thread::JoinHandle<()>>,
     /// The size of the KV block this indexer can handle.
     kv_block_size: u32,
+    /// The hasher events are expected to be produced with.
+    sequence_hasher: Arc<dyn SequenceHasher>,
 }
 
 impl KvIndexer {
@@ -819,11 +1247,36 @@ impl KvIndexer {
         kv_block_size: u32,
         metrics: Arc<KvIndexerMetrics>,
     ) -> Self {
-        let (event_tx, event_rx) = mpsc::channel::<RouterEvent>(2048);
+        Self::new_with_sequence_hasher(
+            token,
+            expiration_duration,
+            kv_block_size,
+            metrics,
+            Arc::new(Xxh3SequenceHasher::default()),
+            None,
+            DEFAULT_EVENT_CHANNEL_CAPACITY,
+        )
+    }
+
+    /// Create a new `KvIndexer` which drops events tagged with a hasher other than `sequence_hasher`.
+    /// When `max_tree_blocks` is set, the radix tree evicts its least recently hit blocks past it.
+    /// Up to `event_capacity` events wait to be applied before their senders block.
+    pub fn new_with_sequence_hasher(
+        token: CancellationToken,
+        expiration_duration: Option<Duration>,
+        kv_block_size: u32,
+        metrics: Arc<KvIndexerMetrics>,
+        sequence_hasher: Arc<dyn SequenceHasher>,
+        max_tree_blocks: Option<usize>,
+        event_capacity: usize,
+    ) -> Self {
+        let hasher_id = sequence_hasher.algorithm_id().into_owned();
+        let (event_tx, event_rx) = mpsc::channel::<RouterEvent>(event_capacity);
         let (match_tx, match_rx) = mpsc::channel::<MatchRequest>(128);
         let (remove_worker_tx, remove_worker_rx) = mpsc::channel::<WorkerId>(16);
         let (get_workers_tx, get_workers_rx) = mpsc::channel::<GetWorkersRequest>(16);
         let (dump_tx, dump_rx) = mpsc::channel::<DumpRequest>(16);
+        let (evict_sequence_tx, evict_sequence_rx) = mpsc::channel::<EvictSequenceRequest>(16);
         let cancel_clone = token.clone();
 
         let task = std::thread::spawn(move || {
@@ -840,18 +1293,25 @@ impl KvIndexer {
                 let mut remove_worker_rx = remove_worker_rx;
                 let mut get_workers_rx = get_workers_rx;
                 let mut dump_rx = dump_rx;
-                let mut trie = RadixTree::new_with_frequency(expiration_duration);
+                let mut evict_sequence_rx = evict_sequence_rx;
+                let mut trie = RadixTree::new_with_frequency(expiration_duration)
+                    .with_sequence_hasher_id(hasher_id)
+                    .with_max_blocks(max_tree_blocks);
+                let mut tree_size = TreeSizeReport::default();
+                let mut queue_depth = 0;
                 loop {
                     tokio::select! {
                         biased;
 
                         _ = cancel.cancelled() => {
                             tracing::debug!("KvCacheIndexer progress loop shutting down");
+                            metrics.event_queue_depth.sub(queue_depth as i64);
                             return;
                         }
 
                         Some(worker) = remove_worker_rx.recv() => {
                             trie.remove_worker(worker);
+                            metrics.report_tree_size(&trie, &mut tree_size);
                         }
 
                         Some(get_workers_req) = get_workers_rx.recv() => {
@@ -863,6 +1323,10 @@ impl KvIndexer {
                             let event_type = KvIndexerMetrics::get_event_type(&event.event.data);
                             let result = trie.apply_event(event);
                             metrics.increment_event_applied(event_type, result);
+                            metrics.report_tree_size(&trie, &mut tree_size);
+                            let depth = event_rx.len();
+                            metrics.event_queue_depth.add(depth as i64 - queue_depth as i64);
+                            queue_depth = depth;
                         }
 
                         Some(dump_req) = dump_rx.recv() => {
@@ -870,6 +1334,12 @@ impl KvIndexer {
                             let _ = dump_req.resp.send(events);
                         }
 
+                        Some(evict_req) = evict_sequence_rx.recv() => {
+                            let evicted = trie.evict_sequence(evict_req.block_hash);
+                            metrics.report_tree_size(&trie, &mut tree_size);
+                            let _ = evict_req.resp.send(evicted);
+                        }
+
                         Some(req) = match_rx.recv() => {
                             let matches = trie.find_matches(req.sequence, req.early_exit);
                             let _ = req.resp.send(matches);
@@ -891,8 +1361,10 @@ impl KvIndexer {
             remove_worker_tx,
             get_workers_tx,
             dump_tx,
+            evict_sequence_tx,
             task: once,
             kv_block_size,
+            sequence_hasher,
         }
     }
 
@@ -900,6 +1372,11 @@ impl KvIndexer {
         self.kv_block_size
     }
 
+    /// The hasher events are expected to be produced with.
+    pub fn sequence_hasher(&self) -> Arc<dyn SequenceHasher> {
+        self.sequence_hasher.clone()
+    }
+
     pub fn new(
         token: CancellationToken,
         kv_block_size: u32,
@@ -943,6 +1420,32 @@ impl KvIndexer {
     pub fn get_workers_sender(&self) -> mpsc::Sender<GetWorkersRequest> {
         self.get_workers_tx.clone()
     }
+
+    /// Workers with at least one block in the tree
+    pub async fn get_workers(&self) -> Result<Vec<WorkerId>, KvRouterError> {
+        request_workers(&self.get_workers_tx).await
+    }
+
+    /// Evict a sequence from the tree, returning the number of evicted blocks. See
+    /// [`RadixTree::evict_sequence`].
+    pub async fn evict_sequence(
+        &self,
+        block_hash: ExternalSequenceBlockHash,
+    ) -> Result<usize, KvRouterError> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        let req = EvictSequenceRequest {
+            block_hash,
+            resp: resp_tx,
+        };
+        if let Err(e) = self.evict_sequence_tx.send(req).await {
+            tracing::error!("Failed to send evict sequence request: {:?}", e);
+            return Err(KvRouterError::IndexerOffline);
+        }
+
+        resp_rx
+            .await
+            .map_err(|_| KvRouterError::IndexerDroppedRequest)
+    }
 }
 
 #[async_trait]
@@ -980,7 +1483,11 @@ impl KvIndexerInterface for KvIndexer {
             tokens,
             tokens.len()
         );
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq_with(
+            self.sequence_hasher.as_ref(),
+            tokens,
+            self.kv_block_size,
+        );
         tracing::debug!("Computed sequence: {:?}", sequence);
         self.find_matches(sequence).await
     }
@@ -1055,6 +1562,8 @@ pub struct KvIndexerSharded {
     remove_worker_tx: Vec<mpsc::Sender<WorkerId>>,
     dump_tx: Vec<mpsc::Sender<DumpRequest>>,
     tasks: Vec<JoinHandle<()>>,
+    /// The hasher events are expected to be produced with.
+    sequence_hasher: Arc<dyn SequenceHasher>,
 }
 
 impl KvIndexerSharded {
@@ -1076,6 +1585,27 @@ impl KvIndexerSharded {
         kv_block_size: u32,
         metrics: Arc<KvIndexerMetrics>,
     ) -> Self {
+        Self::new_with_sequence_hasher(
+            token,
+            num_shards,
+            expiration_duration,
+            kv_block_size,
+            metrics,
+            Arc::new(Xxh3SequenceHasher::default()),
+        )
+    }
+
+    /// Create a new `KvIndexerSharded` whose shards drop events tagged with a hasher other than
+    /// `sequence_hasher`, and which hashes request tokens with it.
+    pub fn new_with_sequence_hasher(
+        token: CancellationToken,
+        num_shards: usize,
+        expiration_duration: Option<Duration>,
+        kv_block_size: u32,
+        metrics: Arc<KvIndexerMetrics>,
+        sequence_hasher: Arc<dyn SequenceHasher>,
+    ) -> Self {
+        let hasher_id = sequence_hasher.algorithm_id().into_owned();
         let worker_assignments: HashMap<WorkerId, usize> = HashMap::new();
         let worker_counts: Vec<usize> = vec![0; num_shards];
 
@@ -1097,6 +1627,7 @@ impl KvIndexerSharded {
             let mut shard_broadcast_rx = request_broadcast_tx.subscribe();
             let cancel = token.clone();
             let metrics = metrics.clone();
+            let hasher_id = hasher_id.clone();
 
             event_tx.push(shard_event_tx);
             remove_worker_tx.push(shard_remove_worker_tx);
@@ -1110,7 +1641,9 @@ impl KvIndexerSharded {
 
             tasks.push(std::thread::spawn(move || {
                 runtime.block_on(async move {
-                    let mut trie = RadixTree::new_with_frequency(expiration_duration);
+                    let mut trie = RadixTree::new_with_frequency(expiration_duration)
+                        .with_sequence_hasher_id(hasher_id);
+                    let mut tree_size = TreeSizeReport::default();
                     loop {
                         tokio::select! {
                             biased;
@@ -1122,6 +1655,7 @@ impl KvIndexerSharded {
 
                             Some(worker) = shard_remove_worker_rx.recv() => {
                                 trie.remove_worker(worker);
+                                metrics.report_tree_size(&trie, &mut tree_size);
                             }
 
                             Some(get_workers_req) = shard_get_workers_rx.recv() => {
@@ -1133,6 +1667,7 @@ impl KvIndexerSharded {
                                 let event_type = KvIndexerMetrics::get_event_type(&event.event.data);
                                 let result = trie.apply_event(event);
                                 metrics.increment_event_applied(event_type, result);
+                                metrics.report_tree_size(&trie, &mut tree_size);
                             }
 
                             Some(dump_req) = shard_dump_rx.recv() => {
@@ -1164,6 +1699,7 @@ impl KvIndexerSharded {
             remove_worker_tx,
             dump_tx, // Add dump_tx field
             tasks,
+            sequence_hasher,
         }
     }
 
@@ -1202,7 +1738,7 @@ impl KvIndexerInterface for KvIndexerSharded {
             for response_num in 0..self.event_tx.len() {
                 match match_rx.recv().await {
                     Some(response) => {
-                        scores.scores.extend(response.scores);
+                        scores.merge_scores(response.scores);
 
                         if response_num == 0 {
                             scores.frequencies = response.frequencies;
@@ -1234,7 +1770,11 @@ impl KvIndexerInterface for KvIndexerSharded {
         &self,
         tokens: &[u32],
     ) -> Result<OverlapScores, KvRouterError> {
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_seq_with(
+            self.sequence_hasher.as_ref(),
+            tokens,
+            self.kv_block_size,
+        );
         self.find_matches(sequence).await
     }
 
@@ -1358,6 +1898,7 @@ mod tests {
                 data: add_blocks(hashes, parent),
                 dp_rank: 0,
             },
+            hasher_id: None,
         }
     }
 
@@ -1374,6 +1915,7 @@ mod tests {
                 }),
                 dp_rank: 0,
             },
+            hasher_id: None,
         }
     }
 
@@ -1698,6 +2240,33 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_radix_tree_rejects_mismatched_hasher() {
+        let mut trie = RadixTree::new().with_sequence_hasher_id("expected");
+        let worker_0 = 0;
+
+        let result = trie
+            .apply_event(create_store_event(worker_0, 0, vec![1, 2], None).with_hasher_id("other"));
+        assert!(matches!(result, Err(KvCacheEventError::HasherMismatch)));
+        assert!(trie.lookup.is_empty());
+
+        // Matching and untagged events are both accepted
+        trie.apply_event(create_store_event(worker_0, 1, vec![1], None).with_hasher_id("expected"))
+            .unwrap();
+        trie.apply_event(create_store_event(
+            worker_0,
+            2,
+            vec![2],
+            Some(ExternalSequenceBlockHash(100)),
+        ))
+        .unwrap();
+        let scores = trie.find_matches(vec![LocalBlockHash(1), LocalBlockHash(2)], false);
+        assert_eq!(
+            scores.scores[&WorkerWithDpRank::from_worker_id(worker_0)],
+            2
+        );
+    }
+
     #[test]
     fn test_remove_worker() {
         setup();
@@ -1730,6 +2299,81 @@ mod tests {
         assert!(result.len() == 1 && result[&WorkerWithDpRank::from_worker_id(worker_1)] == 1);
     }
 
+    #[test]
+    fn test_max_blocks_evicts_least_recently_hit() {
+        setup();
+        let mut trie = RadixTree::new().with_max_blocks(Some(10));
+        let hot: Vec<LocalBlockHash> = (1..=4).map(LocalBlockHash).collect();
+        let cold: Vec<LocalBlockHash> = (10..=13).map(LocalBlockHash).collect();
+
+        trie.apply_event(create_store_event(0, 0, vec![1, 2, 3, 4], None))
+            .unwrap();
+        trie.apply_event(create_store_event(1, 0, vec![10, 11, 12, 13], None))
+            .unwrap();
+        assert_eq!(trie.find_matches(hot.clone(), false).scores.len(), 1);
+        assert_eq!(trie.num_blocks(), 8);
+        assert_eq!(trie.evicted_blocks(), 0);
+
+        // Going over the bound evicts down to 90% of it, starting from the cold leaves
+        trie.apply_event(create_store_event(0, 1, vec![20, 21, 22], None))
+            .unwrap();
+        assert_eq!(trie.num_blocks(), 9);
+        assert_eq!(trie.evicted_blocks(), 2);
+
+        let scores = trie.find_matches(hot, false).scores;
+        assert_eq!(scores[&WorkerWithDpRank::from_worker_id(0)], 4);
+        let scores = trie.find_matches(cold, false).scores;
+        assert_eq!(scores[&WorkerWithDpRank::from_worker_id(1)], 2);
+
+        // The evicted blocks are gone from the lookup too: storing them again is accepted
+        trie.apply_event(create_store_event(
+            1,
+            1,
+            vec![12],
+            Some(ExternalSequenceBlockHash(1100)),
+        ))
+        .unwrap();
+        assert!(
+            trie.apply_event(create_remove_event(1, 2, vec![13]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_evict_sequence() {
+        setup();
+        let mut trie = RadixTree::new();
+        let sequence: Vec<LocalBlockHash> = (1..=4).map(LocalBlockHash).collect();
+        let worker_0 = WorkerWithDpRank::from_worker_id(0);
+        let worker_1 = WorkerWithDpRank::from_worker_id(1);
+
+        trie.apply_event(create_store_event(0, 0, vec![1, 2, 3, 4], None))
+            .unwrap();
+        trie.apply_event(create_store_event(1, 0, vec![1, 2], None))
+            .unwrap();
+
+        // Evicting the second block drops it and its descendants on both workers
+        assert_eq!(trie.evict_sequence(ExternalSequenceBlockHash(200)), 4);
+        let scores = trie.find_matches(sequence.clone(), false).scores;
+        assert_eq!(scores[&worker_0], 1);
+        assert_eq!(scores[&worker_1], 1);
+        assert_eq!(trie.num_blocks(), 2);
+
+        // Unknown sequences are a no-op
+        assert_eq!(trie.evict_sequence(ExternalSequenceBlockHash(200)), 0);
+
+        // The worker can store the evicted blocks again
+        trie.apply_event(create_store_event(
+            0,
+            1,
+            vec![2, 3],
+            Some(ExternalSequenceBlockHash(100)),
+        ))
+        .unwrap();
+        let scores = trie.find_matches(sequence, false).scores;
+        assert_eq!(scores[&worker_0], 3);
+    }
+
     #[test]
     fn test_clear_all_blocks() {
         let mut trie = RadixTree::new();
@@ -1946,6 +2590,42 @@ mod tests {
     ) {
     }
 
+    #[rstest]
+    #[case(EventBackpressurePolicy::DropOldest, vec![1, 4, 5])]
+    #[case(EventBackpressurePolicy::DropNewest, vec![1, 2, 3])]
+    #[tokio::test]
+    async fn test_event_shedding(
+        #[case] policy: EventBackpressurePolicy,
+        #[case] expected: Vec<u64>,
+    ) {
+        let metrics = Arc::new(KvIndexerMetrics::new_unregistered());
+        let (indexer_tx, mut indexer_rx) = mpsc::channel(1);
+        let shed_tx = start_event_shedding(
+            indexer_tx,
+            2,
+            policy,
+            metrics.clone(),
+            CancellationToken::new(),
+        );
+
+        // The indexer takes one event and stalls while two more are queued
+        for event_id in 1..=5 {
+            shed_tx
+                .send(create_store_event(0, event_id, vec![event_id], None))
+                .await
+                .unwrap();
+        }
+        drop(shed_tx);
+
+        let mut applied = Vec::new();
+        while let Some(event) = indexer_rx.recv().await {
+            applied.push(event.event.event_id);
+        }
+        assert_eq!(applied, expected);
+        assert_eq!(metrics.events_dropped.get(), 2);
+        assert_eq!(metrics.event_queue_depth.get(), 0);
+    }
+
     #[tokio::test]
     #[apply(indexer_template)]
     async fn test_kv_indexer_new(num_shards: usize, kv_block_size: u32) {
@@ -1995,6 +2675,39 @@ mod tests {
         // No assertion here, just ensuring it runs without panic
     }
 
+    #[tokio::test]
+    async fn test_kv_indexer_get_workers() {
+        setup();
+        let token = CancellationToken::new();
+        let kv_indexer = KvIndexer::new(
+            token.clone(),
+            32,
+            KvIndexerMetrics::new_unregistered().into(),
+        );
+        assert!(kv_indexer.get_workers().await.unwrap().is_empty());
+
+        let event_tx = kv_indexer.event_sender();
+        for worker_id in [7, 3] {
+            event_tx
+                .send(create_store_event(worker_id, 1, vec![1, 2, 3], None))
+                .await
+                .unwrap();
+        }
+
+        // Events are applied asynchronously by the indexer task
+        let mut workers = Vec::new();
+        for _ in 0..50 {
+            workers = kv_indexer.get_workers().await.unwrap();
+            if workers.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        workers.sort_unstable();
+        assert_eq!(workers, vec![3, 7]);
+        token.cancel();
+    }
+
     #[tokio::test]
     #[apply(indexer_template)]
     async fn test_shutdown(num_shards: usize, kv_block_size: u32) {
@@ -2156,6 +2869,114 @@ mod tests {
         assert!(overlap_scores.scores.is_empty());
     }
 
+    #[test]
+    fn test_overlap_scores_merge() {
+        setup();
+        let worker = |id| WorkerWithDpRank::from_worker_id(id);
+        let shard_a: HashMap<WorkerWithDpRank, u32> = [(worker(1), 4), (worker(2), 1)].into();
+        let shard_b: HashMap<WorkerWithDpRank, u32> = [(worker(1), 2), (worker(3), 5)].into();
+
+        // Only the worker scored by both shards is combined, whatever order they answer in
+        for (first, second) in [(&shard_a, &shard_b), (&shard_b, &shard_a)] {
+            let mut scores = OverlapScores::new();
+            scores.merge_scores(first.clone());
+            scores.merge_scores(second.clone());
+            assert_eq!(scores.scores[&worker(1)], 4);
+            assert_eq!(scores.scores[&worker(2)], 1);
+            assert_eq!(scores.scores[&worker(3)], 5);
+        }
+    }
+
+    #[test]
+    fn test_overlap_worker_scores() {
+        setup();
+        let mut scores = OverlapScores::new();
+        scores.scores = [
+            (WorkerWithDpRank::new(1, 0), 2),
+            (WorkerWithDpRank::new(1, 1), 5),
+            (WorkerWithDpRank::new(2, 0), 3),
+        ]
+        .into();
+
+        let worker_scores = scores.worker_scores();
+        assert_eq!(worker_scores, [(1, 5), (2, 3)].into());
+    }
+
+    /// On startup a router restores the snapshot and then consumes the live stream. Delivery is
+    /// at-least-once, not exactly-once: the stream can still hold events already folded into the
+    /// snapshot (consumed between the purge and the dump) and nothing deduplicates them. Whatever
+    /// the snapshot and the overlap replayed on top of it, the tree must converge to the one of
+    /// every live event applied once, replayed removes of blocks already gone being rejected
+    /// without effect like the indexer rejects them.
+    #[test]
+    fn test_snapshot_restore_converges_with_any_replayed_overlap() {
+        setup();
+
+        // Event ids are the positions in the live stream
+        let live = vec![
+            create_store_event(0, 0, vec![1, 2, 3], None),
+            create_store_event(1, 1, vec![1, 2], None),
+            create_store_event(0, 2, vec![4], Some(ExternalSequenceBlockHash(300))),
+            create_remove_event(0, 3, vec![4]),
+            create_store_event(1, 4, vec![5], Some(ExternalSequenceBlockHash(200))),
+            create_store_event(0, 5, vec![6], Some(ExternalSequenceBlockHash(300))),
+        ];
+
+        // Every live event applied exactly once
+        let mut expected = RadixTree::new();
+        for event in &live {
+            expected.apply_event(event.clone()).unwrap();
+        }
+
+        for snapshot_len in 0..=live.len() {
+            let mut source = RadixTree::new();
+            for event in &live[..snapshot_len] {
+                source.apply_event(event.clone()).unwrap();
+            }
+            let snapshot = source.dump_tree_as_events();
+
+            for replay_from in 0..=snapshot_len {
+                let context =
+                    format!("snapshot of {snapshot_len} events, replay from {replay_from}");
+                let mut restored = RadixTree::new();
+                for event in snapshot.clone() {
+                    restored.apply_event(event).unwrap();
+                }
+                for event in &live[replay_from..] {
+                    let replayed = (event.event.event_id as usize) < snapshot_len;
+                    match restored.apply_event(event.clone()) {
+                        Ok(()) => {}
+                        Err(KvCacheEventError::BlockNotFound) if replayed => {}
+                        Err(e) => panic!("{context}: event {} failed: {e:?}", event.event.event_id),
+                    }
+                }
+
+                for sequence in [vec![1, 2, 3, 4], vec![1, 2, 3, 6], vec![1, 2, 5]] {
+                    let sequence: Vec<LocalBlockHash> =
+                        sequence.into_iter().map(LocalBlockHash).collect();
+                    assert_eq!(
+                        restored.find_matches(sequence.clone(), false).scores,
+                        expected.find_matches(sequence, false).scores,
+                        "{context}"
+                    );
+                }
+                assert_eq!(
+                    restored.dump_tree_as_events().len(),
+                    expected.dump_tree_as_events().len(),
+                    "{context}"
+                );
+                for worker in [0, 1] {
+                    let worker = WorkerWithDpRank::from_worker_id(worker);
+                    assert_eq!(
+                        restored.lookup.get(&worker).map(HashMap::len),
+                        expected.lookup.get(&worker).map(HashMap::len),
+                        "{context}"
+                    );
+                }
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_dump_tree_as_events_round_trip() {
         setup();
@@ -0,0 +1,254 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Durable journal of the reservations made by the scheduler.
+//!
+//! Reservations only live in memory (and in the memory of the other router replicas when replica
+//! sync is enabled). A router which crashes between scheduling a request and dispatching or
+//! freeing it leaves a phantom reservation behind on its replicas. When journaling is enabled,
+//! every reservation is recorded in etcd, without a lease so that it outlives the router, and
+//! removed when the request is freed. On startup, [`ReservationJournal::recover`] frees the
+//! journaled reservations whose router or worker is gone.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+use dashmap::DashMap;
+use dynamo_runtime::{component::Component, transports::etcd::Client as EtcdClient};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    protocols::{WorkerId, WorkerWithDpRank},
+    sequence::ActiveSequencesMultiWorker,
+};
+
+/// Root etcd path of the reservation journals, followed by the component path, the router UUID
+/// and the request id.
+pub const RESERVATION_JOURNAL_ROOT_PATH: &str = "v1/kv_router_reservations";
+
+/// A journaled reservation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub worker: WorkerWithDpRank,
+    pub reserved_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Outcome of [`ReservationJournal::recover`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Reservations of routers which are gone, freed on the router replicas
+    pub freed: usize,
+    /// Reservations on workers which are gone, only removed from the journal
+    pub discarded: usize,
+}
+
+#[derive(Clone)]
+pub struct ReservationJournal {
+    etcd_client: EtcdClient,
+    /// `{RESERVATION_JOURNAL_ROOT_PATH}/{component path}/`
+    prefix: String,
+    router_uuid: String,
+    /// Records still being written, each holding its lock until done, so that removing a
+    /// request waits for its record instead of racing it
+    pending_records: Arc<DashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl ReservationJournal {
+    pub fn new(etcd_client: EtcdClient, component: &Component, router_uuid: &str) -> Self {
+        Self {
+            etcd_client,
+            prefix: format!("{RESERVATION_JOURNAL_ROOT_PATH}/{}/", component.path()),
+            router_uuid: router_uuid.to_string(),
+            pending_records: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn key(&self, request_id: &str) -> String {
+        format!("{}{}/{request_id}", self.prefix, self.router_uuid)
+    }
+
+    /// Journal a reservation of `worker` for the request
+    pub async fn record(&self, request_id: &str, worker: WorkerWithDpRank) -> Result<()> {
+        let entry = JournalEntry {
+            worker,
+            reserved_at: chrono::Utc::now(),
+        };
+        // Lease 0 means no lease: the entry must survive a crash of this router
+        self.etcd_client
+            .kv_put(self.key(request_id), serde_json::to_vec(&entry)?, Some(0))
+            .await
+    }
+
+    /// Journal a reservation of `worker` for the request in the background, without holding up
+    /// the caller. A later [`Self::remove`] of the request waits for the record to be written.
+    pub fn spawn_record(&self, request_id: &str, worker: WorkerWithDpRank) {
+        let lock = Arc::new(tokio::sync::Mutex::new(()));
+        let guard = lock
+            .clone()
+            .try_lock_owned()
+            .expect("a new lock is not held");
+        self.pending_records.insert(request_id.to_string(), lock);
+
+        let journal = self.clone();
+        let request_id = request_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = journal.record(&request_id, worker).await {
+                tracing::warn!("Failed to journal reservation of request {request_id}: {e:?}");
+            }
+            journal.pending_records.remove(&request_id);
+            drop(guard);
+        });
+    }
+
+    /// Remove the reservation of a request from the journal, once freed
+    pub async fn remove(&self, request_id: &str) -> Result<()> {
+        if let Some((_, pending)) = self.pending_records.remove(request_id) {
+            let _recorded = pending.lock().await;
+        }
+        self.etcd_client
+            .kv_delete(self.key(request_id), None)
+            .await
+            .map(|_| ())
+    }
+
+    /// Reconcile the journal against the live routers and workers: reservations of routers which
+    /// are no longer registered are freed on the router replicas through `slots`, reservations on
+    /// workers which are gone are dropped, and both are removed from the journal. Reservations of
+    /// live routers on live workers are left untouched.
+    pub async fn recover(
+        &self,
+        slots: &ActiveSequencesMultiWorker,
+        live_routers: &HashSet<String>,
+        live_workers: &HashSet<WorkerId>,
+    ) -> Result<RecoveryReport> {
+        let mut report = RecoveryReport::default();
+
+        for kv in self.etcd_client.kv_get_prefix(&self.prefix).await? {
+            let key = String::from_utf8_lossy(kv.key()).to_string();
+            let Some((router_uuid, request_id)) = key
+                .strip_prefix(&self.prefix)
+                .and_then(|rest| rest.split_once('/'))
+            else {
+                tracing::warn!("Ignoring malformed reservation journal key: {key}");
+                continue;
+            };
+
+            let entry: JournalEntry = match serde_json::from_slice(kv.value()) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::warn!("Removing unreadable reservation journal entry {key}: {e}");
+                    self.etcd_client.kv_delete(key.as_str(), None).await?;
+                    continue;
+                }
+            };
+
+            let worker_alive = live_workers.contains(&entry.worker.worker_id);
+            let router_alive = live_routers.contains(router_uuid);
+            if worker_alive && router_alive {
+                continue;
+            }
+
+            if worker_alive {
+                tracing::info!(
+                    "Freeing reservation of request {request_id} on worker {:?} left by router {router_uuid}, reserved at {}",
+                    entry.worker,
+                    entry.reserved_at
+                );
+                slots
+                    .free_orphaned(&request_id.to_string(), entry.worker)
+                    .await?;
+                report.freed += 1;
+            } else {
+                report.discarded += 1;
+            }
+            self.etcd_client.kv_delete(key.as_str(), None).await?;
+        }
+
+        if report != RecoveryReport::default() {
+            tracing::info!(
+                "Recovered reservation journal: freed {} orphaned reservations, discarded {} on departed workers",
+                report.freed,
+                report.discarded
+            );
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use dynamo_runtime::{DistributedRuntime, Runtime};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_recover_frees_orphans_and_discards_departed_workers() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace =
+            distributed.namespace(format!("test_journal_recover_{}", Uuid::new_v4()))?;
+        let component = namespace
+            .component("workers")?
+            .service_builder()
+            .create()
+            .await?;
+        let etcd_client = distributed.etcd_client().expect("etcd client");
+
+        let live_worker = WorkerWithDpRank::from_worker_id(1);
+        let departed_worker = WorkerWithDpRank::from_worker_id(2);
+        let live_journal = ReservationJournal::new(etcd_client.clone(), &component, "live");
+        let dead_journal = ReservationJournal::new(etcd_client.clone(), &component, "dead");
+
+        let slots = ActiveSequencesMultiWorker::new(
+            component.clone(),
+            4,
+            HashMap::from([(1, None)]),
+            false,
+            Uuid::new_v4().to_string(),
+        );
+        slots
+            .add_request("orphan".to_string(), None, 8, 0, live_worker)
+            .await?;
+
+        live_journal.record("kept", live_worker).await?;
+        dead_journal.record("orphan", live_worker).await?;
+        dead_journal.record("departed", departed_worker).await?;
+        // A removal right after a background record is applied after it
+        live_journal.spawn_record("freed", live_worker);
+        live_journal.remove("freed").await?;
+
+        let report = live_journal
+            .recover(
+                &slots,
+                &HashSet::from(["live".to_string()]),
+                &HashSet::from([live_worker.worker_id]),
+            )
+            .await?;
+        assert_eq!(
+            report,
+            RecoveryReport {
+                freed: 1,
+                discarded: 1,
+            }
+        );
+
+        // The orphan is freed on the replicas, and only the live reservation stays journaled
+        assert!(slots.worker_of(&"orphan".to_string()).is_none());
+        let remaining: Vec<String> = etcd_client
+            .kv_get_prefix(&live_journal.prefix)
+            .await?
+            .iter()
+            .map(|kv| String::from_utf8_lossy(kv.key()).to_string())
+            .collect();
+        assert_eq!(remaining, vec![live_journal.key("kept")]);
+
+        live_journal.remove("kept").await?;
+        Ok(())
+    }
+}
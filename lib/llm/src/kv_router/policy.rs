@@ -0,0 +1,166 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cluster-wide broadcast of the routing policy.
+//!
+//! Each router replica starts with its own [`KvRouterConfig`], so changing a weight on one
+//! replica leaves the others routing with the old value. When the broadcast is enabled, the
+//! current policy is stored under a single etcd key per component, without a lease so that it
+//! outlives the router which published it. Every replica watches the key and hands each new
+//! config to its [`WorkerSelector`], which swaps it in as a whole between two decisions. A
+//! replica which starts later reads the current policy before routing its first request.
+//!
+//! Only the settings the selector reads on every decision, such as `overlap_score_weight` or
+//! `router_temperature`, follow the broadcast. Settings applied at startup, such as the indexer
+//! or snapshot settings, keep their local values.
+
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use dynamo_runtime::{
+    component::Component,
+    transports::etcd::{Client as EtcdClient, WatchEvent},
+};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::{KvRouterConfig, WorkerSelector};
+
+/// Root etcd path of the routing policies, followed by the component path
+pub const ROUTING_POLICY_ROOT_PATH: &str = "v1/kv_router_policy";
+
+/// A broadcast routing policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingPolicy {
+    /// Incremented by every publication
+    pub version: u64,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub config: KvRouterConfig,
+}
+
+/// The policy last applied by this replica, None until one has been received
+#[derive(Debug, Clone, Default)]
+pub struct AppliedPolicy(Arc<RwLock<Option<RoutingPolicy>>>);
+
+impl AppliedPolicy {
+    pub fn get(&self) -> Option<RoutingPolicy> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn set(&self, policy: RoutingPolicy) {
+        *self.0.write().unwrap() = Some(policy);
+    }
+}
+
+#[derive(Clone)]
+pub struct RoutingPolicyBroadcast {
+    etcd_client: EtcdClient,
+    /// `{ROUTING_POLICY_ROOT_PATH}/{component path}`
+    key: String,
+}
+
+impl RoutingPolicyBroadcast {
+    pub fn new(etcd_client: EtcdClient, component: &Component) -> Self {
+        Self {
+            etcd_client,
+            key: format!("{ROUTING_POLICY_ROOT_PATH}/{}", component.path()),
+        }
+    }
+
+    /// The policy currently published, if any
+    pub async fn current(&self) -> Result<Option<RoutingPolicy>> {
+        let Some(kv) = self
+            .etcd_client
+            .kv_get(self.key.as_str(), None)
+            .await?
+            .pop()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(kv.value())?))
+    }
+
+    /// Publish `config` to every replica watching this component, returning the new policy
+    pub async fn publish(&self, config: KvRouterConfig) -> Result<RoutingPolicy> {
+        let version = self.current().await?.map_or(0, |policy| policy.version) + 1;
+        let policy = RoutingPolicy {
+            version,
+            updated_at: chrono::Utc::now(),
+            config,
+        };
+        // Lease 0 means no lease: the policy must survive the router which published it
+        self.etcd_client
+            .kv_put(self.key.as_str(), serde_json::to_vec(&policy)?, Some(0))
+            .await?;
+        tracing::info!("Published routing policy version {version}");
+        Ok(policy)
+    }
+
+    /// Apply the current policy to `selector`, then every policy published until `cancel` fires.
+    /// Policies are applied in the order etcd delivers them, which is the same on every replica.
+    pub async fn watch(
+        &self,
+        selector: Arc<dyn WorkerSelector + Send + Sync>,
+        applied: AppliedPolicy,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        let (_key, watcher, mut rx) = self
+            .etcd_client
+            .kv_get_and_watch_prefix(&self.key)
+            .await?
+            .dissolve();
+
+        // Apply the current policy before returning, so that no request is routed with the local
+        // config while a policy is published. The watch delivers it again, which is harmless.
+        if let Some(policy) = self.current().await? {
+            apply_policy(selector.as_ref(), &applied, policy);
+        }
+
+        // The prefix watch also matches longer keys, such as those of another component whose
+        // name starts with this one
+        let key = self.key.clone();
+        tokio::spawn(async move {
+            let _watcher = watcher;
+            loop {
+                let event = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    event = rx.recv() => event,
+                };
+                let Some(event) = event else {
+                    tracing::warn!("Routing policy watch closed, keeping the last applied policy");
+                    break;
+                };
+                let WatchEvent::Put(kv) = event else {
+                    // A deleted policy leaves the last one in place until a new one is published
+                    continue;
+                };
+                if kv.key() != key.as_bytes() {
+                    continue;
+                }
+                let policy: RoutingPolicy = match serde_json::from_slice(kv.value()) {
+                    Ok(policy) => policy,
+                    Err(e) => {
+                        tracing::warn!("Ignoring unreadable routing policy: {e}");
+                        continue;
+                    }
+                };
+                apply_policy(selector.as_ref(), &applied, policy);
+            }
+        });
+        Ok(())
+    }
+}
+
+fn apply_policy(
+    selector: &(dyn WorkerSelector + Send + Sync),
+    applied: &AppliedPolicy,
+    policy: RoutingPolicy,
+) {
+    tracing::info!(
+        "Applying routing policy version {} published at {}",
+        policy.version,
+        policy.updated_at
+    );
+    selector.apply_config(&policy.config);
+    applied.set(policy);
+}
@@ -43,6 +43,8 @@ pub enum RouterRequest {
     },
     MarkPrefill,
     MarkFree,
+    /// Reset the load accounting of the router, keeping its cache state
+    ResetSchedulerState,
 }
 
 impl Default for RouterRequest {
@@ -66,9 +68,13 @@ pub enum RouterResponse {
     FreeMarked {
         success: bool,
     },
+    SchedulerStateReset {
+        /// Number of tracked requests forgotten by the reset
+        forgotten_requests: usize,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WorkerSelectionResult {
     /// The full worker information including dp_rank
     pub worker: WorkerWithDpRank,
@@ -79,6 +85,14 @@ pub struct WorkerSelectionResult {
     /// The number of blocks that the selected worker may already have cached.
     /// This is not a guarantee, but an estimate.
     pub overlap_blocks: u32,
+
+    /// Expected time to first token on the selected worker, in seconds. None if the worker does
+    /// not report its prefill throughput.
+    pub expected_ttft_secs: Option<f64>,
+
+    /// Logit of the selected worker, lower is better. None if the worker was not selected by
+    /// its logit, e.g. by prefix affinity.
+    pub logit: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
@@ -3,10 +3,11 @@
 
 use crate::kv_router::{
     KV_EVENT_SUBJECT, KV_METRICS_ENDPOINT, KV_METRICS_SUBJECT,
-    indexer::{RouterEvent, compute_block_hash_for_seq},
+    indexer::{RouterEvent, compute_block_hash_for_seq_with},
     protocols::*,
     scoring::LoadEvent,
 };
+use crate::tokens::hasher::{DEFAULT_SEQUENCE_HASHER, SequenceHasher, Xxh3SequenceHasher};
 use async_trait::async_trait;
 use dynamo_runtime::metrics::{MetricsRegistry, prometheus_names::kvstats};
 use dynamo_runtime::traits::{DistributedRuntimeProvider, events::EventPublisher};
@@ -59,20 +60,20 @@ impl KvEventSource {
         source_config: KvEventSourceConfig,
         cancellation_token: CancellationToken,
         tx: mpsc::UnboundedSender<KvCacheEvent>,
+        sequence_hasher: Arc<dyn SequenceHasher>,
     ) -> Result<Self> {
         match source_config {
             KvEventSourceConfig::Zmq { endpoint, topic } => {
-                let zmq_handle = component
-                    .drt()
-                    .runtime()
-                    .secondary()
-                    .spawn(start_zmq_listener(
+                let zmq_handle = component.drt().runtime().secondary().spawn(
+                    start_zmq_listener_with_sequence_hasher(
                         endpoint,
                         topic,
                         tx,
                         cancellation_token.clone(),
                         kv_block_size,
-                    ));
+                        sequence_hasher,
+                    ),
+                );
 
                 Ok(KvEventSource::Zmq { zmq_handle })
             }
@@ -107,8 +108,27 @@ impl KvEventPublisher {
         worker_id: i64,
         kv_block_size: u32,
         source_config: Option<KvEventSourceConfig>,
+    ) -> Result<Self> {
+        Self::new_with_sequence_hasher(
+            component,
+            worker_id,
+            kv_block_size,
+            source_config,
+            Arc::new(Xxh3SequenceHasher::default()),
+        )
+    }
+
+    /// Create a publisher which hashes block tokens with `sequence_hasher` and tags its events
+    /// with the hasher's id.
+    pub fn new_with_sequence_hasher(
+        component: Component,
+        worker_id: i64,
+        kv_block_size: u32,
+        source_config: Option<KvEventSourceConfig>,
+        sequence_hasher: Arc<dyn SequenceHasher>,
     ) -> Result<Self> {
         let cancellation_token = CancellationToken::new();
+        let hasher_id = sequence_hasher.algorithm_id().into_owned();
 
         let (tx, rx) = mpsc::unbounded_channel::<KvCacheEvent>();
 
@@ -121,6 +141,7 @@ impl KvEventPublisher {
                 config,
                 cancellation_token.clone(),
                 tx.clone(),
+                sequence_hasher,
             )?);
         }
 
@@ -143,7 +164,14 @@ impl KvEventPublisher {
                 tracing::error!("Failed to connect NatsQueue: {}", e);
                 return;
             }
-            start_event_processor(nats_queue, worker_id, cancellation_token_clone, rx).await
+            start_event_processor(
+                nats_queue,
+                worker_id,
+                hasher_id,
+                cancellation_token_clone,
+                rx,
+            )
+            .await
         });
 
         Ok(Self {
@@ -182,6 +210,7 @@ impl Drop for KvEventPublisher {
 async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
     publisher: P,
     worker_id: i64,
+    hasher_id: String,
     cancellation_token: CancellationToken,
     mut rx: mpsc::UnboundedReceiver<KvCacheEvent>,
 ) {
@@ -199,7 +228,8 @@ async fn start_event_processor<P: EventPublisher + Send + Sync + 'static>(
 
                 // Encapsulate in a router event and publish.
                 tracing::trace!("Event processor for worker_id {} processing event: {:?}", worker_id, event.data);
-                let router_event = RouterEvent::new(worker_id, event);
+                let router_event = RouterEvent::new(worker_id, event)
+                    .with_hasher_id(hasher_id.as_str());
                 if let Err(e) = publisher.publish(QUEUE_NAME, &router_event).await {
                     tracing::error!("Failed to publish event: {}", e);
                 }
@@ -228,6 +258,26 @@ pub async fn start_zmq_listener(
     tx: mpsc::UnboundedSender<KvCacheEvent>,
     cancellation_token: CancellationToken,
     kv_block_size: u32,
+) {
+    start_zmq_listener_with_sequence_hasher(
+        zmq_endpoint,
+        zmq_topic,
+        tx,
+        cancellation_token,
+        kv_block_size,
+        Arc::new(Xxh3SequenceHasher::default()),
+    )
+    .await
+}
+
+/// Like [`start_zmq_listener`], hashing the tokens of stored blocks with `sequence_hasher`.
+pub async fn start_zmq_listener_with_sequence_hasher(
+    zmq_endpoint: String,
+    zmq_topic: String,
+    tx: mpsc::UnboundedSender<KvCacheEvent>,
+    cancellation_token: CancellationToken,
+    kv_block_size: u32,
+    sequence_hasher: Arc<dyn SequenceHasher>,
 ) {
     tracing::debug!(
         "KVEventPublisher connecting to ZMQ endpoint {} (topic '{}')",
@@ -335,7 +385,14 @@ pub async fn start_zmq_listener(
 
                 let dp_rank = batch.data_parallel_rank;
                 for raw_event in batch.events.into_iter() {
-                    let event = convert_event(raw_event, seq, kv_block_size, dp_rank, &warning_count);
+                    let event = convert_event(
+                        sequence_hasher.as_ref(),
+                        raw_event,
+                        seq,
+                        kv_block_size,
+                        dp_rank,
+                        &warning_count,
+                    );
                     if tx.send(event).is_err() {
                         tracing::warn!("Failed to send message to channel - receiver dropped");
                         exit_reason = "channel receiver dropped";
@@ -356,6 +413,7 @@ pub async fn start_zmq_listener(
 /// Convert a raw event coming from the ZMQ channel into the internal
 /// [`KvCacheEvent`] representation used by the router.
 fn convert_event(
+    sequence_hasher: &dyn SequenceHasher,
     raw: RawKvEvent,
     event_id: u64,
     kv_block_size: u32,
@@ -382,7 +440,8 @@ fn convert_event(
                     parent_hash: parent_block_hash
                         .map(BlockHashValue::into_u64)
                         .map(ExternalSequenceBlockHash::from),
-                    blocks: create_stored_blocks(
+                    blocks: create_stored_blocks_with(
+                        sequence_hasher,
                         kv_block_size,
                         &token_ids,
                         &num_block_tokens,
@@ -417,12 +476,29 @@ fn convert_event(
 }
 
 pub fn create_stored_block_from_parts(
+    kv_block_size: u32,
+    block_hash: u64,
+    token_ids: &[u32],
+    lora_id: u64,
+) -> KvCacheStoredBlockData {
+    create_stored_block_from_parts_with(
+        &DEFAULT_SEQUENCE_HASHER,
+        kv_block_size,
+        block_hash,
+        token_ids,
+        lora_id,
+    )
+}
+
+/// Like [`create_stored_block_from_parts`], hashing the tokens with `sequence_hasher`.
+pub fn create_stored_block_from_parts_with(
+    sequence_hasher: &dyn SequenceHasher,
     kv_block_size: u32,
     block_hash: u64,
     token_ids: &[u32],
     _lora_id: u64,
 ) -> KvCacheStoredBlockData {
-    let tokens_hash = compute_block_hash_for_seq(token_ids, kv_block_size)[0];
+    let tokens_hash = compute_block_hash_for_seq_with(sequence_hasher, token_ids, kv_block_size)[0];
     tracing::trace!(
         "Creating stored block: external_block_hash={}, tokens_hash={}, token_ids={:?}, kv_block_size={}",
         block_hash,
@@ -443,6 +519,27 @@ pub fn create_stored_blocks(
     block_hashes: &[u64],
     lora_id: u64,
     warning_count: &Arc<AtomicU32>,
+) -> Vec<KvCacheStoredBlockData> {
+    create_stored_blocks_with(
+        &DEFAULT_SEQUENCE_HASHER,
+        kv_block_size,
+        token_ids,
+        num_block_tokens,
+        block_hashes,
+        lora_id,
+        warning_count,
+    )
+}
+
+/// Like [`create_stored_blocks`], hashing the tokens of each block with `sequence_hasher`.
+pub fn create_stored_blocks_with(
+    sequence_hasher: &dyn SequenceHasher,
+    kv_block_size: u32,
+    token_ids: &[u32],
+    num_block_tokens: &[u64],
+    block_hashes: &[u64],
+    lora_id: u64,
+    warning_count: &Arc<AtomicU32>,
 ) -> Vec<KvCacheStoredBlockData> {
     let mut blocks: Vec<KvCacheStoredBlockData> = Vec::new();
 
@@ -460,7 +557,8 @@ pub fn create_stored_blocks(
         }
 
         let tokens = &token_ids[token_offset..(token_offset + *num_tokens_it as usize)];
-        blocks.push(create_stored_block_from_parts(
+        blocks.push(create_stored_block_from_parts_with(
+            sequence_hasher,
             kv_block_size,
             *block_hash_it,
             tokens,
@@ -956,6 +1054,15 @@ mod test_event_processing {
         assert_eq!(stored.block_hash.0, blk_hash);
         let expected_hash = compute_block_hash_for_seq(&token_ids, 4)[0];
         assert_eq!(stored.tokens_hash, expected_hash);
+
+        let hasher = Xxh3SequenceHasher::new(7);
+        let stored =
+            create_stored_block_from_parts_with(&hasher, kv_block_size, blk_hash, &token_ids, 0);
+        assert_eq!(
+            stored.tokens_hash,
+            compute_block_hash_for_seq_with(&hasher, &token_ids, 4)[0]
+        );
+        assert_ne!(stored.tokens_hash, expected_hash);
     }
 
     // ---------------------------------------------------------------------
@@ -1021,7 +1128,14 @@ mod test_event_processing {
             medium: None,
         };
 
-        let out = convert_event(raw_evt, 42, kv_block_size, 0, &Arc::new(AtomicU32::new(0)));
+        let out = convert_event(
+            &DEFAULT_SEQUENCE_HASHER,
+            raw_evt,
+            42,
+            kv_block_size,
+            0,
+            &Arc::new(AtomicU32::new(0)),
+        );
         assert!(matches!(out.data, KvCacheEventData::Stored(_)));
     }
 
@@ -1032,7 +1146,14 @@ mod test_event_processing {
             block_hashes: vec![BlockHashValue::Unsigned(123), BlockHashValue::Signed(456)],
             medium: None,
         };
-        let out = convert_event(raw_evt, 7, kv_block_size, 0, &Arc::new(AtomicU32::new(0)));
+        let out = convert_event(
+            &DEFAULT_SEQUENCE_HASHER,
+            raw_evt,
+            7,
+            kv_block_size,
+            0,
+            &Arc::new(AtomicU32::new(0)),
+        );
 
         assert!(matches!(out.data, KvCacheEventData::Removed(_)));
     }
@@ -1041,7 +1162,14 @@ mod test_event_processing {
     fn test_convert_event_all_blocks_cleared() {
         let kv_block_size = 4;
         let raw_evt = RawKvEvent::AllBlocksCleared;
-        let out = convert_event(raw_evt, 1, kv_block_size, 0, &Arc::new(AtomicU32::new(0)));
+        let out = convert_event(
+            &DEFAULT_SEQUENCE_HASHER,
+            raw_evt,
+            1,
+            kv_block_size,
+            0,
+            &Arc::new(AtomicU32::new(0)),
+        );
         assert!(matches!(out.data, KvCacheEventData::Cleared));
     }
 }
@@ -1130,7 +1258,13 @@ mod tests_startup_helpers {
         tx.send(event).unwrap();
         drop(tx);
 
-        let handle = tokio::spawn(start_event_processor(component, 1, token, rx));
+        let handle = tokio::spawn(start_event_processor(
+            component,
+            1,
+            "xxh3-64-le-seed7".to_string(),
+            token,
+            rx,
+        ));
 
         tokio::time::timeout(tokio::time::Duration::from_secs(1), handle)
             .await
@@ -1139,8 +1273,10 @@ mod tests_startup_helpers {
 
         let published = published.lock().unwrap();
         assert_eq!(published.len(), 1);
-        let (subject, _) = &published[0];
+        let (subject, bytes) = &published[0];
         assert_eq!(subject, QUEUE_NAME);
+        let router_event: RouterEvent = rmp_serde::from_slice(bytes).unwrap();
+        assert_eq!(router_event.hasher_id(), Some("xxh3-64-le-seed7"));
     }
 
     //--------------------------------------------------------------------
@@ -1,12 +1,51 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::{io, path::Path};
+
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
 use crate::kv_router::indexer::RouterEvent;
-use crate::recorder::Recorder;
+use crate::recorder::{Recorder, RecorderRotation};
 
 // Type alias for backward compatibility
 pub type KvRecorder = Recorder<RouterEvent>;
 
+/// Record every event sent on the returned channel to `path`, rotating files per `rotation`, on
+/// top of forwarding it to `indexer_tx`. The recording can be replayed with
+/// [`super::replay::ReplayIndexer::from_recording`] to rebuild the radix tree of the router.
+/// When the recorder falls behind, events are dropped from the recording, never from the indexer.
+pub async fn start_event_tee(
+    indexer_tx: mpsc::Sender<RouterEvent>,
+    path: impl AsRef<Path>,
+    rotation: RecorderRotation,
+    cancel: CancellationToken,
+) -> io::Result<mpsc::Sender<RouterEvent>> {
+    let recorder =
+        KvRecorder::new_with_rotation(cancel.child_token(), path, rotation, None, None).await?;
+    let record_tx = recorder.event_sender();
+    let (tee_tx, mut tee_rx) = mpsc::channel::<RouterEvent>(2048);
+
+    tokio::spawn(async move {
+        // The recorder stops, flushing its file, once dropped
+        let _recorder = recorder;
+        let mut dropped: u64 = 0;
+        while let Some(event) = tee_rx.recv().await {
+            if record_tx.try_send(event.clone()).is_err() {
+                dropped += 1;
+                if dropped.is_power_of_two() {
+                    tracing::warn!("KV event recorder is falling behind, {dropped} events dropped");
+                }
+            }
+            if indexer_tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(tee_tx)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,4 +171,47 @@ mod tests {
             .unwrap();
         assert_eq!(count, 2, "Expected to send 2 events from file to indexer");
     }
+
+    #[tokio::test]
+    async fn test_event_tee_records_and_forwards() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tee.jsonl");
+        let token = CancellationToken::new();
+        let (indexer_tx, mut indexer_rx) = mpsc::channel(16);
+
+        let tee_tx = start_event_tee(
+            indexer_tx,
+            &file_path,
+            RecorderRotation {
+                max_lines: Some(2),
+                ..Default::default()
+            },
+            token.clone(),
+        )
+        .await
+        .unwrap();
+        for event_id in 0..3 {
+            tee_tx
+                .send(create_store_event(1, event_id, vec![event_id + 1], None))
+                .await
+                .unwrap();
+        }
+
+        // Every event reaches the indexer, in order
+        for event_id in 0..3 {
+            let event = indexer_rx.recv().await.unwrap();
+            assert_eq!(event.event.event_id, event_id);
+        }
+
+        // Closing the tee flushes the recording, rotated after two events
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(tee_tx);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let first = fs::read_to_string(&file_path).await.unwrap();
+        let second = fs::read_to_string(dir.path().join("tee1.jsonl"))
+            .await
+            .unwrap();
+        assert_eq!(first.lines().count(), 2);
+        assert_eq!(second.lines().count(), 1);
+    }
 }
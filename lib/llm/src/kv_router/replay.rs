@@ -0,0 +1,206 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Offline replay of captured KV events.
+//!
+//! [`ReplayIndexer`] builds a radix tree from a dump of [`RouterEvent`]s, such as a router
+//! snapshot or a captured KV event stream, and answers overlap queries against it. It needs no
+//! runtime, NATS or etcd, so routing behavior can be checked against production traffic from a
+//! test or a small binary.
+
+use std::{path::Path, sync::Arc};
+
+use tokio::sync::mpsc;
+
+use super::{
+    indexer::{
+        KvCacheEventError, OverlapScores, RadixTree, RouterEvent, compute_block_hash_for_seq_with,
+    },
+    protocols::{LocalBlockHash, WorkerId},
+    recorder::KvRecorder,
+};
+use crate::tokens::hasher::{DEFAULT_SEQUENCE_HASHER, SequenceHasher};
+
+/// A standalone radix tree fed from captured [`RouterEvent`]s.
+pub struct ReplayIndexer {
+    tree: RadixTree,
+    block_size: u32,
+    sequence_hasher: Arc<dyn SequenceHasher>,
+    applied: usize,
+    rejected: usize,
+}
+
+impl ReplayIndexer {
+    /// An empty indexer hashing token queries with the default sequence hasher
+    pub fn new(block_size: u32) -> Self {
+        Self::new_with_sequence_hasher(block_size, Arc::new(DEFAULT_SEQUENCE_HASHER))
+    }
+
+    /// An empty indexer hashing token queries with `sequence_hasher`. Events tagged with a
+    /// different hasher are rejected, as they are by the live indexer.
+    pub fn new_with_sequence_hasher(
+        block_size: u32,
+        sequence_hasher: Arc<dyn SequenceHasher>,
+    ) -> Self {
+        Self {
+            tree: RadixTree::new()
+                .with_sequence_hasher_id(sequence_hasher.algorithm_id().into_owned()),
+            block_size,
+            sequence_hasher,
+            applied: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Build an indexer from a dump of events, applied in order
+    pub fn from_events(block_size: u32, events: impl IntoIterator<Item = RouterEvent>) -> Self {
+        let mut indexer = Self::new(block_size);
+        indexer.apply_events(events);
+        indexer
+    }
+
+    /// Build an indexer from a JSONL file written by a [`KvRecorder`]
+    pub async fn from_recording(block_size: u32, path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let (event_tx, mut event_rx) = mpsc::channel(1024);
+        let send = async move {
+            // The sender is dropped once the file is read, ending the collection below
+            KvRecorder::send_events(path, &event_tx, false, None, None).await
+        };
+        let collect = async move {
+            let mut events = Vec::new();
+            while let Some(event) = event_rx.recv().await {
+                events.push(event);
+            }
+            events
+        };
+        let (sent, events) = tokio::join!(send, collect);
+        sent?;
+        Ok(Self::from_events(block_size, events))
+    }
+
+    /// Apply a single event, as the live indexer would
+    pub fn apply_event(&mut self, event: RouterEvent) -> Result<(), KvCacheEventError> {
+        let result = self.tree.apply_event(event);
+        match result {
+            Ok(()) => self.applied += 1,
+            Err(_) => self.rejected += 1,
+        }
+        result
+    }
+
+    /// Apply events in order. Events the tree rejects, e.g. removals of blocks missing from a
+    /// partial capture, are logged and skipped.
+    pub fn apply_events(&mut self, events: impl IntoIterator<Item = RouterEvent>) {
+        for event in events {
+            let worker_id = event.worker_id();
+            if let Err(e) = self.apply_event(event) {
+                tracing::debug!("Skipping replayed event of worker {worker_id}: {e}");
+            }
+        }
+    }
+
+    /// Number of events applied and rejected so far
+    pub fn event_counts(&self) -> (usize, usize) {
+        (self.applied, self.rejected)
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    /// Workers with at least one block in the tree
+    pub fn workers(&self) -> Vec<WorkerId> {
+        self.tree.get_workers()
+    }
+
+    /// Overlap scores of each worker for a sequence of block hashes
+    pub fn find_matches(&self, sequence: Vec<LocalBlockHash>) -> OverlapScores {
+        self.tree.find_matches(sequence, false)
+    }
+
+    /// Overlap scores of each worker for a prompt, hashed the way the router hashes requests
+    pub fn find_matches_for_tokens(&self, tokens: &[u32]) -> OverlapScores {
+        let sequence =
+            compute_block_hash_for_seq_with(self.sequence_hasher.as_ref(), tokens, self.block_size);
+        self.find_matches(sequence)
+    }
+
+    /// The tree as a minimal list of events, e.g. to save a replayed state as a snapshot
+    pub fn dump_events(&self) -> Vec<RouterEvent> {
+        self.tree.dump_tree_as_events()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::protocols::{
+        ExternalSequenceBlockHash, KvCacheEvent, KvCacheEventData, KvCacheRemoveData,
+        KvCacheStoreData, KvCacheStoredBlockData, WorkerWithDpRank,
+    };
+
+    fn store_event(
+        worker_id: WorkerId,
+        tokens: &[u32],
+        parent_hash: Option<ExternalSequenceBlockHash>,
+    ) -> RouterEvent {
+        let blocks = compute_block_hash_for_seq_with(&DEFAULT_SEQUENCE_HASHER, tokens, 4)
+            .into_iter()
+            .map(|tokens_hash| KvCacheStoredBlockData {
+                block_hash: ExternalSequenceBlockHash(tokens_hash.0),
+                tokens_hash,
+            })
+            .collect();
+        RouterEvent::new(
+            worker_id,
+            KvCacheEvent {
+                event_id: 0,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash,
+                    blocks,
+                }),
+                dp_rank: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn test_replay_answers_overlap_queries() {
+        let prompt: Vec<u32> = (0..12).collect();
+        let remove_missing = RouterEvent::new(
+            2,
+            KvCacheEvent {
+                event_id: 1,
+                data: KvCacheEventData::Removed(KvCacheRemoveData {
+                    block_hashes: vec![ExternalSequenceBlockHash(42)],
+                }),
+                dp_rank: 0,
+            },
+        );
+        let indexer = ReplayIndexer::from_events(
+            4,
+            vec![
+                store_event(1, &prompt, None),
+                store_event(2, &prompt[..4], None),
+                remove_missing,
+            ],
+        );
+
+        assert_eq!(indexer.event_counts(), (2, 1));
+        let mut workers = indexer.workers();
+        workers.sort_unstable();
+        assert_eq!(workers, vec![1, 2]);
+
+        let scores = indexer.find_matches_for_tokens(&prompt);
+        assert_eq!(scores.scores[&WorkerWithDpRank::from_worker_id(1)], 3);
+        assert_eq!(scores.scores[&WorkerWithDpRank::from_worker_id(2)], 1);
+
+        let unrelated: Vec<u32> = (100..108).collect();
+        assert!(
+            indexer
+                .find_matches_for_tokens(&unrelated)
+                .scores
+                .is_empty()
+        );
+    }
+}
@@ -1,28 +1,46 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::local_model::runtime_config::ModelRuntimeConfig;
+use crate::local_model::runtime_config::{DisaggregationRole, ModelRuntimeConfig};
 use anyhow::Result;
+use derive_builder::Builder;
 use dynamo_runtime::component::{Component, Instance};
+use dynamo_runtime::logging::get_distributed_tracing_context;
+use dynamo_runtime::metrics::{MetricsRegistry, prometheus_names::kvrouter};
 use dynamo_runtime::traits::DistributedRuntimeProvider;
 use dynamo_runtime::traits::events::EventPublisher;
-use rand::Rng;
+use prometheus::{Gauge, Histogram, HistogramOpts, IntCounter, IntGauge};
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::{Rng, RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{RwLock, watch};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{RwLock, broadcast, watch};
+use tokio_util::sync::CancellationToken;
 
 use super::KV_HIT_RATE_SUBJECT;
+use super::KV_SHADOW_SELECTION_SUBJECT;
+use super::KV_WORKER_MEMBERSHIP_SUBJECT;
 use super::KvRouterConfig;
 use super::RouterConfigOverride;
 use super::WorkerSelector;
-use super::indexer::OverlapScores;
+use super::admission::{AdmissionDecision, AdmissionPolicy};
+use super::indexer::{OverlapScores, compute_hash};
+use super::journal::ReservationJournal;
 use super::protocols::{DpRank, WorkerId, WorkerSelectionResult, WorkerWithDpRank};
-use super::sequence::ActiveSequencesMultiWorker;
+use super::sequence::{ActiveSequencesMultiWorker, ActiveStateSnapshot, ImportReport};
+use super::subscriber::active_router_uuids;
+use super::{ColdPrefixPolicy, MissingRuntimeConfigsPolicy, UnknownCapacityPolicy};
 
 use crate::tokens::SequenceHash;
 
+/// Hit rate of the requests routed to a worker. Unless the router aggregates hit rates, each
+/// event describes a single request; aggregated events sum the blocks of `request_count` requests.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KVHitRateEvent {
     pub worker_id: WorkerId,
@@ -30,6 +48,269 @@ pub struct KVHitRateEvent {
     pub dp_rank: DpRank,
     pub isl_blocks: usize,
     pub overlap_blocks: u32,
+    #[serde(default = "default_request_count")]
+    pub request_count: usize,
+}
+
+fn default_request_count() -> usize {
+    1
+}
+
+/// The worker a shadow selector would have chosen for a request, next to the worker the live
+/// selector chose. Evaluated and published by a task of its own once the request has been
+/// answered, and dropped when that task falls behind, so shadow selection never delays routing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowSelectionEvent {
+    pub request_id: Option<String>,
+    pub isl_blocks: usize,
+    pub live_worker: WorkerWithDpRank,
+    pub live_overlap_blocks: u32,
+    /// None if the shadow selector failed
+    pub shadow_worker: Option<WorkerWithDpRank>,
+    pub shadow_overlap_blocks: Option<u32>,
+    pub agreed: bool,
+}
+
+impl ShadowSelectionEvent {
+    fn new(
+        request: &SchedulingRequest,
+        live: &WorkerSelectionResult,
+        shadow: Option<&WorkerSelectionResult>,
+    ) -> Self {
+        Self {
+            request_id: request.maybe_request_id.clone(),
+            isl_blocks: live.required_blocks as usize,
+            live_worker: live.worker,
+            live_overlap_blocks: live.overlap_blocks,
+            shadow_worker: shadow.map(|s| s.worker),
+            shadow_overlap_blocks: shadow.map(|s| s.overlap_blocks),
+            agreed: shadow.is_some_and(|s| s.worker == live.worker),
+        }
+    }
+}
+
+/// Shadow selections waiting to be evaluated and published; more are dropped
+const SHADOW_SELECTION_CAPACITY: usize = 256;
+
+/// A decision of the live selector, handed to the shadow selector task
+struct ShadowSelectionJob {
+    workers: HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    request: SchedulingRequest,
+    live: WorkerSelectionResult,
+}
+
+/// A worker joining or leaving the set of workers requests are routed to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerMembershipEvent {
+    pub worker_id: WorkerId,
+    /// True if the worker was added, false if it was removed
+    pub added: bool,
+    /// Unix timestamp (ms) at which the router noticed the change
+    pub timestamp: u64,
+}
+
+/// Holds removed workers out of the routing set for a cooldown, even if they reappear in the
+/// instance set meanwhile. A flapping worker (removed then quickly re-added) may have an
+/// inconsistent state while it flaps; the cooldown gives it time to stabilize and register its
+/// cache again, and it rejoins with a fresh load tracking.
+struct RejoinCooldown {
+    cooldown: Duration,
+    /// When each worker still cooling down was removed
+    removed_at: HashMap<WorkerId, Instant>,
+}
+
+impl RejoinCooldown {
+    fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            removed_at: HashMap::new(),
+        }
+    }
+
+    /// Record the workers of the routing set `previous` missing from `workers`, then remove from
+    /// `workers` those removed less than the cooldown ago. Returns the time until the first
+    /// worker held out is re-admitted, None if none is.
+    fn hold_out<V>(
+        &mut self,
+        previous: &HashMap<WorkerId, V>,
+        workers: &mut HashMap<WorkerId, V>,
+        now: Instant,
+    ) -> Option<Duration> {
+        for worker_id in previous.keys() {
+            if !workers.contains_key(worker_id) {
+                self.removed_at.insert(*worker_id, now);
+            }
+        }
+        let cooldown = self.cooldown;
+        self.removed_at
+            .retain(|_, removed_at| now.duration_since(*removed_at) < cooldown);
+
+        let mut readmit_in: Option<Duration> = None;
+        for (worker_id, removed_at) in &self.removed_at {
+            if workers.remove(worker_id).is_none() {
+                continue;
+            }
+            let remaining = cooldown - now.duration_since(*removed_at);
+            tracing::info!(
+                "Worker {worker_id} reappeared {:?} after its removal; holding it out of routing for another {remaining:?}",
+                now.duration_since(*removed_at)
+            );
+            readmit_in = Some(readmit_in.map_or(remaining, |first| first.min(remaining)));
+        }
+        readmit_in
+    }
+}
+
+/// The membership events turning the workers `previous` into `current`, removals first
+fn membership_changes<V>(
+    previous: &HashMap<WorkerId, V>,
+    current: &HashMap<WorkerId, V>,
+) -> Vec<WorkerMembershipEvent> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let mut removed: Vec<WorkerId> = previous
+        .keys()
+        .filter(|id| !current.contains_key(id))
+        .copied()
+        .collect();
+    let mut added: Vec<WorkerId> = current
+        .keys()
+        .filter(|id| !previous.contains_key(id))
+        .copied()
+        .collect();
+    removed.sort_unstable();
+    added.sort_unstable();
+    removed
+        .into_iter()
+        .map(|worker_id| (worker_id, false))
+        .chain(added.into_iter().map(|worker_id| (worker_id, true)))
+        .map(|(worker_id, added)| WorkerMembershipEvent {
+            worker_id,
+            added,
+            timestamp,
+        })
+        .collect()
+}
+
+/// Accumulates [`KVHitRateEvent`]s per worker dp rank until they are drained, to publish one
+/// aggregated event per worker per window.
+#[derive(Default)]
+struct HitRateAggregator(Mutex<HashMap<WorkerWithDpRank, KVHitRateEvent>>);
+
+impl HitRateAggregator {
+    fn record(&self, event: KVHitRateEvent) {
+        let worker = WorkerWithDpRank::new(event.worker_id, event.dp_rank);
+        let mut pending = self.0.lock().unwrap();
+        match pending.get_mut(&worker) {
+            Some(aggregate) => {
+                aggregate.isl_blocks += event.isl_blocks;
+                aggregate.overlap_blocks = aggregate
+                    .overlap_blocks
+                    .saturating_add(event.overlap_blocks);
+                aggregate.request_count += event.request_count;
+            }
+            None => {
+                pending.insert(worker, event);
+            }
+        }
+    }
+
+    /// Take the aggregated events accumulated since the last drain
+    fn drain(&self) -> Vec<KVHitRateEvent> {
+        self.0
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, event)| event)
+            .collect()
+    }
+}
+
+/// Width of the buckets of the hit rate history
+const HIT_RATE_BUCKET_SECS: i64 = 10;
+
+/// Number of buckets of the hit rate history, i.e. five minutes of history
+const HIT_RATE_HISTORY_BUCKETS: usize = 30;
+
+/// Blocks of the requests routed over some period, and how many of them were cached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HitRateCounts {
+    pub isl_blocks: usize,
+    pub overlap_blocks: u64,
+    pub request_count: usize,
+}
+
+impl HitRateCounts {
+    fn add(&mut self, event: &KVHitRateEvent) {
+        self.isl_blocks += event.isl_blocks;
+        self.overlap_blocks += event.overlap_blocks as u64;
+        self.request_count += event.request_count;
+    }
+
+    /// Fraction of the blocks which were cached, None without any block
+    pub fn hit_rate(&self) -> Option<f64> {
+        (self.isl_blocks > 0).then(|| self.overlap_blocks as f64 / self.isl_blocks as f64)
+    }
+}
+
+/// Hit rates of the requests routed during one bucket of [`KvScheduler::hit_rate_history`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HitRateBucket {
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub cluster: HitRateCounts,
+    /// Per worker, over all its dp ranks
+    pub workers: BTreeMap<WorkerId, HitRateCounts>,
+}
+
+/// Ring buffer of the hit rates of the last [`HIT_RATE_HISTORY_BUCKETS`] buckets, to compare
+/// locality before and after a routing config change without an external metrics store
+#[derive(Debug, Default)]
+struct HitRateHistory(Mutex<VecDeque<HitRateBucket>>);
+
+impl HitRateHistory {
+    fn record(&self, event: &KVHitRateEvent, now: chrono::DateTime<chrono::Utc>) {
+        let timestamp = now.timestamp();
+        let start = chrono::DateTime::from_timestamp(
+            timestamp - timestamp.rem_euclid(HIT_RATE_BUCKET_SECS),
+            0,
+        )
+        .unwrap_or(now);
+
+        let mut buckets = self.0.lock().unwrap();
+        if buckets.back().is_none_or(|bucket| bucket.start != start) {
+            if buckets.len() == HIT_RATE_HISTORY_BUCKETS {
+                buckets.pop_front();
+            }
+            buckets.push_back(HitRateBucket {
+                start,
+                cluster: HitRateCounts::default(),
+                workers: BTreeMap::new(),
+            });
+        }
+        let bucket = buckets.back_mut().expect("bucket just pushed");
+        bucket.cluster.add(event);
+        bucket
+            .workers
+            .entry(event.worker_id)
+            .or_default()
+            .add(event);
+    }
+
+    /// The buckets within the history window before `now`, oldest first. Buckets without any
+    /// routed request are omitted.
+    fn buckets(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<HitRateBucket> {
+        let window =
+            chrono::Duration::seconds(HIT_RATE_BUCKET_SECS * HIT_RATE_HISTORY_BUCKETS as i64);
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|bucket| now - bucket.start < window)
+            .cloned()
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +319,223 @@ pub struct PotentialLoad {
     pub dp_rank: DpRank,
     pub potential_prefill_tokens: usize,
     pub potential_decode_blocks: usize,
+    /// Estimated risk in [0, 1] that the request evicts blocks of the active requests of the
+    /// worker, see [`eviction_risk`]
+    #[serde(default)]
+    pub eviction_risk: f64,
+}
+
+/// Interval at which the loads are compared to the last pushed snapshot between two periodic
+/// load updates
+const LOAD_CHANGE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Relative change of the load of a worker which is pushed without waiting for the next update
+const SIGNIFICANT_LOAD_CHANGE: f64 = 0.2;
+
+/// Number of load snapshots a subscriber may fall behind before it skips the oldest ones
+const LOAD_UPDATES_CAPACITY: usize = 16;
+
+/// Whether the loads changed enough since the `previous` snapshot to push the `current` one
+/// before the next periodic update: a worker joined or left, or its load moved by more than
+/// [`SIGNIFICANT_LOAD_CHANGE`]
+fn significant_load_change(previous: &[PotentialLoad], current: &[PotentialLoad]) -> bool {
+    if previous.len() != current.len() {
+        return true;
+    }
+    let moved = |before: usize, after: usize| {
+        before.abs_diff(after) as f64 > SIGNIFICANT_LOAD_CHANGE * before.max(1) as f64
+    };
+    previous.iter().zip(current).any(|(before, after)| {
+        (before.worker_id, before.dp_rank) != (after.worker_id, after.dp_rank)
+            || moved(
+                before.potential_decode_blocks,
+                after.potential_decode_blocks,
+            )
+            || moved(
+                before.potential_prefill_tokens,
+                after.potential_prefill_tokens,
+            )
+    })
+}
+
+/// Estimated risk, in [0, 1], that placing a request on a worker evicts blocks its active
+/// requests still need: the share of the `new_blocks` of the request which do not fit in the
+/// blocks left free by the `active_blocks`, out of the `total_blocks` of the worker. Blocks which
+/// overflow the capacity beyond the active ones only evict the request's own blocks, and a worker
+/// which does not report its capacity has no known risk.
+pub fn eviction_risk(active_blocks: usize, new_blocks: usize, total_blocks: Option<u64>) -> f64 {
+    let Some(total_blocks) = total_blocks else {
+        return 0.0;
+    };
+    if new_blocks == 0 {
+        return 0.0;
+    }
+    let free_blocks = (total_blocks as usize).saturating_sub(active_blocks);
+    let evicted_blocks = new_blocks.saturating_sub(free_blocks).min(active_blocks);
+    evicted_blocks as f64 / new_blocks as f64
+}
+
+/// The blocks of a request which a worker would have to allocate, those not covered by its
+/// `overlap`, and the blocks active on the worker before the request, out of the
+/// `potential_blocks` it would hold with the request
+fn new_and_active_blocks(
+    isl_tokens: usize,
+    overlap: u32,
+    block_size: u32,
+    potential_blocks: usize,
+) -> (usize, usize) {
+    let new_blocks = (isl_tokens / block_size as usize).saturating_sub(overlap as usize);
+    (new_blocks, potential_blocks.saturating_sub(new_blocks))
+}
+
+/// KV capacity of a single dp rank of a worker, its `total_kv_blocks` split evenly between ranks
+fn rank_capacity(config: Option<&ModelRuntimeConfig>) -> Option<u64> {
+    let config = config?;
+    Some(config.total_kv_blocks? / config.data_parallel_size.max(1) as u64)
+}
+
+/// Potential loads of all workers tracked by `slots` for a request, sorted by worker
+async fn potential_loads(
+    slots: &ActiveSequencesMultiWorker,
+    workers_with_configs: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    block_size: u32,
+    token_seq: Option<Vec<SequenceHash>>,
+    isl_tokens: usize,
+    overlaps: OverlapScores,
+) -> Vec<PotentialLoad> {
+    let (decode_blocks, prefill_tokens) = slots
+        .potential_blocks_and_tokens(token_seq, isl_tokens, overlaps.clone())
+        .await;
+
+    // Get all unique WorkerWithDpRank from both hashmaps
+    let mut workers: HashSet<WorkerWithDpRank> = HashSet::new();
+    workers.extend(decode_blocks.keys().copied());
+    workers.extend(prefill_tokens.keys().copied());
+
+    // Create PotentialLoad for each worker
+    let mut loads = Vec::new();
+    for worker in workers {
+        let potential_decode_blocks = decode_blocks.get(&worker).copied().unwrap_or(0);
+        let overlap = overlaps.scores.get(&worker).copied().unwrap_or(0);
+        let (new_blocks, active_blocks) =
+            new_and_active_blocks(isl_tokens, overlap, block_size, potential_decode_blocks);
+        let total_blocks = workers_with_configs
+            .get(&worker.worker_id)
+            .and_then(|config| rank_capacity(config.as_ref()));
+        loads.push(PotentialLoad {
+            worker_id: worker.worker_id,
+            dp_rank: worker.dp_rank,
+            potential_prefill_tokens: prefill_tokens.get(&worker).copied().unwrap_or(isl_tokens),
+            potential_decode_blocks,
+            eviction_risk: eviction_risk(active_blocks, new_blocks, total_blocks),
+        });
+    }
+    loads.sort_by_key(|load| (load.worker_id, load.dp_rank));
+
+    loads
+}
+
+/// Number of recent scheduling decisions kept for [`KvScheduler::dump_state`]
+const RECENT_DECISIONS_CAPACITY: usize = 64;
+
+/// A worker selection made by the scheduler, kept for diagnostics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulingDecision {
+    pub request_id: Option<String>,
+    pub worker: WorkerWithDpRank,
+    pub isl_tokens: usize,
+    pub overlap_blocks: u32,
+    /// Expected time to first token on the worker, if it reports its prefill throughput
+    #[serde(default)]
+    pub expected_ttft_secs: Option<f64>,
+    /// False for queries which did not reserve capacity on the worker
+    pub update_states: bool,
+    pub decided_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Load tracked by the scheduler for a single worker dp rank
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlotOccupancy {
+    pub worker: WorkerWithDpRank,
+    pub active_blocks: usize,
+    pub active_tokens: usize,
+}
+
+/// Snapshot of the whole scheduler state, for support and debugging
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerState {
+    pub block_size: u32,
+    /// Requests waiting to be picked up by the scheduler loop
+    pub queue_depth: usize,
+    /// Whether routing is degraded to load only because the queue is deep
+    #[serde(default)]
+    pub routing_degraded: bool,
+    /// Known workers and their runtime configs, sorted by worker id
+    pub workers: Vec<(WorkerId, Option<ModelRuntimeConfig>)>,
+    /// Tracked load per worker dp rank, sorted by worker
+    pub slots: Vec<SlotOccupancy>,
+    pub tracked_requests: usize,
+    pub utilization: ClusterUtilization,
+    /// Most recent decisions, oldest first
+    pub recent_decisions: Vec<SchedulingDecision>,
+}
+
+/// KV cache usage of a single worker, summed over its dp ranks
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WorkerUtilization {
+    pub worker_id: WorkerId,
+    pub used_blocks: usize,
+    /// `None` if the worker has not reported `total_kv_blocks`
+    pub total_blocks: Option<u64>,
+    pub utilization: Option<f64>,
+}
+
+/// Aggregate KV cache usage of the cluster, e.g. to drive autoscaling.
+///
+/// The cluster totals only cover workers reporting `total_kv_blocks`; the others are listed in
+/// `workers` without capacity.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ClusterUtilization {
+    pub total_blocks: u64,
+    pub used_blocks: usize,
+    /// `used_blocks / total_blocks`, `None` if no worker reports its capacity
+    pub utilization: Option<f64>,
+    /// Per-worker breakdown, sorted by worker id
+    pub workers: Vec<WorkerUtilization>,
+}
+
+impl ClusterUtilization {
+    fn new(
+        active_blocks: &HashMap<WorkerWithDpRank, usize>,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    ) -> Self {
+        let mut used_per_worker: HashMap<WorkerId, usize> = HashMap::new();
+        for (worker, blocks) in active_blocks {
+            *used_per_worker.entry(worker.worker_id).or_default() += blocks;
+        }
+
+        let mut utilization = ClusterUtilization::default();
+        for (worker_id, config) in workers {
+            let used_blocks = used_per_worker.get(worker_id).copied().unwrap_or(0);
+            let total_blocks = config.as_ref().and_then(|c| c.total_kv_blocks);
+            if let Some(total_blocks) = total_blocks {
+                utilization.total_blocks += total_blocks;
+                utilization.used_blocks += used_blocks;
+            }
+            utilization.workers.push(WorkerUtilization {
+                worker_id: *worker_id,
+                used_blocks,
+                total_blocks,
+                utilization: total_blocks
+                    .filter(|total| *total > 0)
+                    .map(|total| used_blocks as f64 / total as f64),
+            });
+        }
+        utilization.workers.sort_by_key(|w| w.worker_id);
+        utilization.utilization = (utilization.total_blocks > 0)
+            .then(|| utilization.used_blocks as f64 / utilization.total_blocks as f64);
+        utilization
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -50,16 +548,96 @@ pub enum KvSchedulerError {
 
     #[error("endpoint subscriber shutdown")]
     SubscriberShutdown,
+
+    #[error("failed to reserve capacity for request: {0}")]
+    ReservationFailed(String),
+
+    #[error("no worker has published a runtime config; refusing to route")]
+    MissingRuntimeConfigs,
+
+    #[error(
+        "request of {isl} tokens exceeds the max context length of every worker ({max_context})"
+    )]
+    ContextTooLong { isl: usize, max_context: u64 },
+
+    #[error("request was cancelled before being scheduled")]
+    Cancelled,
+
+    #[error("every worker has a NaN or infinite logit")]
+    InvalidLogits,
+
+    #[error("request denied by the admission policy: {0}")]
+    AdmissionDenied(String),
+
+    #[error("router is shutting down; not accepting new requests")]
+    ShuttingDown,
+
+    #[error("request {0} is not tracked by the router")]
+    UnknownRequest(String),
+
+    #[error("request {0} was already freed")]
+    AlreadyFreed(String),
+
+    #[error("prefill of request {0} was already marked completed")]
+    PrefillAlreadyCompleted(String),
+
+    #[error("failed to update the tracked request: {0}")]
+    TrackingFailed(String),
 }
 
 #[derive(Debug)]
 pub struct SchedulingResponse {
     pub best_worker: WorkerWithDpRank,
     pub overlap_blocks: u32,
+    /// Logit of the worker, None if it was not selected by its logit
+    pub logit: Option<f64>,
+    /// Number of workers the worker was selected among, 0 if it was not selected
+    pub candidates: usize,
+}
+
+/// Stage of disaggregated serving a request is scheduled for, restricting the candidate workers
+/// by their [`DisaggregationRole`]. Aggregated workers (and those without a runtime config) are
+/// candidates for every phase.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchedulingPhase {
+    #[default]
+    Any,
+    Prefill,
+    Decode,
+}
+
+impl SchedulingPhase {
+    fn accepts(self, role: DisaggregationRole) -> bool {
+        match (self, role) {
+            (SchedulingPhase::Any, _) | (_, DisaggregationRole::Aggregated) => true,
+            (SchedulingPhase::Prefill, role) => role == DisaggregationRole::Prefill,
+            (SchedulingPhase::Decode, role) => role == DisaggregationRole::Decode,
+        }
+    }
+
+    /// The workers which may serve this phase
+    fn filter_workers(self, workers: &mut HashMap<WorkerId, Option<ModelRuntimeConfig>>) {
+        if self == SchedulingPhase::Any {
+            return;
+        }
+        workers.retain(|_, config| {
+            self.accepts(
+                config
+                    .as_ref()
+                    .map(|c| c.disaggregation_role)
+                    .unwrap_or_default(),
+            )
+        });
+    }
 }
 
 pub struct SchedulingRequest {
     pub maybe_request_id: Option<String>,
+    // Sequence hashes of the full blocks of the request, None when they are not computed (e.g.
+    // active blocks are not tracked). Without them the request is assumed to share no block with
+    // the active requests, and prefix-keyed policies (consistent hashing, affinity) are skipped,
+    // as they are for Some of an empty sequence. The cached overlap is taken from `overlaps`
+    // either way.
     pub token_seq: Option<Vec<SequenceHash>>,
     pub isl_tokens: usize,
     pub overlaps: OverlapScores,
@@ -69,12 +647,70 @@ pub struct SchedulingRequest {
     pub router_config_override: Option<RouterConfigOverride>,
     // Whether to update scheduler states (false for query_instance_id requests)
     pub update_states: bool,
+    // Worker which last served this request's prefix and the decay factor in [0, 1] to apply to
+    // its overlap credit, set by the scheduler when affinity decay is enabled
+    pub affinity_decay: Option<(WorkerWithDpRank, f64)>,
+    // Restricts the candidate workers by disaggregation role
+    pub phase: SchedulingPhase,
+    // Queued requests of higher priority are scheduled first; 0 unless set by the admission policy
+    pub priority: i32,
     // Option to take it out to send the response without moving the struct
-    resp_tx: Option<tokio::sync::oneshot::Sender<SchedulingResponse>>,
+    resp_tx: Option<tokio::sync::oneshot::Sender<Result<SchedulingResponse, KvSchedulerError>>>,
 }
 
 impl SchedulingRequest {
+    /// A request to evaluate a [`WorkerSelector`] on outside of a scheduler, e.g. to benchmark
+    /// it, without loads nor a caller waiting for the response
+    pub fn new(isl_tokens: usize, overlaps: OverlapScores) -> Self {
+        Self {
+            maybe_request_id: None,
+            token_seq: None,
+            isl_tokens,
+            overlaps,
+            decode_blocks: HashMap::new(),
+            prefill_tokens: HashMap::new(),
+            router_config_override: None,
+            update_states: false,
+            affinity_decay: None,
+            phase: SchedulingPhase::Any,
+            priority: 0,
+            resp_tx: None,
+        }
+    }
+
     pub fn respond(&mut self, response: SchedulingResponse) {
+        self.send_response(Ok(response));
+    }
+
+    /// Fail the request instead of responding with a worker
+    pub fn respond_err(&mut self, error: KvSchedulerError) {
+        self.send_response(Err(error));
+    }
+
+    /// A copy of the request without its response channel, to evaluate it elsewhere
+    fn detached(&self) -> Self {
+        Self {
+            maybe_request_id: self.maybe_request_id.clone(),
+            token_seq: self.token_seq.clone(),
+            isl_tokens: self.isl_tokens,
+            overlaps: self.overlaps.clone(),
+            decode_blocks: self.decode_blocks.clone(),
+            prefill_tokens: self.prefill_tokens.clone(),
+            router_config_override: self.router_config_override.clone(),
+            update_states: self.update_states,
+            affinity_decay: self.affinity_decay,
+            phase: self.phase,
+            priority: self.priority,
+            resp_tx: None,
+        }
+    }
+
+    /// Whether the caller stopped waiting for the response, e.g. because its future was dropped
+    fn is_abandoned(&self) -> bool {
+        self.resp_tx.as_ref().is_some_and(|tx| tx.is_closed())
+    }
+
+    fn send_response(&mut self, response: Result<SchedulingResponse, KvSchedulerError>) {
         // Changed to &mut self
         if let Some(tx) = self.resp_tx.take() {
             // Use take() to extract the sender
@@ -87,501 +723,4630 @@ impl SchedulingRequest {
     }
 }
 
-pub struct KvScheduler {
-    request_tx: tokio::sync::mpsc::Sender<SchedulingRequest>,
-    slots: Arc<ActiveSequencesMultiWorker>,
+/// A request taken from the channel and waiting in the scheduler loop, ordered by priority, aged
+/// by its wait if [`QueueAging`] is enabled, then arrival
+struct QueuedRequest {
+    request: SchedulingRequest,
+    arrival: u64,
+    enqueued_at: Instant,
+    aged_priority: f64,
 }
 
-impl KvScheduler {
-    pub async fn start(
-        component: Component,
-        block_size: u32,
-        instances_rx: watch::Receiver<Vec<Instance>>,
-        runtime_configs_rx: watch::Receiver<HashMap<WorkerId, ModelRuntimeConfig>>,
-        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
-        replica_sync: bool,
-        router_uuid: String,
-    ) -> Result<Self, KvSchedulerError> {
-        let selector = selector.unwrap_or(Box::new(DefaultWorkerSelector::default()));
-        let instances: Vec<Instance> = instances_rx.borrow().clone();
-        let runtime_configs: HashMap<WorkerId, ModelRuntimeConfig> =
-            runtime_configs_rx.borrow().clone();
-
-        // Create shared workers_with_configs wrapped in Arc<RwLock>
-        let workers_with_configs: Arc<RwLock<HashMap<WorkerId, Option<ModelRuntimeConfig>>>> = {
-            let mut initial_map = HashMap::new();
-            for instance in &instances {
-                let worker_id = instance.instance_id;
-                let config = runtime_configs.get(&worker_id).cloned();
-                if config.is_some() {
-                    tracing::info!("Runtime config found for worker_id: {}", worker_id);
-                }
-                initial_map.insert(worker_id, config);
-            }
-            Arc::new(RwLock::new(initial_map))
+impl QueuedRequest {
+    fn new(
+        request: SchedulingRequest,
+        arrival: u64,
+        aging: Option<&QueueAging>,
+        now: Instant,
+    ) -> Self {
+        let aged_priority = match aging {
+            Some(aging) => aging.aged_priority(request.priority, now),
+            None => f64::from(request.priority),
         };
+        Self {
+            request,
+            arrival,
+            enqueued_at: now,
+            aged_priority,
+        }
+    }
+}
 
-        let slots = Arc::new(ActiveSequencesMultiWorker::new(
-            component.clone(),
-            block_size as usize,
-            workers_with_configs.read().await.clone(), // this includes dp_size info
-            replica_sync,
-            router_uuid,
-        ));
+impl PartialEq for QueuedRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
 
-        // Spawn background task to monitor and update workers_with_configs
-        let workers_monitor = workers_with_configs.clone();
-        let slots_monitor = slots.clone();
-        let mut instances_monitor_rx = instances_rx.clone();
-        let mut configs_monitor_rx = runtime_configs_rx.clone();
-        let monitor_cancel_token = component.drt().primary_token();
-        tokio::spawn(async move {
-            tracing::trace!("workers monitoring task started");
-            loop {
-                // Wait for either instances or configs to change
-                tokio::select! {
-                    _ = monitor_cancel_token.cancelled() => {
-                        tracing::trace!("workers monitoring task shutting down");
-                        break;
-                    }
-                    result = instances_monitor_rx.changed() => {
-                        if result.is_err() {
-                            tracing::warn!("endpoint watch sender shutdown in monitor");
-                            break;
-                        }
-                    }
-                    result = configs_monitor_rx.changed() => {
-                        if result.is_err() {
-                            tracing::warn!("runtime configs watch sender shutdown in monitor");
-                            break;
-                        }
-                    }
-                }
+impl Eq for QueuedRequest {}
 
-                // Get the latest values from both channels
-                let new_instances = instances_monitor_rx.borrow_and_update().clone();
-                let new_configs = configs_monitor_rx.borrow_and_update().clone();
+impl PartialOrd for QueuedRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-                // Build the new workers_with_configs map
-                let mut new_workers_with_configs = HashMap::new();
-                for instance in &new_instances {
-                    let worker_id = instance.instance_id;
-                    let config = new_configs.get(&worker_id).cloned();
-                    if config.is_some() {
-                        tracing::info!("Runtime config found for worker_id: {}", worker_id);
-                    }
-                    new_workers_with_configs.insert(worker_id, config);
-                }
+impl Ord for QueuedRequest {
+    // The max-heap pops the highest priority first, and the earliest arrival among equals
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.aged_priority
+            .total_cmp(&other.aged_priority)
+            .then_with(|| other.arrival.cmp(&self.arrival))
+    }
+}
 
-                // Update workers when instances change
-                slots_monitor.update_workers(new_workers_with_configs.clone());
+/// Raises the priority of a queued request by one level per `interval` it waited, so that while
+/// the scheduler is gated, e.g. by the rate limits of the workers, requests of low priority are
+/// eventually scheduled instead of being postponed by every request of higher priority.
+#[derive(Debug, Clone, Copy)]
+pub struct QueueAging {
+    interval: Duration,
+    /// The waits are counted from this instant rather than from now, see
+    /// [`QueueAging::aged_priority`]
+    epoch: Instant,
+}
 
-                // Update the shared workers_with_configs
-                let mut workers_map = workers_monitor.write().await;
-                *workers_map = new_workers_with_configs;
-                tracing::trace!(
-                    "Updated workers_with_configs with {} workers",
-                    workers_map.len()
-                );
-            }
-            tracing::trace!("workers monitoring task shutting down");
-        });
+impl QueueAging {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            epoch: Instant::now(),
+        }
+    }
 
-        let slots_clone = slots.clone();
-        let workers_scheduler = workers_with_configs.clone();
-        let (request_tx, request_rx) = tokio::sync::mpsc::channel::<SchedulingRequest>(1024);
-        let scheduler_cancel_token = component.drt().primary_token();
-        let ns_clone = component.namespace().clone();
+    /// Priority of a request of `priority` queued at `enqueued_at`. At any instant `now`, the
+    /// aged priority `priority + (now - enqueued_at) / interval` differs from this one by the
+    /// same `(now - epoch) / interval` for every queued request, so the queue keeps its order as
+    /// the requests wait and never needs to be re-sorted.
+    fn aged_priority(&self, priority: i32, enqueued_at: Instant) -> f64 {
+        let queued_since_epoch = enqueued_at.saturating_duration_since(self.epoch);
+        f64::from(priority) - queued_since_epoch.as_secs_f64() / self.interval.as_secs_f64()
+    }
+}
 
-        // Background task to handle scheduling requests
-        tokio::spawn(async move {
-            let mut request_rx = request_rx;
-            tracing::trace!("scheduler background task started");
+/// Rejects incoming requests of low priority with [`KvSchedulerError::AllWorkersBusy`] while the
+/// queue of the scheduler is deep, rather than queuing them first come, first served. Requests of
+/// at least `min_priority` are always queued. Below it, a request is shed once `high_water_mark`
+/// requests are queued, minus `priority_step` per priority level under `min_priority - 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadShedding {
+    pub high_water_mark: usize,
+    pub min_priority: i32,
+    pub priority_step: usize,
+}
 
-            loop {
-                // Check for cancellation at beginning of loop
-                if scheduler_cancel_token.is_cancelled() {
-                    tracing::trace!("scheduler background task shutting down");
-                    break;
-                }
+impl LoadShedding {
+    /// Queue depth at which requests of `priority` are shed, None if they never are
+    fn threshold(&self, priority: i32) -> Option<usize> {
+        if priority >= self.min_priority {
+            return None;
+        }
+        let levels = (i64::from(self.min_priority) - i64::from(priority) - 1) as usize;
+        Some(
+            self.high_water_mark
+                .saturating_sub(levels.saturating_mul(self.priority_step)),
+        )
+    }
 
-                // Wait for a new request
-                let Some(mut request) = request_rx.recv().await else {
-                    tracing::warn!("scheduler shutdown");
-                    break;
-                };
-                tracing::trace!("received request to be scheduled");
+    /// Whether to reject a request of `priority` arriving with `queue_depth` requests queued
+    fn sheds(&self, priority: i32, queue_depth: usize) -> bool {
+        self.threshold(priority)
+            .is_some_and(|threshold| queue_depth >= threshold)
+    }
+}
 
-                let (decode_blocks, prefill_tokens) = slots_clone
-                    .potential_blocks_and_tokens(
-                        request.token_seq.clone(),
-                        request.isl_tokens,
-                        request.overlaps.clone(),
-                    )
-                    .await;
-                request.decode_blocks = decode_blocks;
-                request.prefill_tokens = prefill_tokens;
+/// Queue a request received by the scheduler loop, unless load shedding rejects it
+fn enqueue(
+    pending: &mut BinaryHeap<QueuedRequest>,
+    arrivals: &mut u64,
+    shedding: Option<&LoadShedding>,
+    aging: Option<&QueueAging>,
+    mut request: SchedulingRequest,
+) {
+    if let Some(shedding) = shedding
+        && shedding.sheds(request.priority, pending.len())
+    {
+        tracing::debug!(
+            "shedding request {:?} of priority {} with {} requests queued",
+            request.maybe_request_id,
+            request.priority,
+            pending.len()
+        );
+        request.respond_err(KvSchedulerError::AllWorkersBusy);
+        return;
+    }
+    pending.push(QueuedRequest::new(
+        request,
+        *arrivals,
+        aging,
+        Instant::now(),
+    ));
+    *arrivals += 1;
+}
 
-                // Read the current workers configuration
-                let workers = workers_scheduler.read().await.clone();
+/// Run the admission policy on a request received by the scheduler loop, responding to it if
+/// denied. Returns the request to queue, None if denied.
+async fn admit(
+    policy: Option<&dyn AdmissionPolicy>,
+    mut request: SchedulingRequest,
+) -> Option<SchedulingRequest> {
+    let Some(policy) = policy else {
+        return Some(request);
+    };
+    match policy.admit(&request).await {
+        AdmissionDecision::Allow => {}
+        AdmissionDecision::Deny(reason) => {
+            tracing::debug!(
+                "admission policy denied request {:?}: {reason}",
+                request.maybe_request_id
+            );
+            request.respond_err(KvSchedulerError::AdmissionDenied(reason));
+            return None;
+        }
+        AdmissionDecision::Reprioritize(priority) => request.priority = priority,
+    }
+    Some(request)
+}
 
-                match selector.select_worker(&workers, &request, block_size) {
-                    Ok(selection) => {
-                        let event = KVHitRateEvent {
-                            worker_id: selection.worker.worker_id,
-                            dp_rank: selection.worker.dp_rank,
-                            isl_blocks: selection.required_blocks as usize,
-                            overlap_blocks: selection.overlap_blocks,
-                        };
-                        if let Err(e) = ns_clone.publish(KV_HIT_RATE_SUBJECT, &event).await {
-                            tracing::warn!("Failed to publish KV hit rate event: {:?}", e);
-                        }
+/// Queue a request on the scheduler loop and wait for the selected worker
+#[allow(clippy::too_many_arguments)]
+async fn submit(
+    request_tx: &tokio::sync::mpsc::Sender<SchedulingRequest>,
+    maybe_request_id: Option<String>,
+    isl_tokens: usize,
+    token_seq: Option<Vec<SequenceHash>>,
+    overlaps: OverlapScores,
+    router_config_override: Option<RouterConfigOverride>,
+    update_states: bool,
+    phase: SchedulingPhase,
+) -> Result<WorkerWithDpRank, KvSchedulerError> {
+    let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+    let request = SchedulingRequest {
+        maybe_request_id,
+        token_seq,
+        isl_tokens,
+        overlaps,
+        decode_blocks: HashMap::new(),
+        prefill_tokens: HashMap::new(),
+        router_config_override,
+        update_states,
+        affinity_decay: None,
+        phase,
+        priority: 0,
+        resp_tx: Some(resp_tx), // Wrap in Some()
+    };
 
-                        let response = SchedulingResponse {
-                            best_worker: selection.worker,
-                            overlap_blocks: selection.overlap_blocks,
-                        };
-                        request.respond(response);
+    request_tx
+        .send(request)
+        .await
+        .map_err(|_| KvSchedulerError::SubscriberShutdown)?;
+    let response = resp_rx
+        .await
+        .map_err(|_| KvSchedulerError::SubscriberShutdown)??;
+    record_decision_span_event(&response);
 
-                        // Skip state update if not requested
-                        if !request.update_states {
-                            continue;
-                        }
+    Ok(response.best_worker)
+}
 
-                        let Some(request_id) = request.maybe_request_id else {
-                            tracing::error!(
-                                "No request_id provided to add_request to the slot tracker"
-                            );
-                            continue;
-                        };
+/// Record a decision as an event of the current span when a distributed trace is active, so that
+/// the trace shows why the request went to its worker, next to the spans of the worker. The
+/// OpenTelemetry layer exports the events of a span as its span events.
+fn record_decision_span_event(response: &SchedulingResponse) {
+    if get_distributed_tracing_context().is_none() {
+        return;
+    }
+    tracing::info!(
+        worker_id = response.best_worker.worker_id,
+        dp_rank = response.best_worker.dp_rank,
+        overlap_blocks = response.overlap_blocks,
+        logit = response.logit,
+        candidates = response.candidates,
+        "kv router scheduling decision"
+    );
+}
 
-                        if let Err(e) = slots_clone
-                            .add_request(
-                                request_id.clone(),
-                                request.token_seq,
-                                request.isl_tokens,
-                                selection.overlap_blocks,
-                                selection.worker,
-                            )
-                            .await
-                        {
-                            tracing::warn!(
-                                "Failed to add request {request_id} to local slot tracker: {e:?}"
-                            );
-                        }
-                    }
-                    Err(KvSchedulerError::NoEndpoints) => {
-                        tracing::trace!("no endpoints available; waiting for endpoints update");
-                        tokio::time::sleep(Duration::from_millis(5)).await;
-                        continue;
-                    }
-                    // TODO: this is not actually hooked up
-                    Err(KvSchedulerError::AllWorkersBusy) => {
-                        tracing::trace!("all workers busy; waiting for more capacity");
-                        tokio::time::sleep(Duration::from_millis(5)).await;
-                        continue;
-                    }
-                    Err(e) => {
-                        tracing::error!("error scheduling request: {:?}", e);
-                        break;
-                    }
-                }
-            }
+/// A worker picked by [`KvScheduler::schedule_provisional`] ahead of the precise decision.
+///
+/// The provisional worker is chosen from the loads seen at the last scheduling decision and is
+/// reserved right away, while the precise decision runs in the background. If the precise
+/// decision picks another worker before the caller dispatches, the reservation is moved there.
+/// Dispatching right away with [`ProvisionalSchedule::dispatch`] keeps the provisional worker,
+/// trading optimal placement for latency; [`ProvisionalSchedule::refined`] waits for the precise
+/// decision instead.
+pub struct ProvisionalSchedule {
+    worker: WorkerWithDpRank,
+    state: Arc<tokio::sync::Mutex<ProvisionalState>>,
+    refined_rx: tokio::sync::oneshot::Receiver<()>,
+}
 
-            tracing::trace!("background endpoint subscriber shutting down");
-        });
+struct ProvisionalState {
+    worker: WorkerWithDpRank,
+    dispatched: bool,
+}
 
-        Ok(KvScheduler { request_tx, slots })
+impl ProvisionalSchedule {
+    /// The worker initially reserved for the request
+    pub fn provisional_worker(&self) -> WorkerWithDpRank {
+        self.worker
     }
 
-    pub async fn schedule(
-        &self,
-        maybe_request_id: Option<String>,
-        isl_tokens: usize,
-        token_seq: Option<Vec<SequenceHash>>,
-        overlaps: OverlapScores,
-        router_config_override: Option<&RouterConfigOverride>,
-        update_states: bool,
-    ) -> Result<WorkerWithDpRank, KvSchedulerError> {
-        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
-        let request = SchedulingRequest {
-            maybe_request_id,
-            token_seq,
-            isl_tokens,
-            overlaps,
-            decode_blocks: HashMap::new(),
-            prefill_tokens: HashMap::new(),
-            router_config_override: router_config_override.cloned(),
-            update_states,
-            resp_tx: Some(resp_tx), // Wrap in Some()
-        };
+    /// Dispatch to the currently reserved worker, after which the reservation is no longer moved
+    pub async fn dispatch(self) -> WorkerWithDpRank {
+        let mut state = self.state.lock().await;
+        state.dispatched = true;
+        state.worker
+    }
 
-        self.request_tx
-            .send(request)
-            .await
-            .map_err(|_| KvSchedulerError::SubscriberShutdown)?;
-        let response = resp_rx
-            .await
-            .map_err(|_| KvSchedulerError::SubscriberShutdown)?;
+    /// Wait for the precise decision and dispatch to the worker it selected
+    pub async fn refined(mut self) -> WorkerWithDpRank {
+        let _ = (&mut self.refined_rx).await;
+        self.dispatch().await
+    }
+}
+
+/// The worker dp rank with the lowest last-known decode load net of its overlap, both in blocks,
+/// as a cheap stand-in for the full selection
+fn provisional_worker(
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    last_loads: &HashMap<WorkerWithDpRank, usize>,
+    overlaps: &OverlapScores,
+) -> Option<WorkerWithDpRank> {
+    workers
+        .iter()
+        .flat_map(|(worker_id, config)| {
+            let dp_size = config.as_ref().map_or(1, |c| c.data_parallel_size.max(1));
+            (0..dp_size).map(|dp_rank| WorkerWithDpRank::new(*worker_id, dp_rank))
+        })
+        .min_by_key(|worker| {
+            let load = last_loads.get(worker).copied().unwrap_or(0) as i64;
+            let overlap = overlaps.scores.get(worker).copied().unwrap_or(0) as i64;
+            (load - overlap, *worker)
+        })
+}
+
+pub struct KvScheduler {
+    request_tx: tokio::sync::mpsc::Sender<SchedulingRequest>,
+    slots: Arc<ActiveSequencesMultiWorker>,
+    selector: SwappableSelector,
+    workers_with_configs: Arc<RwLock<HashMap<WorkerId, Option<ModelRuntimeConfig>>>>,
+    block_size: u32,
+    cancelled: Arc<CancelledRequests>,
+    /// Requests recently marked prefill completed or freed, to report repeated calls on them
+    retired: RetiredRequests,
+    recent_decisions: Arc<Mutex<VecDeque<SchedulingDecision>>>,
+    hit_rate_history: Arc<HitRateHistory>,
+    journal: Option<ReservationJournal>,
+    /// Potential decode blocks per worker computed for the last scheduled request
+    last_loads: Arc<Mutex<HashMap<WorkerWithDpRank, usize>>>,
+    /// Requests taken from `request_tx` by the scheduler loop and not scheduled yet
+    queued: Arc<AtomicUsize>,
+    /// Load snapshots pushed to the subscribers of [`KvScheduler::subscribe_loads`]
+    loads_tx: broadcast::Sender<Vec<PotentialLoad>>,
+    degradation: Option<RoutingDegradation>,
+    /// Cancelled when the router stops accepting requests
+    intake: CancellationToken,
+    /// Position of the round robin over the workers of the requests bypassing routing
+    bypass_cursor: AtomicUsize,
+    /// Set by [`KvScheduler::reset_state`] for the scheduler loop to reset its affinity and
+    /// rate limits before the next decision
+    reset_requested: Arc<AtomicBool>,
+}
 
-        Ok(response.best_worker)
+/// The selector of a [`KvScheduler`], shared with its background task so that
+/// [`KvScheduler::set_selector`] can replace it at runtime. As a [`WorkerSelector`], it delegates
+/// each call to the selector in place when the call is made.
+#[derive(Clone)]
+struct SwappableSelector(Arc<std::sync::RwLock<Arc<dyn WorkerSelector + Send + Sync>>>);
+
+impl SwappableSelector {
+    fn new(selector: Arc<dyn WorkerSelector + Send + Sync>) -> Self {
+        Self(Arc::new(std::sync::RwLock::new(selector)))
     }
 
-    pub async fn add_request(
-        &self,
-        request_id: String,
-        token_sequence: Option<Vec<SequenceHash>>,
-        isl: usize,
-        overlap: u32,
-        worker: WorkerWithDpRank,
-    ) {
-        let _ = self
-            .slots
-            .add_request(request_id, token_sequence, isl, overlap, worker)
-            .await;
+    fn current(&self) -> Arc<dyn WorkerSelector + Send + Sync> {
+        self.0.read().unwrap().clone()
     }
 
-    pub async fn mark_prefill_completed(&self, request_id: &str) -> Result<()> {
-        self.slots
-            .mark_prefill_completed(&request_id.to_string())
-            .await
+    fn set(&self, selector: Arc<dyn WorkerSelector + Send + Sync>) {
+        *self.0.write().unwrap() = selector;
     }
+}
 
-    pub async fn free(&self, request_id: &str) -> Result<()> {
-        self.slots.free(&request_id.to_string()).await
+impl WorkerSelector for SwappableSelector {
+    fn select_worker(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        self.current().select_worker(workers, request, block_size)
     }
 
-    pub async fn get_potential_loads(
+    fn rank_workers(
         &self,
-        token_seq: Option<Vec<SequenceHash>>,
-        isl_tokens: usize,
-        overlaps: OverlapScores,
-    ) -> Vec<PotentialLoad> {
-        let (decode_blocks, prefill_tokens) = self
-            .slots
-            .potential_blocks_and_tokens(token_seq, isl_tokens, overlaps)
-            .await;
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Result<Vec<WorkerSelectionResult>, KvSchedulerError> {
+        self.current().rank_workers(workers, request, block_size)
+    }
 
-        // Get all unique WorkerWithDpRank from both hashmaps
-        let mut workers: HashSet<WorkerWithDpRank> = HashSet::new();
-        workers.extend(decode_blocks.keys().copied());
-        workers.extend(prefill_tokens.keys().copied());
-
-        // Create PotentialLoad for each worker
-        let mut loads = Vec::new();
-        for worker in workers {
-            loads.push(PotentialLoad {
-                worker_id: worker.worker_id,
-                dp_rank: worker.dp_rank,
-                potential_prefill_tokens: prefill_tokens
-                    .get(&worker)
-                    .copied()
-                    .unwrap_or(isl_tokens),
-                potential_decode_blocks: decode_blocks.get(&worker).copied().unwrap_or(0),
-            });
-        }
+    fn apply_config(&self, config: &KvRouterConfig) {
+        self.current().apply_config(config)
+    }
 
-        loads
+    fn observe_workers(&self, workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>) {
+        self.current().observe_workers(workers)
+    }
+
+    fn reset_load_state(&self) {
+        self.current().reset_load_state()
     }
 }
 
-// Helper function for softmax sampling
-fn softmax_sample(logits: &HashMap<WorkerWithDpRank, f64>, temperature: f64) -> WorkerWithDpRank {
-    if logits.is_empty() {
-        panic!("Empty logits for softmax sampling");
+/// Switches routing to load only, without querying the indexer for overlaps, while the queue of
+/// the scheduler is deeper than `threshold`, so that an overloaded router drains its backlog
+/// faster. Full-quality routing resumes once the depth is back to half the threshold, so the
+/// mode does not flap around the threshold.
+#[derive(Debug)]
+struct RoutingDegradation {
+    threshold: usize,
+    degraded: AtomicBool,
+}
+
+impl RoutingDegradation {
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            degraded: AtomicBool::new(false),
+        }
     }
 
-    // Guard: if temperature is 0, return the key with the smallest logit value
-    if temperature == 0.0 {
-        // Find the minimum logit value
-        let min_logit = logits.values().fold(f64::INFINITY, |a, &b| a.min(b));
+    /// Update the mode with the current `queue_depth`, returning whether routing is degraded
+    fn update(&self, queue_depth: usize) -> bool {
+        let was_degraded = self.degraded.load(AtomicOrdering::Relaxed);
+        let degraded = if was_degraded {
+            queue_depth > self.threshold / 2
+        } else {
+            queue_depth > self.threshold
+        };
+        if degraded != was_degraded
+            && self
+                .degraded
+                .compare_exchange(
+                    was_degraded,
+                    degraded,
+                    AtomicOrdering::Relaxed,
+                    AtomicOrdering::Relaxed,
+                )
+                .is_ok()
+        {
+            DegradedRoutingMetrics::get()
+                .routing_degraded
+                .set(degraded as i64);
+            if degraded {
+                tracing::warn!(
+                    "Scheduler queue depth {queue_depth} exceeds {}, routing by load only \
+                     without overlap until the backlog drains",
+                    self.threshold
+                );
+            } else {
+                tracing::info!(
+                    "Scheduler queue depth back to {queue_depth}, resuming full-quality routing"
+                );
+            }
+        }
+        degraded
+    }
+}
 
-        // Collect all keys with the minimum logit value (to handle ties)
-        let min_keys: Vec<_> = logits
-            .iter()
-            .filter(|&(_, &v)| v == min_logit)
-            .map(|(k, _)| *k)
-            .collect();
+/// Request ids cancelled while possibly still queued, with the time of cancellation.
+/// An entry is removed when its request is dequeued, or after [`CancelledRequests::TTL`] if the
+/// request was never queued or had already been scheduled.
+#[derive(Default)]
+struct CancelledRequests(Mutex<HashMap<String, Instant>>);
 
-        // Randomly select from the minimum keys (handles single key case naturally)
-        let mut rng = rand::rng();
-        let index = rng.random_range(0..min_keys.len());
-        return min_keys[index];
+impl CancelledRequests {
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn insert(&self, request_id: String) {
+        let now = Instant::now();
+        let mut cancelled = self.0.lock().unwrap();
+        cancelled.retain(|_, at| now.duration_since(*at) < Self::TTL);
+        cancelled.insert(request_id, now);
     }
 
-    let keys: Vec<_> = logits.keys().copied().collect();
-    let values: Vec<_> = logits.values().copied().collect();
+    /// Whether the request was cancelled, forgetting the cancellation
+    fn take(&self, request_id: &str) -> bool {
+        self.0.lock().unwrap().remove(request_id).is_some()
+    }
+}
 
-    // Find min and max for normalization
-    let min_val = values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-    let max_val = values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+/// What was last done to a request through [`KvScheduler::mark_prefill_completed`] or
+/// [`KvScheduler::free`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RetiredStage {
+    PrefillCompleted,
+    Freed,
+}
 
-    let probabilities = if min_val == max_val {
-        // All values are the same, uniform probability
-        vec![1.0 / keys.len() as f64; keys.len()]
-    } else {
-        // Normalize values
-        let normalized: Vec<_> = values
-            .iter()
-            .map(|&v| {
-                // Lower is better, so negate
-                // Note we don't need to do actual min-max norm here, just off by an offset
-                let norm = v / (max_val - min_val);
-                -norm
-            })
-            .collect();
+/// Requests recently marked prefill completed or freed, so that a repeated call on one of them
+/// fails with [`KvSchedulerError::PrefillAlreadyCompleted`] or [`KvSchedulerError::AlreadyFreed`]
+/// rather than [`KvSchedulerError::UnknownRequest`]. Entries are forgotten after
+/// [`RetiredRequests::TTL`], the oldest ones beyond [`RetiredRequests::CAPACITY`], and as soon as
+/// their id is reserved again.
+#[derive(Default)]
+struct RetiredRequests(Mutex<RetiredRequestsInner>);
 
-        // Apply temperature and softmax
-        let scaled: Vec<_> = normalized.iter().map(|&v| v / temperature).collect();
+#[derive(Default)]
+struct RetiredRequestsInner {
+    stages: HashMap<String, (RetiredStage, Instant)>,
+    order: VecDeque<(String, Instant)>,
+}
 
-        let max_scaled = scaled.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-        let exp_values: Vec<_> = scaled.iter().map(|&v| (v - max_scaled).exp()).collect();
+impl RetiredRequests {
+    const TTL: Duration = Duration::from_secs(300);
+    const CAPACITY: usize = 65536;
 
-        let sum_exp: f64 = exp_values.iter().sum();
-        exp_values.iter().map(|&v| v / sum_exp).collect()
-    };
+    fn record(&self, request_id: &str, stage: RetiredStage) {
+        self.record_at(request_id, stage, Instant::now());
+    }
 
-    // Sample from the probability distribution
-    let mut rng = rand::rng();
-    let sample: f64 = rng.random();
+    fn record_at(&self, request_id: &str, stage: RetiredStage, now: Instant) {
+        let mut inner = self.0.lock().unwrap();
+        inner.stages.insert(request_id.to_string(), (stage, now));
+        inner.order.push_back((request_id.to_string(), now));
 
-    let mut cumsum = 0.0;
-    for (i, &prob) in probabilities.iter().enumerate() {
-        cumsum += prob;
-        if sample <= cumsum {
-            return keys[i];
+        while let Some((oldest, at)) = inner.order.front().cloned() {
+            if now.duration_since(at) < Self::TTL && inner.order.len() <= Self::CAPACITY {
+                break;
+            }
+            inner.order.pop_front();
+            // Only forget the stage if it was not recorded again since
+            if inner
+                .stages
+                .get(&oldest)
+                .is_some_and(|(_, recorded)| *recorded == at)
+            {
+                inner.stages.remove(&oldest);
+            }
         }
     }
 
-    // Fallback to last key (shouldn't normally reach here)
-    keys[keys.len() - 1]
+    /// Forget the stage of a request id which is reserved again, e.g. by a migration retry
+    fn forget(&self, request_id: &str) {
+        self.0.lock().unwrap().stages.remove(request_id);
+    }
+
+    fn stage(&self, request_id: &str) -> Option<RetiredStage> {
+        self.0
+            .lock()
+            .unwrap()
+            .stages
+            .get(request_id)
+            .map(|(stage, _)| *stage)
+    }
 }
 
-// Default implementation matching the Python _cost_function
-#[derive(Debug, Clone, Default)]
-pub struct DefaultWorkerSelector {
-    pub kv_router_config: KvRouterConfig,
+/// Options of [`KvScheduler::start`]
+#[derive(Clone, Builder)]
+pub struct KvSchedulerConfig {
+    /// UUID of the router, identifying its reservations to the replicas
+    #[builder(setter(into))]
+    pub router_uuid: String,
+
+    /// Whether to sync reservations with the other router replicas
+    #[builder(default)]
+    pub replica_sync: bool,
+
+    /// Whether to reserve capacity before responding, failing the request if it cannot be tracked
+    #[builder(default)]
+    pub strict_slot_tracking: bool,
+
+    /// Half-life of the affinity of a prefix to the worker which last served it
+    #[builder(default)]
+    pub affinity_half_life: Option<Duration>,
+
+    #[builder(default)]
+    pub max_tracked_requests: Option<usize>,
+
+    /// Journal to persist reservations to, freeing those of crashed routers on startup
+    #[builder(default)]
+    pub journal: Option<ReservationJournal>,
+
+    /// Window to aggregate KV hit rate events over, instead of publishing one per request
+    #[builder(default)]
+    pub hit_rate_window: Option<Duration>,
+
+    /// Pauses the per-request hit rate events after repeated publish failures
+    #[builder(default)]
+    pub hit_rate_publish_breaker: Option<PublishBreaker>,
+
+    /// Default maximum number of requests per second dispatched to a worker
+    #[builder(default)]
+    pub worker_max_rps: Option<f64>,
+
+    /// How long a removed worker is held out of routing, even if it reappears meanwhile
+    #[builder(default)]
+    pub rejoin_cooldown: Option<Duration>,
+
+    /// Policy approving, denying or re-prioritizing every request before it is scheduled
+    #[builder(default)]
+    pub admission_policy: Option<Arc<dyn AdmissionPolicy>>,
+
+    /// Interval between two load snapshots pushed to the load subscribers
+    #[builder(default = "Duration::from_secs(1)")]
+    pub load_update_interval: Duration,
+
+    /// Queue depth above which requests are routed by load only, without overlap
+    #[builder(default)]
+    pub degraded_queue_depth: Option<usize>,
+
+    /// Rejects low-priority requests while the queue is deep
+    #[builder(default)]
+    pub load_shedding: Option<LoadShedding>,
+
+    /// Wait in the queue worth one priority level, see [`QueueAging`] (default: no aging)
+    #[builder(default)]
+    pub queue_aging: Option<Duration>,
+
+    /// Once cancelled, new requests are refused while the queued ones are still scheduled
+    /// (default: never cancelled)
+    #[builder(default)]
+    pub intake_token: Option<CancellationToken>,
+
+    /// Stops the scheduler tasks (default: the primary token of the component)
+    #[builder(default)]
+    pub cancellation_token: Option<CancellationToken>,
 }
 
-impl DefaultWorkerSelector {
-    pub fn new(kv_router_config: Option<KvRouterConfig>) -> Self {
-        Self {
-            kv_router_config: kv_router_config.unwrap_or_default(),
-        }
+impl KvSchedulerConfig {
+    pub fn builder() -> KvSchedulerConfigBuilder {
+        KvSchedulerConfigBuilder::default()
     }
 }
 
-impl WorkerSelector for DefaultWorkerSelector {
-    fn select_worker(
-        &self,
-        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
-        request: &SchedulingRequest,
+impl KvScheduler {
+    pub async fn start(
+        component: Component,
         block_size: u32,
-    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
-        assert!(request.isl_tokens > 0);
+        instances_rx: watch::Receiver<Vec<Instance>>,
+        runtime_configs_rx: watch::Receiver<HashMap<WorkerId, ModelRuntimeConfig>>,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        shadow_selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        config: KvSchedulerConfig,
+    ) -> Result<Self, KvSchedulerError> {
+        let KvSchedulerConfig {
+            router_uuid,
+            replica_sync,
+            strict_slot_tracking,
+            affinity_half_life,
+            max_tracked_requests,
+            journal,
+            hit_rate_window,
+            hit_rate_publish_breaker,
+            worker_max_rps,
+            rejoin_cooldown,
+            admission_policy,
+            load_update_interval,
+            degraded_queue_depth,
+            load_shedding,
+            queue_aging,
+            intake_token,
+            cancellation_token,
+        } = config;
+        let cancellation_token =
+            cancellation_token.unwrap_or_else(|| component.drt().primary_token());
+        let selector = SwappableSelector::new(match selector {
+            Some(selector) => Arc::from(selector),
+            None => Arc::new(DefaultWorkerSelector::default()),
+        });
+        let shadow_selector: Option<Arc<dyn WorkerSelector + Send + Sync>> =
+            shadow_selector.map(Arc::from);
+        let instances: Vec<Instance> = instances_rx.borrow().clone();
+        let runtime_configs: HashMap<WorkerId, ModelRuntimeConfig> =
+            runtime_configs_rx.borrow().clone();
 
-        if workers.is_empty() {
-            return Err(KvSchedulerError::NoEndpoints);
+        // Create shared workers_with_configs wrapped in Arc<RwLock>
+        let initial_workers = workers_with_configs_from(&instances, &runtime_configs);
+        let mut runtime_configs_missing = all_runtime_configs_missing(&initial_workers);
+        if runtime_configs_missing {
+            warn_runtime_configs_missing(initial_workers.len());
         }
+        selector.observe_workers(&initial_workers);
+        if let Some(shadow_selector) = shadow_selector.as_ref() {
+            shadow_selector.observe_workers(&initial_workers);
+        }
+        let workers_with_configs: Arc<RwLock<HashMap<WorkerId, Option<ModelRuntimeConfig>>>> =
+            Arc::new(RwLock::new(initial_workers));
 
-        let isl = request.isl_tokens;
-        let request_blocks = isl.div_ceil(block_size as usize);
-        let overlaps = &request.overlaps.scores;
-
-        let decode_blocks = &request.decode_blocks;
-        let prefill_tokens = &request.prefill_tokens;
+        // Register the metrics of the default selector before its first decision
+        LogitSpreadMetrics::from_component(&component);
+        DegradedRoutingMetrics::from_component(&component);
+        let queue_wait_metrics = QueueWaitMetrics::from_component(&component);
+        let decision_latency = DecisionLatencyMetrics::from_component(&component);
 
-        let mut worker_logits = HashMap::new();
-        let mut max_logit = f64::NEG_INFINITY;
+        let slots = Arc::new(
+            ActiveSequencesMultiWorker::new(
+                component.clone(),
+                block_size as usize,
+                workers_with_configs.read().await.clone(), // this includes dp_size info
+                replica_sync,
+                router_uuid,
+            )
+            .with_max_tracked_requests(max_tracked_requests),
+        );
 
-        // Calculate logits for each worker with dp_rank
-        // Outer loop: iterate over all workers from runtime config
-        // Inner loop: iterate over all dp_ranks for each worker
+        // Free the reservations left behind by crashed routers. The workers are listed from the
+        // store rather than taken from the instance watch, which may not be populated yet
+        if let Some(journal) = journal.clone() {
+            let slots = slots.clone();
+            let component = component.clone();
+            tokio::spawn(async move {
+                let recovery = async {
+                    let etcd_client = component
+                        .drt()
+                        .etcd_client()
+                        .ok_or_else(|| anyhow::anyhow!("etcd client not available"))?;
+                    let live_routers = active_router_uuids(&etcd_client, &component).await?;
+                    let live_workers: HashSet<WorkerId> = component
+                        .list_instances()
+                        .await?
+                        .iter()
+                        .map(|instance| instance.instance_id)
+                        .collect();
+                    journal.recover(&slots, &live_routers, &live_workers).await
+                };
+                if let Err(e) = recovery.await {
+                    tracing::warn!("Failed to recover the reservation journal: {e:?}");
+                }
+            });
+        }
+
+        // Spawn background task to monitor and update workers_with_configs
+        let workers_monitor = workers_with_configs.clone();
+        let slots_monitor = slots.clone();
+        let selector_monitor = selector.clone();
+        let shadow_selector_monitor = shadow_selector.clone();
+        let mut instances_monitor_rx = instances_rx.clone();
+        let mut configs_monitor_rx = runtime_configs_rx.clone();
+        let monitor_cancel_token = cancellation_token.clone();
+        let monitor_namespace = component.namespace().clone();
+        tokio::spawn(async move {
+            tracing::trace!("workers monitoring task started");
+            let mut rejoin_cooldown = rejoin_cooldown.map(RejoinCooldown::new);
+            // Time until a worker held out by the cooldown is re-admitted
+            let mut readmit_in: Option<Duration> = None;
+            loop {
+                // Wait for either instances or configs to change, or for a held out worker to
+                // be re-admitted
+                tokio::select! {
+                    _ = monitor_cancel_token.cancelled() => {
+                        tracing::trace!("workers monitoring task shutting down");
+                        break;
+                    }
+                    result = instances_monitor_rx.changed() => {
+                        if result.is_err() {
+                            tracing::warn!("endpoint watch sender shutdown in monitor");
+                            break;
+                        }
+                    }
+                    result = configs_monitor_rx.changed() => {
+                        if result.is_err() {
+                            tracing::warn!("runtime configs watch sender shutdown in monitor");
+                            break;
+                        }
+                    }
+                    _ = tokio::time::sleep(readmit_in.unwrap_or_default()), if readmit_in.is_some() => {}
+                }
+
+                // Get the latest values from both channels
+                let new_instances = instances_monitor_rx.borrow_and_update().clone();
+                let new_configs = configs_monitor_rx.borrow_and_update().clone();
+
+                // Build the new workers_with_configs map
+                let mut new_workers_with_configs =
+                    workers_with_configs_from(&new_instances, &new_configs);
+                if let Some(cooldown) = rejoin_cooldown.as_mut() {
+                    let previous = workers_monitor.read().await;
+                    readmit_in =
+                        cooldown.hold_out(&previous, &mut new_workers_with_configs, Instant::now());
+                }
+
+                let missing = all_runtime_configs_missing(&new_workers_with_configs);
+                if missing && !runtime_configs_missing {
+                    warn_runtime_configs_missing(new_workers_with_configs.len());
+                } else if !missing && runtime_configs_missing {
+                    tracing::info!("Runtime configs available again, leaving degraded routing");
+                }
+                runtime_configs_missing = missing;
+
+                // Update workers when instances change
+                slots_monitor.update_workers(new_workers_with_configs.clone());
+
+                // Update the shared workers_with_configs
+                let membership_events = {
+                    let mut workers_map = workers_monitor.write().await;
+                    let events = membership_changes(&workers_map, &new_workers_with_configs);
+                    *workers_map = new_workers_with_configs;
+                    selector_monitor.observe_workers(&workers_map);
+                    if let Some(shadow_selector) = shadow_selector_monitor.as_ref() {
+                        shadow_selector.observe_workers(&workers_map);
+                    }
+                    tracing::trace!(
+                        "Updated workers_with_configs with {} workers",
+                        workers_map.len()
+                    );
+                    events
+                };
+
+                // Notify the external consumers of the fleet changes
+                for event in membership_events {
+                    tracing::debug!(
+                        "Worker {} {} the routing set",
+                        event.worker_id,
+                        if event.added { "joined" } else { "left" }
+                    );
+                    if let Err(e) = monitor_namespace
+                        .publish(KV_WORKER_MEMBERSHIP_SUBJECT, &event)
+                        .await
+                    {
+                        tracing::warn!("Failed to publish worker membership event: {e:?}");
+                    }
+                }
+            }
+            tracing::trace!("workers monitoring task shutting down");
+        });
+
+        // Push the loads to their subscribers periodically and on significant changes. Nothing
+        // is computed while there is no subscriber.
+        let (loads_tx, _) = broadcast::channel(LOAD_UPDATES_CAPACITY);
+        let loads_publisher = loads_tx.clone();
+        let slots_publisher = slots.clone();
+        let workers_publisher = workers_with_configs.clone();
+        let publisher_cancel_token = cancellation_token.clone();
+        tokio::spawn(async move {
+            let mut poll =
+                tokio::time::interval(LOAD_CHANGE_POLL_INTERVAL.min(load_update_interval));
+            poll.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut last_pushed: Option<(Instant, Vec<PotentialLoad>)> = None;
+            loop {
+                tokio::select! {
+                    _ = publisher_cancel_token.cancelled() => break,
+                    _ = poll.tick() => {}
+                }
+                if loads_publisher.receiver_count() == 0 {
+                    last_pushed = None;
+                    continue;
+                }
+                let workers = workers_publisher.read().await.clone();
+                let loads = potential_loads(
+                    &slots_publisher,
+                    &workers,
+                    block_size,
+                    None,
+                    0,
+                    OverlapScores::default(),
+                )
+                .await;
+                let due = last_pushed.as_ref().is_none_or(|(pushed_at, previous)| {
+                    pushed_at.elapsed() >= load_update_interval
+                        || significant_load_change(previous, &loads)
+                });
+                if due {
+                    // Only fails if the last subscriber just left
+                    let _ = loads_publisher.send(loads.clone());
+                    last_pushed = Some((Instant::now(), loads));
+                }
+            }
+        });
+
+        let cancelled = Arc::new(CancelledRequests::default());
+        let cancelled_scheduler = cancelled.clone();
+
+        // Publish the aggregated hit rates once per window, flushing on shutdown
+        let hit_rates = hit_rate_window.map(|window| {
+            let hit_rates = Arc::new(HitRateAggregator::default());
+            let aggregator = hit_rates.clone();
+            let namespace = component.namespace().clone();
+            let cancel_token = cancellation_token.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(window);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                interval.tick().await;
+                loop {
+                    let shutdown = tokio::select! {
+                        _ = cancel_token.cancelled() => true,
+                        _ = interval.tick() => false,
+                    };
+                    for event in aggregator.drain() {
+                        if let Err(e) = namespace.publish(KV_HIT_RATE_SUBJECT, &event).await {
+                            tracing::warn!("Failed to publish aggregated KV hit rate event: {e:?}");
+                        }
+                    }
+                    if shutdown {
+                        break;
+                    }
+                }
+            });
+            hit_rates
+        });
+        let journal_scheduler = journal.clone();
+        let recent_decisions = Arc::new(Mutex::new(VecDeque::with_capacity(
+            RECENT_DECISIONS_CAPACITY,
+        )));
+        let recent_decisions_scheduler = recent_decisions.clone();
+        let hit_rate_history = Arc::new(HitRateHistory::default());
+        let hit_rate_history_scheduler = hit_rate_history.clone();
+        let slots_clone = slots.clone();
+        let workers_scheduler = workers_with_configs.clone();
+        let selector_scheduler = selector.clone();
+        let last_loads = Arc::new(Mutex::new(HashMap::new()));
+        let last_loads_scheduler = last_loads.clone();
+        let (request_tx, request_rx) = tokio::sync::mpsc::channel::<SchedulingRequest>(1024);
+        let queued = Arc::new(AtomicUsize::new(0));
+        let queued_scheduler = queued.clone();
+        let reset_requested = Arc::new(AtomicBool::new(false));
+        let reset_requested_scheduler = reset_requested.clone();
+        let scheduler_cancel_token = cancellation_token.clone();
+        let ns_clone = component.namespace().clone();
+
+        // The shadow selector runs on its own task, so that neither its selection nor publishing
+        // its events delays the live decisions
+        let shadow_tx = shadow_selector.map(|shadow_selector| {
+            let (shadow_tx, mut shadow_rx) =
+                tokio::sync::mpsc::channel::<ShadowSelectionJob>(SHADOW_SELECTION_CAPACITY);
+            let namespace = component.namespace().clone();
+            let shadow_cancel_token = cancellation_token.clone();
+            tokio::spawn(async move {
+                loop {
+                    let job = tokio::select! {
+                        _ = shadow_cancel_token.cancelled() => break,
+                        job = shadow_rx.recv() => job,
+                    };
+                    let Some(job) = job else {
+                        break;
+                    };
+                    let shadow = shadow_selector
+                        .select_worker(&job.workers, &job.request, block_size)
+                        .inspect_err(|e| tracing::debug!("shadow selector failed: {e}"))
+                        .ok();
+                    let event = ShadowSelectionEvent::new(&job.request, &job.live, shadow.as_ref());
+                    if let Err(e) = namespace.publish(KV_SHADOW_SELECTION_SUBJECT, &event).await {
+                        tracing::warn!("Failed to publish shadow selection event: {:?}", e);
+                    }
+                }
+            });
+            shadow_tx
+        });
+
+        // Background task to handle scheduling requests
+        tokio::spawn(async move {
+            let mut request_rx = request_rx;
+            let selector = selector_scheduler;
+            let mut affinity = affinity_half_life.map(AffinityTracker::new);
+            let mut rate_limiter = WorkerRateLimiter::new(worker_max_rps);
+            let mut hit_rate_breaker =
+                PublishCircuitBreaker::new("KV hit rate events", hit_rate_publish_breaker);
+            let mut pending: BinaryHeap<QueuedRequest> = BinaryHeap::new();
+            let mut arrivals: u64 = 0;
+            let aging = queue_aging.map(QueueAging::new);
+            tracing::trace!("scheduler background task started");
+
+            loop {
+                // Check for cancellation at beginning of loop
+                if scheduler_cancel_token.is_cancelled() {
+                    tracing::trace!("scheduler background task shutting down");
+                    break;
+                }
+
+                // Wait for a new request if none is pending, then take every request already
+                // sent, so that the next one scheduled is the one of highest priority
+                if pending.is_empty() {
+                    let request = tokio::select! {
+                        _ = scheduler_cancel_token.cancelled() => {
+                            tracing::trace!("scheduler background task shutting down");
+                            break;
+                        }
+                        request = request_rx.recv() => request,
+                    };
+                    let Some(request) = request else {
+                        tracing::warn!("scheduler shutdown");
+                        break;
+                    };
+                    if let Some(request) = admit(admission_policy.as_deref(), request).await {
+                        enqueue(
+                            &mut pending,
+                            &mut arrivals,
+                            load_shedding.as_ref(),
+                            aging.as_ref(),
+                            request,
+                        );
+                    }
+                }
+                while let Ok(request) = request_rx.try_recv() {
+                    if let Some(request) = admit(admission_policy.as_deref(), request).await {
+                        enqueue(
+                            &mut pending,
+                            &mut arrivals,
+                            load_shedding.as_ref(),
+                            aging.as_ref(),
+                            request,
+                        );
+                    }
+                }
+                let Some(QueuedRequest {
+                    mut request,
+                    enqueued_at,
+                    ..
+                }) = pending.pop()
+                else {
+                    continue;
+                };
+                queued_scheduler.store(pending.len(), AtomicOrdering::Relaxed);
+                queue_wait_metrics.record(enqueued_at.elapsed());
+
+                if reset_requested_scheduler.swap(false, AtomicOrdering::AcqRel) {
+                    affinity = affinity_half_life.map(AffinityTracker::new);
+                    rate_limiter = WorkerRateLimiter::new(worker_max_rps);
+                }
+                tracing::trace!("received request to be scheduled");
+
+                if let Some(request_id) = request.maybe_request_id.as_deref()
+                    && cancelled_scheduler.take(request_id)
+                {
+                    tracing::debug!("skipping cancelled request {request_id}");
+                    request.respond_err(KvSchedulerError::Cancelled);
+                    continue;
+                }
+
+                // Nobody would dispatch or free a reservation made for a caller which is gone
+                if request.is_abandoned() {
+                    tracing::debug!(
+                        "skipping request {:?} whose caller stopped waiting",
+                        request.maybe_request_id
+                    );
+                    continue;
+                }
+
+                // A request id which is still reserved is being retried, e.g. migrated after its
+                // stream dropped: release the stale reservation and select again, away from the
+                // worker which failed it if another one can serve the request
+                let stale_worker = if request.update_states
+                    && let Some(request_id) = request.maybe_request_id.as_ref()
+                    && let Some(worker) = slots_clone.worker_of(request_id)
+                {
+                    tracing::debug!(
+                        "request {request_id} is already active on worker {worker:?}; releasing it to select again"
+                    );
+                    if let Err(e) = slots_clone.free(request_id).await {
+                        tracing::warn!(
+                            "Failed to release the stale reservation of request {request_id}: {e:?}"
+                        );
+                    }
+                    Some(worker)
+                } else {
+                    None
+                };
+
+                let load_started = Instant::now();
+                let (decode_blocks, prefill_tokens) = slots_clone
+                    .potential_blocks_and_tokens(
+                        request.token_seq.clone(),
+                        request.isl_tokens,
+                        request.overlaps.clone(),
+                    )
+                    .await;
+                DecisionLatencyMetrics::observe(&decision_latency.load, load_started);
+                last_loads_scheduler
+                    .lock()
+                    .unwrap()
+                    .clone_from(&decode_blocks);
+                request.decode_blocks = decode_blocks;
+                request.prefill_tokens = prefill_tokens;
+
+                let prefix_key = request
+                    .token_seq
+                    .as_ref()
+                    .and_then(|seq| seq.first().copied());
+                let now = Instant::now();
+                if let (Some(affinity), Some(key)) = (affinity.as_ref(), prefix_key) {
+                    request.affinity_decay = affinity.decay(key, now);
+                }
+
+                // Read the current workers configuration
+                let mut workers = workers_scheduler.read().await.clone();
+                if !workers.is_empty() {
+                    request.phase.filter_workers(&mut workers);
+                    if workers.is_empty() {
+                        tracing::error!("no worker available for the {:?} phase", request.phase);
+                        request.respond_err(KvSchedulerError::NoEndpoints);
+                        continue;
+                    }
+                }
+
+                // Queries do not dispatch, so they are not rate limited
+                if request.update_states
+                    && let Some(wait) = rate_limiter.retain_available(&mut workers, Instant::now())
+                    && workers.is_empty()
+                {
+                    // Every worker is rate limited: wait briefly for a token rather than failing
+                    tokio::time::sleep(wait.min(RATE_LIMIT_MAX_WAIT)).await;
+                    workers = workers_scheduler.read().await.clone();
+                    request.phase.filter_workers(&mut workers);
+                    rate_limiter.retain_available(&mut workers, Instant::now());
+                    if workers.is_empty() {
+                        tracing::warn!("all workers are rate limited");
+                        request.respond_err(KvSchedulerError::AllWorkersBusy);
+                        continue;
+                    }
+                }
+                if let Some(stale_worker) = stale_worker
+                    && workers.len() > 1
+                {
+                    workers.remove(&stale_worker.worker_id);
+                }
+
+                // The same selector makes the whole decision, even if it is replaced meanwhile
+                let current_selector = selector.current();
+                let selection_started = Instant::now();
+                match current_selector.select_worker(&workers, &request, block_size) {
+                    Ok(selection) => {
+                        // In strict mode the reservation is made before responding, so that a
+                        // failure can be surfaced to the caller instead of undercounting load
+                        let strict = strict_slot_tracking && request.update_states;
+                        let selection = if strict {
+                            match reserve_strict(
+                                &slots_clone,
+                                current_selector.as_ref(),
+                                &workers,
+                                &request,
+                                selection,
+                                block_size,
+                            )
+                            .await
+                            {
+                                Ok(selection) => selection,
+                                Err(e) => {
+                                    tracing::error!("failed to reserve capacity for request: {e}");
+                                    request.respond_err(e);
+                                    continue;
+                                }
+                            }
+                        } else {
+                            selection
+                        };
+                        DecisionLatencyMetrics::observe(
+                            &decision_latency.selection,
+                            selection_started,
+                        );
+
+                        if request.update_states {
+                            rate_limiter.consume(
+                                selection.worker.worker_id,
+                                workers
+                                    .get(&selection.worker.worker_id)
+                                    .and_then(Option::as_ref),
+                                Instant::now(),
+                            );
+                        }
+
+                        let event = KVHitRateEvent {
+                            worker_id: selection.worker.worker_id,
+                            dp_rank: selection.worker.dp_rank,
+                            isl_blocks: selection.required_blocks as usize,
+                            overlap_blocks: selection.overlap_blocks,
+                            request_count: 1,
+                        };
+                        let publish_started = Instant::now();
+                        hit_rate_history_scheduler.record(&event, chrono::Utc::now());
+                        if let Some(hit_rates) = hit_rates.as_ref() {
+                            hit_rates.record(event);
+                        } else if hit_rate_breaker.allows(Instant::now()) {
+                            let result = ns_clone.publish(KV_HIT_RATE_SUBJECT, &event).await;
+                            hit_rate_breaker.record(result, Instant::now());
+                        }
+                        DecisionLatencyMetrics::observe(&decision_latency.publish, publish_started);
+
+                        let response = SchedulingResponse {
+                            best_worker: selection.worker,
+                            overlap_blocks: selection.overlap_blocks,
+                            logit: selection.logit,
+                            candidates: workers.len(),
+                        };
+                        request.respond(response);
+
+                        if let Some(shadow_tx) = shadow_tx.as_ref() {
+                            let job = ShadowSelectionJob {
+                                workers: workers.clone(),
+                                request: request.detached(),
+                                live: selection.clone(),
+                            };
+                            if shadow_tx.try_send(job).is_err() {
+                                tracing::debug!(
+                                    "Shadow selector falling behind, dropping a shadow selection"
+                                );
+                            }
+                        }
+
+                        {
+                            let mut recent = recent_decisions_scheduler.lock().unwrap();
+                            if recent.len() == RECENT_DECISIONS_CAPACITY {
+                                recent.pop_front();
+                            }
+                            recent.push_back(SchedulingDecision {
+                                request_id: request.maybe_request_id.clone(),
+                                worker: selection.worker,
+                                isl_tokens: request.isl_tokens,
+                                overlap_blocks: selection.overlap_blocks,
+                                expected_ttft_secs: selection.expected_ttft_secs,
+                                update_states: request.update_states,
+                                decided_at: chrono::Utc::now(),
+                            });
+                        }
+
+                        if request.update_states
+                            && let (Some(affinity), Some(key)) = (affinity.as_mut(), prefix_key)
+                        {
+                            affinity.record(key, selection.worker, now);
+                        }
+
+                        // Skip state update if not requested
+                        if !request.update_states {
+                            continue;
+                        }
+
+                        let Some(request_id) = request.maybe_request_id else {
+                            tracing::error!(
+                                "No request_id provided to add_request to the slot tracker"
+                            );
+                            continue;
+                        };
+
+                        // In strict mode the request was already reserved
+                        let state_update_started = Instant::now();
+                        if !strict
+                            && let Err(e) = slots_clone
+                                .add_request(
+                                    request_id.clone(),
+                                    request.token_seq,
+                                    request.isl_tokens,
+                                    selection.overlap_blocks,
+                                    selection.worker,
+                                )
+                                .await
+                        {
+                            tracing::warn!(
+                                "Failed to add request {request_id} to local slot tracker: {e:?}"
+                            );
+                            continue;
+                        }
+
+                        if let Some(journal) = &journal_scheduler {
+                            journal.spawn_record(&request_id, selection.worker);
+                        }
+
+                        // Decode workers receive the KV cache from the prefill worker, so there is
+                        // no prefill to account for
+                        if request.phase == SchedulingPhase::Decode
+                            && let Err(e) = slots_clone.mark_prefill_completed(&request_id).await
+                        {
+                            tracing::warn!(
+                                "Failed to mark prefill completed for decode request {request_id}: {e:?}"
+                            );
+                        }
+                        DecisionLatencyMetrics::observe(
+                            &decision_latency.state_update,
+                            state_update_started,
+                        );
+                    }
+                    Err(
+                        e @ (KvSchedulerError::MissingRuntimeConfigs
+                        | KvSchedulerError::ContextTooLong { .. }
+                        | KvSchedulerError::InvalidLogits),
+                    ) => {
+                        tracing::error!("refusing to schedule request: {e}");
+                        request.respond_err(e);
+                        continue;
+                    }
+                    Err(KvSchedulerError::NoEndpoints) => {
+                        tracing::trace!("no endpoints available; waiting for endpoints update");
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        continue;
+                    }
+                    // TODO: this is not actually hooked up
+                    Err(KvSchedulerError::AllWorkersBusy) => {
+                        tracing::trace!("all workers busy; waiting for more capacity");
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::error!("error scheduling request: {:?}", e);
+                        break;
+                    }
+                }
+            }
+
+            tracing::trace!("background endpoint subscriber shutting down");
+        });
+
+        Ok(KvScheduler {
+            request_tx,
+            slots,
+            selector,
+            workers_with_configs,
+            block_size,
+            cancelled,
+            retired: RetiredRequests::default(),
+            recent_decisions,
+            hit_rate_history,
+            journal,
+            last_loads,
+            queued,
+            loads_tx,
+            degradation: degraded_queue_depth.map(RoutingDegradation::new),
+            intake: intake_token.unwrap_or_default(),
+            bypass_cursor: AtomicUsize::new(0),
+            reset_requested,
+        })
+    }
+
+    pub async fn schedule(
+        &self,
+        maybe_request_id: Option<String>,
+        isl_tokens: usize,
+        token_seq: Option<Vec<SequenceHash>>,
+        overlaps: OverlapScores,
+        router_config_override: Option<&RouterConfigOverride>,
+        update_states: bool,
+    ) -> Result<WorkerWithDpRank, KvSchedulerError> {
+        self.schedule_in_phase(
+            maybe_request_id,
+            isl_tokens,
+            token_seq,
+            overlaps,
+            router_config_override,
+            update_states,
+            SchedulingPhase::Any,
+        )
+        .await
+    }
+
+    /// Select a prefill (or aggregated) worker for the first stage of disaggregated serving and
+    /// reserve the request's prefill on it.
+    pub async fn schedule_prefill(
+        &self,
+        request_id: String,
+        isl_tokens: usize,
+        token_seq: Option<Vec<SequenceHash>>,
+        overlaps: OverlapScores,
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> Result<WorkerWithDpRank, KvSchedulerError> {
+        self.schedule_in_phase(
+            Some(request_id),
+            isl_tokens,
+            token_seq,
+            overlaps,
+            router_config_override,
+            true,
+            SchedulingPhase::Prefill,
+        )
+        .await
+    }
+
+    /// Select a decode (or aggregated) worker once the prefill stage is done. The reservation
+    /// made by [`KvScheduler::schedule_prefill`] for the same request is released, and the request
+    /// is tracked on the decode worker with its prefill already completed, since the KV cache is
+    /// transferred from the prefill worker.
+    pub async fn schedule_decode(
+        &self,
+        request_id: String,
+        isl_tokens: usize,
+        token_seq: Option<Vec<SequenceHash>>,
+        overlaps: OverlapScores,
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> Result<WorkerWithDpRank, KvSchedulerError> {
+        if let Err(e) = self.slots.free(&request_id).await {
+            tracing::debug!("No prefill reservation to release for request {request_id}: {e:?}");
+        }
+        self.schedule_in_phase(
+            Some(request_id),
+            isl_tokens,
+            token_seq,
+            overlaps,
+            router_config_override,
+            true,
+            SchedulingPhase::Decode,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn schedule_in_phase(
+        &self,
+        maybe_request_id: Option<String>,
+        isl_tokens: usize,
+        token_seq: Option<Vec<SequenceHash>>,
+        overlaps: OverlapScores,
+        router_config_override: Option<&RouterConfigOverride>,
+        update_states: bool,
+        phase: SchedulingPhase,
+    ) -> Result<WorkerWithDpRank, KvSchedulerError> {
+        if self.intake.is_cancelled() {
+            return Err(KvSchedulerError::ShuttingDown);
+        }
+        if update_states && let Some(request_id) = maybe_request_id.as_deref() {
+            self.retired.forget(request_id);
+        }
+        submit(
+            &self.request_tx,
+            maybe_request_id,
+            isl_tokens,
+            token_seq,
+            overlaps,
+            router_config_override.cloned(),
+            update_states,
+            phase,
+        )
+        .await
+    }
+
+    /// Reserve a provisional worker for the request without waiting for the scheduler, then
+    /// refine the decision in the background. See [`ProvisionalSchedule`] for the consistency
+    /// tradeoff. Falls back to a regular [`KvScheduler::schedule`] before the first decision,
+    /// when no loads are known yet.
+    pub async fn schedule_provisional(
+        &self,
+        request_id: String,
+        isl_tokens: usize,
+        token_seq: Option<Vec<SequenceHash>>,
+        overlaps: OverlapScores,
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> Result<ProvisionalSchedule, KvSchedulerError> {
+        if self.intake.is_cancelled() {
+            return Err(KvSchedulerError::ShuttingDown);
+        }
+        self.retired.forget(&request_id);
+        let provisional = {
+            let workers = self.workers_with_configs.read().await;
+            let last_loads = self.last_loads.lock().unwrap();
+            if last_loads.is_empty() {
+                None
+            } else {
+                provisional_worker(&workers, &last_loads, &overlaps)
+            }
+        };
+        let (refined_tx, refined_rx) = tokio::sync::oneshot::channel();
+
+        let Some(worker) = provisional else {
+            let worker = self
+                .schedule(
+                    Some(request_id),
+                    isl_tokens,
+                    token_seq,
+                    overlaps,
+                    router_config_override,
+                    true,
+                )
+                .await?;
+            let _ = refined_tx.send(());
+            return Ok(ProvisionalSchedule {
+                worker,
+                state: Arc::new(tokio::sync::Mutex::new(ProvisionalState {
+                    worker,
+                    dispatched: false,
+                })),
+                refined_rx,
+            });
+        };
+
+        let overlap_of =
+            |worker: &WorkerWithDpRank| overlaps.scores.get(worker).copied().unwrap_or(0);
+        self.slots
+            .add_request(
+                request_id.clone(),
+                token_seq.clone(),
+                isl_tokens,
+                overlap_of(&worker),
+                worker,
+            )
+            .await
+            .map_err(|e| KvSchedulerError::ReservationFailed(e.to_string()))?;
+
+        let state = Arc::new(tokio::sync::Mutex::new(ProvisionalState {
+            worker,
+            dispatched: false,
+        }));
+        let refine_state = state.clone();
+        let request_tx = self.request_tx.clone();
+        let slots = self.slots.clone();
+        let router_config_override = router_config_override.cloned();
+        tokio::spawn(async move {
+            let refined = submit(
+                &request_tx,
+                None,
+                isl_tokens,
+                token_seq.clone(),
+                overlaps.clone(),
+                router_config_override,
+                false,
+                SchedulingPhase::Any,
+            )
+            .await;
+
+            match refined {
+                Ok(refined) => {
+                    let mut state = refine_state.lock().await;
+                    if !state.dispatched && refined != state.worker {
+                        tracing::debug!(
+                            "Moving reservation of request {request_id} from provisional worker {:?} to {refined:?}",
+                            state.worker
+                        );
+                        let overlap = overlaps.scores.get(&refined).copied().unwrap_or(0);
+                        let moved = match slots.free(&request_id).await {
+                            Ok(()) => {
+                                slots
+                                    .add_request(
+                                        request_id.clone(),
+                                        token_seq,
+                                        isl_tokens,
+                                        overlap,
+                                        refined,
+                                    )
+                                    .await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        match moved {
+                            Ok(()) => state.worker = refined,
+                            Err(e) => tracing::warn!(
+                                "Failed to move reservation of request {request_id}: {e:?}"
+                            ),
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::debug!("Keeping provisional worker of request {request_id}: {e}");
+                }
+            }
+            let _ = refined_tx.send(());
+        });
+
+        Ok(ProvisionalSchedule {
+            worker,
+            state,
+            refined_rx,
+        })
+    }
+
+    /// Rank the workers for a request and return the `n` best ones (lowest logit first) together
+    /// with their overlap blocks, for speculative dispatch to several workers at once.
+    ///
+    /// No capacity is reserved on any of the returned workers and no hit-rate events are published:
+    /// reservations are the caller's responsibility (e.g. via [`KvScheduler::add_request`] for the
+    /// worker that wins). This intentionally trades capacity accounting accuracy for tail latency.
+    pub async fn schedule_top_n(
+        &self,
+        n: usize,
+        isl_tokens: usize,
+        token_seq: Option<Vec<SequenceHash>>,
+        overlaps: OverlapScores,
+        router_config_override: Option<&RouterConfigOverride>,
+    ) -> Result<Vec<SchedulingResponse>, KvSchedulerError> {
+        let (decode_blocks, prefill_tokens) = self
+            .slots
+            .potential_blocks_and_tokens(token_seq.clone(), isl_tokens, overlaps.clone())
+            .await;
+
+        let request = SchedulingRequest {
+            maybe_request_id: None,
+            token_seq,
+            isl_tokens,
+            overlaps,
+            decode_blocks,
+            prefill_tokens,
+            router_config_override: router_config_override.cloned(),
+            update_states: false,
+            affinity_decay: None,
+            phase: SchedulingPhase::Any,
+            priority: 0,
+            resp_tx: None,
+        };
+
+        let workers = self.workers_with_configs.read().await.clone();
+        let ranked = self
+            .selector
+            .rank_workers(&workers, &request, self.block_size)?;
+
+        Ok(ranked
+            .into_iter()
+            .take(n)
+            .map(|selection| SchedulingResponse {
+                best_worker: selection.worker,
+                overlap_blocks: selection.overlap_blocks,
+                logit: selection.logit,
+                candidates: workers.len(),
+            })
+            .collect())
+    }
+
+    /// The selector making the decisions of this scheduler. It delegates to the selector in
+    /// place at each call, including one set later by [`KvScheduler::set_selector`].
+    pub fn selector(&self) -> Arc<dyn WorkerSelector + Send + Sync> {
+        Arc::new(self.selector.clone())
+    }
+
+    /// Replace the selector making the decisions of this scheduler, e.g. to evaluate a routing
+    /// algorithm without restarting or to roll it back. Requests scheduled from now on use
+    /// `selector`, while a decision in progress completes with the previous one.
+    pub fn set_selector(&self, selector: Box<dyn WorkerSelector + Send + Sync>) {
+        // Without the current workers, e.g. while the monitor updates them, the selector learns
+        // them on their next change
+        if let Ok(workers) = self.workers_with_configs.try_read() {
+            selector.observe_workers(&workers);
+        }
+        self.selector.set(Arc::from(selector));
+    }
+
+    pub async fn add_request(
+        &self,
+        request_id: String,
+        token_sequence: Option<Vec<SequenceHash>>,
+        isl: usize,
+        overlap: u32,
+        worker: WorkerWithDpRank,
+    ) {
+        self.retired.forget(&request_id);
+        let _ = self
+            .slots
+            .add_request(request_id, token_sequence, isl, overlap, worker)
+            .await;
+    }
+
+    /// Mark the prefill of a scheduled request completed. Marking a tracked request again has no
+    /// effect. Fails with [`KvSchedulerError::PrefillAlreadyCompleted`] or
+    /// [`KvSchedulerError::AlreadyFreed`] if it is no longer tracked after being marked or freed,
+    /// and with [`KvSchedulerError::UnknownRequest`] if the router never tracked it, e.g. because
+    /// of a wrong id.
+    pub async fn mark_prefill_completed(&self, request_id: &str) -> Result<(), KvSchedulerError> {
+        let request_id_owned = request_id.to_string();
+        if self.slots.worker_of(&request_id_owned).is_none() {
+            return Err(match self.retired.stage(request_id) {
+                Some(RetiredStage::Freed) => KvSchedulerError::AlreadyFreed(request_id_owned),
+                Some(RetiredStage::PrefillCompleted) => {
+                    KvSchedulerError::PrefillAlreadyCompleted(request_id_owned)
+                }
+                None => KvSchedulerError::UnknownRequest(request_id_owned),
+            });
+        }
+        self.slots
+            .mark_prefill_completed(&request_id_owned)
+            .await
+            .map_err(|e| KvSchedulerError::TrackingFailed(e.to_string()))?;
+        self.retired
+            .record(request_id, RetiredStage::PrefillCompleted);
+        Ok(())
+    }
+
+    /// Free a scheduled request. Fails with [`KvSchedulerError::AlreadyFreed`] if it was already
+    /// freed, and with [`KvSchedulerError::UnknownRequest`] if the router never tracked it.
+    pub async fn free(&self, request_id: &str) -> Result<(), KvSchedulerError> {
+        let request_id_owned = request_id.to_string();
+        if self.slots.worker_of(&request_id_owned).is_none() {
+            return Err(match self.retired.stage(request_id) {
+                Some(RetiredStage::Freed) => KvSchedulerError::AlreadyFreed(request_id_owned),
+                _ => KvSchedulerError::UnknownRequest(request_id_owned),
+            });
+        }
+        self.slots
+            .free(&request_id_owned)
+            .await
+            .map_err(|e| KvSchedulerError::TrackingFailed(e.to_string()))?;
+        self.retired.record(request_id, RetiredStage::Freed);
+        if let Some(journal) = &self.journal
+            && let Err(e) = journal.remove(request_id).await
+        {
+            tracing::warn!(
+                "Failed to remove request {request_id} from the reservation journal: {e:?}"
+            );
+        }
+        Ok(())
+    }
+
+    /// Cancel a request which may still be queued, so the scheduler fails it with
+    /// [`KvSchedulerError::Cancelled`] instead of selecting and reserving a worker for it.
+    /// Has no effect on a request that was already scheduled; `free` it instead.
+    pub fn cancel(&self, request_id: &str) {
+        self.cancelled.insert(request_id.to_string());
+    }
+
+    pub async fn get_potential_loads(
+        &self,
+        token_seq: Option<Vec<SequenceHash>>,
+        isl_tokens: usize,
+        overlaps: OverlapScores,
+    ) -> Vec<PotentialLoad> {
+        let workers = self.workers_with_configs.read().await.clone();
+        potential_loads(
+            &self.slots,
+            &workers,
+            self.block_size,
+            token_seq,
+            isl_tokens,
+            overlaps,
+        )
+        .await
+    }
+
+    /// Stream of the potential loads of all workers, without any request, pushed every
+    /// `load_update_interval` and on every significant change. A subscriber which falls behind
+    /// skips the snapshots it missed.
+    pub fn subscribe_loads(&self) -> impl futures::Stream<Item = Vec<PotentialLoad>> + use<> {
+        futures::stream::unfold(self.loads_tx.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(loads) => return Some((loads, rx)),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::debug!("Load subscriber lagging, skipped {skipped} snapshots");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Fraction of the cluster's total KV cache in use, from the active blocks tracked by the
+    /// scheduler and the `total_kv_blocks` of the workers' runtime configs
+    pub async fn cluster_utilization(&self) -> ClusterUtilization {
+        let active_blocks = self.slots.active_blocks().await;
+        let workers = self.workers_with_configs.read().await;
+        ClusterUtilization::new(&active_blocks, &workers)
+    }
+
+    /// Request ids tracked by the scheduler with their assigned worker
+    pub fn active_requests(&self) -> Vec<(String, WorkerId)> {
+        self.slots.active_requests()
+    }
+
+    /// Snapshot the tracked requests to hand them over to another router
+    pub async fn export_active_state(&self) -> ActiveStateSnapshot {
+        self.slots.export_active_state().await
+    }
+
+    /// Track the requests handed over by another router
+    pub async fn import_active_state(
+        &self,
+        snapshot: ActiveStateSnapshot,
+    ) -> anyhow::Result<ImportReport> {
+        self.slots.import_active_state(snapshot).await
+    }
+
+    /// Reset the load accounting of the scheduler, e.g. once it drifted from what the workers
+    /// actually serve: every tracked request is forgotten, as are the prefix affinities, the
+    /// rate limits and the smoothed loads of the selector. The cache state of the indexer is
+    /// left intact, and so are the requests still queued. Returns the number of requests
+    /// forgotten; freeing one of them later fails with [`KvSchedulerError::UnknownRequest`].
+    pub fn reset_state(&self) -> usize {
+        let forgotten = self.slots.reset();
+        self.last_loads.lock().unwrap().clear();
+        self.selector.reset_load_state();
+        self.reset_requested.store(true, AtomicOrdering::Release);
+
+        if let Some(journal) = self.journal.clone() {
+            let forgotten = forgotten.clone();
+            tokio::spawn(async move {
+                for request_id in forgotten {
+                    if let Err(e) = journal.remove(&request_id).await {
+                        tracing::warn!(
+                            "Failed to remove request {request_id} from the reservation journal: {e:?}"
+                        );
+                    }
+                }
+            });
+        }
+
+        tracing::warn!(
+            "Reset the scheduler state, forgetting {} tracked requests",
+            forgotten.len()
+        );
+        forgotten.len()
+    }
+
+    /// The next worker dp rank in round robin, for requests bypassing routing, e.g. health
+    /// checks. Nothing is scored or tracked, so the request never reaches the scheduler loop.
+    pub async fn round_robin_worker(&self) -> Result<WorkerWithDpRank, KvSchedulerError> {
+        let workers = self.workers_with_configs.read().await;
+        let cursor = self.bypass_cursor.fetch_add(1, AtomicOrdering::Relaxed);
+        round_robin_pick(&workers, cursor).ok_or(KvSchedulerError::NoEndpoints)
+    }
+
+    /// Requests submitted and not scheduled yet, whether still in the channel or taken by the
+    /// scheduler loop
+    pub fn queue_depth(&self) -> usize {
+        self.request_tx.max_capacity() - self.request_tx.capacity()
+            + self.queued.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Reports the [`KvScheduler::queue_depth`] without borrowing the scheduler, e.g. to wait
+    /// for the queue to drain on shutdown. Reports 0 once the scheduler is dropped.
+    pub fn queue_depth_probe(&self) -> impl Fn() -> usize + Send + Sync + use<> {
+        let request_tx = self.request_tx.downgrade();
+        let queued = self.queued.clone();
+        move || {
+            request_tx
+                .upgrade()
+                .map_or(0, |tx| tx.max_capacity() - tx.capacity())
+                + queued.load(AtomicOrdering::Relaxed)
+        }
+    }
+
+    /// Whether the next request should be routed by load only because the queue is too deep,
+    /// see `degraded_queue_depth`. Always false without a threshold.
+    pub fn routing_degraded(&self) -> bool {
+        let Some(degradation) = &self.degradation else {
+            return false;
+        };
+        let degraded = degradation.update(self.queue_depth());
+        if degraded {
+            DegradedRoutingMetrics::get().degraded_requests.inc();
+        }
+        degraded
+    }
+
+    /// Cluster and per-worker hit rates of the requests routed over the last few minutes, in
+    /// buckets of 10 seconds, oldest first
+    pub fn hit_rate_history(&self) -> Vec<HitRateBucket> {
+        self.hit_rate_history.buckets(chrono::Utc::now())
+    }
+
+    /// Assemble the workers, their configs, the tracked load, the queue depth and the most recent
+    /// decisions into a single serializable snapshot
+    pub async fn dump_state(&self) -> SchedulerState {
+        let active_blocks = self.slots.active_blocks().await;
+        let active_tokens = self.slots.active_tokens().await;
+        let workers_with_configs = self.workers_with_configs.read().await.clone();
+
+        let mut slots: Vec<SlotOccupancy> = active_blocks
+            .iter()
+            .map(|(worker, blocks)| SlotOccupancy {
+                worker: *worker,
+                active_blocks: *blocks,
+                active_tokens: active_tokens.get(worker).copied().unwrap_or(0),
+            })
+            .collect();
+        slots.sort_by_key(|slot| slot.worker);
+
+        let mut workers: Vec<(WorkerId, Option<ModelRuntimeConfig>)> = workers_with_configs
+            .iter()
+            .map(|(worker_id, config)| (*worker_id, config.clone()))
+            .collect();
+        workers.sort_by_key(|(worker_id, _)| *worker_id);
+
+        SchedulerState {
+            block_size: self.block_size,
+            queue_depth: self.queue_depth(),
+            routing_degraded: self
+                .degradation
+                .as_ref()
+                .is_some_and(|degradation| degradation.degraded.load(AtomicOrdering::Relaxed)),
+            workers,
+            slots,
+            tracked_requests: self.slots.num_tracked_requests(),
+            utilization: ClusterUtilization::new(&active_blocks, &workers_with_configs),
+            recent_decisions: self
+                .recent_decisions
+                .lock()
+                .unwrap()
+                .iter()
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Random workers scored on top of the `router_max_candidates` best overlapping ones, so that idle
+/// workers without overlap still get a chance to be selected.
+const EXPLORATION_CANDIDATES: usize = 2;
+
+/// Restrict the workers to the `max_candidates` with the highest overlap, topped up with random
+/// workers if fewer have any overlap, plus [`EXPLORATION_CANDIDATES`] random others. `None` if
+/// there are not more workers than that anyway.
+fn top_overlap_candidates<R: Rng + ?Sized>(
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    overlaps: &HashMap<WorkerWithDpRank, u32>,
+    max_candidates: usize,
+    rng: &mut R,
+) -> Option<HashMap<WorkerId, Option<ModelRuntimeConfig>>> {
+    let max_candidates = max_candidates.max(1);
+    if workers.len() <= max_candidates + EXPLORATION_CANDIDATES {
+        return None;
+    }
+
+    // Best overlap of each worker over its dp ranks
+    let mut best_overlaps: HashMap<WorkerId, u32> = HashMap::new();
+    for (worker, overlap) in overlaps {
+        if *overlap > 0 && workers.contains_key(&worker.worker_id) {
+            let best = best_overlaps.entry(worker.worker_id).or_default();
+            *best = (*best).max(*overlap);
+        }
+    }
+    let mut ranked: Vec<(WorkerId, u32)> = best_overlaps.into_iter().collect();
+    ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut candidates: HashSet<WorkerId> = ranked
+        .into_iter()
+        .take(max_candidates)
+        .map(|(worker_id, _)| worker_id)
+        .collect();
+    let random_count = max_candidates - candidates.len() + EXPLORATION_CANDIDATES;
+    // Sorted, so that a seeded generator picks the same workers whatever the map order
+    let mut others: Vec<WorkerId> = workers
+        .keys()
+        .filter(|worker_id| !candidates.contains(worker_id))
+        .copied()
+        .collect();
+    others.sort_unstable();
+    let others = others.into_iter().choose_multiple(rng, random_count);
+    candidates.extend(others);
+
+    Some(
+        candidates
+            .into_iter()
+            .map(|worker_id| (worker_id, workers[&worker_id].clone()))
+            .collect(),
+    )
+}
+
+/// Number of multi-worker decisions per window of the logit spread monitor
+const LOGIT_SPREAD_WINDOW: usize = 200;
+
+/// Relative logit spread below which a decision did not discriminate between workers
+const DEGENERATE_LOGIT_SPREAD: f64 = 1e-3;
+
+/// Fraction of non-discriminating decisions above which a window is flagged as degenerate
+const DEGENERATE_WINDOW_FRACTION: f64 = 0.9;
+
+/// Metrics of the logit spread of the decisions of [`DefaultWorkerSelector`]
+#[derive(Clone)]
+pub struct LogitSpreadMetrics {
+    /// Relative logit spread of the last decision among several workers
+    pub logit_spread: Gauge,
+    /// Number of windows in which nearly every decision had a near-zero logit spread
+    pub degenerate_windows: IntCounter,
+}
+
+static LOGIT_SPREAD_METRICS: OnceLock<LogitSpreadMetrics> = OnceLock::new();
+
+impl LogitSpreadMetrics {
+    /// Creates the metrics from a Component, memoizing the result in LOGIT_SPREAD_METRICS to
+    /// avoid duplicate registration issues.
+    pub fn from_component(component: &Component) -> Self {
+        LOGIT_SPREAD_METRICS
+            .get_or_init(|| {
+                let metrics = component
+                    .create_gauge(
+                        kvrouter::LOGIT_SPREAD,
+                        "Relative spread of the worker logits of the last routing decision",
+                        &[],
+                    )
+                    .and_then(|logit_spread| {
+                        Ok(Self {
+                            logit_spread,
+                            degenerate_windows: component.create_intcounter(
+                                kvrouter::DEGENERATE_ROUTING_WINDOWS,
+                                "Number of decision windows in which the worker logits barely differed",
+                                &[],
+                            )?,
+                        })
+                    });
+                metrics.unwrap_or_else(|e| {
+                    tracing::warn!(
+                        "Failed to create logit spread metrics from component: {e}. Using unregistered metrics as fallback."
+                    );
+                    Self::new_unregistered()
+                })
+            })
+            .clone()
+    }
+
+    /// Creates metrics which are not registered with a MetricsRegistry.
+    pub fn new_unregistered() -> Self {
+        Self {
+            logit_spread: Gauge::new(
+                kvrouter::LOGIT_SPREAD,
+                "Relative spread of the worker logits of the last routing decision",
+            )
+            .unwrap(),
+            degenerate_windows: IntCounter::new(
+                kvrouter::DEGENERATE_ROUTING_WINDOWS,
+                "Number of decision windows in which the worker logits barely differed",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// The registered metrics, or unregistered ones if no scheduler registered them
+    fn get() -> Self {
+        LOGIT_SPREAD_METRICS
+            .get_or_init(Self::new_unregistered)
+            .clone()
+    }
+}
+
+/// Metrics of the degradation of routing under a deep queue
+#[derive(Clone)]
+pub struct DegradedRoutingMetrics {
+    /// 1 while routing is degraded, 0 otherwise
+    pub routing_degraded: IntGauge,
+    /// Number of requests routed without overlap data because routing was degraded
+    pub degraded_requests: IntCounter,
+}
+
+static DEGRADED_ROUTING_METRICS: OnceLock<DegradedRoutingMetrics> = OnceLock::new();
+
+impl DegradedRoutingMetrics {
+    /// Creates the metrics from a Component, memoizing the result in DEGRADED_ROUTING_METRICS to
+    /// avoid duplicate registration issues.
+    pub fn from_component(component: &Component) -> Self {
+        DEGRADED_ROUTING_METRICS
+            .get_or_init(|| {
+                let metrics = component
+                    .create_intgauge(
+                        kvrouter::ROUTING_DEGRADED,
+                        "Whether routing is degraded to load only because the scheduler queue is deep",
+                        &[],
+                    )
+                    .and_then(|routing_degraded| {
+                        Ok(Self {
+                            routing_degraded,
+                            degraded_requests: component.create_intcounter(
+                                kvrouter::DEGRADED_ROUTING_REQUESTS,
+                                "Number of requests routed without overlap data because routing was degraded",
+                                &[],
+                            )?,
+                        })
+                    });
+                metrics.unwrap_or_else(|e| {
+                    tracing::warn!(
+                        "Failed to create degraded routing metrics from component: {e}. Using unregistered metrics as fallback."
+                    );
+                    Self::new_unregistered()
+                })
+            })
+            .clone()
+    }
+
+    /// Creates metrics which are not registered with a MetricsRegistry.
+    pub fn new_unregistered() -> Self {
+        Self {
+            routing_degraded: IntGauge::new(
+                kvrouter::ROUTING_DEGRADED,
+                "Whether routing is degraded to load only because the scheduler queue is deep",
+            )
+            .unwrap(),
+            degraded_requests: IntCounter::new(
+                kvrouter::DEGRADED_ROUTING_REQUESTS,
+                "Number of requests routed without overlap data because routing was degraded",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// The registered metrics, or unregistered ones if no scheduler registered them
+    fn get() -> Self {
+        DEGRADED_ROUTING_METRICS
+            .get_or_init(Self::new_unregistered)
+            .clone()
+    }
+}
+
+/// Metrics of the wait of the requests in the queue of the scheduler
+#[derive(Clone)]
+pub struct QueueWaitMetrics {
+    /// Longest time a request waited in the queue before being dequeued
+    pub max_queue_wait_seconds: Gauge,
+}
+
+static QUEUE_WAIT_METRICS: OnceLock<QueueWaitMetrics> = OnceLock::new();
+
+impl QueueWaitMetrics {
+    /// Creates the metrics from a Component, memoizing the result in QUEUE_WAIT_METRICS to avoid
+    /// duplicate registration issues.
+    pub fn from_component(component: &Component) -> Self {
+        QUEUE_WAIT_METRICS
+            .get_or_init(|| {
+                component
+                    .create_gauge(
+                        kvrouter::MAX_QUEUE_WAIT_SECONDS,
+                        "Longest time a request waited in the scheduler queue before being dequeued",
+                        &[],
+                    )
+                    .map(|max_queue_wait_seconds| Self {
+                        max_queue_wait_seconds,
+                    })
+                    .unwrap_or_else(|e| {
+                        tracing::warn!(
+                            "Failed to create queue wait metrics from component: {e}. Using unregistered metrics as fallback."
+                        );
+                        Self::new_unregistered()
+                    })
+            })
+            .clone()
+    }
+
+    /// Creates metrics which are not registered with a MetricsRegistry.
+    pub fn new_unregistered() -> Self {
+        Self {
+            max_queue_wait_seconds: Gauge::new(
+                kvrouter::MAX_QUEUE_WAIT_SECONDS,
+                "Longest time a request waited in the scheduler queue before being dequeued",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Record the wait of a dequeued request, raising the max wait if it waited longer
+    fn record(&self, wait: Duration) {
+        let wait = wait.as_secs_f64();
+        if wait > self.max_queue_wait_seconds.get() {
+            self.max_queue_wait_seconds.set(wait);
+        }
+    }
+}
+
+/// Buckets of the decision phase histograms, from 10us to 250ms
+const DECISION_PHASE_BUCKETS: [f64; 13] = [
+    0.00001, 0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25,
+];
+
+const DECISION_PHASE_HELP: &str = "Time spent in a phase of a routing decision";
+
+/// Latency of the phases of a routing decision, each a histogram of
+/// `kvrouter::DECISION_PHASE_SECONDS` labelled by its phase. Waits between the phases, e.g. for
+/// a rate limited worker, are not counted.
+#[derive(Clone)]
+pub struct DecisionLatencyMetrics {
+    /// Query of the overlap of the request with the cache of every worker
+    pub overlap: Histogram,
+    /// Computation of the potential blocks and tokens of every worker
+    pub load: Histogram,
+    /// Selection of the worker, including its reservation in strict slot tracking mode
+    pub selection: Histogram,
+    /// Publish of the KV hit rate event of the decision
+    pub publish: Histogram,
+    /// Reservation of the request on the selected worker
+    pub state_update: Histogram,
+}
+
+static DECISION_LATENCY_METRICS: OnceLock<DecisionLatencyMetrics> = OnceLock::new();
+
+impl DecisionLatencyMetrics {
+    /// Creates the metrics from a Component, memoizing the result in DECISION_LATENCY_METRICS to
+    /// avoid duplicate registration issues.
+    pub fn from_component(component: &Component) -> Self {
+        DECISION_LATENCY_METRICS
+            .get_or_init(|| {
+                Self::register(component).unwrap_or_else(|e| {
+                    tracing::warn!(
+                        "Failed to create decision latency metrics from component: {e}. Using unregistered metrics as fallback."
+                    );
+                    Self::new_unregistered()
+                })
+            })
+            .clone()
+    }
+
+    fn register(component: &Component) -> Result<Self> {
+        let phase = |phase: &str| {
+            component.create_histogram(
+                kvrouter::DECISION_PHASE_SECONDS,
+                DECISION_PHASE_HELP,
+                &[("phase", phase)],
+                Some(DECISION_PHASE_BUCKETS.to_vec()),
+            )
+        };
+        Ok(Self {
+            overlap: phase("overlap")?,
+            load: phase("load")?,
+            selection: phase("selection")?,
+            publish: phase("publish")?,
+            state_update: phase("state_update")?,
+        })
+    }
+
+    /// Creates metrics which are not registered with a MetricsRegistry.
+    pub fn new_unregistered() -> Self {
+        let phase = |phase: &str| {
+            Histogram::with_opts(
+                HistogramOpts::new(kvrouter::DECISION_PHASE_SECONDS, DECISION_PHASE_HELP)
+                    .const_label("phase", phase)
+                    .buckets(DECISION_PHASE_BUCKETS.to_vec()),
+            )
+            .unwrap()
+        };
+        Self {
+            overlap: phase("overlap"),
+            load: phase("load"),
+            selection: phase("selection"),
+            publish: phase("publish"),
+            state_update: phase("state_update"),
+        }
+    }
+
+    /// The registered metrics, or unregistered ones if no scheduler registered them
+    pub(crate) fn get() -> Self {
+        DECISION_LATENCY_METRICS
+            .get_or_init(Self::new_unregistered)
+            .clone()
+    }
+
+    /// Record the time elapsed since `started` in the histogram of a phase
+    pub(crate) fn observe(phase: &Histogram, started: Instant) {
+        phase.observe(started.elapsed().as_secs_f64());
+    }
+}
+
+/// Spread of the logits relative to the largest magnitude among them, None with fewer than two
+/// workers, where there is nothing to discriminate
+fn relative_logit_spread(logits: &HashMap<WorkerWithDpRank, f64>) -> Option<f64> {
+    if logits.len() < 2 {
+        return None;
+    }
+    let (min, max) = logits
+        .values()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &logit| {
+            (min.min(logit), max.max(logit))
+        });
+    let magnitude = min.abs().max(max.abs());
+    Some(if magnitude > 0.0 {
+        (max - min) / magnitude
+    } else {
+        0.0
+    })
+}
+
+/// Watches whether the cost function still discriminates between workers. When nearly every
+/// decision of a window had near-identical logits, e.g. because overlaps are always zero and
+/// loads are not updating, routing is effectively random and a warning is raised.
+#[derive(Debug, Default)]
+struct LogitSpreadMonitor {
+    /// Decisions and degenerate decisions in the current window
+    window: Mutex<(usize, usize)>,
+}
+
+impl LogitSpreadMonitor {
+    /// Record the logits of a decision, returning whether it completed a degenerate window
+    fn record(&self, logits: &HashMap<WorkerWithDpRank, f64>) -> bool {
+        let Some(spread) = relative_logit_spread(logits) else {
+            return false;
+        };
+        let metrics = LogitSpreadMetrics::get();
+        metrics.logit_spread.set(spread);
+
+        let mut window = self.window.lock().unwrap();
+        window.0 += 1;
+        if spread < DEGENERATE_LOGIT_SPREAD {
+            window.1 += 1;
+        }
+        if window.0 < LOGIT_SPREAD_WINDOW {
+            return false;
+        }
+
+        let (decisions, degenerate) = std::mem::take(&mut *window);
+        let degenerate_window = degenerate as f64 >= DEGENERATE_WINDOW_FRACTION * decisions as f64;
+        if degenerate_window {
+            metrics.degenerate_windows.inc();
+            tracing::warn!(
+                "{degenerate} of the last {decisions} routing decisions had near-identical worker \
+                 logits: the cost function is not discriminating between workers and routing is \
+                 effectively random. Check that KV events and worker loads are being received."
+            );
+        }
+        degenerate_window
+    }
+}
+
+/// Exponential moving average of the decode load of each worker, see `router_load_smoothing`
+#[derive(Debug, Default)]
+struct LoadSmoother {
+    averages: Mutex<HashMap<WorkerWithDpRank, f64>>,
+}
+
+impl LoadSmoother {
+    /// Fold the latest `load` of `worker` into its average, returning the new average. The first
+    /// load of a worker starts its average.
+    fn smooth(&self, worker: WorkerWithDpRank, load: f64, factor: f64) -> f64 {
+        let mut averages = self.averages.lock().unwrap();
+        *averages
+            .entry(worker)
+            .and_modify(|average| *average = factor * load + (1.0 - factor) * *average)
+            .or_insert(load)
+    }
+
+    /// Forget the averages of the workers which left
+    fn retain(&self, workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>) {
+        self.averages
+            .lock()
+            .unwrap()
+            .retain(|worker, _| workers.contains_key(&worker.worker_id));
+    }
+}
+
+/// When each worker joined, to boost it during its warmup, see `router_warmup_secs`
+#[derive(Debug, Default)]
+struct WarmupTracker {
+    /// None until the workers are first observed. The workers present then are established and
+    /// map to None; only the workers which joined later are warming up.
+    joined: Mutex<Option<HashMap<WorkerId, Option<Instant>>>>,
+}
+
+impl WarmupTracker {
+    /// Record the workers joining and forget those which left since the last observation
+    fn observe(&self, worker_ids: impl Iterator<Item = WorkerId>, now: Instant) {
+        let mut joined = self.joined.lock().unwrap();
+        let joined_at = if joined.is_some() { Some(now) } else { None };
+        let previous = joined.take().unwrap_or_default();
+        *joined = Some(
+            worker_ids
+                .map(|worker_id| {
+                    let joined = previous.get(&worker_id).copied().unwrap_or(joined_at);
+                    (worker_id, joined)
+                })
+                .collect(),
+        );
+    }
+
+    /// Fraction of the warmup of `worker_id` still to go at `now`, 0 once warmed up or for the
+    /// workers established before the router started
+    fn remaining(&self, worker_id: WorkerId, warmup: Duration, now: Instant) -> f64 {
+        let joined = self.joined.lock().unwrap();
+        let Some(Some(joined_at)) = joined.as_ref().and_then(|joined| joined.get(&worker_id))
+        else {
+            return 0.0;
+        };
+        let elapsed = now.saturating_duration_since(*joined_at);
+        (1.0 - elapsed.as_secs_f64() / warmup.as_secs_f64()).max(0.0)
+    }
+}
+
+/// Drop the workers whose logit is NaN or infinite, e.g. because of a corrupt runtime config,
+/// so they cannot skew sampling among the valid workers. Fails if no valid logit remains.
+fn sanitize_logits(logits: &mut HashMap<WorkerWithDpRank, f64>) -> Result<(), KvSchedulerError> {
+    logits.retain(|worker, logit| {
+        if logit.is_finite() {
+            return true;
+        }
+        tracing::warn!(
+            "Dropping worker_id={} dp_rank={} from selection: invalid logit {logit}",
+            worker.worker_id,
+            worker.dp_rank
+        );
+        false
+    });
+    if logits.is_empty() {
+        return Err(KvSchedulerError::InvalidLogits);
+    }
+    Ok(())
+}
+
+/// The worker dp rank at position `cursor`, wrapping around, of the dp ranks of `workers` sorted
+/// by worker id and rank. None without workers.
+fn round_robin_pick(
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    cursor: usize,
+) -> Option<WorkerWithDpRank> {
+    let mut ranks: Vec<WorkerWithDpRank> = workers
+        .iter()
+        .flat_map(|(worker_id, config)| {
+            let dp_size = config.as_ref().map_or(1, |c| c.data_parallel_size);
+            (0..dp_size).map(|dp_rank| WorkerWithDpRank::new(*worker_id, dp_rank))
+        })
+        .collect();
+    if ranks.is_empty() {
+        return None;
+    }
+    ranks.sort_unstable();
+    Some(ranks[cursor % ranks.len()])
+}
+
+/// Cached blocks, for a single worker, from which overlaps credited to workers unknown to the
+/// scheduler are logged
+const STALE_OVERLAP_LOG_BLOCKS: u32 = 4;
+
+/// The largest overlap of a worker which is not in `workers`, typically one removed between the
+/// overlap query and scheduling. That overlap is lost to the request.
+fn stale_overlap(
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    overlaps: &HashMap<WorkerWithDpRank, u32>,
+) -> Option<(WorkerWithDpRank, u32)> {
+    overlaps
+        .iter()
+        .filter(|(worker, _)| !workers.contains_key(&worker.worker_id))
+        .map(|(worker, overlap)| (*worker, *overlap))
+        .max_by_key(|(worker, overlap)| (*overlap, std::cmp::Reverse(*worker)))
+}
+
+/// Drop the workers without any overlap from the candidates if the best overlap exceeds
+/// `threshold` blocks, unless no candidate has overlap.
+fn exclude_zero_overlap(
+    worker_logits: &mut HashMap<WorkerWithDpRank, f64>,
+    overlaps: &HashMap<WorkerWithDpRank, u32>,
+    threshold: u32,
+) {
+    let overlap = |worker: &WorkerWithDpRank| overlaps.get(worker).copied().unwrap_or(0);
+    let max_overlap = worker_logits.keys().map(overlap).max().unwrap_or(0);
+    if max_overlap <= threshold {
+        return;
+    }
+    let before = worker_logits.len();
+    worker_logits.retain(|worker, _| overlap(worker) > 0);
+    tracing::debug!(
+        "Best overlap of {max_overlap} blocks exceeds {threshold}, excluded {} workers without overlap",
+        before - worker_logits.len()
+    );
+}
+
+/// Build the worker map from an instances snapshot, attaching each worker's runtime config.
+///
+/// Duplicate instance ids (e.g. from flapping etcd registrations) are logged and collapsed into
+/// a single worker. The runtime config is looked up by worker id, so every duplicate maps to the
+/// same config.
+fn workers_with_configs_from(
+    instances: &[Instance],
+    runtime_configs: &HashMap<WorkerId, ModelRuntimeConfig>,
+) -> HashMap<WorkerId, Option<ModelRuntimeConfig>> {
+    let mut workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = HashMap::new();
+    let mut duplicates: HashSet<WorkerId> = HashSet::new();
+
+    for instance in instances {
+        let worker_id = instance.instance_id;
+        if workers.contains_key(&worker_id) {
+            duplicates.insert(worker_id);
+            continue;
+        }
+
+        let config = runtime_configs.get(&worker_id).cloned();
+        if config.is_some() {
+            tracing::info!("Runtime config found for worker_id: {}", worker_id);
+        }
+        workers.insert(worker_id, config);
+    }
+
+    if !duplicates.is_empty() {
+        let mut duplicates: Vec<WorkerId> = duplicates.into_iter().collect();
+        duplicates.sort_unstable();
+        tracing::warn!(
+            "Duplicate instance ids in instances snapshot, deduplicating: {:?}",
+            duplicates
+        );
+    }
+
+    workers
+}
+
+/// Tracks which worker last served each prefix (keyed by the first sequence hash of the request),
+/// to decay the affinity towards that worker while the prefix is idle.
+struct AffinityTracker {
+    half_life: Duration,
+    last_routed: HashMap<SequenceHash, (WorkerWithDpRank, Instant)>,
+    records_since_prune: usize,
+}
+
+impl AffinityTracker {
+    /// Entries idle for this many half-lives have fully decayed and are dropped
+    const PRUNE_HALF_LIVES: u32 = 16;
+    const PRUNE_INTERVAL: usize = 1024;
+
+    fn new(half_life: Duration) -> Self {
+        Self {
+            half_life,
+            last_routed: HashMap::new(),
+            records_since_prune: 0,
+        }
+    }
+
+    /// The worker which last served `key` and the factor `0.5^(idle / half_life)` to scale its
+    /// overlap credit by
+    fn decay(&self, key: SequenceHash, now: Instant) -> Option<(WorkerWithDpRank, f64)> {
+        let (worker, last_routed) = self.last_routed.get(&key)?;
+        let idle = now.saturating_duration_since(*last_routed);
+        let factor = 0.5_f64.powf(idle.as_secs_f64() / self.half_life.as_secs_f64());
+        Some((*worker, factor))
+    }
+
+    fn record(&mut self, key: SequenceHash, worker: WorkerWithDpRank, now: Instant) {
+        self.last_routed.insert(key, (worker, now));
+
+        self.records_since_prune += 1;
+        if self.records_since_prune >= Self::PRUNE_INTERVAL {
+            self.records_since_prune = 0;
+            let max_idle = self.half_life * Self::PRUNE_HALF_LIVES;
+            self.last_routed.retain(|_, (_, last_routed)| {
+                now.saturating_duration_since(*last_routed) < max_idle
+            });
+        }
+    }
+}
+
+/// Longest the scheduler waits for a token when every worker is rate limited
+const RATE_LIMIT_MAX_WAIT: Duration = Duration::from_millis(500);
+
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per-worker token buckets capping the dispatch rate to each worker. A worker's rate comes from
+/// its runtime config, falling back to the router-wide default; workers with neither are
+/// unlimited. Buckets allow bursts of one second worth of requests.
+struct WorkerRateLimiter {
+    default_rate: Option<f64>,
+    buckets: HashMap<WorkerId, TokenBucket>,
+}
+
+impl WorkerRateLimiter {
+    fn new(default_rate: Option<f64>) -> Self {
+        Self {
+            default_rate,
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn rate(&self, config: Option<&ModelRuntimeConfig>) -> Option<f64> {
+        config
+            .and_then(|c| c.max_requests_per_second)
+            .map(f64::from)
+            .or(self.default_rate)
+            .filter(|rate| *rate > 0.0)
+    }
+
+    fn refill(&mut self, worker_id: WorkerId, rate: f64, now: Instant) -> &mut TokenBucket {
+        let burst = rate.max(1.0);
+        let bucket = self.buckets.entry(worker_id).or_insert(TokenBucket {
+            rate,
+            tokens: burst,
+            last_refill: now,
+        });
+        bucket.rate = rate;
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.last_refill = now;
+        bucket
+    }
+
+    /// Time until a request may be dispatched to the worker, zero if it can be right away
+    fn wait(
+        &mut self,
+        worker_id: WorkerId,
+        config: Option<&ModelRuntimeConfig>,
+        now: Instant,
+    ) -> Duration {
+        let Some(rate) = self.rate(config) else {
+            return Duration::ZERO;
+        };
+        let bucket = self.refill(worker_id, rate, now);
+        if bucket.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+        }
+    }
+
+    /// Take a token from the worker's bucket for a dispatched request
+    fn consume(&mut self, worker_id: WorkerId, config: Option<&ModelRuntimeConfig>, now: Instant) {
+        if let Some(rate) = self.rate(config) {
+            self.refill(worker_id, rate, now).tokens -= 1.0;
+        }
+    }
+
+    /// Drop the rate limited workers, returning the shortest wait until one of them may be
+    /// dispatched to, or `None` if no worker was dropped
+    fn retain_available(
+        &mut self,
+        workers: &mut HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        now: Instant,
+    ) -> Option<Duration> {
+        // A bucket which refilled completely is no different from a fresh one
+        self.buckets.retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.last_refill);
+            bucket.tokens + elapsed.as_secs_f64() * bucket.rate < bucket.rate.max(1.0)
+        });
+        let mut shortest_wait: Option<Duration> = None;
+        workers.retain(|worker_id, config| {
+            let wait = self.wait(*worker_id, config.as_ref(), now);
+            if wait.is_zero() {
+                return true;
+            }
+            shortest_wait = Some(shortest_wait.map_or(wait, |shortest| shortest.min(wait)));
+            false
+        });
+        shortest_wait
+    }
+}
+
+/// Pauses a publish after `failure_threshold` consecutive failures, for `cooldown`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishBreaker {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+/// Circuit breaker around a publish which keeps failing, e.g. during a NATS outage, so that it
+/// neither delays every decision nor logs a warning for each. Once open, publishes are skipped
+/// for the cooldown of the [`PublishBreaker`], then a single probe is let through: a success
+/// closes the breaker, a failure opens it for another cooldown. Without settings it never opens.
+struct PublishCircuitBreaker {
+    /// What is published, for the logs
+    label: &'static str,
+    settings: Option<PublishBreaker>,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl PublishCircuitBreaker {
+    fn new(label: &'static str, settings: Option<PublishBreaker>) -> Self {
+        Self {
+            label,
+            settings,
+            consecutive_failures: 0,
+            open_until: None,
+        }
+    }
+
+    /// Whether to attempt a publish at `now`
+    fn allows(&self, now: Instant) -> bool {
+        self.open_until.is_none_or(|until| now >= until)
+    }
+
+    /// Record the outcome of a publish attempted at `now`
+    fn record<E: std::fmt::Debug>(&mut self, result: Result<(), E>, now: Instant) {
+        let label = self.label;
+        let error = match result {
+            Ok(()) => {
+                if self.open_until.take().is_some() {
+                    let failures = self.consecutive_failures;
+                    tracing::info!("Publishing {label} again after {failures} failures");
+                }
+                self.consecutive_failures = 0;
+                return;
+            }
+            Err(error) => error,
+        };
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let Some(settings) = self
+            .settings
+            .filter(|settings| self.consecutive_failures >= settings.failure_threshold)
+        else {
+            tracing::warn!("Failed to publish {label}: {error:?}");
+            return;
+        };
+        // Only the opening is logged, not every failed probe
+        if self.open_until.replace(now + settings.cooldown).is_none() {
+            tracing::warn!(
+                "Failed to publish {label} {} times in a row, last error: {error:?}; pausing \
+                 publishing for {:?}",
+                self.consecutive_failures,
+                settings.cooldown
+            );
+        }
+    }
+}
+
+/// Drop the workers whose max context length is below the request's ISL. Workers which do not
+/// report a max context length are kept.
+fn workers_fitting_context(
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    isl_tokens: usize,
+) -> Result<Cow<'_, HashMap<WorkerId, Option<ModelRuntimeConfig>>>, KvSchedulerError> {
+    let fits = |config: &Option<ModelRuntimeConfig>| {
+        config
+            .as_ref()
+            .and_then(|c| c.max_context_length)
+            .is_none_or(|max_context| isl_tokens as u64 <= max_context)
+    };
+
+    if workers.values().all(fits) {
+        return Ok(Cow::Borrowed(workers));
+    }
+
+    let fitting: HashMap<WorkerId, Option<ModelRuntimeConfig>> = workers
+        .iter()
+        .filter(|(_, config)| fits(config))
+        .map(|(worker_id, config)| (*worker_id, config.clone()))
+        .collect();
+
+    if fitting.is_empty() {
+        let max_context = workers
+            .values()
+            .filter_map(|config| config.as_ref().and_then(|c| c.max_context_length))
+            .max()
+            .unwrap_or_default();
+        return Err(KvSchedulerError::ContextTooLong {
+            isl: isl_tokens,
+            max_context,
+        });
+    }
+
+    tracing::debug!(
+        "Excluding {} workers whose max context length is below the request's {isl_tokens} tokens",
+        workers.len() - fitting.len()
+    );
+    Ok(Cow::Owned(fitting))
+}
+
+/// Drop the workers which do not report `total_kv_blocks` when the policy excludes them. They are
+/// kept if no worker reports its capacity, since there would be nothing left to route to.
+fn workers_by_capacity_policy<'a>(
+    workers: Cow<'a, HashMap<WorkerId, Option<ModelRuntimeConfig>>>,
+    policy: UnknownCapacityPolicy,
+) -> Cow<'a, HashMap<WorkerId, Option<ModelRuntimeConfig>>> {
+    let known = |config: &Option<ModelRuntimeConfig>| {
+        config.as_ref().is_some_and(|c| c.total_kv_blocks.is_some())
+    };
+    if policy != UnknownCapacityPolicy::Exclude || workers.values().all(known) {
+        return workers;
+    }
+    if !workers.values().any(known) {
+        tracing::debug!(
+            "No worker reports its KV capacity; routing to workers of unknown capacity"
+        );
+        return workers;
+    }
+
+    let known_workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = workers
+        .iter()
+        .filter(|(_, config)| known(config))
+        .map(|(worker_id, config)| (*worker_id, config.clone()))
+        .collect();
+    tracing::debug!(
+        "Excluding {} workers which do not report their KV capacity",
+        workers.len() - known_workers.len()
+    );
+    Cow::Owned(known_workers)
+}
+
+/// Whether there are workers but none of them has published a runtime config
+fn all_runtime_configs_missing(workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>) -> bool {
+    !workers.is_empty() && workers.values().all(Option::is_none)
+}
+
+fn warn_runtime_configs_missing(num_workers: usize) {
+    tracing::warn!(
+        "None of the {num_workers} workers has published a runtime config; routing without \
+         dp rank or KV capacity information. Set router_missing_runtime_configs to fail_fast \
+         to refuse routing instead"
+    );
+}
+
+/// Reserve the selected worker in the slot tracker before responding.
+/// On failure the worker is excluded and the request is re-selected among the remaining workers,
+/// until a reservation succeeds or no workers are left.
+async fn reserve_strict(
+    slots: &ActiveSequencesMultiWorker,
+    selector: &(dyn WorkerSelector + Send + Sync),
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    request: &SchedulingRequest,
+    mut selection: WorkerSelectionResult,
+    block_size: u32,
+) -> Result<WorkerSelectionResult, KvSchedulerError> {
+    let Some(request_id) = request.maybe_request_id.clone() else {
+        return Err(KvSchedulerError::ReservationFailed(
+            "no request_id provided to add_request to the slot tracker".to_string(),
+        ));
+    };
+
+    let mut remaining = workers.clone();
+    loop {
+        let result = slots
+            .add_request(
+                request_id.clone(),
+                request.token_seq.clone(),
+                request.isl_tokens,
+                selection.overlap_blocks,
+                selection.worker,
+            )
+            .await;
+
+        let Err(e) = result else {
+            return Ok(selection);
+        };
+
+        tracing::warn!(
+            "Failed to reserve request {request_id} on worker {:?}, re-selecting: {e:?}",
+            selection.worker
+        );
+        remaining.remove(&selection.worker.worker_id);
+        if remaining.is_empty() {
+            return Err(KvSchedulerError::ReservationFailed(e.to_string()));
+        }
+        selection = selector.select_worker(&remaining, request, block_size)?;
+    }
+}
+
+/// Pick a stable worker for `key` using rendezvous (highest random weight) hashing, so that
+/// only keys owned by a removed worker move when membership changes.
+fn consistent_hash_select(
+    workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    key: SequenceHash,
+) -> WorkerWithDpRank {
+    workers
+        .iter()
+        .flat_map(|(worker_id, config)| {
+            let data_parallel_size = config.as_ref().map(|c| c.data_parallel_size).unwrap_or(1);
+            (0..data_parallel_size).map(move |dp_rank| WorkerWithDpRank::new(*worker_id, dp_rank))
+        })
+        .max_by_key(|worker| {
+            let mut bytes = Vec::with_capacity(20);
+            bytes.extend_from_slice(&key.to_le_bytes());
+            bytes.extend_from_slice(&worker.worker_id.to_le_bytes());
+            bytes.extend_from_slice(&worker.dp_rank.to_le_bytes());
+            (compute_hash(&bytes), *worker)
+        })
+        .expect("consistent_hash_select called with no workers")
+}
+
+/// Source of the randomness of worker selection: seeded with `router_seed` for reproducible
+/// routing decisions, the thread-local generator otherwise
+#[derive(Debug, Default)]
+struct SelectionRng(Option<Mutex<StdRng>>);
+
+impl SelectionRng {
+    fn new(seed: Option<u64>) -> Self {
+        Self(seed.map(|seed| Mutex::new(StdRng::seed_from_u64(seed))))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        match &self.0 {
+            Some(rng) => f(&mut *rng.lock().unwrap()),
+            None => f(&mut rand::rng()),
+        }
+    }
+}
+
+// Helper function for softmax sampling
+/// Sample a worker with probabilities from the softmax of the negated, normalized logits. The
+/// workers are sorted first, so that the same `rng` state always yields the same worker.
+fn softmax_sample<R: Rng + ?Sized>(
+    logits: &HashMap<WorkerWithDpRank, f64>,
+    temperature: f64,
+    rng: &mut R,
+) -> WorkerWithDpRank {
+    if logits.is_empty() {
+        panic!("Empty logits for softmax sampling");
+    }
+
+    // Guard: if temperature is 0, return the key with the smallest logit value
+    if temperature == 0.0 {
+        // Find the minimum logit value
+        let min_logit = logits.values().fold(f64::INFINITY, |a, &b| a.min(b));
+
+        // Collect all keys with the minimum logit value (to handle ties)
+        let mut min_keys: Vec<_> = logits
+            .iter()
+            .filter(|&(_, &v)| v == min_logit)
+            .map(|(k, _)| *k)
+            .collect();
+        min_keys.sort_unstable();
+
+        // Randomly select from the minimum keys (handles single key case naturally)
+        let index = rng.random_range(0..min_keys.len());
+        return min_keys[index];
+    }
+
+    let mut keys: Vec<_> = logits.keys().copied().collect();
+    keys.sort_unstable();
+    let values: Vec<_> = keys.iter().map(|key| logits[key]).collect();
+
+    // Find min and max for normalization
+    let min_val = values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
+    let max_val = values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+
+    // All values are the same, e.g. on a cold start: sample uniformly, without computing the
+    // probabilities
+    if min_val == max_val {
+        return keys[rng.random_range(0..keys.len())];
+    }
+
+    // Normalize values
+    let normalized: Vec<_> = values
+        .iter()
+        .map(|&v| {
+            // Lower is better, so negate
+            // Note we don't need to do actual min-max norm here, just off by an offset
+            let norm = v / (max_val - min_val);
+            -norm
+        })
+        .collect();
+
+    // Apply temperature and softmax
+    let scaled: Vec<_> = normalized.iter().map(|&v| v / temperature).collect();
+
+    let max_scaled = scaled.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    let exp_values: Vec<_> = scaled.iter().map(|&v| (v - max_scaled).exp()).collect();
+
+    let sum_exp: f64 = exp_values.iter().sum();
+    let probabilities: Vec<_> = exp_values.iter().map(|&v| v / sum_exp).collect();
+
+    // Sample from the probability distribution
+    keys[sample_index(&probabilities, rng.random())]
+}
+
+/// The index selected by `sample`, uniform in [0, 1), in the cumulative distribution of
+/// `probabilities`. The probabilities are renormalized by their actual sum, so that floating-point
+/// drift doesn't leave the top of the range unselected. Should the walk still select nothing, e.g.
+/// because of NaN probabilities, the most probable index is returned rather than an arbitrary one.
+fn sample_index(probabilities: &[f64], sample: f64) -> usize {
+    let total: f64 = probabilities.iter().sum();
+    if total.is_finite() && total > 0.0 {
+        let target = sample * total;
+        let mut cumsum = 0.0;
+        for (i, &prob) in probabilities.iter().enumerate() {
+            cumsum += prob;
+            if target <= cumsum {
+                return i;
+            }
+        }
+    }
+
+    tracing::debug!(
+        "Softmax probabilities sum to {total}, selecting the most probable worker for sample \
+         {sample}"
+    );
+    probabilities
+        .iter()
+        .enumerate()
+        .filter(|(_, prob)| !prob.is_nan())
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Lowest speculative decoding acceptance rate taken into account, so that a near-zero rate
+/// doesn't make a worker's decode load unbounded
+const MIN_SPEC_DECODE_ACCEPTANCE_RATE: f64 = 0.1;
+
+/// Factor applied to the decode load of a worker: the inverse of its speculative decoding
+/// acceptance rate, since rejected speculations are wasted decode work. 1 for workers which
+/// don't report a valid rate.
+fn spec_decode_load_scale(config: Option<&ModelRuntimeConfig>) -> f64 {
+    match config.and_then(|config| config.spec_decode_acceptance_rate) {
+        Some(rate) if rate > 0.0 && rate <= 1.0 => 1.0 / rate.max(MIN_SPEC_DECODE_ACCEPTANCE_RATE),
+        _ => 1.0,
+    }
+}
+
+/// Expected time to first token, in seconds, of a worker which would have
+/// `potential_prefill_tokens` to prefill, queued requests included, at
+/// `prefill_tokens_per_second`. None if the throughput is unknown or zero.
+pub fn estimate_ttft_secs(
+    potential_prefill_tokens: usize,
+    prefill_tokens_per_second: Option<u64>,
+) -> Option<f64> {
+    prefill_tokens_per_second
+        .filter(|&throughput| throughput > 0)
+        .map(|throughput| potential_prefill_tokens as f64 / throughput as f64)
+}
+
+/// The objectives traded off when selecting a worker; lower is better for both
+#[derive(Debug, Clone, Copy)]
+struct WorkerObjectives {
+    /// Blocks the worker would have to prefill, a proxy for time to first token
+    prefill_blocks: f64,
+    /// Decode blocks the worker would hold, a proxy for decode load balance
+    decode_blocks: f64,
+    /// Risk that the request evicts blocks of the active requests of the worker, see
+    /// [`eviction_risk`]
+    eviction_risk: f64,
+}
+
+// Default implementation matching the Python _cost_function
+#[derive(Debug, Clone, Default)]
+pub struct DefaultWorkerSelector {
+    pub kv_router_config: KvRouterConfig,
+    /// Config received from the routing policy broadcast, which replaces `kv_router_config`
+    broadcast_config: Arc<std::sync::RwLock<Option<KvRouterConfig>>>,
+    spread_monitor: Arc<LogitSpreadMonitor>,
+    load_smoother: Arc<LoadSmoother>,
+    warmup: Arc<WarmupTracker>,
+    rng: Arc<SelectionRng>,
+}
+
+impl DefaultWorkerSelector {
+    pub fn new(kv_router_config: Option<KvRouterConfig>) -> Self {
+        let kv_router_config = kv_router_config.unwrap_or_default();
+        Self {
+            kv_router_config,
+            broadcast_config: Arc::default(),
+            spread_monitor: Arc::default(),
+            load_smoother: Arc::default(),
+            warmup: Arc::default(),
+            rng: Arc::new(SelectionRng::new(kv_router_config.router_seed)),
+        }
+    }
+
+    /// The config of a decision: the selector config with the overrides of the request applied
+    fn config(&self, request: &SchedulingRequest) -> KvRouterConfig {
+        match request.router_config_override.as_ref() {
+            Some(router_override) => router_override.apply(&self.kv_router_config),
+            None => self.kv_router_config,
+        }
+    }
+
+    /// A selector with the broadcast config in place of the local one, so that a decision never
+    /// mixes settings of two policies
+    fn with_broadcast_config(&self) -> Option<Self> {
+        let config = (*self.broadcast_config.read().unwrap())?;
+        Some(Self {
+            kv_router_config: config,
+            broadcast_config: Arc::default(),
+            spread_monitor: self.spread_monitor.clone(),
+            load_smoother: self.load_smoother.clone(),
+            warmup: self.warmup.clone(),
+            rng: self.rng.clone(),
+        })
+    }
+
+    /// The warmup window, if workers which joined recently are boosted
+    fn warmup_window(&self, router_config: &KvRouterConfig) -> Option<Duration> {
+        let secs = router_config.router_warmup_secs;
+        (secs > 0.0 && router_config.router_warmup_boost > 0.0)
+            .then(|| Duration::from_secs_f64(secs))
+    }
+
+    /// Refuse to route if configured to fail fast and no worker has a runtime config
+    fn check_runtime_configs(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+    ) -> Result<(), KvSchedulerError> {
+        if self.kv_router_config.router_missing_runtime_configs
+            == MissingRuntimeConfigsPolicy::FailFast
+            && all_runtime_configs_missing(workers)
+        {
+            return Err(KvSchedulerError::MissingRuntimeConfigs);
+        }
+        Ok(())
+    }
+
+    /// Compute the objectives of every worker and dp_rank for this request, with the worker's
+    /// cached blocks
+    fn worker_objectives(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Vec<(WorkerWithDpRank, u32, WorkerObjectives)> {
+        let router_config = self.config(request);
+        let isl = request.isl_tokens;
+        let overlaps = &request.overlaps.scores;
+
+        let decode_blocks = &request.decode_blocks;
+        let prefill_tokens = &request.prefill_tokens;
+
+        let mut objectives = Vec::new();
+
+        // Outer loop: iterate over all workers from runtime config
+        // Inner loop: iterate over all dp_ranks for each worker
         for (worker_id, config) in workers.iter() {
             // Get data_parallel_size from runtime config
             // data_parallel_size defaults to 1 in ModelRuntimeConfig
             let data_parallel_size = config.as_ref().map(|c| c.data_parallel_size).unwrap_or(1); // Fallback if config is None
+            let acceptance_scale = spec_decode_load_scale(config.as_ref());
+
+            // Iterate over all dp_ranks for this worker
+            for dp_rank in 0..data_parallel_size {
+                let worker = WorkerWithDpRank::new(*worker_id, dp_rank);
+
+                // Get overlap for this worker (defaults to 0 if not in overlaps)
+                let overlap = *overlaps.get(&worker).unwrap_or(&0);
+
+                // this is the number of prefill tokens the worker would have if the request were scheduled there
+                let mut prefill_token = *prefill_tokens.get(&worker).unwrap_or(&isl);
+
+                // Give back part of the cache credit of the worker which last served this prefix
+                // if the prefix has been idle
+                if let Some((affine_worker, factor)) = request.affinity_decay
+                    && affine_worker == worker
+                {
+                    let cached_tokens = (overlap as usize * block_size as usize).min(isl);
+                    prefill_token += ((1.0 - factor) * cached_tokens as f64).round() as usize;
+                }
+
+                // Give back the cached tokens which the engine prefills anyway
+                let efficiency = router_config.router_overlap_token_efficiency;
+                if efficiency != 1.0 && overlap > 0 {
+                    let cached_tokens = (overlap as usize * block_size as usize).min(isl);
+                    let saved = saved_prefill_tokens(overlap, block_size, isl, efficiency);
+                    prefill_token = (prefill_token + cached_tokens)
+                        .saturating_sub(saved.round() as usize)
+                        .min(isl);
+                }
+
+                // Trade the flat cache credit for one weighted by block position
+                if let Some(exponent) = router_config.router_overlap_recency_exponent
+                    && overlap > 0
+                {
+                    let request_blocks = isl.div_ceil(block_size as usize);
+                    let weighted = request.overlaps.weighted_score(&worker, |position| {
+                        overlap_recency_weight(position, request_blocks, exponent)
+                    });
+                    let credit_delta = (overlap as f64 - weighted) * block_size as f64;
+                    prefill_token = (prefill_token as f64 + credit_delta)
+                        .clamp(0.0, isl as f64)
+                        .round() as usize;
+                }
+                let potential_prefill_block = (prefill_token as f64) / (block_size as f64);
+
+                // this is the number of decode blocks the worker would have if the request were scheduled there
+                let potential_blocks = *decode_blocks
+                    .get(&worker)
+                    .unwrap_or(&(potential_prefill_block.floor() as usize));
+                let decode_block = potential_blocks as f64 * acceptance_scale;
+
+                let (new_blocks, active_blocks) =
+                    new_and_active_blocks(isl, overlap, block_size, potential_blocks);
+                let risk = eviction_risk(active_blocks, new_blocks, rank_capacity(config.as_ref()));
+
+                objectives.push((
+                    worker,
+                    overlap,
+                    WorkerObjectives {
+                        prefill_blocks: potential_prefill_block,
+                        decode_blocks: decode_block,
+                        eviction_risk: risk,
+                    },
+                ));
+            }
+        }
+
+        objectives
+    }
+
+    /// Expected time to first token of the request on `worker`, from its potential prefill tokens
+    /// and the prefill throughput in its runtime config
+    pub fn expected_ttft_secs(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        worker: WorkerWithDpRank,
+    ) -> Option<f64> {
+        let throughput = workers
+            .get(&worker.worker_id)
+            .and_then(|config| config.as_ref())
+            .and_then(|config| config.prefill_tokens_per_second);
+        let prefill_tokens = *request
+            .prefill_tokens
+            .get(&worker)
+            .unwrap_or(&request.isl_tokens);
+        estimate_ttft_secs(prefill_tokens, throughput)
+    }
+
+    /// Compute the logit (lower is better) of every worker and dp_rank for this request
+    fn worker_logits(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> HashMap<WorkerWithDpRank, f64> {
+        let router_config = self.config(request);
+        let mut objectives = self.worker_objectives(workers, request, block_size);
+
+        // Replace the instantaneous decode load by its moving average. With the default factor
+        // of 1 this is a no-op and no average is kept.
+        let smoothing = router_config.router_load_smoothing;
+        let mut raw_decode_blocks = HashMap::new();
+        if smoothing > 0.0 && smoothing < 1.0 {
+            self.load_smoother.retain(workers);
+            for (worker, _, objective) in objectives.iter_mut() {
+                raw_decode_blocks.insert(*worker, objective.decode_blocks);
+                objective.decode_blocks =
+                    self.load_smoother
+                        .smooth(*worker, objective.decode_blocks, smoothing);
+            }
+        }
+
+        // Scale the overlap weight up with cache pressure, since re-prefill is more expensive
+        // when caches are full. With the default scale of 0 this is a no-op.
+        let pressure_scale = router_config.router_pressure_overlap_scale;
+        let pressure_multiplier = match self.cache_pressure(workers, request, block_size) {
+            Some(pressure) if pressure_scale != 0.0 => {
+                let multiplier =
+                    1.0 + pressure_scale * pressure.powf(router_config.router_pressure_exponent);
+                tracing::debug!(
+                    "Cluster cache pressure {pressure:.3}, scaling overlap weight by {multiplier:.3}"
+                );
+                multiplier
+            }
+            _ => 1.0,
+        };
+
+        let overlap_weight = router_config.overlap_score_weight * pressure_multiplier;
+        let decode_load_weight = router_config.decode_load_weight;
+
+        // Optionally bring both objectives to [0, 1] by dividing by their max over the workers,
+        // so that the weights balance objectives on different scales
+        let (prefill_scale, decode_scale) = if router_config.router_normalize_objectives {
+            let max_of = |f: fn(&WorkerObjectives) -> f64| {
+                let max = objectives
+                    .iter()
+                    .map(|(_, _, o)| f(o))
+                    .fold(0.0_f64, f64::max);
+                if max > 0.0 { max } else { 1.0 }
+            };
+            (max_of(|o| o.prefill_blocks), max_of(|o| o.decode_blocks))
+        } else {
+            (1.0, 1.0)
+        };
+
+        let warmup = self.warmup_window(&router_config);
+        let now = Instant::now();
+
+        let mut worker_logits = HashMap::new();
+        for (worker, overlap, objective) in objectives {
+            let prefill_blocks = objective.prefill_blocks / prefill_scale;
+            let decode_blocks = objective.decode_blocks / decode_scale;
+
+            // Calculate logit (lower is better)
+            let mut logit = overlap_weight * prefill_blocks + decode_load_weight * decode_blocks;
+            tracing::info!(
+                "Formula for worker_id={} dp_rank={:?} with {overlap} cached blocks: {logit:.3} \
+                 = {overlap_weight:.1} * prefill_blocks + {decode_load_weight:.1} * decode_blocks \
+                 = {overlap_weight:.1} * {prefill_blocks:.3} + {decode_load_weight:.1} * {decode_blocks:.3}",
+                worker.worker_id,
+                worker.dp_rank
+            );
+
+            // Penalize the workers whose active requests would lose cache to this one
+            let eviction_risk_weight = router_config.router_eviction_risk_weight;
+            if eviction_risk_weight > 0.0 && objective.eviction_risk > 0.0 {
+                let penalty = eviction_risk_weight * objective.eviction_risk * prefill_blocks;
+                tracing::info!(
+                    "Eviction risk of worker_id={} dp_rank={:?}: {:.3}, raising its logit by \
+                     {penalty:.3}",
+                    worker.worker_id,
+                    worker.dp_rank,
+                    objective.eviction_risk
+                );
+                logit += penalty;
+            }
+            if let Some(raw) = raw_decode_blocks.get(&worker) {
+                tracing::info!(
+                    "Smoothed decode_blocks of worker_id={} dp_rank={:?}: {:.3} \
+                     (instantaneous {raw:.3})",
+                    worker.worker_id,
+                    worker.dp_rank,
+                    objective.decode_blocks
+                );
+            }
+
+            // Favor the workers which joined recently, so their cold cache gets traffic
+            if let Some(warmup) = warmup {
+                let remaining = self.warmup.remaining(worker.worker_id, warmup, now);
+                if remaining > 0.0 {
+                    let boost = router_config.router_warmup_boost.clamp(0.0, 1.0) * remaining;
+                    tracing::info!(
+                        "Worker worker_id={} dp_rank={:?} warming up, lowering its logit \
+                         {logit:.3} by {:.0}%",
+                        worker.worker_id,
+                        worker.dp_rank,
+                        boost * 100.0
+                    );
+                    logit *= 1.0 - boost;
+                }
+            }
+            worker_logits.insert(worker, logit);
+        }
+
+        worker_logits
+    }
+
+    /// Cluster-wide cache pressure in [0, 1]: the potential decode blocks of the workers with a
+    /// known capacity, divided by their total capacity. Workers which do not report
+    /// `total_kv_blocks` count with the capacity assumed by `router_unknown_capacity`, if any.
+    /// `None` if no worker has a known capacity.
+    pub fn cache_pressure(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Option<f64> {
+        let unknown_capacity = self.config(request).router_unknown_capacity;
+        let mut used_blocks = 0.0;
+        let mut total_blocks = 0u64;
+
+        for (worker_id, config) in workers.iter() {
+            let Some(worker_total_blocks) =
+                unknown_capacity.capacity(config.as_ref().and_then(|c| c.total_kv_blocks))
+            else {
+                continue;
+            };
+            total_blocks += worker_total_blocks;
+
+            let dp_size = config.as_ref().map_or(1, |c| c.data_parallel_size);
+            for dp_rank in 0..dp_size {
+                let worker = WorkerWithDpRank::new(*worker_id, dp_rank);
+                let prefill_token = *request
+                    .prefill_tokens
+                    .get(&worker)
+                    .unwrap_or(&request.isl_tokens);
+                let potential_prefill_block = (prefill_token as f64) / (block_size as f64);
+                used_blocks += *request
+                    .decode_blocks
+                    .get(&worker)
+                    .unwrap_or(&(potential_prefill_block.floor() as usize))
+                    as f64;
+            }
+        }
+
+        (total_blocks > 0).then(|| (used_blocks / total_blocks as f64).clamp(0.0, 1.0))
+    }
+}
+
+/// Prefill tokens saved by `overlap` cached blocks of a request of `isl` tokens: the tokens of the
+/// cached blocks, at most the whole request, scaled by `efficiency` in [0, 1]
+fn saved_prefill_tokens(overlap: u32, block_size: u32, isl: usize, efficiency: f64) -> f64 {
+    let cached_tokens = (overlap as usize * block_size as usize).min(isl);
+    efficiency.clamp(0.0, 1.0) * cached_tokens as f64
+}
+
+/// Cache credit of the overlapping block at `position` of a request of `request_blocks` blocks,
+/// averaging 1 over the whole request so that a full prefix hit keeps its unweighted credit
+fn overlap_recency_weight(position: usize, request_blocks: usize, exponent: f64) -> f64 {
+    let relative = (position as f64 + 0.5) / request_blocks.max(1) as f64;
+    (exponent + 1.0) * relative.min(1.0).powf(exponent)
+}
+
+impl WorkerSelector for DefaultWorkerSelector {
+    fn select_worker(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        if let Some(selector) = self.with_broadcast_config() {
+            return selector.select_worker(workers, request, block_size);
+        }
+        assert!(request.isl_tokens > 0);
+        let router_config = self.config(request);
+
+        if workers.is_empty() {
+            return Err(KvSchedulerError::NoEndpoints);
+        }
+        self.check_runtime_configs(workers)?;
+        if let Some((worker, overlap)) = stale_overlap(workers, &request.overlaps.scores)
+            && overlap >= STALE_OVERLAP_LOG_BLOCKS
+        {
+            tracing::warn!(
+                "Ignoring {overlap} cached blocks of worker_id={} dp_rank={:?}, which is no \
+                 longer routable: worker membership churn is degrading cache reuse",
+                worker.worker_id,
+                worker.dp_rank
+            );
+        }
+        let workers = workers_fitting_context(workers, request.isl_tokens)?;
+        let workers = workers_by_capacity_policy(workers, router_config.router_unknown_capacity);
+        let candidates = router_config.router_max_candidates.and_then(|max| {
+            self.rng
+                .with(|rng| top_overlap_candidates(&workers, &request.overlaps.scores, max, rng))
+        });
+        let workers = match candidates {
+            Some(candidates) => Cow::Owned(candidates),
+            None => workers,
+        };
+        let workers = workers.as_ref();
+
+        let request_blocks = request.isl_tokens.div_ceil(block_size as usize);
+        let overlaps = &request.overlaps.scores;
+
+        // Without overlap data, or for a prefix no worker has cached, optionally route by prefix
+        // rather than by load to preserve or build up cache locality
+        let no_overlap_data = router_config.router_consistent_hash_fallback && overlaps.is_empty();
+        let cold_prefix = router_config.router_cold_prefix_policy
+            == ColdPrefixPolicy::PrefixAffinity
+            && overlaps.values().all(|overlap| *overlap == 0);
+        if (no_overlap_data || cold_prefix)
+            && let Some(&key) = request.token_seq.as_ref().and_then(|seq| seq.first())
+        {
+            let worker = consistent_hash_select(workers, key);
+            let reason = if no_overlap_data {
+                "No overlap data available, consistent-hash fallback routing in effect"
+            } else {
+                "Prefix cached on no worker, prefix-affinity routing in effect"
+            };
+            tracing::info!(
+                "{reason}: selected worker_id={} dp_rank={:?}",
+                worker.worker_id,
+                worker.dp_rank
+            );
+            return Ok(WorkerSelectionResult {
+                worker,
+                required_blocks: request_blocks as u64,
+                overlap_blocks: 0,
+                expected_ttft_secs: self.expected_ttft_secs(workers, request, worker),
+                logit: None,
+            });
+        }
+
+        let mut worker_logits = self.worker_logits(workers, request, block_size);
+        if let Some(threshold) = router_config.router_exclude_zero_overlap_above {
+            exclude_zero_overlap(&mut worker_logits, overlaps, threshold);
+        }
+        sanitize_logits(&mut worker_logits)?;
+        self.spread_monitor.record(&worker_logits);
+
+        // Use softmax sampling to select worker
+        let best_worker = self
+            .rng
+            .with(|rng| softmax_sample(&worker_logits, router_config.router_temperature, rng));
+        let best_logit = worker_logits[&best_worker];
+
+        let best_overlap = *overlaps.get(&best_worker).unwrap_or(&0);
+        let expected_ttft_secs = self.expected_ttft_secs(workers, request, best_worker);
+
+        // this is a runtime config set on a per worker basis, not per dp-rank
+        let total_blocks_info = workers
+            .get(&best_worker.worker_id)
+            .and_then(|cfg| cfg.as_ref())
+            .and_then(|cfg| cfg.total_kv_blocks)
+            .map(|blocks| format!(", total blocks: {}", blocks))
+            .unwrap_or_default();
+        let ttft_info = expected_ttft_secs
+            .map(|ttft| format!(", expected TTFT: {ttft:.3}s"))
+            .unwrap_or_default();
+
+        tracing::info!(
+            "Selected worker: worker_id={} dp_rank={:?}, logit: {:.3}, cached blocks: {}{}{}",
+            best_worker.worker_id,
+            best_worker.dp_rank,
+            best_logit,
+            best_overlap,
+            total_blocks_info,
+            ttft_info
+        );
+
+        Ok(WorkerSelectionResult {
+            worker: best_worker,
+            required_blocks: request_blocks as u64,
+            overlap_blocks: overlaps.get(&best_worker).copied().unwrap_or(0),
+            expected_ttft_secs,
+            logit: Some(best_logit),
+        })
+    }
+
+    fn rank_workers(
+        &self,
+        workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+        request: &SchedulingRequest,
+        block_size: u32,
+    ) -> Result<Vec<WorkerSelectionResult>, KvSchedulerError> {
+        if let Some(selector) = self.with_broadcast_config() {
+            return selector.rank_workers(workers, request, block_size);
+        }
+        assert!(request.isl_tokens > 0);
+
+        if workers.is_empty() {
+            return Err(KvSchedulerError::NoEndpoints);
+        }
+        self.check_runtime_configs(workers)?;
+        let workers = workers_fitting_context(workers, request.isl_tokens)?;
+        let workers =
+            workers_by_capacity_policy(workers, self.config(request).router_unknown_capacity);
+        let workers = workers.as_ref();
+
+        let request_blocks = request.isl_tokens.div_ceil(block_size as usize);
+        let overlaps = &request.overlaps.scores;
+
+        let mut logits = self.worker_logits(workers, request, block_size);
+        sanitize_logits(&mut logits)?;
+        let mut ranked: Vec<(WorkerWithDpRank, f64)> = logits.into_iter().collect();
+        // Ties are broken by worker id so the ordering is deterministic
+        ranked.sort_by(|(a_worker, a_logit), (b_worker, b_logit)| {
+            a_logit
+                .total_cmp(b_logit)
+                .then_with(|| a_worker.cmp(b_worker))
+        });
+
+        Ok(ranked
+            .into_iter()
+            .map(|(worker, logit)| WorkerSelectionResult {
+                worker,
+                required_blocks: request_blocks as u64,
+                overlap_blocks: overlaps.get(&worker).copied().unwrap_or(0),
+                expected_ttft_secs: self.expected_ttft_secs(workers, request, worker),
+                logit: Some(logit),
+            })
+            .collect())
+    }
+
+    fn apply_config(&self, config: &KvRouterConfig) {
+        *self.broadcast_config.write().unwrap() = Some(*config);
+    }
+
+    /// Track the workers joining, whether or not warmup is enabled, as a request may enable it
+    fn observe_workers(&self, workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>) {
+        self.warmup.observe(workers.keys().copied(), Instant::now());
+    }
+
+    fn reset_load_state(&self) {
+        self.load_smoother.averages.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dynamo_runtime::component::TransportType;
+
+    fn instance(instance_id: WorkerId) -> Instance {
+        Instance {
+            component: "backend".to_string(),
+            endpoint: "generate".to_string(),
+            namespace: "test".to_string(),
+            instance_id,
+            transport: TransportType::NatsTcp(format!("subject-{instance_id}")),
+        }
+    }
+
+    fn make_request(
+        isl_tokens: usize,
+        overlaps: &[(WorkerWithDpRank, u32)],
+        prefill_tokens: &[(WorkerWithDpRank, usize)],
+    ) -> SchedulingRequest {
+        SchedulingRequest {
+            maybe_request_id: None,
+            token_seq: None,
+            isl_tokens,
+            overlaps: OverlapScores {
+                scores: overlaps.iter().copied().collect(),
+                frequencies: Vec::new(),
+            },
+            decode_blocks: HashMap::new(),
+            prefill_tokens: prefill_tokens.iter().copied().collect(),
+            router_config_override: None,
+            update_states: false,
+            affinity_decay: None,
+            phase: SchedulingPhase::Any,
+            priority: 0,
+            resp_tx: None,
+        }
+    }
+
+    #[test]
+    fn test_rank_workers_orders_by_logit() {
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let worker3 = WorkerWithDpRank::from_worker_id(3);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None), (3, None)].into_iter().collect();
+
+        // 4 blocks of 16 tokens; worker1 has 3 cached, worker3 has 2, worker2 has 1
+        let request = make_request(
+            64,
+            &[(worker1, 3), (worker2, 1), (worker3, 2)],
+            &[(worker1, 16), (worker2, 48), (worker3, 32)],
+        );
+
+        let selector = DefaultWorkerSelector::default();
+        let ranked = selector.rank_workers(&workers, &request, 16).unwrap();
+        let order: Vec<WorkerWithDpRank> = ranked.iter().map(|r| r.worker).collect();
+        assert_eq!(order, vec![worker1, worker3, worker2]);
+        assert_eq!(ranked[0].overlap_blocks, 3);
+        assert_eq!(ranked[0].required_blocks, 4);
+    }
+
+    #[test]
+    fn test_affinity_decay() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (1..=2).map(|id| (id, None)).collect();
+        let selector = DefaultWorkerSelector::default();
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+
+        // Worker 1 has 4 of 4 blocks cached, worker 2 has none
+        let mut request = make_request(64, &[(worker1, 4)], &[(worker1, 0)]);
+        let fresh = selector.worker_logits(&workers, &request, 16);
+
+        request.affinity_decay = Some((worker1, 1.0));
+        assert_eq!(selector.worker_logits(&workers, &request, 16), fresh);
+
+        // A fully decayed affinity loses the whole cache credit
+        request.affinity_decay = Some((worker1, 0.0));
+        let decayed = selector.worker_logits(&workers, &request, 16);
+        assert_eq!(decayed[&worker1], decayed[&worker2]);
+
+        let mut tracker = AffinityTracker::new(Duration::from_secs(10));
+        let now = Instant::now();
+        tracker.record(7, worker1, now);
+        let (worker, factor) = tracker.decay(7, now + Duration::from_secs(10)).unwrap();
+        assert_eq!(worker, worker1);
+        assert!((factor - 0.5).abs() < 1e-9);
+        assert!(tracker.decay(8, now).is_none());
+    }
 
-            // Iterate over all dp_ranks for this worker
-            for dp_rank in 0..data_parallel_size {
-                let worker = WorkerWithDpRank::new(*worker_id, dp_rank);
+    #[test]
+    fn test_logit_spread_monitor() {
+        let w1 = WorkerWithDpRank::from_worker_id(1);
+        let w2 = WorkerWithDpRank::from_worker_id(2);
+        let logits = |a: f64, b: f64| -> HashMap<WorkerWithDpRank, f64> {
+            [(w1, a), (w2, b)].into_iter().collect()
+        };
 
-                // Get overlap for this worker (defaults to 0 if not in overlaps)
-                let overlap = *overlaps.get(&worker).unwrap_or(&0);
+        assert_eq!(relative_logit_spread(&logits(2.0, 4.0)), Some(0.5));
+        assert_eq!(relative_logit_spread(&logits(0.0, 0.0)), Some(0.0));
+        assert_eq!(
+            relative_logit_spread(&[(w1, 1.0)].into_iter().collect()),
+            None
+        );
+
+        // Single-worker decisions do not count towards the window
+        let monitor = LogitSpreadMonitor::default();
+        assert!(!monitor.record(&[(w1, 1.0)].into_iter().collect()));
+
+        let flat = logits(10.0, 10.0 + 1e-6);
+        for _ in 1..LOGIT_SPREAD_WINDOW {
+            assert!(!monitor.record(&flat));
+        }
+        assert!(monitor.record(&flat));
+
+        // A window of discriminating decisions is not flagged
+        for _ in 1..LOGIT_SPREAD_WINDOW {
+            monitor.record(&logits(1.0, 3.0));
+        }
+        assert!(!monitor.record(&flat));
+    }
+
+    #[test]
+    fn test_shadow_selection_event() {
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let request = make_request(64, &[(worker1, 4)], &[]);
+        let selection = |worker, overlap_blocks| WorkerSelectionResult {
+            worker,
+            required_blocks: 4,
+            overlap_blocks,
+            expected_ttft_secs: None,
+            logit: None,
+        };
+        let live = selection(worker1, 4);
+
+        let event = ShadowSelectionEvent::new(&request, &live, Some(&selection(worker2, 0)));
+        assert!(!event.agreed);
+        assert_eq!(event.shadow_worker, Some(worker2));
+        assert!(ShadowSelectionEvent::new(&request, &live, Some(&live)).agreed);
+
+        let failed = ShadowSelectionEvent::new(&request, &live, None);
+        assert!(!failed.agreed);
+        assert_eq!(failed.shadow_worker, None);
+    }
+
+    #[test]
+    fn test_broadcast_config_replaces_local_config() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (1..=2).map(|id| (id, None)).collect();
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+
+        // Worker 1 has the prompt cached but a heavier decode load
+        let mut request = make_request(64, &[(worker1, 4)], &[(worker1, 0), (worker2, 64)]);
+        request.decode_blocks = [(worker1, 6), (worker2, 4)].into_iter().collect();
+
+        let selector = DefaultWorkerSelector::default();
+        let selected = selector.select_worker(&workers, &request, 16).unwrap();
+        assert_eq!(selected.worker, worker1);
+
+        selector.apply_config(&KvRouterConfig {
+            overlap_score_weight: 0.0,
+            ..Default::default()
+        });
+        let selected = selector.select_worker(&workers, &request, 16).unwrap();
+        assert_eq!(selected.worker, worker2);
+    }
+
+    #[test]
+    fn test_overlap_recency_weighting() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (1..=3).map(|id| (id, None)).collect();
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let worker3 = WorkerWithDpRank::from_worker_id(3);
+
+        // Worker 1 holds the whole 8 block prompt, worker 2 only the leading 2 blocks
+        let request = make_request(
+            128,
+            &[(worker1, 8), (worker2, 2)],
+            &[(worker1, 0), (worker2, 96), (worker3, 128)],
+        );
+        let flat = DefaultWorkerSelector::default().worker_logits(&workers, &request, 16);
+        let weighted = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_overlap_recency_exponent: Some(1.0),
+            ..Default::default()
+        }))
+        .worker_logits(&workers, &request, 16);
+
+        // A full hit and no hit are unaffected, a leading-prefix hit is discounted
+        assert!((weighted[&worker1] - flat[&worker1]).abs() < 1e-9);
+        assert_eq!(weighted[&worker3], flat[&worker3]);
+        assert!(weighted[&worker2] > flat[&worker2]);
+        assert!(weighted[&worker2] < weighted[&worker3]);
+
+        assert_eq!(request.overlaps.overlap_positions(&worker2), 0..2);
+        assert_eq!(request.overlaps.weighted_score(&worker2, |_| 0.5), 1.0);
+    }
+
+    #[test]
+    fn test_cache_pressure_scales_overlap_weight() {
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let mut config = ModelRuntimeConfig::new();
+        config.total_kv_blocks = Some(100);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, Some(config.clone())), (2, Some(config))]
+                .into_iter()
+                .collect();
+
+        let mut request = make_request(64, &[], &[(worker1, 16), (worker2, 64)]);
+        request.decode_blocks = [(worker1, 50), (worker2, 50)].into_iter().collect();
+
+        let flat = DefaultWorkerSelector::default();
+        assert_eq!(flat.cache_pressure(&workers, &request, 16), Some(0.5));
+
+        let adaptive = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_pressure_overlap_scale: 2.0,
+            ..Default::default()
+        }));
+        let flat_logits = flat.worker_logits(&workers, &request, 16);
+        let adaptive_logits = adaptive.worker_logits(&workers, &request, 16);
+
+        // Pressure 0.5 with scale 2 doubles the weight of the prefill term
+        assert_eq!(flat_logits[&worker1], 51.0);
+        assert_eq!(adaptive_logits[&worker1], 52.0);
+        assert_eq!(adaptive_logits[&worker2], 58.0);
+
+        // Without capacity information there is no pressure signal
+        let unknown: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None)].into_iter().collect();
+        assert_eq!(flat.cache_pressure(&unknown, &request, 16), None);
+    }
+
+    #[test]
+    fn test_eviction_risk() {
+        // 90 active blocks leave 10 free for the 20 new ones, the other 10 are evicted
+        assert_eq!(eviction_risk(90, 20, Some(100)), 0.5);
+        // Beyond the active blocks, the request only evicts its own blocks
+        assert_eq!(eviction_risk(10, 200, Some(100)), 0.05);
+        assert_eq!(eviction_risk(40, 20, Some(100)), 0.0);
+        assert_eq!(eviction_risk(90, 0, Some(100)), 0.0);
+        assert_eq!(eviction_risk(90, 20, None), 0.0);
+
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let mut config = ModelRuntimeConfig::new();
+        config.total_kv_blocks = Some(100);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, Some(config.clone())), (2, Some(config.clone()))]
+                .into_iter()
+                .collect();
+
+        // A cold request of 20 blocks, worker 1 is nearly full
+        let mut request = make_request(320, &[], &[(worker1, 320), (worker2, 320)]);
+        request.decode_blocks = [(worker1, 110), (worker2, 60)].into_iter().collect();
+
+        let unpenalized = DefaultWorkerSelector::default().worker_logits(&workers, &request, 16);
+        assert_eq!(unpenalized[&worker1], 130.0);
+        assert_eq!(unpenalized[&worker2], 80.0);
+
+        let penalized = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_eviction_risk_weight: 2.0,
+            ..Default::default()
+        }))
+        .worker_logits(&workers, &request, 16);
+        assert_eq!(penalized[&worker1], 150.0);
+        assert_eq!(penalized[&worker2], 80.0);
+
+        // The capacity of a worker is split between its dp ranks
+        config.data_parallel_size = 2;
+        assert_eq!(rank_capacity(Some(&config)), Some(50));
+        assert_eq!(rank_capacity(None), None);
+    }
+
+    #[test]
+    fn test_unknown_capacity_policy() {
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let mut config = ModelRuntimeConfig::new();
+        config.total_kv_blocks = Some(100);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, Some(config)), (2, Some(ModelRuntimeConfig::new()))]
+                .into_iter()
+                .collect();
+
+        let mut request = make_request(64, &[], &[(worker1, 64), (worker2, 64)]);
+        request.decode_blocks = [(worker1, 50), (worker2, 150)].into_iter().collect();
+
+        let selector = |policy| {
+            DefaultWorkerSelector::new(Some(KvRouterConfig {
+                router_unknown_capacity: policy,
+                ..Default::default()
+            }))
+        };
+
+        // Unlimited: worker 2 is ignored by the pressure but may still be selected
+        let unlimited = selector(UnknownCapacityPolicy::Unlimited);
+        assert_eq!(unlimited.cache_pressure(&workers, &request, 16), Some(0.5));
+        assert_eq!(
+            unlimited
+                .rank_workers(&workers, &request, 16)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // A default capacity puts worker 2 into the pressure
+        let default_capacity = selector(UnknownCapacityPolicy::DefaultCapacity(300));
+        assert_eq!(
+            default_capacity.cache_pressure(&workers, &request, 16),
+            Some(0.5)
+        );
+
+        // Excluded: only worker 1 is routed to
+        let exclude = selector(UnknownCapacityPolicy::Exclude);
+        let ranked = exclude.rank_workers(&workers, &request, 16).unwrap();
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].worker, worker1);
+        assert_eq!(
+            exclude
+                .select_worker(&workers, &request, 16)
+                .unwrap()
+                .worker,
+            worker1
+        );
+
+        // Unless no worker reports its capacity
+        let unknown: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None)].into_iter().collect();
+        assert_eq!(
+            exclude.rank_workers(&unknown, &request, 16).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_missing_runtime_configs_policy() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None)].into_iter().collect();
+        let request = make_request(64, &[], &[]);
+
+        let degraded = DefaultWorkerSelector::default();
+        assert!(degraded.select_worker(&workers, &request, 16).is_ok());
+
+        let fail_fast = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_missing_runtime_configs: MissingRuntimeConfigsPolicy::FailFast,
+            ..Default::default()
+        }));
+        assert!(matches!(
+            fail_fast.select_worker(&workers, &request, 16),
+            Err(KvSchedulerError::MissingRuntimeConfigs)
+        ));
+
+        // A single worker with a config is enough
+        let mut workers = workers;
+        workers.insert(3, Some(ModelRuntimeConfig::new()));
+        assert!(fail_fast.select_worker(&workers, &request, 16).is_ok());
+    }
+
+    #[test]
+    fn test_cluster_utilization() {
+        let mut config = ModelRuntimeConfig::new();
+        config.total_kv_blocks = Some(100);
+        config.data_parallel_size = 2;
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, Some(config)), (2, None)].into_iter().collect();
+        let active_blocks: HashMap<WorkerWithDpRank, usize> = [
+            (WorkerWithDpRank::new(1, 0), 20),
+            (WorkerWithDpRank::new(1, 1), 30),
+            (WorkerWithDpRank::new(2, 0), 40),
+        ]
+        .into_iter()
+        .collect();
+
+        let utilization = ClusterUtilization::new(&active_blocks, &workers);
+        assert_eq!(utilization.total_blocks, 100);
+        assert_eq!(utilization.used_blocks, 50);
+        assert_eq!(utilization.utilization, Some(0.5));
+        assert_eq!(utilization.workers.len(), 2);
+        assert_eq!(utilization.workers[1].used_blocks, 40);
+        assert_eq!(utilization.workers[1].utilization, None);
+    }
+
+    #[test]
+    fn test_top_overlap_candidates() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (0..1000).map(|worker_id| (worker_id, None)).collect();
+        let overlaps: HashMap<WorkerWithDpRank, u32> = [(7, 5), (42, 9), (99, 1), (500, 3)]
+            .into_iter()
+            .map(|(worker_id, overlap)| (WorkerWithDpRank::from_worker_id(worker_id), overlap))
+            .collect();
+
+        // Not more workers than candidates: nothing to filter
+        assert!(top_overlap_candidates(&workers, &overlaps, 1000, &mut rand::rng()).is_none());
+
+        let candidates = top_overlap_candidates(&workers, &overlaps, 3, &mut rand::rng()).unwrap();
+        assert_eq!(candidates.len(), 3 + EXPLORATION_CANDIDATES);
+        for worker_id in [42, 7, 500] {
+            assert!(candidates.contains_key(&worker_id));
+        }
+
+        // Too few overlapping workers: topped up with random ones
+        let candidates = top_overlap_candidates(&workers, &overlaps, 10, &mut rand::rng()).unwrap();
+        assert_eq!(candidates.len(), 10 + EXPLORATION_CANDIDATES);
+        for worker_id in [42, 7, 500, 99] {
+            assert!(candidates.contains_key(&worker_id));
+        }
+    }
+
+    #[test]
+    fn test_sanitize_logits() {
+        let worker = WorkerWithDpRank::from_worker_id;
+        let mut logits: HashMap<WorkerWithDpRank, f64> = [
+            (worker(1), f64::NAN),
+            (worker(2), 3.0),
+            (worker(3), f64::INFINITY),
+            (worker(4), 5.0),
+        ]
+        .into();
+        sanitize_logits(&mut logits).unwrap();
+        assert_eq!(logits.len(), 2);
+
+        // The NaN logit no longer competes: the best valid worker always wins
+        for _ in 0..100 {
+            assert_eq!(softmax_sample(&logits, 0.0, &mut rand::rng()), worker(2));
+            assert!(
+                matches!(softmax_sample(&logits, 1.0, &mut rand::rng()), w if w == worker(2) || w == worker(4))
+            );
+        }
+
+        let mut invalid: HashMap<WorkerWithDpRank, f64> =
+            [(worker(1), f64::NAN), (worker(2), f64::NEG_INFINITY)].into();
+        assert!(matches!(
+            sanitize_logits(&mut invalid),
+            Err(KvSchedulerError::InvalidLogits)
+        ));
+    }
+
+    #[test]
+    fn test_exclude_zero_overlap() {
+        let w1 = WorkerWithDpRank::from_worker_id(1);
+        let w2 = WorkerWithDpRank::from_worker_id(2);
+        let w3 = WorkerWithDpRank::from_worker_id(3);
+        let logits: HashMap<WorkerWithDpRank, f64> =
+            [(w1, 1.0), (w2, 2.0), (w3, 3.0)].into_iter().collect();
+        let overlaps: HashMap<WorkerWithDpRank, u32> = [(w2, 4), (w3, 1)].into_iter().collect();
+
+        // Best overlap of 4 does not exceed the threshold: nothing is excluded
+        let mut candidates = logits.clone();
+        exclude_zero_overlap(&mut candidates, &overlaps, 4);
+        assert_eq!(candidates.len(), 3);
+
+        let mut candidates = logits.clone();
+        exclude_zero_overlap(&mut candidates, &overlaps, 3);
+        assert!(!candidates.contains_key(&w1));
+        assert_eq!(candidates.len(), 2);
+    }
+
+    #[test]
+    fn test_normalized_objectives() {
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None)].into_iter().collect();
+
+        let mut request = make_request(64, &[], &[(worker1, 64), (worker2, 16)]);
+        request.decode_blocks = [(worker1, 100), (worker2, 200)].into_iter().collect();
+
+        // Unnormalized, decode load dominates
+        let default = DefaultWorkerSelector::default();
+        let logits = default.worker_logits(&workers, &request, 16);
+        assert_eq!(logits[&worker1], 104.0);
+        assert_eq!(logits[&worker2], 201.0);
+
+        let normalized = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_normalize_objectives: true,
+            ..Default::default()
+        }));
+        let logits = normalized.worker_logits(&workers, &request, 16);
+        assert_eq!(logits[&worker1], 1.5);
+        assert_eq!(logits[&worker2], 1.25);
+
+        // Per-request overrides take precedence over the selector config, in both directions
+        request.router_config_override = Some(RouterConfigOverride {
+            router_normalize_objectives: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(
+            default.worker_logits(&workers, &request, 16)[&worker2],
+            1.25
+        );
+        request.router_config_override = Some(RouterConfigOverride {
+            router_normalize_objectives: Some(false),
+            ..Default::default()
+        });
+        assert_eq!(
+            normalized.worker_logits(&workers, &request, 16)[&worker2],
+            201.0
+        );
+    }
+
+    #[test]
+    fn test_load_smoothing() {
+        let worker = WorkerWithDpRank::from_worker_id(1);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None)].into_iter().collect();
+        let mut request = make_request(64, &[], &[(worker, 64)]);
+
+        let default = DefaultWorkerSelector::default();
+        let smoothed = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_load_smoothing: 0.5,
+            ..Default::default()
+        }));
+
+        // The first load starts the average
+        request.decode_blocks = [(worker, 100)].into_iter().collect();
+        assert_eq!(
+            smoothed.worker_logits(&workers, &request, 16)[&worker],
+            104.0
+        );
+
+        // A spike only moves the smoothed load halfway
+        request.decode_blocks = [(worker, 200)].into_iter().collect();
+        assert_eq!(
+            default.worker_logits(&workers, &request, 16)[&worker],
+            204.0
+        );
+        assert_eq!(
+            smoothed.worker_logits(&workers, &request, 16)[&worker],
+            154.0
+        );
+        assert_eq!(
+            smoothed.worker_logits(&workers, &request, 16)[&worker],
+            179.0
+        );
+
+        // The average of a worker which left is forgotten
+        let empty = HashMap::new();
+        smoothed.worker_logits(&empty, &request, 16);
+        request.decode_blocks = [(worker, 100)].into_iter().collect();
+        assert_eq!(
+            smoothed.worker_logits(&workers, &request, 16)[&worker],
+            104.0
+        );
+    }
+
+    #[test]
+    fn test_spec_decode_acceptance_rate_scales_decode_load() {
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let config = |rate| {
+            Some(ModelRuntimeConfig {
+                spec_decode_acceptance_rate: rate,
+                ..Default::default()
+            })
+        };
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, config(None)), (2, config(Some(0.5)))]
+                .into_iter()
+                .collect();
+        let mut request = make_request(64, &[], &[(worker1, 64), (worker2, 64)]);
+        request.decode_blocks = [(worker1, 10), (worker2, 10)].into_iter().collect();
+
+        // Prefill is unchanged, the decode load of the worker accepting half its speculations
+        // counts double
+        let logits = DefaultWorkerSelector::default().worker_logits(&workers, &request, 16);
+        assert_eq!(logits[&worker1], 14.0);
+        assert_eq!(logits[&worker2], 24.0);
+
+        // Out of range rates are ignored, tiny ones are bounded
+        assert_eq!(spec_decode_load_scale(config(Some(0.0)).as_ref()), 1.0);
+        assert_eq!(spec_decode_load_scale(config(Some(1.5)).as_ref()), 1.0);
+        assert_eq!(spec_decode_load_scale(config(Some(0.01)).as_ref()), 10.0);
+    }
+
+    #[test]
+    fn test_seeded_selection_is_reproducible() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (0..8).map(|worker_id| (worker_id, None)).collect();
+        let prefill: Vec<(WorkerWithDpRank, usize)> = (0..8)
+            .map(|worker_id| (WorkerWithDpRank::from_worker_id(worker_id), 64))
+            .collect();
+        let request = make_request(64, &[], &prefill);
+
+        let decisions = |seed| {
+            let selector = DefaultWorkerSelector::new(Some(KvRouterConfig {
+                router_seed: Some(seed),
+                router_max_candidates: Some(2),
+                ..Default::default()
+            }));
+            (0..32)
+                .map(|_| {
+                    selector
+                        .select_worker(&workers, &request, 16)
+                        .unwrap()
+                        .worker
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(decisions(7), decisions(7));
+        assert_ne!(decisions(7), decisions(8));
+    }
+
+    #[test]
+    fn test_overlap_token_efficiency() {
+        let worker = WorkerWithDpRank::from_worker_id(1);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None)].into_iter().collect();
+        // 4 of the 8 blocks of the request are cached, leaving 64 of its 128 tokens to prefill
+        let mut request = make_request(128, &[(worker, 4)], &[(worker, 64)]);
+        request.decode_blocks = [(worker, 0)].into_iter().collect();
+
+        let selector = |efficiency| {
+            DefaultWorkerSelector::new(Some(KvRouterConfig {
+                router_overlap_token_efficiency: efficiency,
+                ..Default::default()
+            }))
+        };
+        // By default, every cached token is a saved prefill token
+        assert_eq!(
+            selector(1.0).worker_logits(&workers, &request, 16)[&worker],
+            4.0
+        );
+        // Only half of the cached tokens are saved
+        assert_eq!(
+            selector(0.5).worker_logits(&workers, &request, 16)[&worker],
+            6.0
+        );
+        // No saving at all, the whole request is prefilled
+        assert_eq!(
+            selector(0.0).worker_logits(&workers, &request, 16)[&worker],
+            8.0
+        );
+
+        assert_eq!(saved_prefill_tokens(4, 16, 40, 1.0), 40.0);
+        assert_eq!(saved_prefill_tokens(4, 16, 128, 0.25), 16.0);
+    }
+
+    #[test]
+    fn test_load_shedding() {
+        let shedding = LoadShedding {
+            high_water_mark: 10,
+            min_priority: 1,
+            priority_step: 4,
+        };
+        assert_eq!(shedding.threshold(1), None);
+        assert_eq!(shedding.threshold(0), Some(10));
+        assert_eq!(shedding.threshold(-1), Some(6));
+        assert_eq!(shedding.threshold(-5), Some(0));
+
+        let mut pending = BinaryHeap::new();
+        let mut arrivals = 0;
+        let mut enqueue_with_priority = |priority| {
+            let (tx, mut rx) = tokio::sync::oneshot::channel();
+            let request = SchedulingRequest {
+                priority,
+                resp_tx: Some(tx),
+                ..make_request(64, &[], &[])
+            };
+            enqueue(&mut pending, &mut arrivals, Some(&shedding), None, request);
+            match rx.try_recv() {
+                Ok(Err(KvSchedulerError::AllWorkersBusy)) => false,
+                Err(_) => true,
+                Ok(other) => panic!("unexpected response {other:?}"),
+            }
+        };
+
+        for _ in 0..6 {
+            assert!(enqueue_with_priority(0));
+        }
+        // At 6 queued requests, priority -1 is shed while priority 0 is still queued
+        assert!(!enqueue_with_priority(-1));
+        for _ in 0..4 {
+            assert!(enqueue_with_priority(0));
+        }
+        // At the high-water mark, only high-priority requests are queued
+        assert!(!enqueue_with_priority(0));
+        assert!(enqueue_with_priority(1));
+        assert_eq!(pending.len(), 11);
+    }
+
+    #[test]
+    fn test_publish_circuit_breaker() {
+        let mut breaker = PublishCircuitBreaker::new(
+            "test events",
+            Some(PublishBreaker {
+                failure_threshold: 3,
+                cooldown: Duration::from_secs(10),
+            }),
+        );
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        breaker.record(Err("down"), at(0));
+        breaker.record(Ok::<(), &str>(()), at(0));
+        breaker.record(Err("down"), at(1));
+        breaker.record(Err("down"), at(1));
+        assert!(breaker.allows(at(1)));
+
+        // The third failure in a row opens the breaker for the cooldown
+        breaker.record(Err("down"), at(2));
+        assert!(!breaker.allows(at(2)));
+        assert!(!breaker.allows(at(11)));
+
+        // A failed probe opens it for another cooldown
+        assert!(breaker.allows(at(12)));
+        breaker.record(Err("down"), at(12));
+        assert!(!breaker.allows(at(21)));
+
+        // A successful probe closes it
+        assert!(breaker.allows(at(22)));
+        breaker.record(Ok::<(), &str>(()), at(22));
+        assert!(breaker.allows(at(22)));
+        breaker.record(Err("down"), at(23));
+        assert!(breaker.allows(at(23)));
+
+        // Without settings, it never opens
+        let mut unbounded = PublishCircuitBreaker::new("test events", None);
+        for _ in 0..100 {
+            unbounded.record(Err("down"), at(0));
+        }
+        assert!(unbounded.allows(at(0)));
+    }
+
+    #[test]
+    fn test_swappable_selector() {
+        struct Busy;
+        impl WorkerSelector for Busy {
+            fn select_worker(
+                &self,
+                _workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+                _request: &SchedulingRequest,
+                _block_size: u32,
+            ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+                Err(KvSchedulerError::AllWorkersBusy)
+            }
+        }
+
+        let worker = WorkerWithDpRank::new(1, 0);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = [(1, None)].into();
+        let request = make_request(64, &[(worker, 4)], &[]);
+
+        let selector = SwappableSelector::new(Arc::new(DefaultWorkerSelector::default()));
+        // A handle taken before the swap follows it
+        let handle: Arc<dyn WorkerSelector + Send + Sync> = Arc::new(selector.clone());
+        assert_eq!(
+            handle.select_worker(&workers, &request, 16).unwrap().worker,
+            worker
+        );
+
+        selector.set(Arc::new(Busy));
+        assert!(matches!(
+            handle.select_worker(&workers, &request, 16),
+            Err(KvSchedulerError::AllWorkersBusy)
+        ));
+
+        // Rolling back restores the previous decisions
+        selector.set(Arc::new(DefaultWorkerSelector::default()));
+        assert!(handle.select_worker(&workers, &request, 16).is_ok());
+    }
+
+    #[test]
+    fn test_round_robin_pick() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = [
+            (2, None),
+            (
+                1,
+                Some(ModelRuntimeConfig {
+                    data_parallel_size: 2,
+                    ..Default::default()
+                }),
+            ),
+        ]
+        .into_iter()
+        .collect();
+
+        let picks: Vec<WorkerWithDpRank> = (0..4)
+            .map(|cursor| round_robin_pick(&workers, cursor).unwrap())
+            .collect();
+        assert_eq!(
+            picks,
+            vec![
+                WorkerWithDpRank::new(1, 0),
+                WorkerWithDpRank::new(1, 1),
+                WorkerWithDpRank::new(2, 0),
+                WorkerWithDpRank::new(1, 0),
+            ]
+        );
+        assert_eq!(round_robin_pick(&HashMap::new(), 0), None);
+    }
+
+    #[test]
+    fn test_stale_overlap() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None)].into_iter().collect();
+        let known = WorkerWithDpRank::from_worker_id(1);
+        let removed = WorkerWithDpRank::new(2, 1);
+        let other_removed = WorkerWithDpRank::from_worker_id(3);
+
+        let overlaps = [(known, 20)].into_iter().collect();
+        assert_eq!(stale_overlap(&workers, &overlaps), None);
+
+        let overlaps = [(known, 20), (removed, 8), (other_removed, 3)]
+            .into_iter()
+            .collect();
+        assert_eq!(stale_overlap(&workers, &overlaps), Some((removed, 8)));
+    }
+
+    #[test]
+    fn test_warmup_boost() {
+        let tracker = WarmupTracker::default();
+        let warmup = Duration::from_secs(60);
+        let start = Instant::now();
+
+        // The workers present at startup are established, later ones warm up
+        tracker.observe([1].into_iter(), start);
+        tracker.observe([1, 2].into_iter(), start);
+        assert_eq!(tracker.remaining(1, warmup, start), 0.0);
+        assert_eq!(tracker.remaining(2, warmup, start), 1.0);
+
+        // The boost decays linearly over the window
+        let later = start + Duration::from_secs(15);
+        tracker.observe([1, 2].into_iter(), later);
+        assert_eq!(tracker.remaining(2, warmup, later), 0.75);
+        assert_eq!(tracker.remaining(2, warmup, start + warmup * 2), 0.0);
+
+        // A worker which rejoins warms up again
+        tracker.observe([1].into_iter(), later);
+        tracker.observe([1, 2].into_iter(), later);
+        assert_eq!(tracker.remaining(2, warmup, later), 1.0);
+
+        // Through the selector, a new cold worker is favored over an established one
+        let selector = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_warmup_secs: 3600.0,
+            router_warmup_boost: 0.5,
+            ..Default::default()
+        }));
+        let established = WorkerWithDpRank::from_worker_id(1);
+        let new = WorkerWithDpRank::from_worker_id(2);
+        let request = make_request(64, &[], &[(established, 64), (new, 64)]);
+        let mut workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None)].into_iter().collect();
+        selector.observe_workers(&workers);
+        workers.insert(2, None);
+        selector.observe_workers(&workers);
+
+        let logits = selector.worker_logits(&workers, &request, 16);
+        assert_eq!(logits[&established], 8.0);
+        assert!(logits[&new] > 4.0 && logits[&new] < 4.01);
+    }
+
+    #[test]
+    fn test_warmup_override() {
+        let selector = DefaultWorkerSelector::default();
+        let established = WorkerWithDpRank::from_worker_id(1);
+        let new = WorkerWithDpRank::from_worker_id(2);
+        let mut request = make_request(64, &[], &[(established, 64), (new, 64)]);
+        let mut workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None)].into_iter().collect();
+        selector.observe_workers(&workers);
+        workers.insert(2, None);
+        selector.observe_workers(&workers);
+
+        // Warmup is disabled by the router config, but joins are tracked anyway
+        let logits = selector.worker_logits(&workers, &request, 16);
+        assert_eq!(logits[&new], 8.0);
+
+        request.router_config_override = Some(RouterConfigOverride {
+            router_warmup_secs: Some(3600.0),
+            router_warmup_boost: Some(0.5),
+            ..Default::default()
+        });
+        let logits = selector.worker_logits(&workers, &request, 16);
+        assert_eq!(logits[&established], 8.0);
+        assert!(logits[&new] > 4.0 && logits[&new] < 4.01);
+    }
+
+    #[test]
+    fn test_warmup_ignores_filtered_decisions() {
+        let selector = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_warmup_secs: 3600.0,
+            router_warmup_boost: 0.5,
+            ..Default::default()
+        }));
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let request = make_request(64, &[], &[(worker1, 64), (worker2, 64)]);
+        let all: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None)].into_iter().collect();
+        let filtered: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None)].into_iter().collect();
+        selector.observe_workers(&all);
+
+        // Worker 2 is left out of a decision, e.g. rate limited, without leaving the routing set
+        selector.select_worker(&all, &request, 16).unwrap();
+        assert_eq!(
+            selector
+                .select_worker(&filtered, &request, 16)
+                .unwrap()
+                .worker,
+            worker1
+        );
+        selector.select_worker(&all, &request, 16).unwrap();
+
+        // So it is not mistaken for a worker which just joined
+        let logits = selector.worker_logits(&all, &request, 16);
+        assert_eq!(logits[&worker1], 8.0);
+        assert_eq!(logits[&worker2], 8.0);
+    }
+
+    #[test]
+    fn test_hit_rate_history() {
+        let history = HitRateHistory::default();
+        let event = |worker_id, isl_blocks, overlap_blocks| KVHitRateEvent {
+            worker_id,
+            dp_rank: 0,
+            isl_blocks,
+            overlap_blocks,
+            request_count: 1,
+        };
+        let start = chrono::DateTime::from_timestamp(1_000_000, 0).unwrap();
+        let at = |secs| start + chrono::Duration::seconds(secs);
+
+        history.record(&event(1, 10, 5), at(0));
+        history.record(&event(2, 10, 0), at(9));
+        history.record(&event(1, 20, 20), at(10));
+
+        let buckets = history.buckets(at(10));
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].start, start);
+        assert_eq!(buckets[0].cluster.hit_rate(), Some(0.25));
+        assert_eq!(buckets[0].workers[&1].hit_rate(), Some(0.5));
+        assert_eq!(buckets[0].workers[&2].hit_rate(), Some(0.0));
+        assert_eq!(buckets[1].cluster.hit_rate(), Some(1.0));
+        assert_eq!(buckets[1].cluster.request_count, 1);
+
+        // Buckets older than the window are no longer reported
+        let window = HIT_RATE_BUCKET_SECS * HIT_RATE_HISTORY_BUCKETS as i64;
+        assert_eq!(history.buckets(at(window + 5)).len(), 1);
+
+        // The ring buffer keeps the most recent buckets only
+        for i in 0..HIT_RATE_HISTORY_BUCKETS as i64 {
+            history.record(&event(1, 1, 1), at(20 + i * HIT_RATE_BUCKET_SECS));
+        }
+        let buckets = history.buckets(at(10 + window));
+        assert_eq!(buckets.len(), HIT_RATE_HISTORY_BUCKETS);
+        assert_eq!(buckets[0].start, at(20));
+    }
+
+    #[test]
+    fn test_significant_load_change() {
+        let load = |worker_id, potential_decode_blocks| PotentialLoad {
+            worker_id,
+            dp_rank: 0,
+            potential_prefill_tokens: 0,
+            potential_decode_blocks,
+            eviction_risk: 0.0,
+        };
+        let previous = vec![load(1, 100), load(2, 0)];
+
+        assert!(!significant_load_change(&previous, &previous));
+        assert!(!significant_load_change(
+            &previous,
+            &[load(1, 110), load(2, 0)]
+        ));
+        assert!(significant_load_change(
+            &previous,
+            &[load(1, 130), load(2, 0)]
+        ));
+        // An idle worker picking up load is significant
+        assert!(significant_load_change(
+            &previous,
+            &[load(1, 100), load(2, 2)]
+        ));
+        // So is a worker joining or being replaced
+        assert!(significant_load_change(&previous, &[load(1, 100)]));
+        assert!(significant_load_change(
+            &previous,
+            &[load(1, 100), load(3, 0)]
+        ));
+    }
+
+    #[test]
+    fn test_routing_degradation_hysteresis() {
+        let degradation = RoutingDegradation::new(100);
+        assert!(!degradation.update(100));
+        assert!(degradation.update(101));
+        // Stays degraded until the backlog is half drained
+        assert!(degradation.update(80));
+        assert!(degradation.update(51));
+        assert!(!degradation.update(50));
+        assert!(!degradation.update(80));
+    }
+
+    #[test]
+    fn test_context_too_long() {
+        let config = |max_context_length| {
+            Some(ModelRuntimeConfig {
+                max_context_length: Some(max_context_length),
+                ..Default::default()
+            })
+        };
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, config(32)), (2, config(128))].into_iter().collect();
+        let selector = DefaultWorkerSelector::default();
+
+        // Only worker 2 can fit 64 tokens
+        let request = make_request(64, &[(WorkerWithDpRank::from_worker_id(1), 4)], &[]);
+        for _ in 0..10 {
+            let selection = selector.select_worker(&workers, &request, 16).unwrap();
+            assert_eq!(selection.worker.worker_id, 2);
+        }
+
+        let request = make_request(256, &[], &[]);
+        assert!(matches!(
+            selector.select_worker(&workers, &request, 16),
+            Err(KvSchedulerError::ContextTooLong {
+                isl: 256,
+                max_context: 128
+            })
+        ));
+    }
 
-                // this is the number of prefill tokens the worker would have if the request were scheduled there
-                let prefill_token = *prefill_tokens.get(&worker).unwrap_or(&isl);
-                let potential_prefill_block = (prefill_token as f64) / (block_size as f64);
+    #[test]
+    fn test_consistent_hash_fallback_is_stable() {
+        let mut workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (1..=4).map(|id| (id, None)).collect();
+        let selector = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_consistent_hash_fallback: true,
+            ..Default::default()
+        }));
 
-                // this is the number of decode blocks the worker would have if the request were scheduled there
-                let decode_block = *decode_blocks
-                    .get(&worker)
-                    .unwrap_or(&(potential_prefill_block.floor() as usize))
-                    as f64;
+        let mut request = make_request(64, &[], &[]);
+        request.token_seq = Some(vec![12345, 678]);
 
-                // Use override if provided, otherwise use default config
-                let overlap_weight = request
-                    .router_config_override
-                    .as_ref()
-                    .and_then(|cfg| cfg.overlap_score_weight)
-                    .unwrap_or(self.kv_router_config.overlap_score_weight);
+        let first = selector
+            .select_worker(&workers, &request, 16)
+            .unwrap()
+            .worker;
+        for _ in 0..10 {
+            assert_eq!(
+                selector
+                    .select_worker(&workers, &request, 16)
+                    .unwrap()
+                    .worker,
+                first
+            );
+        }
 
-                // Calculate logit (lower is better)
-                let logit = overlap_weight * potential_prefill_block + decode_block;
-                max_logit = max_logit.max(logit);
+        // Removing a different worker must not move the key
+        let other = (1..=4).find(|id| *id != first.worker_id).unwrap();
+        workers.remove(&other);
+        assert_eq!(
+            selector
+                .select_worker(&workers, &request, 16)
+                .unwrap()
+                .worker,
+            first
+        );
+    }
 
-                worker_logits.insert(worker, logit);
+    #[test]
+    fn test_cold_prefix_affinity_converges() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            (1..=4).map(|id| (id, None)).collect();
+        let load_only = DefaultWorkerSelector::default();
+        let affinity = DefaultWorkerSelector::new(Some(KvRouterConfig {
+            router_cold_prefix_policy: ColdPrefixPolicy::PrefixAffinity,
+            ..Default::default()
+        }));
 
-                tracing::info!(
-                    "Formula for worker_id={} dp_rank={:?} with {overlap} cached blocks: {logit:.3} \
-                     = {overlap_weight:.1} * prefill_blocks + decode_blocks \
-                     = {overlap_weight:.1} * {potential_prefill_block:.3} + {decode_block:.3}",
-                    worker.worker_id,
-                    worker.dp_rank
-                );
+        // The same cold prompt sent repeatedly, each copy adding load to the worker serving it
+        let route = |selector: &DefaultWorkerSelector| {
+            let mut request = make_request(64, &[], &[]);
+            request.token_seq = Some(vec![4242, 17]);
+            let mut chosen = HashSet::new();
+            for _ in 0..8 {
+                let worker = selector
+                    .select_worker(&workers, &request, 16)
+                    .unwrap()
+                    .worker;
+                *request.decode_blocks.entry(worker).or_default() += 4;
+                chosen.insert(worker);
             }
-        }
+            chosen
+        };
 
-        // Use softmax sampling to select worker
-        // Use override if provided, otherwise use default config
-        let temperature = request
-            .router_config_override
-            .as_ref()
-            .and_then(|cfg| cfg.router_temperature)
-            .unwrap_or(self.kv_router_config.router_temperature);
-        let best_worker = softmax_sample(&worker_logits, temperature);
-        let best_logit = worker_logits[&best_worker];
+        // Load-only routing spreads the copies, prefix affinity keeps them on one worker
+        assert!(route(&load_only).len() > 1);
+        assert_eq!(route(&affinity).len(), 1);
 
-        let best_overlap = *overlaps.get(&best_worker).unwrap_or(&0);
+        // Once some worker caches the prefix, the cost function routes as usual
+        let worker4 = WorkerWithDpRank::from_worker_id(4);
+        let mut request = make_request(64, &[(worker4, 4)], &[(worker4, 0)]);
+        request.token_seq = Some(vec![4242, 17]);
+        assert_eq!(
+            affinity
+                .select_worker(&workers, &request, 16)
+                .unwrap()
+                .worker,
+            worker4
+        );
+    }
 
-        // this is a runtime config set on a per worker basis, not per dp-rank
-        let total_blocks_info = workers
-            .get(&best_worker.worker_id)
-            .and_then(|cfg| cfg.as_ref())
-            .and_then(|cfg| cfg.total_kv_blocks)
-            .map(|blocks| format!(", total blocks: {}", blocks))
-            .unwrap_or_default();
+    #[test]
+    fn test_workers_with_configs_dedupes_instances() {
+        let instances = vec![instance(1), instance(2), instance(1), instance(2)];
+        let mut configs = HashMap::new();
+        configs.insert(2, ModelRuntimeConfig::default());
 
-        tracing::info!(
-            "Selected worker: worker_id={} dp_rank={:?}, logit: {:.3}, cached blocks: {}{}",
-            best_worker.worker_id,
-            best_worker.dp_rank,
-            best_logit,
-            best_overlap,
-            total_blocks_info
+        let workers = workers_with_configs_from(&instances, &configs);
+        assert_eq!(workers.len(), 2);
+        assert!(workers[&1].is_none());
+        assert!(workers[&2].is_some());
+    }
+
+    #[test]
+    fn test_rejoin_cooldown() {
+        let mut cooldown = RejoinCooldown::new(Duration::from_secs(10));
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let all: HashMap<WorkerId, ()> = [(1, ()), (2, ())].into();
+
+        // Worker 2 leaves, then flaps back 3s later and is held out
+        let mut workers: HashMap<WorkerId, ()> = [(1, ())].into();
+        assert_eq!(cooldown.hold_out(&all, &mut workers, at(0)), None);
+        let previous = workers.clone();
+        let mut workers = all.clone();
+        assert_eq!(
+            cooldown.hold_out(&previous, &mut workers, at(3)),
+            Some(Duration::from_secs(7))
         );
+        assert_eq!(workers.keys().copied().collect::<Vec<_>>(), vec![1]);
 
-        Ok(WorkerSelectionResult {
-            worker: best_worker,
-            required_blocks: request_blocks as u64,
-            overlap_blocks: overlaps.get(&best_worker).copied().unwrap_or(0),
-        })
+        // Once the cooldown elapsed, it is re-admitted
+        let mut workers = all.clone();
+        assert_eq!(cooldown.hold_out(&previous, &mut workers, at(10)), None);
+        assert_eq!(workers.len(), 2);
+
+        // Workers which stay do not cool down
+        let mut workers = all.clone();
+        assert_eq!(cooldown.hold_out(&all, &mut workers, at(11)), None);
+        assert_eq!(workers.len(), 2);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_membership_changes() {
+        let previous = HashMap::from([(1, ()), (2, ()), (3, ())]);
+        let current = HashMap::from([(2, ()), (4, ()), (5, ())]);
+
+        let changes: Vec<(WorkerId, bool)> = membership_changes(&previous, &current)
+            .into_iter()
+            .map(|event| (event.worker_id, event.added))
+            .collect();
+        assert_eq!(changes, vec![(1, false), (3, false), (4, true), (5, true)]);
+
+        // A config update of the same workers changes nothing
+        assert!(membership_changes(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_sample_index_fallback() {
+        // Probabilities drifting below 1 still cover the whole sample range, in proportion
+        let drifted = [0.2, 0.5, 0.2];
+        assert_eq!(sample_index(&drifted, 0.1), 0);
+        assert_eq!(sample_index(&drifted, 0.5), 1);
+        assert_eq!(sample_index(&drifted, 0.95), 2);
+        assert_eq!(sample_index(&drifted, 1.0), 2);
+
+        // Probabilities which can't be walked select the most probable index, not the last one
+        assert_eq!(sample_index(&[0.1, f64::NAN, 0.6, 0.3], 0.5), 2);
+        assert_eq!(sample_index(&[0.7, f64::INFINITY, 0.1], 0.9), 1);
+    }
 
     #[test]
     fn test_softmax_sample_single_key() {
@@ -592,22 +5357,43 @@ mod tests {
 
         // Test with different temperatures
         for temperature in &[0.1, 1.0, 10.0] {
-            let result = softmax_sample(&logits, *temperature);
+            let result = softmax_sample(&logits, *temperature, &mut rand::rng());
             assert_eq!(result, worker, "Should return the only available worker");
         }
 
         // Test with different logit values
         logits.clear();
         logits.insert(worker, -100.0); // Very negative value
-        assert_eq!(softmax_sample(&logits, 1.0), worker);
+        assert_eq!(softmax_sample(&logits, 1.0, &mut rand::rng()), worker);
 
         logits.clear();
         logits.insert(worker, 100.0); // Very positive value
-        assert_eq!(softmax_sample(&logits, 1.0), worker);
+        assert_eq!(softmax_sample(&logits, 1.0, &mut rand::rng()), worker);
 
         logits.clear();
         logits.insert(worker, 0.0); // Zero value
-        assert_eq!(softmax_sample(&logits, 1.0), worker);
+        assert_eq!(softmax_sample(&logits, 1.0, &mut rand::rng()), worker);
+    }
+
+    #[test]
+    fn test_softmax_sample_equal_logits_uniform() {
+        let logits: HashMap<WorkerWithDpRank, f64> = (0..4)
+            .map(|worker_id| (WorkerWithDpRank::from_worker_id(worker_id), 7.0))
+            .collect();
+
+        let mut counts: HashMap<WorkerWithDpRank, usize> = HashMap::new();
+        for _ in 0..4000 {
+            *counts
+                .entry(softmax_sample(&logits, 1.0, &mut rand::rng()))
+                .or_default() += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        for count in counts.values() {
+            assert!(
+                (800..1200).contains(count),
+                "counts not uniform: {counts:?}"
+            );
+        }
     }
 
     #[test]
@@ -625,7 +5411,7 @@ mod tests {
 
         // With temperature 0, should always return worker 2 (smallest logit)
         for _ in 0..10 {
-            let result = softmax_sample(&logits, 0.0);
+            let result = softmax_sample(&logits, 0.0, &mut rand::rng());
             assert_eq!(
                 result, worker2,
                 "Should return worker with smallest logit when temperature is 0"
@@ -641,7 +5427,831 @@ mod tests {
         logits.insert(worker20, -5.0); // This has the smallest logit
         logits.insert(worker30, 0.0);
 
-        let result = softmax_sample(&logits, 0.0);
+        let result = softmax_sample(&logits, 0.0, &mut rand::rng());
         assert_eq!(result, worker20, "Should handle negative logits correctly");
     }
+
+    #[test]
+    fn test_scheduling_phase_filters_by_role() {
+        let config = |disaggregation_role| {
+            Some(ModelRuntimeConfig {
+                disaggregation_role,
+                ..Default::default()
+            })
+        };
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = [
+            (1, config(DisaggregationRole::Prefill)),
+            (2, config(DisaggregationRole::Decode)),
+            (3, config(DisaggregationRole::Aggregated)),
+            (4, None),
+        ]
+        .into_iter()
+        .collect();
+
+        let candidates = |phase: SchedulingPhase| {
+            let mut workers = workers.clone();
+            phase.filter_workers(&mut workers);
+            let mut ids: Vec<WorkerId> = workers.into_keys().collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(candidates(SchedulingPhase::Any), vec![1, 2, 3, 4]);
+        assert_eq!(candidates(SchedulingPhase::Prefill), vec![1, 3, 4]);
+        assert_eq!(candidates(SchedulingPhase::Decode), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_cancelled_requests_are_taken_once() {
+        let cancelled = CancelledRequests::default();
+        cancelled.insert("req-1".to_string());
+
+        assert!(!cancelled.take("req-2"));
+        assert!(cancelled.take("req-1"));
+        assert!(
+            !cancelled.take("req-1"),
+            "cancellation should be forgotten once taken"
+        );
+    }
+
+    #[test]
+    fn test_retired_requests_report_their_last_stage() {
+        let retired = RetiredRequests::default();
+        assert_eq!(retired.stage("req-1"), None);
+
+        retired.record("req-1", RetiredStage::PrefillCompleted);
+        assert_eq!(retired.stage("req-1"), Some(RetiredStage::PrefillCompleted));
+        retired.record("req-1", RetiredStage::Freed);
+        assert_eq!(retired.stage("req-1"), Some(RetiredStage::Freed));
+
+        // Entries older than the TTL are forgotten, but not one recorded again since
+        let start = Instant::now();
+        retired.record_at("req-2", RetiredStage::Freed, start);
+        retired.record_at("req-3", RetiredStage::Freed, start);
+        retired.record_at(
+            "req-3",
+            RetiredStage::Freed,
+            start + RetiredRequests::TTL / 2,
+        );
+        retired.record_at(
+            "req-4",
+            RetiredStage::Freed,
+            start + RetiredRequests::TTL + Duration::from_secs(1),
+        );
+        assert_eq!(retired.stage("req-2"), None);
+        assert_eq!(retired.stage("req-3"), Some(RetiredStage::Freed));
+        assert_eq!(retired.stage("req-4"), Some(RetiredStage::Freed));
+    }
+
+    #[test]
+    fn test_provisional_worker_nets_overlap_against_last_loads() {
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None), (3, None)].into_iter().collect();
+        let worker = WorkerWithDpRank::from_worker_id;
+        let last_loads: HashMap<WorkerWithDpRank, usize> =
+            [(worker(1), 10), (worker(2), 4), (worker(3), 6)].into();
+
+        let no_overlap = OverlapScores::new();
+        assert_eq!(
+            provisional_worker(&workers, &last_loads, &no_overlap),
+            Some(worker(2))
+        );
+
+        // Worker 3 saves enough prefill to beat the less loaded worker 2
+        let mut overlaps = OverlapScores::new();
+        overlaps.scores.insert(worker(3), 5);
+        assert_eq!(
+            provisional_worker(&workers, &last_loads, &overlaps),
+            Some(worker(3))
+        );
+
+        assert_eq!(
+            provisional_worker(&HashMap::new(), &last_loads, &overlaps),
+            None
+        );
+    }
+
+    #[test]
+    fn test_worker_rate_limiter() {
+        let limited = Some(ModelRuntimeConfig {
+            max_requests_per_second: Some(2),
+            ..Default::default()
+        });
+        let all_workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, limited.clone()), (2, None)].into_iter().collect();
+        let mut limiter = WorkerRateLimiter::new(None);
+        let start = Instant::now();
+
+        // Worker 1 allows a burst of 2 requests, worker 2 is unlimited
+        for _ in 0..2 {
+            let mut workers = all_workers.clone();
+            assert_eq!(limiter.retain_available(&mut workers, start), None);
+            limiter.consume(1, limited.as_ref(), start);
+            limiter.consume(2, None, start);
+        }
+        let mut workers = all_workers.clone();
+        let wait = limiter.retain_available(&mut workers, start);
+        assert_eq!(wait, Some(Duration::from_millis(500)));
+        assert_eq!(workers.keys().copied().collect::<Vec<_>>(), vec![2]);
+
+        // A token is back after 1 / rate seconds
+        let mut workers = all_workers.clone();
+        assert_eq!(
+            limiter.retain_available(&mut workers, start + Duration::from_millis(500)),
+            None
+        );
+        assert_eq!(workers.len(), 2);
+
+        // The router-wide default applies to workers without their own limit
+        let mut limiter = WorkerRateLimiter::new(Some(1.0));
+        limiter.consume(2, None, start);
+        let mut workers = all_workers.clone();
+        limiter.retain_available(&mut workers, start);
+        assert_eq!(workers.keys().copied().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_hit_rate_aggregator_sums_per_worker() {
+        let event = |worker_id, isl_blocks, overlap_blocks| KVHitRateEvent {
+            worker_id,
+            dp_rank: 0,
+            isl_blocks,
+            overlap_blocks,
+            request_count: 1,
+        };
+        let aggregator = HitRateAggregator::default();
+        aggregator.record(event(1, 10, 4));
+        aggregator.record(event(1, 6, 2));
+        aggregator.record(event(2, 8, 0));
+
+        let mut events = aggregator.drain();
+        events.sort_by_key(|e| e.worker_id);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            (
+                events[0].isl_blocks,
+                events[0].overlap_blocks,
+                events[0].request_count
+            ),
+            (16, 6, 2)
+        );
+        assert_eq!(
+            (
+                events[1].isl_blocks,
+                events[1].overlap_blocks,
+                events[1].request_count
+            ),
+            (8, 0, 1)
+        );
+        assert!(
+            aggregator.drain().is_empty(),
+            "drain should reset the window"
+        );
+
+        // Events published by older routers count as a single request
+        let legacy: KVHitRateEvent =
+            serde_json::from_str(r#"{"worker_id":1,"isl_blocks":3,"overlap_blocks":1}"#).unwrap();
+        assert_eq!(legacy.request_count, 1);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_monitor_removes_workers_from_slot_tracker() -> Result<()> {
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_monitor_removes_workers")?;
+        let component = namespace
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let (instances_tx, instances_rx) = watch::channel(vec![instance(1), instance(2)]);
+        let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+        let scheduler = KvScheduler::start(
+            component,
+            4,
+            instances_rx,
+            configs_rx,
+            None,
+            None,
+            KvSchedulerConfig::builder()
+                .router_uuid("test-router")
+                .build()?,
+        )
+        .await?;
+
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        scheduler
+            .add_request("req-1".to_string(), None, 16, 0, worker1)
+            .await;
+        scheduler
+            .add_request("req-2".to_string(), None, 16, 0, worker2)
+            .await;
+        assert!(scheduler.slots.active_tokens().await[&worker1] > 0);
+
+        // Drop worker 1 from the instances and wait for the monitor task to apply it
+        instances_tx.send(vec![instance(2)])?;
+        let active_tokens = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let active_tokens = scheduler.slots.active_tokens().await;
+                if !active_tokens.contains_key(&worker1) {
+                    return active_tokens;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+
+        assert!(active_tokens[&worker2] > 0);
+        assert_eq!(scheduler.slots.num_workers(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reused_request_id_marks_its_prefill_completed() -> Result<()> {
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_reused_request_id")?;
+        let component = namespace
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let (_instances_tx, instances_rx) = watch::channel(vec![instance(1)]);
+        let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+        let scheduler = KvScheduler::start(
+            component,
+            4,
+            instances_rx,
+            configs_rx,
+            None,
+            None,
+            KvSchedulerConfig::builder()
+                .router_uuid("test-router")
+                .build()?,
+        )
+        .await?;
+
+        let worker = WorkerWithDpRank::from_worker_id(1);
+        let request_id = "req".to_string();
+        scheduler
+            .add_request(request_id.clone(), None, 16, 0, worker)
+            .await;
+        scheduler.mark_prefill_completed(&request_id).await?;
+        scheduler.free(&request_id).await?;
+
+        // The id is reserved again, e.g. by a migration retry, well within the retired TTL
+        scheduler
+            .add_request(request_id.clone(), None, 16, 0, worker)
+            .await;
+        scheduler.mark_prefill_completed(&request_id).await?;
+
+        // Its prefill tokens are released while the request is still tracked
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while scheduler
+                .slots
+                .active_tokens()
+                .await
+                .get(&worker)
+                .is_some_and(|tokens| *tokens > 0)
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        assert_eq!(
+            scheduler.active_requests(),
+            vec![(request_id.clone(), worker.worker_id)]
+        );
+
+        scheduler.free(&request_id).await?;
+        assert!(matches!(
+            scheduler.free(&request_id).await,
+            Err(KvSchedulerError::AlreadyFreed(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_dropped_receiver_makes_no_reservation() -> Result<()> {
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_dropped_receiver")?;
+        let component = namespace
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let (_instances_tx, instances_rx) = watch::channel(vec![instance(1), instance(2)]);
+        let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+        let scheduler = KvScheduler::start(
+            component,
+            4,
+            instances_rx,
+            configs_rx,
+            None,
+            None,
+            KvSchedulerConfig::builder()
+                .router_uuid("test-router")
+                .build()?,
+        )
+        .await?;
+
+        // The caller drops its receiver before the scheduler loop gets to the request
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        drop(resp_rx);
+        let mut abandoned = make_request(16, &[], &[]);
+        abandoned.maybe_request_id = Some("abandoned".to_string());
+        abandoned.update_states = true;
+        abandoned.resp_tx = Some(resp_tx);
+        scheduler
+            .request_tx
+            .send(abandoned)
+            .await
+            .map_err(|_| anyhow::anyhow!("scheduler shut down"))?;
+
+        // Requests are handled in order, so the abandoned one was processed once this returns
+        scheduler
+            .schedule(
+                Some("live".to_string()),
+                16,
+                None,
+                OverlapScores::new(),
+                None,
+                true,
+            )
+            .await?;
+
+        assert_eq!(
+            scheduler
+                .active_requests()
+                .into_iter()
+                .map(|(request_id, _)| request_id)
+                .collect::<Vec<_>>(),
+            vec!["live".to_string()]
+        );
+        assert!(
+            scheduler
+                .slots
+                .worker_of(&"abandoned".to_string())
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_retried_request_is_reselected_away_from_its_worker() -> Result<()> {
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_retried_request")?;
+        let component = namespace
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let (_instances_tx, instances_rx) = watch::channel(vec![instance(1), instance(2)]);
+        let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+        let scheduler = KvScheduler::start(
+            component,
+            4,
+            instances_rx,
+            configs_rx,
+            None,
+            None,
+            KvSchedulerConfig::builder()
+                .router_uuid("test-router")
+                .build()?,
+        )
+        .await?;
+
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let mut overlaps = OverlapScores::new();
+        overlaps.scores.insert(worker1, 4);
+
+        let first = scheduler
+            .schedule(
+                Some("req".to_string()),
+                16,
+                None,
+                overlaps.clone(),
+                None,
+                true,
+            )
+            .await?;
+        assert_eq!(first, worker1);
+
+        // The stream on worker 1 dropped before it was freed, and the request is migrated under
+        // the same id: it moves to the other worker despite the overlap of the one which failed
+        let retry = scheduler
+            .schedule(Some("req".to_string()), 16, None, overlaps, None, true)
+            .await?;
+        assert_eq!(retry, worker2);
+        assert_eq!(
+            scheduler.active_requests(),
+            vec![("req".to_string(), worker2.worker_id)]
+        );
+        assert!(
+            !scheduler
+                .slots
+                .active_tokens()
+                .await
+                .get(&worker1)
+                .is_some_and(|tokens| *tokens > 0)
+        );
+
+        // The retry holds the only reservation of the id
+        scheduler.free("req").await?;
+        assert!(scheduler.active_requests().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_invalid_logits_fail_only_their_request() -> Result<()> {
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+
+        /// Fails the requests of 13 tokens as if every worker had a corrupt logit
+        struct CorruptOn13(DefaultWorkerSelector);
+        impl WorkerSelector for CorruptOn13 {
+            fn select_worker(
+                &self,
+                workers: &HashMap<WorkerId, Option<ModelRuntimeConfig>>,
+                request: &SchedulingRequest,
+                block_size: u32,
+            ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+                if request.isl_tokens == 13 {
+                    return Err(KvSchedulerError::InvalidLogits);
+                }
+                self.0.select_worker(workers, request, block_size)
+            }
+        }
+
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_invalid_logits")?;
+        let component = namespace
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let (_instances_tx, instances_rx) = watch::channel(vec![instance(1), instance(2)]);
+        let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+        let scheduler = KvScheduler::start(
+            component,
+            4,
+            instances_rx,
+            configs_rx,
+            Some(Box::new(CorruptOn13(DefaultWorkerSelector::default()))),
+            None,
+            KvSchedulerConfig::builder()
+                .router_uuid("test-router")
+                .build()?,
+        )
+        .await?;
+
+        // The failing request gets an error rather than a dropped response
+        let result = scheduler
+            .schedule(
+                Some("corrupt".to_string()),
+                13,
+                None,
+                OverlapScores::new(),
+                None,
+                true,
+            )
+            .await;
+        assert!(matches!(result, Err(KvSchedulerError::InvalidLogits)));
+
+        // The scheduler loop is still running
+        scheduler
+            .schedule(
+                Some("live".to_string()),
+                16,
+                None,
+                OverlapScores::new(),
+                None,
+                true,
+            )
+            .await?;
+        assert_eq!(
+            scheduler
+                .active_requests()
+                .into_iter()
+                .map(|(request_id, _)| request_id)
+                .collect::<Vec<_>>(),
+            vec!["live".to_string()]
+        );
+
+        Ok(())
+    }
+
+    /// Slots tracking only `known` workers, so that reserving any other worker fails
+    async fn slots_knowing(
+        test_name: &str,
+        known: &[WorkerId],
+    ) -> Result<ActiveSequencesMultiWorker> {
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let component = distributed
+            .namespace(test_name)?
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+        Ok(ActiveSequencesMultiWorker::new(
+            component,
+            4,
+            known.iter().map(|worker_id| (*worker_id, None)).collect(),
+            false,
+            "test-router".to_string(),
+        ))
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reserve_strict_reselects_after_failed_reservation() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let slots = slots_knowing("test_reserve_strict_reselects", &[3]).await?;
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None), (3, None)].into_iter().collect();
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let mut request = make_request(16, &[(worker1, 4)], &[(worker1, 0)]);
+        request.maybe_request_id = Some("request".to_string());
+
+        // Worker 1 caches the prefix but is unknown to the slot tracker
+        let selector = DefaultWorkerSelector::default();
+        let selection = selector.select_worker(&workers, &request, 4)?;
+        assert_eq!(selection.worker, worker1);
+
+        let reserved = reserve_strict(&slots, &selector, &workers, &request, selection, 4).await?;
+        let worker3 = WorkerWithDpRank::from_worker_id(3);
+        assert_eq!(reserved.worker, worker3);
+        assert_eq!(slots.worker_of(&"request".to_string()), Some(worker3));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reserve_strict_fails_once_workers_are_exhausted() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let slots = slots_knowing("test_reserve_strict_exhausted", &[3]).await?;
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> =
+            [(1, None), (2, None)].into_iter().collect();
+        let mut request = make_request(16, &[], &[]);
+        request.maybe_request_id = Some("request".to_string());
+
+        let selector = DefaultWorkerSelector::default();
+        let selection = selector.select_worker(&workers, &request, 4)?;
+        let result = reserve_strict(&slots, &selector, &workers, &request, selection, 4).await;
+        assert!(matches!(
+            result,
+            Err(KvSchedulerError::ReservationFailed(_))
+        ));
+        assert!(slots.worker_of(&"request".to_string()).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_failed_reservation_publishes_no_hit_rate() -> Result<()> {
+        use dynamo_runtime::traits::events::EventSubscriber;
+        use dynamo_runtime::{DistributedRuntime, Runtime};
+        use futures::StreamExt;
+
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_failed_reservation_hit_rate")?;
+        let component = namespace
+            .component("scheduler")?
+            .service_builder()
+            .create()
+            .await?;
+        let mut hit_rates = Box::pin(
+            namespace
+                .subscribe_with_type::<KVHitRateEvent>(KV_HIT_RATE_SUBJECT)
+                .await?,
+        );
+
+        let (_instances_tx, instances_rx) = watch::channel(vec![instance(1), instance(2)]);
+        let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+        let scheduler = KvScheduler::start(
+            component,
+            4,
+            instances_rx,
+            configs_rx,
+            None,
+            None,
+            KvSchedulerConfig::builder()
+                .router_uuid("test-router")
+                .strict_slot_tracking(true)
+                .build()?,
+        )
+        .await?;
+
+        // Reserving a request id which is already tracked fails on every worker
+        let schedule = |request_id: &str, isl_tokens| {
+            scheduler.schedule(
+                Some(request_id.to_string()),
+                isl_tokens,
+                None,
+                OverlapScores::new(),
+                None,
+                true,
+            )
+        };
+        schedule("request", 16).await?;
+        let result = schedule("request", 32).await;
+        assert!(matches!(
+            result,
+            Err(KvSchedulerError::ReservationFailed(_))
+        ));
+        schedule("other", 64).await?;
+
+        // Only the two successful decisions are published, in order
+        let mut isl_blocks = Vec::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), hit_rates.next())
+                .await?
+                .expect("hit rate subscription ended")?;
+            isl_blocks.push(event.isl_blocks);
+        }
+        assert_eq!(isl_blocks, vec![4, 16]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expected_ttft() {
+        assert_eq!(estimate_ttft_secs(1000, Some(2000)), Some(0.5));
+        assert_eq!(estimate_ttft_secs(1000, Some(0)), None);
+        assert_eq!(estimate_ttft_secs(1000, None), None);
+
+        let worker1 = WorkerWithDpRank::from_worker_id(1);
+        let worker2 = WorkerWithDpRank::from_worker_id(2);
+        let workers: HashMap<WorkerId, Option<ModelRuntimeConfig>> = [
+            (
+                1,
+                Some(ModelRuntimeConfig {
+                    prefill_tokens_per_second: Some(1000),
+                    ..Default::default()
+                }),
+            ),
+            (2, None),
+        ]
+        .into_iter()
+        .collect();
+        let selector = DefaultWorkerSelector::default();
+
+        // Worker 1 has 256 tokens queued on top of the uncached part of the request
+        let request = make_request(64, &[(worker1, 2)], &[(worker1, 288)]);
+        assert_eq!(
+            selector.expected_ttft_secs(&workers, &request, worker1),
+            Some(0.288)
+        );
+        assert_eq!(
+            selector.expected_ttft_secs(&workers, &request, worker2),
+            None
+        );
+
+        let ranked = selector.rank_workers(&workers, &request, 16).unwrap();
+        let ttft: HashMap<_, _> = ranked
+            .iter()
+            .map(|result| (result.worker, result.expected_ttft_secs))
+            .collect();
+        assert_eq!(ttft[&worker1], Some(0.288));
+        assert_eq!(ttft[&worker2], None);
+    }
+
+    struct IslAdmission;
+
+    #[async_trait::async_trait]
+    impl AdmissionPolicy for IslAdmission {
+        async fn admit(&self, request: &SchedulingRequest) -> AdmissionDecision {
+            match request.isl_tokens {
+                isl if isl > 100 => AdmissionDecision::Deny("too long".to_string()),
+                isl if isl < 10 => AdmissionDecision::Reprioritize(5),
+                _ => AdmissionDecision::Allow,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admission_policy() {
+        let policy = IslAdmission;
+
+        // Without a policy every request is admitted as is
+        let admitted = admit(None, make_request(200, &[], &[])).await.unwrap();
+        assert_eq!(admitted.priority, 0);
+
+        let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+        let mut denied = make_request(200, &[], &[]);
+        denied.resp_tx = Some(resp_tx);
+        assert!(admit(Some(&policy), denied).await.is_none());
+        assert!(matches!(
+            resp_rx.await.unwrap(),
+            Err(KvSchedulerError::AdmissionDenied(reason)) if reason == "too long"
+        ));
+
+        // Reprioritized requests are scheduled first, the others in arrival order
+        let mut pending = BinaryHeap::new();
+        for (arrival, isl) in [50, 60, 5, 70].into_iter().enumerate() {
+            let request = admit(Some(&policy), make_request(isl, &[], &[]))
+                .await
+                .unwrap();
+            pending.push(QueuedRequest::new(
+                request,
+                arrival as u64,
+                None,
+                Instant::now(),
+            ));
+        }
+        let order: Vec<usize> = std::iter::from_fn(|| pending.pop())
+            .map(|queued| queued.request.isl_tokens)
+            .collect();
+        assert_eq!(order, vec![5, 50, 60, 70]);
+    }
+
+    #[test]
+    fn test_queue_aging() {
+        let aging = QueueAging::new(Duration::from_secs(10));
+        let at = |secs| aging.epoch + Duration::from_secs(secs);
+        let queued = |priority, arrival, enqueued_at| {
+            let request = SchedulingRequest {
+                priority,
+                isl_tokens: arrival as usize,
+                ..make_request(64, &[], &[])
+            };
+            QueuedRequest::new(request, arrival, Some(&aging), enqueued_at)
+        };
+        let order = |requests: Vec<QueuedRequest>| -> Vec<usize> {
+            let mut pending: BinaryHeap<QueuedRequest> = requests.into_iter().collect();
+            std::iter::from_fn(|| pending.pop())
+                .map(|queued| queued.request.isl_tokens)
+                .collect()
+        };
+
+        // A request of priority 0 which waited 25s outranks one of priority 2 queued just now,
+        // but not one of priority 3
+        assert_eq!(
+            order(vec![queued(0, 0, at(0)), queued(2, 1, at(25))]),
+            vec![0, 1]
+        );
+        assert_eq!(
+            order(vec![queued(0, 0, at(0)), queued(3, 1, at(25))]),
+            vec![1, 0]
+        );
+        // Among requests queued together, priority then arrival still decide
+        assert_eq!(
+            order(vec![
+                queued(0, 0, at(5)),
+                queued(1, 1, at(5)),
+                queued(0, 2, at(5))
+            ]),
+            vec![1, 0, 2]
+        );
+
+        let metrics = QueueWaitMetrics::new_unregistered();
+        metrics.record(Duration::from_secs(3));
+        metrics.record(Duration::from_secs(1));
+        assert_eq!(metrics.max_queue_wait_seconds.get(), 3.0);
+    }
 }
@@ -28,17 +28,20 @@ use anyhow::Result;
 use dashmap::DashMap;
 use derive_getters::Getters;
 use dynamo_runtime::component::Component;
+use dynamo_runtime::metrics::{MetricsRegistry, prometheus_names::kvrouter};
 use dynamo_runtime::traits::DistributedRuntimeProvider;
 use dynamo_runtime::traits::events::{EventPublisher, EventSubscriber};
 use futures::StreamExt;
-use std::collections::{HashMap, HashSet};
+use prometheus::{IntCounter, IntGauge};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::{Rc, Weak};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 use tokio::time::Instant;
 use uuid::Uuid;
 
-use super::protocols::{ActiveSequenceEvent, ActiveSequenceEventData, WorkerWithDpRank};
+use super::protocols::{ActiveSequenceEvent, ActiveSequenceEventData, WorkerId, WorkerWithDpRank};
 use crate::kv_router::ACTIVE_SEQUENCES_SUBJECT;
 use crate::local_model::runtime_config::ModelRuntimeConfig;
 use dynamo_runtime::CancellationToken;
@@ -164,17 +167,23 @@ impl ActiveSequences {
             .unwrap_or_else(|| panic!("prefill_tokens < 0 with overlap {overlap} and ISL {isl}"))
     }
 
+    /// Blocks and prefill tokens the worker would hold if the request were added. With a
+    /// `token_sequence`, only its blocks which are not already active count as new. Without one,
+    /// no sharing with active requests can be detected, so every full block of the `isl` tokens
+    /// counts as new, as it would for a sequence sharing no block. `Some` of an empty sequence is
+    /// a request shorter than a block, which adds no block. The prefill tokens only depend on the
+    /// cached `overlap`.
     pub fn potential_blocks_and_tokens(
         &self,
         token_sequence: Option<&[SequenceHash]>,
         isl: usize,
         overlap: u32,
     ) -> (usize, usize) {
-        let potential_blocks = if let Some(token_seq) = token_sequence {
-            self.new_blocks(token_seq) + self.active_blocks()
-        } else {
-            self.active_blocks()
+        let new_blocks = match token_sequence {
+            Some(token_seq) => self.new_blocks(token_seq),
+            None => isl / self.block_size,
         };
+        let potential_blocks = new_blocks + self.active_blocks();
         let potential_tokens = self.new_tokens(isl, overlap) + self.active_tokens;
         (potential_blocks, potential_tokens)
     }
@@ -217,6 +226,24 @@ impl ActiveSequences {
         self.active_blocks()
     }
 
+    /// The blocks and outstanding prefill tokens of every active request on `worker`, sorted by
+    /// request id
+    pub fn export_requests(&self, worker: WorkerWithDpRank) -> Vec<ActiveRequestState> {
+        let mut requests: Vec<ActiveRequestState> = self
+            .active_seqs
+            .iter()
+            .map(|(request_id, sequence)| ActiveRequestState {
+                request_id: request_id.clone(),
+                worker,
+                token_sequence: (!sequence.is_empty())
+                    .then(|| sequence.iter().map(|(block, _)| *block).collect()),
+                prefill_tokens: self.prefill_tokens.get(request_id).copied(),
+            })
+            .collect();
+        requests.sort_unstable_by(|a, b| a.request_id.cmp(&b.request_id));
+        requests
+    }
+
     /// Force expiry of stale requests if the timer has elapsed
     /// Returns the set of expired request IDs that were removed
     pub fn force_expiry(&mut self) -> HashSet<RequestId> {
@@ -275,9 +302,119 @@ enum UpdateSequences {
     ActiveTokens {
         resp_tx: tokio::sync::oneshot::Sender<usize>,
     },
+    Export {
+        worker: WorkerWithDpRank,
+        resp_tx: tokio::sync::oneshot::Sender<Vec<ActiveRequestState>>,
+    },
+    Reset,
     Shutdown,
 }
 
+/// Errors of [`ActiveSequencesMultiWorker`] the caller may want to handle specifically
+#[derive(Debug, thiserror::Error)]
+pub enum SequenceError {
+    #[error("Request {request_id} is already active on worker {worker:?}")]
+    DuplicateRequestId {
+        request_id: RequestId,
+        worker: WorkerWithDpRank,
+    },
+}
+
+/// An active request as exported by [`ActiveSequencesMultiWorker::export_active_state`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveRequestState {
+    pub request_id: RequestId,
+    pub worker: WorkerWithDpRank,
+    /// None if the request was added without its block hashes
+    pub token_sequence: Option<Vec<SequenceHash>>,
+    /// Prefill tokens still outstanding, None once prefill completed
+    pub prefill_tokens: Option<usize>,
+}
+
+/// The in-flight requests of a router, to hand its load accounting over to another router,
+/// e.g. during a blue-green deploy. Unlike a radix tree snapshot, which holds what the workers
+/// have cached, this holds what they are currently serving.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveStateSnapshot {
+    pub block_size: usize,
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    pub requests: Vec<ActiveRequestState>,
+}
+
+/// Outcome of [`ActiveSequencesMultiWorker::import_active_state`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Requests already tracked by this router
+    pub already_tracked: usize,
+    /// Requests on workers this router does not know
+    pub unknown_worker: usize,
+}
+
+/// Metrics for the requests tracked by [`ActiveSequencesMultiWorker`].
+#[derive(Clone)]
+pub struct ActiveSequencesMetrics {
+    /// Number of requests currently tracked across all workers
+    pub tracked_requests: IntGauge,
+    /// Number of requests evicted because the tracked requests cap was exceeded
+    pub tracked_requests_evicted: IntCounter,
+}
+
+static ACTIVE_SEQUENCES_METRICS: OnceLock<Arc<ActiveSequencesMetrics>> = OnceLock::new();
+
+impl ActiveSequencesMetrics {
+    /// Creates the metrics from a Component, memoizing the result in ACTIVE_SEQUENCES_METRICS to
+    /// avoid duplicate registration issues.
+    pub fn from_component(component: &Component) -> Arc<Self> {
+        ACTIVE_SEQUENCES_METRICS
+            .get_or_init(|| {
+                let metrics = component
+                    .create_intgauge(
+                        kvrouter::TRACKED_REQUESTS,
+                        "Number of active requests tracked by the KV router",
+                        &[],
+                    )
+                    .and_then(|tracked_requests| {
+                        Ok(Self {
+                            tracked_requests,
+                            tracked_requests_evicted: component.create_intcounter(
+                                kvrouter::TRACKED_REQUESTS_EVICTED,
+                                "Number of tracked requests evicted by the KV router because the cap was exceeded",
+                                &[],
+                            )?,
+                        })
+                    });
+                match metrics {
+                    Ok(metrics) => Arc::new(metrics),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to create active sequences metrics from component: {}. Using unregistered metrics as fallback.",
+                            e
+                        );
+                        Arc::new(Self::new_unregistered())
+                    }
+                }
+            })
+            .clone()
+    }
+
+    /// Creates metrics which are not registered with a MetricsRegistry.
+    pub fn new_unregistered() -> Self {
+        Self {
+            tracked_requests: IntGauge::new(
+                kvrouter::TRACKED_REQUESTS,
+                "Number of active requests tracked by the KV router",
+            )
+            .unwrap(),
+            tracked_requests_evicted: IntCounter::new(
+                kvrouter::TRACKED_REQUESTS_EVICTED,
+                "Number of tracked requests evicted by the KV router because the cap was exceeded",
+            )
+            .unwrap(),
+        }
+    }
+}
+
 /// Multi-worker extension of ActiveSequences that distributes requests across multiple threads
 pub struct ActiveSequencesMultiWorker {
     senders: Arc<DashMap<WorkerWithDpRank, tokio::sync::mpsc::UnboundedSender<UpdateSequences>>>,
@@ -287,9 +424,18 @@ pub struct ActiveSequencesMultiWorker {
     component: Component,
     router_id: Uuid,
     replica_sync: bool,
+    /// Tracked requests in insertion order, used to evict the oldest ones when above
+    /// `max_tracked_requests`. May contain ids which were already freed; those are skipped.
+    tracked_order: Arc<Mutex<VecDeque<RequestId>>>,
+    max_tracked_requests: Option<usize>,
+    metrics: Arc<ActiveSequencesMetrics>,
 }
 
 impl ActiveSequencesMultiWorker {
+    /// Track the requests of `workers_with_configs` and of the workers added later. All of them
+    /// must use `block_size`: the potential blocks of a request are computed with it for every
+    /// worker. Workers of a model with a different block size are refused before they reach the
+    /// tracker, see [`crate::discovery::ModelManager::kv_chooser_for`].
     pub fn new(
         component: Component,
         block_size: usize,
@@ -302,6 +448,8 @@ impl ActiveSequencesMultiWorker {
         let senders = Arc::new(DashMap::new());
         let handles = Arc::new(DashMap::new());
         let request_to_worker = Arc::new(DashMap::new());
+        let tracked_order = Arc::new(Mutex::new(VecDeque::new()));
+        let metrics = ActiveSequencesMetrics::from_component(&component);
         let router_id = Uuid::parse_str(&router_uuid).unwrap_or_else(|e| {
             tracing::warn!(
                 "Failed to parse router UUID '{}': {}, using new UUID",
@@ -333,12 +481,17 @@ impl ActiveSequencesMultiWorker {
             component: component.clone(),
             router_id,
             replica_sync,
+            tracked_order: tracked_order.clone(),
+            max_tracked_requests: None,
+            metrics: metrics.clone(),
         };
 
         // Start the subscription loop only if replica_sync is enabled
         if replica_sync {
             let senders_clone = senders.clone();
             let request_to_worker_clone = request_to_worker.clone();
+            let tracked_order_clone = tracked_order.clone();
+            let metrics_clone = metrics.clone();
             let component_clone = component.clone();
             let router_id_clone = router_id;
             let cancel_token = component.drt().runtime().child_token();
@@ -348,6 +501,8 @@ impl ActiveSequencesMultiWorker {
                 if let Err(e) = Self::subscribe_to_events(
                     senders_clone,
                     request_to_worker_clone,
+                    tracked_order_clone,
+                    metrics_clone,
                     component_clone,
                     router_id_clone,
                     cancel_token,
@@ -362,6 +517,89 @@ impl ActiveSequencesMultiWorker {
         multi_worker
     }
 
+    /// Cap the number of tracked requests across all workers. When exceeded, the oldest requests
+    /// are evicted as if freed, so that requests which are never freed (e.g. lost completion
+    /// events) cannot grow the router's memory without bound. `None` disables the cap.
+    pub fn with_max_tracked_requests(mut self, max_tracked_requests: Option<usize>) -> Self {
+        self.max_tracked_requests = max_tracked_requests;
+        self
+    }
+
+    /// Number of requests currently tracked across all workers
+    pub fn num_tracked_requests(&self) -> usize {
+        self.request_to_worker.len()
+    }
+
+    /// Every tracked request id with the worker it is assigned to, sorted by request id, e.g. to
+    /// reconcile against the requests the workers actually have in flight
+    pub fn active_requests(&self) -> Vec<(RequestId, WorkerId)> {
+        let mut active: Vec<(RequestId, WorkerId)> = self
+            .request_to_worker
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().worker_id))
+            .collect();
+        active.sort_unstable();
+        active
+    }
+
+    /// Forget every tracked request, e.g. once the load accounting drifted from what the workers
+    /// actually serve. Only this router is reset: nothing is published to the replicas, which
+    /// keep their own accounting. Returns the ids of the forgotten requests.
+    pub fn reset(&self) -> Vec<RequestId> {
+        let forgotten: Vec<RequestId> = self
+            .request_to_worker
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        self.request_to_worker.clear();
+        self.tracked_order.lock().unwrap().clear();
+        for entry in self.senders.iter() {
+            if let Err(e) = entry.value().send(UpdateSequences::Reset) {
+                tracing::error!(
+                    "Failed to send reset command to worker {:?}: {}",
+                    entry.key(),
+                    e
+                );
+            }
+        }
+        self.metrics.tracked_requests.set(0);
+        forgotten
+    }
+
+    /// The worker a tracked request is assigned to
+    pub fn worker_of(&self, request_id: &RequestId) -> Option<WorkerWithDpRank> {
+        self.request_to_worker.get(request_id).map(|entry| *entry)
+    }
+
+    /// Evict the oldest tracked requests while above `max_tracked_requests`, and refresh the
+    /// tracked requests gauge.
+    fn enforce_max_tracked_requests(&self) {
+        if let Some(max_tracked_requests) = self.max_tracked_requests {
+            let mut tracked_order = self.tracked_order.lock().unwrap();
+            while self.request_to_worker.len() > max_tracked_requests {
+                let Some(request_id) = tracked_order.pop_front() else {
+                    break;
+                };
+                let Some((_, worker)) = self.request_to_worker.remove(&request_id) else {
+                    // Already freed
+                    continue;
+                };
+                tracing::warn!(
+                    "Tracked requests exceed the cap of {max_tracked_requests}, evicting oldest \
+                     request {request_id} on worker {worker:?} (it was likely never freed)"
+                );
+                if let Some(sender) = self.senders.get(&worker) {
+                    let _ = sender.send(UpdateSequences::Free { request_id });
+                }
+                self.metrics.tracked_requests_evicted.inc();
+            }
+        }
+
+        self.metrics
+            .tracked_requests
+            .set(self.request_to_worker.len() as i64);
+    }
+
     /// Helper method to start a worker task
     fn start_worker(
         block_size: usize,
@@ -442,6 +680,12 @@ impl ActiveSequencesMultiWorker {
                                     let active_tokens = active_sequences.active_tokens();
                                     let _ = resp_tx.send(active_tokens);
                                 }
+                                UpdateSequences::Export { worker, resp_tx } => {
+                                    let _ = resp_tx.send(active_sequences.export_requests(worker));
+                                }
+                                UpdateSequences::Reset => {
+                                    active_sequences = ActiveSequences::new(block_size);
+                                }
                                 UpdateSequences::Shutdown => {
                                     break;
                                 }
@@ -468,6 +712,8 @@ impl ActiveSequencesMultiWorker {
             DashMap<WorkerWithDpRank, tokio::sync::mpsc::UnboundedSender<UpdateSequences>>,
         >,
         request_to_worker: Arc<DashMap<RequestId, WorkerWithDpRank>>,
+        tracked_order: Arc<Mutex<VecDeque<RequestId>>>,
+        metrics: Arc<ActiveSequencesMetrics>,
         component: Component,
         router_id: Uuid,
         cancel_token: CancellationToken,
@@ -505,6 +751,12 @@ impl ActiveSequencesMultiWorker {
                             overlap,
                         } => {
                             request_to_worker.insert(event.request_id.clone(), event.worker);
+                            push_tracked(
+                                &tracked_order,
+                                &request_to_worker,
+                                event.request_id.clone(),
+                            );
+                            metrics.tracked_requests.set(request_to_worker.len() as i64);
 
                             if let Some(sender) = senders.get(&event.worker) {
                                 // For replicated events, we create a dummy response channel since we don't need to handle expired requests
@@ -531,6 +783,7 @@ impl ActiveSequencesMultiWorker {
                                     request_id: event.request_id.clone(),
                                 });
                             }
+                            metrics.tracked_requests.set(request_to_worker.len() as i64);
                         }
                         ActiveSequenceEventData::MarkPrefillCompleted => {
                             if let Some(worker) = request_to_worker.get(&event.request_id)
@@ -591,6 +844,9 @@ impl ActiveSequencesMultiWorker {
             self.request_to_worker
                 .retain(|_request_id, mapped_worker| mapped_worker != worker);
         }
+        self.metrics
+            .tracked_requests
+            .set(self.request_to_worker.len() as i64);
 
         // Add new workers
         for worker in &workers_to_add {
@@ -617,6 +873,15 @@ impl ActiveSequencesMultiWorker {
             return Err(anyhow::anyhow!("Worker {:?} not found", worker));
         }
 
+        // Adding an active id again would double-count its blocks and tokens
+        if let Some(existing) = self.worker_of(&request_id) {
+            return Err(SequenceError::DuplicateRequestId {
+                request_id,
+                worker: existing,
+            }
+            .into());
+        }
+
         // Create response channel
         let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
 
@@ -639,6 +904,11 @@ impl ActiveSequencesMultiWorker {
 
         // Update local state with full WorkerWithDpRank
         self.request_to_worker.insert(request_id.clone(), worker);
+        push_tracked(
+            &self.tracked_order,
+            &self.request_to_worker,
+            request_id.clone(),
+        );
 
         self.senders
             .get(&worker)
@@ -662,6 +932,8 @@ impl ActiveSequencesMultiWorker {
             self.request_to_worker.remove(expired_id);
         }
 
+        self.enforce_max_tracked_requests();
+
         Ok(())
     }
 
@@ -695,6 +967,36 @@ impl ActiveSequencesMultiWorker {
             .map_err(|_| anyhow::anyhow!("Failed to send free command to worker"))?;
 
         self.request_to_worker.remove(request_id);
+        self.metrics
+            .tracked_requests
+            .set(self.request_to_worker.len() as i64);
+
+        Ok(())
+    }
+
+    /// Free a reservation which may only be tracked by other router replicas, e.g. one left
+    /// behind by a crashed router: it is freed locally if tracked here, and the free is published
+    /// to the replicas when replica sync is enabled.
+    pub async fn free_orphaned(
+        &self,
+        request_id: &RequestId,
+        worker: WorkerWithDpRank,
+    ) -> Result<()> {
+        if self.request_to_worker.contains_key(request_id) {
+            return self.free(request_id).await;
+        }
+
+        if self.replica_sync {
+            let event = ActiveSequenceEvent {
+                request_id: request_id.clone(),
+                worker,
+                data: ActiveSequenceEventData::Free,
+                router_id: self.router_id,
+            };
+            self.component
+                .publish(ACTIVE_SEQUENCES_SUBJECT, &event)
+                .await?;
+        }
 
         Ok(())
     }
@@ -878,6 +1180,116 @@ impl ActiveSequencesMultiWorker {
         self.query_workers(None, |_, resp_tx| UpdateSequences::ActiveTokens { resp_tx })
             .await
     }
+
+    /// Snapshot every tracked request with its worker, blocks and outstanding prefill, for
+    /// [`Self::import_active_state`] on another router
+    pub async fn export_active_state(&self) -> ActiveStateSnapshot {
+        let mut receivers = Vec::new();
+        for entry in self.senders.iter() {
+            let worker = *entry.key();
+            let (resp_tx, resp_rx) = tokio::sync::oneshot::channel();
+            if let Err(e) = entry
+                .value()
+                .send(UpdateSequences::Export { worker, resp_tx })
+            {
+                tracing::error!(
+                    "Failed to send export command to worker {:?}: {}",
+                    worker,
+                    e
+                );
+                continue;
+            }
+            receivers.push((worker, resp_rx));
+        }
+
+        let mut requests = Vec::new();
+        for (worker, receiver) in receivers {
+            match tokio::time::timeout(tokio::time::Duration::from_secs(1), receiver).await {
+                Ok(Ok(worker_requests)) => requests.extend(worker_requests),
+                Ok(Err(_)) => {
+                    tracing::error!("Worker {:?} dropped response channel", worker);
+                }
+                Err(_) => {
+                    tracing::error!("Timeout waiting for response from worker {:?}", worker);
+                }
+            }
+        }
+        requests.sort_unstable_by(|a, b| a.request_id.cmp(&b.request_id));
+
+        ActiveStateSnapshot {
+            block_size: self.block_size,
+            exported_at: chrono::Utc::now(),
+            requests,
+        }
+    }
+
+    /// Track the requests of a snapshot exported by another router, so that load accounting
+    /// continues across a router handoff. Imported requests are published to the replicas like
+    /// new ones. Requests already tracked or on unknown workers are skipped.
+    pub async fn import_active_state(&self, snapshot: ActiveStateSnapshot) -> Result<ImportReport> {
+        if snapshot.block_size != self.block_size {
+            anyhow::bail!(
+                "Cannot import active state with block size {} into a router with block size {}",
+                snapshot.block_size,
+                self.block_size
+            );
+        }
+
+        let mut report = ImportReport::default();
+        for request in snapshot.requests {
+            if self.request_to_worker.contains_key(&request.request_id) {
+                report.already_tracked += 1;
+                continue;
+            }
+            if !self.senders.contains_key(&request.worker) {
+                tracing::warn!(
+                    "Skipping imported request {} on unknown worker {:?}",
+                    request.request_id,
+                    request.worker
+                );
+                report.unknown_worker += 1;
+                continue;
+            }
+
+            // With no overlap, the outstanding prefill is exactly the isl
+            let prefill_tokens = request.prefill_tokens.unwrap_or(0);
+            self.add_request(
+                request.request_id.clone(),
+                request.token_sequence,
+                prefill_tokens,
+                0,
+                request.worker,
+            )
+            .await?;
+            if request.prefill_tokens.is_none() {
+                self.mark_prefill_completed(&request.request_id).await?;
+            }
+            report.imported += 1;
+        }
+
+        tracing::info!(
+            "Imported active state exported at {}: {} requests imported, {} already tracked, {} on unknown workers",
+            snapshot.exported_at,
+            report.imported,
+            report.already_tracked,
+            report.unknown_worker
+        );
+        Ok(report)
+    }
+}
+
+/// Append a newly tracked request to `tracked_order`, dropping the ids of the freed requests
+/// once they dominate the queue, so that it stays bounded whether or not a cap is set
+fn push_tracked(
+    tracked_order: &Mutex<VecDeque<RequestId>>,
+    request_to_worker: &DashMap<RequestId, WorkerWithDpRank>,
+    request_id: RequestId,
+) {
+    let mut tracked_order = tracked_order.lock().unwrap();
+    tracked_order.push_back(request_id);
+    if tracked_order.len() > 2 * request_to_worker.len() + 1024 {
+        tracked_order.retain(|request_id| request_to_worker.contains_key(request_id));
+    }
 }
 
 impl Drop for ActiveSequencesMultiWorker {
@@ -925,6 +1337,38 @@ mod tests {
         assert_eq!(seq_manager.active_tokens(), 0);
     }
 
+    #[test]
+    fn test_potential_load_with_and_without_sequence() {
+        let mut seq_manager = ActiveSequences::new(4);
+        seq_manager.add_request("request_1".to_string(), Some(vec![1, 2, 3]), 12, 0);
+
+        // Known blocks only count once
+        assert_eq!(
+            seq_manager.potential_blocks_and_tokens(Some(&[1, 2, 9]), 12, 0),
+            (4, 24)
+        );
+        // Without hashes every full block is new, as for a sequence sharing none
+        assert_eq!(
+            seq_manager.potential_blocks_and_tokens(None, 12, 0),
+            seq_manager.potential_blocks_and_tokens(Some(&[7, 8, 9]), 12, 0)
+        );
+        assert_eq!(
+            seq_manager.potential_blocks_and_tokens(None, 14, 0),
+            (6, 26)
+        );
+        // A request shorter than a block adds no block, with or without hashes
+        assert_eq!(
+            seq_manager.potential_blocks_and_tokens(Some(&[]), 3, 0),
+            (3, 15)
+        );
+        assert_eq!(seq_manager.potential_blocks_and_tokens(None, 3, 0), (3, 15));
+        // The cached overlap reduces the prefill tokens either way
+        assert_eq!(
+            seq_manager.potential_blocks_and_tokens(None, 12, 2),
+            (6, 16)
+        );
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_multi_worker_cross_instance_sync() -> Result<()> {
@@ -1234,4 +1678,319 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_max_tracked_requests_evicts_oldest() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_max_tracked_requests")?;
+        let component = namespace
+            .component("sequences")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let mut workers_with_configs = HashMap::new();
+        workers_with_configs.insert(0, None);
+        workers_with_configs.insert(1, None);
+
+        let seq_manager = ActiveSequencesMultiWorker::new(
+            component,
+            4,
+            workers_with_configs,
+            false,
+            Uuid::new_v4().to_string(),
+        )
+        .with_max_tracked_requests(Some(2));
+
+        for i in 0..3 {
+            seq_manager
+                .add_request(
+                    format!("request_{i}"),
+                    None,
+                    12,
+                    0,
+                    WorkerWithDpRank::from_worker_id(i % 2),
+                )
+                .await?;
+        }
+
+        // The oldest request was evicted and its prefill tokens released
+        assert_eq!(seq_manager.num_tracked_requests(), 2);
+        assert_eq!(
+            seq_manager.active_requests(),
+            vec![("request_1".to_string(), 1), ("request_2".to_string(), 0)]
+        );
+        assert!(seq_manager.free(&"request_0".to_string()).await.is_err());
+        let active_tokens = seq_manager.active_tokens().await;
+        assert_eq!(active_tokens[&WorkerWithDpRank::from_worker_id(0)], 12);
+        assert_eq!(active_tokens[&WorkerWithDpRank::from_worker_id(1)], 12);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_tracked_order_stays_bounded_without_cap() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_tracked_order_bounded")?;
+        let component = namespace
+            .component("sequences")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let mut workers_with_configs = HashMap::new();
+        workers_with_configs.insert(0, None);
+
+        let seq_manager = ActiveSequencesMultiWorker::new(
+            component,
+            4,
+            workers_with_configs,
+            false,
+            Uuid::new_v4().to_string(),
+        );
+        let worker = WorkerWithDpRank::from_worker_id(0);
+        seq_manager
+            .add_request("long_lived".to_string(), None, 8, 0, worker)
+            .await?;
+        for i in 0..10_000 {
+            let request_id = format!("request_{i}");
+            seq_manager
+                .add_request(request_id.clone(), None, 8, 0, worker)
+                .await?;
+            seq_manager.free(&request_id).await?;
+        }
+
+        // Only the ids of the requests freed since the last compaction are left
+        let tracked_order_len = seq_manager.tracked_order.lock().unwrap().len();
+        assert!(
+            tracked_order_len <= 2 * seq_manager.num_tracked_requests() + 1025,
+            "tracked order grew to {tracked_order_len}"
+        );
+        assert_eq!(seq_manager.num_tracked_requests(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reset_forgets_tracked_requests() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_reset_sequences")?;
+        let component = namespace
+            .component("sequences")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let mut workers_with_configs = HashMap::new();
+        workers_with_configs.insert(0, None);
+        workers_with_configs.insert(1, None);
+
+        let seq_manager = ActiveSequencesMultiWorker::new(
+            component,
+            4,
+            workers_with_configs,
+            false,
+            Uuid::new_v4().to_string(),
+        );
+        for i in 0..3 {
+            seq_manager
+                .add_request(
+                    format!("request_{i}"),
+                    Some(vec![i as u64, 100]),
+                    8,
+                    0,
+                    WorkerWithDpRank::from_worker_id(i % 2),
+                )
+                .await?;
+        }
+
+        let mut forgotten = seq_manager.reset();
+        forgotten.sort();
+        assert_eq!(forgotten, vec!["request_0", "request_1", "request_2"]);
+        assert_eq!(seq_manager.num_tracked_requests(), 0);
+        assert!(seq_manager.free(&"request_0".to_string()).await.is_err());
+        for (_, blocks) in seq_manager.active_blocks().await {
+            assert_eq!(blocks, 0);
+        }
+        for (_, tokens) in seq_manager.active_tokens().await {
+            assert_eq!(tokens, 0);
+        }
+
+        // Requests are tracked again after the reset
+        seq_manager
+            .add_request(
+                "request_3".to_string(),
+                None,
+                8,
+                0,
+                WorkerWithDpRank::from_worker_id(0),
+            )
+            .await?;
+        assert_eq!(seq_manager.num_tracked_requests(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_add_request_rejects_duplicate_id() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_duplicate_request_id")?;
+        let component = namespace
+            .component("sequences")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let mut workers_with_configs = HashMap::new();
+        workers_with_configs.insert(0, None);
+        workers_with_configs.insert(1, None);
+
+        let seq_manager = ActiveSequencesMultiWorker::new(
+            component,
+            4,
+            workers_with_configs,
+            false,
+            Uuid::new_v4().to_string(),
+        );
+
+        let worker_0 = WorkerWithDpRank::from_worker_id(0);
+        let worker_1 = WorkerWithDpRank::from_worker_id(1);
+        seq_manager
+            .add_request(
+                "request_0".to_string(),
+                Some(vec![0, 1, 2]),
+                12,
+                0,
+                worker_0,
+            )
+            .await?;
+
+        // Re-adding the id, on the same or another worker, is rejected without touching state
+        for worker in [worker_0, worker_1] {
+            let err = seq_manager
+                .add_request("request_0".to_string(), Some(vec![3]), 4, 0, worker)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<SequenceError>(),
+                Some(SequenceError::DuplicateRequestId { worker, .. }) if *worker == worker_0
+            ));
+        }
+
+        assert_eq!(
+            seq_manager.worker_of(&"request_0".to_string()),
+            Some(worker_0)
+        );
+        assert_eq!(seq_manager.num_tracked_requests(), 1);
+        let active_tokens = seq_manager.active_tokens().await;
+        assert_eq!(active_tokens[&worker_0], 12);
+        assert_eq!(active_tokens[&worker_1], 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_export_import_active_state() -> Result<()> {
+        dynamo_runtime::logging::init();
+
+        let runtime = Runtime::from_current()?;
+        let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
+        let namespace = distributed.namespace("test_export_import_active_state")?;
+        let component = namespace
+            .component("sequences")?
+            .service_builder()
+            .create()
+            .await?;
+
+        let workers_with_configs: HashMap<i64, Option<ModelRuntimeConfig>> =
+            [(0, None), (1, None)].into_iter().collect();
+        let old_router = ActiveSequencesMultiWorker::new(
+            component.clone(),
+            4,
+            workers_with_configs.clone(),
+            false,
+            Uuid::new_v4().to_string(),
+        );
+        let worker_0 = WorkerWithDpRank::from_worker_id(0);
+        let worker_1 = WorkerWithDpRank::from_worker_id(1);
+        old_router
+            .add_request(
+                "request_0".to_string(),
+                Some(vec![0, 1, 2]),
+                12,
+                1,
+                worker_0,
+            )
+            .await?;
+        old_router
+            .add_request("request_1".to_string(), None, 8, 0, worker_1)
+            .await?;
+        old_router
+            .mark_prefill_completed(&"request_1".to_string())
+            .await?;
+
+        let snapshot = old_router.export_active_state().await;
+        assert_eq!(
+            snapshot.requests,
+            vec![
+                ActiveRequestState {
+                    request_id: "request_0".to_string(),
+                    worker: worker_0,
+                    token_sequence: Some(vec![0, 1, 2]),
+                    prefill_tokens: Some(8),
+                },
+                ActiveRequestState {
+                    request_id: "request_1".to_string(),
+                    worker: worker_1,
+                    token_sequence: None,
+                    prefill_tokens: None,
+                },
+            ]
+        );
+
+        // The snapshot survives serialization, and the new router accounts the same load
+        let snapshot: ActiveStateSnapshot =
+            serde_json::from_str(&serde_json::to_string(&snapshot)?)?;
+        let new_router = ActiveSequencesMultiWorker::new(
+            component,
+            4,
+            workers_with_configs,
+            false,
+            Uuid::new_v4().to_string(),
+        );
+        let report = new_router.import_active_state(snapshot.clone()).await?;
+        assert_eq!(report.imported, 2);
+        assert_eq!(new_router.active_requests(), old_router.active_requests());
+        assert_eq!(
+            new_router.active_blocks().await,
+            old_router.active_blocks().await
+        );
+        assert_eq!(
+            new_router.active_tokens().await,
+            old_router.active_tokens().await
+        );
+
+        // Importing again is a no-op
+        let report = new_router.import_active_state(snapshot).await?;
+        assert_eq!(report.already_tracked, 2);
+
+        Ok(())
+    }
 }
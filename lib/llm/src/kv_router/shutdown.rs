@@ -0,0 +1,189 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Ordered shutdown of the tasks of a KV router.
+//!
+//! The scheduler, the KV event subscriber and the indexer each run in their own task. Cancelling
+//! them all at once lets the subscriber stop while the scheduler still routes on its data, or the
+//! indexer stop in the middle of the final snapshot. Instead, once the root token of the router
+//! is cancelled, [`ShutdownSequence::run`] cancels one token per phase, in order:
+//!
+//! 1. [`ShutdownSequence::intake`]: the scheduler refuses new requests.
+//! 2. The requests already queued are scheduled, for at most [`DRAIN_TIMEOUT`].
+//! 3. [`ShutdownSequence::scheduler`]: the scheduler tasks stop.
+//! 4. [`ShutdownSequence::subscriber`]: the subscriber uploads a final snapshot, if it takes part
+//!    in snapshots, then stops consuming events and removes its consumers. It reports it has
+//!    stopped through [`ShutdownSequence::subscriber_stopped`].
+//! 5. [`ShutdownSequence::indexer`]: once the subscriber stopped, or after
+//!    [`SUBSCRIBER_STOP_TIMEOUT`], the indexer and the remaining tasks stop.
+//!
+//! Each phase starts only after the previous one completed or timed out, so the snapshot always
+//! reflects every event the subscriber acknowledged, and no request is routed once the
+//! subscriber stopped updating the indexer.
+
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+/// Maximum time to wait for the queued requests to be scheduled
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum time to wait for the subscriber to upload its final snapshot and stop
+pub const SUBSCRIBER_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval at which the queue depth is checked while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The cancellation tokens of the phases of the shutdown of a router, see the module docs
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSequence {
+    intake: CancellationToken,
+    scheduler: CancellationToken,
+    subscriber: CancellationToken,
+    subscriber_stopped: CancellationToken,
+    indexer: CancellationToken,
+}
+
+impl ShutdownSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancelled first: the scheduler refuses new requests
+    pub fn intake(&self) -> CancellationToken {
+        self.intake.clone()
+    }
+
+    /// Cancelled once the queued requests are scheduled: the scheduler tasks stop
+    pub fn scheduler(&self) -> CancellationToken {
+        self.scheduler.clone()
+    }
+
+    /// Cancelled after the scheduler: the subscriber takes its final snapshot and stops
+    pub fn subscriber(&self) -> CancellationToken {
+        self.subscriber.clone()
+    }
+
+    /// To be cancelled by the subscriber once it has stopped, or right away by a router without
+    /// subscriber
+    pub fn subscriber_stopped(&self) -> CancellationToken {
+        self.subscriber_stopped.clone()
+    }
+
+    /// Cancelled last, once the subscriber stopped: the indexer and the remaining tasks stop
+    pub fn indexer(&self) -> CancellationToken {
+        self.indexer.clone()
+    }
+
+    /// Wait for `root` to be cancelled, then cancel the phases in order. `queue_depth` reports
+    /// the requests submitted to the scheduler and not scheduled yet.
+    pub async fn run(&self, root: CancellationToken, queue_depth: impl Fn() -> usize) {
+        self.run_with_timeouts(root, queue_depth, DRAIN_TIMEOUT, SUBSCRIBER_STOP_TIMEOUT)
+            .await
+    }
+
+    async fn run_with_timeouts(
+        &self,
+        root: CancellationToken,
+        queue_depth: impl Fn() -> usize,
+        drain_timeout: Duration,
+        subscriber_stop_timeout: Duration,
+    ) {
+        root.cancelled().await;
+
+        tracing::info!("Shutting down the KV router, no longer accepting requests");
+        self.intake.cancel();
+
+        let drain = async {
+            while queue_depth() > 0 {
+                tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        };
+        if tokio::time::timeout(drain_timeout, drain).await.is_err() {
+            tracing::warn!(
+                "{} requests still queued after {drain_timeout:?}, stopping the scheduler anyway",
+                queue_depth()
+            );
+        }
+        self.scheduler.cancel();
+
+        self.subscriber.cancel();
+        if tokio::time::timeout(subscriber_stop_timeout, self.subscriber_stopped.cancelled())
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "KV event subscriber still running after {subscriber_stop_timeout:?}, stopping \
+                 the indexer anyway"
+            );
+        }
+        self.indexer.cancel();
+        tracing::info!("KV router shut down");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    #[tokio::test]
+    async fn test_shutdown_phases_run_in_order() {
+        let root = CancellationToken::new();
+        let shutdown = ShutdownSequence::new();
+        let queued = Arc::new(AtomicUsize::new(2));
+
+        let run = {
+            let shutdown = shutdown.clone();
+            let root = root.clone();
+            let queued = queued.clone();
+            tokio::spawn(async move {
+                shutdown
+                    .run(root, move || queued.load(Ordering::Relaxed))
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        assert!(!shutdown.intake().is_cancelled());
+
+        let phase = |token: CancellationToken| async move {
+            tokio::time::timeout(Duration::from_secs(5), token.cancelled_owned())
+                .await
+                .expect("phase not reached")
+        };
+
+        root.cancel();
+        phase(shutdown.intake()).await;
+
+        // The scheduler keeps running while requests are queued
+        tokio::time::sleep(DRAIN_POLL_INTERVAL * 2).await;
+        assert!(!shutdown.scheduler().is_cancelled());
+        queued.store(0, Ordering::Relaxed);
+        phase(shutdown.subscriber()).await;
+        assert!(shutdown.scheduler().is_cancelled());
+
+        // The indexer outlives the subscriber
+        assert!(!shutdown.indexer().is_cancelled());
+        shutdown.subscriber_stopped().cancel();
+        run.await.unwrap();
+        assert!(shutdown.indexer().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_phases_time_out() {
+        let root = CancellationToken::new();
+        let shutdown = ShutdownSequence::new();
+        root.cancel();
+
+        // Neither the queue drains nor the subscriber reports it stopped
+        let timeout = Duration::from_millis(20);
+        shutdown
+            .run_with_timeouts(root, || 1, timeout, timeout)
+            .await;
+        assert!(shutdown.scheduler().is_cancelled());
+        assert!(shutdown.indexer().is_cancelled());
+    }
+}
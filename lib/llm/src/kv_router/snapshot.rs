@@ -0,0 +1,132 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Encoding of the radix tree snapshots uploaded to the object store.
+//!
+//! A snapshot is wrapped in a small envelope: [`SNAPSHOT_MAGIC`], the schema version and the
+//! [`SnapshotFormat`] of the payload, so that a router reads snapshots written in any format by
+//! its replicas. Snapshots written before the envelope existed have no magic and are bincode.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::indexer::RouterEvent;
+
+/// First bytes of every snapshot written with an envelope
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"DYNSNAP\0";
+
+/// Version of the envelope layout, following the magic
+pub const SNAPSHOT_SCHEMA_VERSION: u8 = 1;
+
+/// Serialization format of the events of a snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotFormat {
+    /// Human-readable, e.g. to inspect a snapshot while debugging
+    #[default]
+    Json,
+    /// Compact binary, noticeably smaller and faster to transfer for large radix trees
+    MessagePack,
+}
+
+impl SnapshotFormat {
+    fn tag(self) -> u8 {
+        match self {
+            SnapshotFormat::Json => 0,
+            SnapshotFormat::MessagePack => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(SnapshotFormat::Json),
+            1 => Ok(SnapshotFormat::MessagePack),
+            _ => anyhow::bail!("Unknown snapshot format {tag}"),
+        }
+    }
+}
+
+/// Serialize the events of a snapshot in `format`, within the envelope
+pub fn encode_snapshot(events: &[RouterEvent], format: SnapshotFormat) -> Result<Vec<u8>> {
+    let mut bytes = Vec::from(SNAPSHOT_MAGIC.as_slice());
+    bytes.push(SNAPSHOT_SCHEMA_VERSION);
+    bytes.push(format.tag());
+    match format {
+        SnapshotFormat::Json => serde_json::to_writer(&mut bytes, events)?,
+        SnapshotFormat::MessagePack => rmp_serde::encode::write(&mut bytes, events)?,
+    }
+    Ok(bytes)
+}
+
+/// Deserialize the events of a snapshot written by [`encode_snapshot`], whatever its format.
+/// Returns None for a snapshot without envelope, written by an older router in bincode.
+pub fn decode_snapshot(bytes: &[u8]) -> Result<Option<Vec<RouterEvent>>> {
+    let Some(rest) = bytes.strip_prefix(SNAPSHOT_MAGIC.as_slice()) else {
+        return Ok(None);
+    };
+    let [version, tag, payload @ ..] = rest else {
+        anyhow::bail!("Truncated snapshot envelope");
+    };
+    if *version != SNAPSHOT_SCHEMA_VERSION {
+        anyhow::bail!(
+            "Unsupported snapshot schema version {version}, expected {SNAPSHOT_SCHEMA_VERSION}"
+        );
+    }
+    let events = match SnapshotFormat::from_tag(*tag)? {
+        SnapshotFormat::Json => serde_json::from_slice(payload)?,
+        SnapshotFormat::MessagePack => rmp_serde::from_slice(payload)?,
+    };
+    Ok(Some(events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::protocols::{
+        ExternalSequenceBlockHash, KvCacheEvent, KvCacheEventData, KvCacheStoreData,
+        KvCacheStoredBlockData, LocalBlockHash,
+    };
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let events: Vec<RouterEvent> = (0..16)
+            .map(|i| {
+                RouterEvent::new(
+                    i % 4,
+                    KvCacheEvent {
+                        event_id: i as u64,
+                        data: KvCacheEventData::Stored(KvCacheStoreData {
+                            parent_hash: (i > 0).then(|| ExternalSequenceBlockHash(i as u64 - 1)),
+                            blocks: vec![KvCacheStoredBlockData {
+                                block_hash: ExternalSequenceBlockHash(i as u64),
+                                tokens_hash: LocalBlockHash(u64::MAX - i as u64),
+                            }],
+                        }),
+                        dp_rank: 0,
+                    },
+                )
+                .with_hasher_id("xxh3")
+            })
+            .collect();
+
+        let json = encode_snapshot(&events, SnapshotFormat::Json).unwrap();
+        let msgpack = encode_snapshot(&events, SnapshotFormat::MessagePack).unwrap();
+        assert!(msgpack.len() < json.len());
+
+        for bytes in [json, msgpack] {
+            let decoded = decode_snapshot(&bytes).unwrap().unwrap();
+            assert_eq!(decoded.len(), events.len());
+            assert_eq!(
+                serde_json::to_value(&decoded).unwrap(),
+                serde_json::to_value(&events).unwrap()
+            );
+        }
+
+        // Snapshots without envelope are left to the legacy decoder
+        assert!(decode_snapshot(b"[]").unwrap().is_none());
+
+        let mut unknown = encode_snapshot(&events, SnapshotFormat::Json).unwrap();
+        unknown[SNAPSHOT_MAGIC.len()] = SNAPSHOT_SCHEMA_VERSION + 1;
+        assert!(decode_snapshot(&unknown).is_err());
+    }
+}
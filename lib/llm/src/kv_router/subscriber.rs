@@ -3,11 +3,20 @@
 
 //! Background processes for the KV Router including event consumption and snapshot uploads.
 
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::Result;
+use derive_builder::Builder;
 use dynamo_runtime::{
     component::Component,
+    metrics::{MetricsRegistry, prometheus_names::kvrouter},
     prelude::*,
     traits::events::EventPublisher,
     transports::{
@@ -15,6 +24,8 @@ use dynamo_runtime::{
         nats::{NatsQueue, Slug},
     },
 };
+use prometheus::{CounterVec, GaugeVec, IntCounterVec, Opts};
+use serde::Serialize;
 use tokio::sync::{mpsc, oneshot};
 use tokio_util::sync::CancellationToken;
 
@@ -22,12 +33,624 @@ use crate::{
     discovery::KV_ROUTERS_ROOT_PATH,
     kv_router::{
         KV_EVENT_SUBJECT, RADIX_STATE_BUCKET, RADIX_STATE_FILE, ROUTER_CLEANUP_LOCK,
-        ROUTER_SNAPSHOT_LOCK,
+        ROUTER_SNAPSHOT_LOCK, ROUTER_SNAPSHOT_TIMESTAMP,
         indexer::{DumpRequest, GetWorkersRequest, RouterEvent},
         protocols::WorkerId,
+        snapshot::{SnapshotFormat, decode_snapshot, encode_snapshot},
     },
 };
 
+/// Name of the JetStream stream carrying KV events for `component`.
+pub fn kv_event_stream_name(component: &Component) -> String {
+    kv_event_stream_name_for(component, KV_EVENT_SUBJECT)
+}
+
+/// Name of the JetStream stream carrying the KV events published on `event_subject` of
+/// `component`, for workers which do not publish on [`KV_EVENT_SUBJECT`].
+pub fn kv_event_stream_name_for(component: &Component, event_subject: &str) -> String {
+    Slug::slugify(&format!("{}.{event_subject}", component.subject()))
+        .to_string()
+        .replace("_", "-")
+}
+
+/// Name of the object store bucket holding radix tree snapshots for `component`.
+pub fn radix_state_bucket_name(component: &Component) -> String {
+    Slug::slugify(&format!("{}-{RADIX_STATE_BUCKET}", component.subject()))
+        .to_string()
+        .replace("_", "-")
+}
+
+/// The NATS and etcd resources tied to a single router replica, for debugging.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouterIdentity {
+    /// UUID of the router, also the last segment of its etcd entry under [`KV_ROUTERS_ROOT_PATH`]
+    pub router_uuid: String,
+    /// Name of the durable NATS consumer used by the router (same as the router UUID)
+    pub consumer_name: String,
+    /// JetStream stream the router consumes KV events from
+    pub stream_name: String,
+    /// Object store bucket holding radix tree snapshots
+    pub bucket_name: String,
+}
+
+impl RouterIdentity {
+    pub fn new(component: &Component, router_uuid: &str) -> Self {
+        Self {
+            router_uuid: router_uuid.to_string(),
+            consumer_name: router_uuid.to_string(),
+            stream_name: kv_event_stream_name(component),
+            bucket_name: radix_state_bucket_name(component),
+        }
+    }
+}
+
+/// Active NATS consumers on the KV event stream compared with active routers registered in etcd.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsumerReport {
+    /// Consumer names found on the KV event stream
+    pub consumers: Vec<String>,
+    /// Router UUIDs registered in etcd
+    pub routers: Vec<String>,
+    /// Consumers with no matching router; these are deleted by the orphan cleanup
+    pub orphaned_consumers: Vec<String>,
+    /// Routers with no matching consumer
+    pub routers_without_consumer: Vec<String>,
+}
+
+impl ConsumerReport {
+    fn new(consumers: Vec<String>, routers: HashSet<String>) -> Self {
+        let consumer_set: HashSet<&String> = consumers.iter().collect();
+        let mut orphaned_consumers: Vec<String> = consumers
+            .iter()
+            .filter(|consumer| !routers.contains(*consumer))
+            .cloned()
+            .collect();
+        let mut routers_without_consumer: Vec<String> = routers
+            .iter()
+            .filter(|router| !consumer_set.contains(router))
+            .cloned()
+            .collect();
+        let mut consumers = consumers;
+        let mut routers: Vec<String> = routers.into_iter().collect();
+        consumers.sort();
+        routers.sort();
+        orphaned_consumers.sort();
+        routers_without_consumer.sort();
+        Self {
+            consumers,
+            routers,
+            orphaned_consumers,
+            routers_without_consumer,
+        }
+    }
+}
+
+/// List the consumers on the KV event stream of `component` against the routers registered in
+/// etcd, i.e. what the orphan cleanup computes, without deleting anything.
+pub async fn consumer_report(component: &Component) -> Result<ConsumerReport> {
+    let etcd_client = component
+        .drt()
+        .etcd_client()
+        .ok_or_else(|| anyhow::anyhow!("etcd client not available"))?;
+    let consumers = component
+        .drt()
+        .nats_client()
+        .list_consumers(&kv_event_stream_name(component))
+        .await?;
+    let routers = active_router_uuids(&etcd_client, component).await?;
+    Ok(ConsumerReport::new(consumers, routers))
+}
+
+/// UUIDs of the routers registered in etcd for `component`.
+pub(crate) async fn active_router_uuids(
+    etcd_client: &EtcdClient,
+    component: &Component,
+) -> Result<HashSet<String>> {
+    let router_prefix = format!("{}/{}/", KV_ROUTERS_ROOT_PATH, component.path());
+    let router_entries = etcd_client.kv_get_prefix(&router_prefix).await?;
+    Ok(router_entries
+        .iter()
+        .filter_map(|kv| {
+            String::from_utf8_lossy(kv.key())
+                .split('/')
+                .next_back()
+                .map(str::to_string)
+        })
+        .collect())
+}
+
+/// etcd key registering the router `router_uuid` of `component`
+fn router_key(component: &Component, router_uuid: &str) -> String {
+    format!("{KV_ROUTERS_ROOT_PATH}/{}/{router_uuid}", component.path())
+}
+
+/// Register `router_uuid` in etcd, under the primary lease so that the claim of a router which
+/// dies expires with it. Two replicas sharing a UUID would share a NATS consumer and corrupt
+/// each other's position in the event stream, so a UUID already claimed by a live router is
+/// refused.
+pub(crate) async fn claim_router_uuid(
+    etcd_client: &EtcdClient,
+    component: &Component,
+    router_uuid: &str,
+    value: Vec<u8>,
+) -> Result<()> {
+    let key = router_key(component, router_uuid);
+    if let Err(e) = etcd_client.kv_create(&key, value, None).await {
+        if !etcd_client.kv_get(key.as_str(), None).await?.is_empty() {
+            anyhow::bail!(
+                "Router UUID {router_uuid} is already claimed by a live router of component {}. \
+                 Every router replica must use a unique UUID, otherwise replicas share a KV event \
+                 consumer and corrupt each other's consumption position.",
+                component.path()
+            );
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Remove the claim of `router_uuid` made by [`claim_router_uuid`], so the UUID may be reused
+/// without waiting for the lease to expire
+pub(crate) async fn release_router_uuid(
+    etcd_client: &EtcdClient,
+    component: &Component,
+    router_uuid: &str,
+) -> Result<()> {
+    etcd_client
+        .kv_delete(router_key(component, router_uuid), None)
+        .await?;
+    Ok(())
+}
+
+/// Dequeue timeout used right after a poll returned an event.
+const MIN_DEQUEUE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Upper bound the dequeue timeout backs off to while the event stream is idle.
+const MAX_DEQUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Adapts the NATS dequeue timeout to the event rate: short while events keep arriving so the
+/// fetch is re-issued promptly, doubling on every empty poll up to a maximum while idle so the
+/// subscriber does not spin issuing fetches that return nothing.
+#[derive(Debug, Clone, Copy)]
+struct DequeueTimeout {
+    current: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl DequeueTimeout {
+    fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            current: min,
+            min,
+            max: max.max(min),
+        }
+    }
+
+    fn get(&self) -> Duration {
+        self.current
+    }
+
+    /// Record the outcome of a poll: whether it returned data
+    fn record(&mut self, got_data: bool) {
+        self.current = if got_data {
+            self.min
+        } else {
+            (self.current * 2).min(self.max)
+        };
+    }
+}
+
+/// Consecutive dequeue failures after which the NATS connection is re-established.
+const DEQUEUE_ERRORS_BEFORE_RECONNECT: u32 = 3;
+
+/// Upper bound of the backoff between failed dequeues.
+const MAX_DEQUEUE_ERROR_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Backoff after the `consecutive_errors`-th failed dequeue in a row: 100ms, doubling up to
+/// [`MAX_DEQUEUE_ERROR_BACKOFF`].
+fn dequeue_error_backoff(consecutive_errors: u32) -> Duration {
+    let exponent = consecutive_errors.saturating_sub(1).min(16);
+    (Duration::from_millis(100) * 2u32.pow(exponent)).min(MAX_DEQUEUE_ERROR_BACKOFF)
+}
+
+/// Minimum interval between two logged dequeue failures; the failures in between are counted
+/// and reported with the next log.
+const DEQUEUE_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Duration of consecutive dequeue failures after which the outage is reported once as such.
+const DEQUEUE_OUTAGE_ALERT_AFTER: Duration = Duration::from_secs(60);
+
+/// What to log for a failed dequeue, as decided by [`DequeueErrorLog`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DequeueErrorReport {
+    /// Failures not logged since the previous report
+    suppressed: u64,
+    /// Set once per outage, when the failures have lasted [`DEQUEUE_OUTAGE_ALERT_AFTER`]
+    outage: Option<Duration>,
+}
+
+/// Rate limits the logs of failed dequeues, so that a NATS outage does not flood the logs with
+/// the same error every backoff, and escalates a persistent outage to a single alert.
+#[derive(Debug)]
+struct DequeueErrorLog {
+    interval: Duration,
+    alert_after: Duration,
+    failing_since: Option<Instant>,
+    last_logged: Option<Instant>,
+    suppressed: u64,
+    alerted: bool,
+}
+
+impl DequeueErrorLog {
+    fn new(interval: Duration, alert_after: Duration) -> Self {
+        Self {
+            interval,
+            alert_after,
+            failing_since: None,
+            last_logged: None,
+            suppressed: 0,
+            alerted: false,
+        }
+    }
+
+    /// Record a failed dequeue, returning what to log if anything
+    fn report(&mut self, now: Instant) -> Option<DequeueErrorReport> {
+        let failing_since = *self.failing_since.get_or_insert(now);
+        let escalate = !self.alerted && now.duration_since(failing_since) >= self.alert_after;
+        if !escalate
+            && self
+                .last_logged
+                .is_some_and(|logged| now.duration_since(logged) < self.interval)
+        {
+            self.suppressed += 1;
+            return None;
+        }
+        self.alerted |= escalate;
+        self.last_logged = Some(now);
+        Some(DequeueErrorReport {
+            suppressed: std::mem::take(&mut self.suppressed),
+            outage: escalate.then(|| now.duration_since(failing_since)),
+        })
+    }
+
+    /// Log a failed dequeue, unless one was logged less than `interval` ago
+    fn error(&mut self, stream_name: &str, consecutive_errors: u32, error: &anyhow::Error) {
+        let Some(report) = self.report(Instant::now()) else {
+            return;
+        };
+        if let Some(outage) = report.outage {
+            tracing::error!(
+                "KV event consumption from stream {stream_name} has been failing for {:.0}s; \
+                 routing decisions use a stale view of the workers' caches until it recovers: \
+                 {error:?}",
+                outage.as_secs_f64()
+            );
+        } else {
+            tracing::error!(
+                "Failed to dequeue from stream {stream_name} ({consecutive_errors} consecutive \
+                 failures, {} not logged since the last one): {error:?}",
+                report.suppressed
+            );
+        }
+    }
+
+    /// Record a successful dequeue, ending the current outage if any
+    fn recovered(&mut self, stream_name: &str) {
+        let Some(failing_since) = self.failing_since.take() else {
+            return;
+        };
+        tracing::info!(
+            "KV event consumption from stream {stream_name} recovered after failing for {:.1}s \
+             ({} failures not logged)",
+            failing_since.elapsed().as_secs_f64(),
+            self.suppressed
+        );
+        self.last_logged = None;
+        self.suppressed = 0;
+        self.alerted = false;
+    }
+}
+
+/// Window over which [`WorkerEventStats::events_per_sec`] is measured.
+const EVENT_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// KV event health of a single worker, as seen by the subscriber.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerEventStats {
+    pub worker_id: WorkerId,
+    /// Events received from the worker since it was first seen
+    pub total_events: u64,
+    /// Event rate over the last complete window of [`EVENT_RATE_WINDOW`]
+    pub events_per_sec: f64,
+    /// Seconds since the last event from the worker
+    pub secs_since_last_event: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct WorkerEventCount {
+    total: u64,
+    window_start: Instant,
+    window_count: u64,
+    events_per_sec: f64,
+    last_event: Instant,
+}
+
+impl WorkerEventCount {
+    fn new(now: Instant) -> Self {
+        Self {
+            total: 0,
+            window_start: now,
+            window_count: 0,
+            events_per_sec: 0.0,
+            last_event: now,
+        }
+    }
+
+    fn roll_window(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= EVENT_RATE_WINDOW {
+            self.events_per_sec = self.window_count as f64 / elapsed.as_secs_f64();
+            self.window_start = now;
+            self.window_count = 0;
+        }
+    }
+}
+
+/// Per-worker counters of the KV events consumed by the subscriber, used to spot workers which
+/// flood the event stream or have gone silent. Cheap to clone; clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct WorkerEventCounters {
+    counts: Arc<Mutex<HashMap<WorkerId, WorkerEventCount>>>,
+}
+
+impl WorkerEventCounters {
+    fn record_at(&self, worker_id: WorkerId, now: Instant) {
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts
+            .entry(worker_id)
+            .or_insert_with(|| WorkerEventCount::new(now));
+        count.roll_window(now);
+        count.total += 1;
+        count.window_count += 1;
+        count.last_event = now;
+    }
+
+    fn remove_worker(&self, worker_id: WorkerId) {
+        self.counts.lock().unwrap().remove(&worker_id);
+    }
+
+    /// Event counts and rates of every worker seen so far, sorted by worker id
+    pub fn snapshot(&self) -> Vec<WorkerEventStats> {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> Vec<WorkerEventStats> {
+        let mut counts = self.counts.lock().unwrap();
+        let mut stats: Vec<WorkerEventStats> = counts
+            .iter_mut()
+            .map(|(worker_id, count)| {
+                count.roll_window(now);
+                WorkerEventStats {
+                    worker_id: *worker_id,
+                    total_events: count.total,
+                    events_per_sec: count.events_per_sec,
+                    secs_since_last_event: now.duration_since(count.last_event).as_secs_f64(),
+                }
+            })
+            .collect();
+        stats.sort_by_key(|stats| stats.worker_id);
+        stats
+    }
+}
+
+/// Smoothing factor of the stream growth rate tracked by [`AdaptiveSnapshotThreshold`].
+const STREAM_GROWTH_EMA_ALPHA: f64 = 0.3;
+
+/// Lowest fraction of the configured threshold the adaptive threshold may drop to, so that a
+/// burst cannot make the routers snapshot on every check.
+const MIN_ADAPTIVE_THRESHOLD_FRACTION: f64 = 0.1;
+
+/// Snapshot threshold which follows the growth rate of the KV event stream.
+///
+/// A snapshot is triggered once the stream, growing at its recent rate, would exceed the
+/// configured threshold within the horizon: the effective threshold is the configured one minus
+/// the messages expected over the horizon. Quiet streams snapshot at the configured threshold,
+/// bursts snapshot proportionally sooner, which bounds the stream size.
+#[derive(Debug, Clone)]
+struct AdaptiveSnapshotThreshold {
+    base: u64,
+    horizon: Duration,
+    /// Smoothed growth rate in messages per second
+    growth_rate: Option<f64>,
+    last_sample: Option<(Instant, u64)>,
+}
+
+impl AdaptiveSnapshotThreshold {
+    fn new(base: u64, horizon: Duration) -> Self {
+        Self {
+            base,
+            horizon,
+            growth_rate: None,
+            last_sample: None,
+        }
+    }
+
+    /// Record the current stream size and return the effective threshold
+    fn observe(&mut self, message_count: u64, now: Instant) -> u64 {
+        if let Some((at, count)) = self.last_sample {
+            let elapsed = now.duration_since(at).as_secs_f64();
+            // A shrinking stream was purged; the drop says nothing about the growth rate
+            if elapsed > 0.0 && message_count >= count {
+                let rate = (message_count - count) as f64 / elapsed;
+                self.growth_rate = Some(match self.growth_rate {
+                    Some(ema) => ema + STREAM_GROWTH_EMA_ALPHA * (rate - ema),
+                    None => rate,
+                });
+            }
+        }
+        self.last_sample = Some((now, message_count));
+        self.threshold()
+    }
+
+    fn threshold(&self) -> u64 {
+        let expected = self.growth_rate.unwrap_or(0.0) * self.horizon.as_secs_f64();
+        let floor = (self.base as f64 * MIN_ADAPTIVE_THRESHOLD_FRACTION).ceil();
+        (self.base as f64 - expected).max(floor) as u64
+    }
+}
+
+/// The snapshot threshold currently applied by the subscriber, which differs from the configured
+/// one in adaptive mode. Cheap to clone; clones share the same value.
+#[derive(Debug, Clone)]
+pub struct EffectiveSnapshotThreshold(Arc<AtomicU64>);
+
+impl Default for EffectiveSnapshotThreshold {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(u64::MAX)))
+    }
+}
+
+impl EffectiveSnapshotThreshold {
+    /// The threshold applied at the last stream check, None if snapshots are disabled
+    pub fn get(&self) -> Option<u64> {
+        Some(self.0.load(Ordering::Relaxed)).filter(|threshold| *threshold != u64::MAX)
+    }
+
+    fn set(&self, threshold: u64) {
+        self.0.store(threshold, Ordering::Relaxed);
+    }
+}
+
+/// `lock` label of the snapshot write lock, taken to purge the stream and upload a snapshot
+pub const LOCK_SNAPSHOT: &str = "snapshot";
+/// `lock` label of the snapshot read lock, taken to download the snapshot on startup
+pub const LOCK_SNAPSHOT_READ: &str = "snapshot_read";
+/// `lock` label of the cleanup lock, taken to delete the consumer of a departed router
+pub const LOCK_CLEANUP: &str = "cleanup";
+
+/// Acquisition and hold metrics of the distributed locks the router replicas contend on. A lock
+/// which is mostly unavailable while this router barely holds it points at a slow or stuck
+/// holder on another replica.
+pub struct RouterLockMetrics {
+    /// Acquisition attempts by lock and result (`acquired` or `unavailable`)
+    pub attempts: IntCounterVec,
+    /// Total time the lock was held by this router, by lock
+    pub hold_seconds: CounterVec,
+    /// Duration of the last hold of the lock by this router, by lock
+    pub last_hold_seconds: GaugeVec,
+}
+
+static ROUTER_LOCK_METRICS: OnceLock<Arc<RouterLockMetrics>> = OnceLock::new();
+
+impl RouterLockMetrics {
+    /// Creates the metrics from a Component, memoizing the result in ROUTER_LOCK_METRICS to avoid
+    /// duplicate registration issues.
+    pub fn from_component(component: &Component) -> Arc<Self> {
+        ROUTER_LOCK_METRICS
+            .get_or_init(|| {
+                let metrics = component
+                    .create_intcountervec(
+                        kvrouter::LOCK_ACQUISITION_ATTEMPTS,
+                        "Number of attempts to acquire a router lock, by lock and result",
+                        &["lock", "result"],
+                        &[],
+                    )
+                    .and_then(|attempts| {
+                        Ok(Self {
+                            attempts,
+                            hold_seconds: component.create_countervec(
+                                kvrouter::LOCK_HOLD_SECONDS,
+                                "Total time a router lock was held by this router",
+                                &["lock"],
+                                &[],
+                            )?,
+                            last_hold_seconds: component.create_gaugevec(
+                                kvrouter::LOCK_LAST_HOLD_SECONDS,
+                                "Duration of the last hold of a router lock by this router",
+                                &["lock"],
+                                &[],
+                            )?,
+                        })
+                    });
+                match metrics {
+                    Ok(metrics) => Arc::new(metrics),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to create router lock metrics from component: {e}. Using unregistered metrics as fallback."
+                        );
+                        Arc::new(Self::new_unregistered())
+                    }
+                }
+            })
+            .clone()
+    }
+
+    /// Creates metrics which are not registered with a MetricsRegistry, for tests or as a
+    /// fallback.
+    pub fn new_unregistered() -> Self {
+        Self {
+            attempts: IntCounterVec::new(
+                Opts::new(
+                    kvrouter::LOCK_ACQUISITION_ATTEMPTS,
+                    "Number of attempts to acquire a router lock, by lock and result",
+                ),
+                &["lock", "result"],
+            )
+            .unwrap(),
+            hold_seconds: CounterVec::new(
+                Opts::new(
+                    kvrouter::LOCK_HOLD_SECONDS,
+                    "Total time a router lock was held by this router",
+                ),
+                &["lock"],
+            )
+            .unwrap(),
+            last_hold_seconds: GaugeVec::new(
+                Opts::new(
+                    kvrouter::LOCK_LAST_HOLD_SECONDS,
+                    "Duration of the last hold of a router lock by this router",
+                ),
+                &["lock"],
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Record an acquisition attempt, returning a hold which records its duration when dropped
+    /// if the lock was acquired
+    fn attempt(&self, lock: &'static str, acquired: bool) -> Option<LockHold<'_>> {
+        let result = if acquired { "acquired" } else { "unavailable" };
+        self.attempts.with_label_values(&[lock, result]).inc();
+        acquired.then(|| LockHold {
+            metrics: self,
+            lock,
+            start: Instant::now(),
+        })
+    }
+}
+
+/// A lock held by this router, recording the hold duration when dropped
+struct LockHold<'a> {
+    metrics: &'a RouterLockMetrics,
+    lock: &'static str,
+    start: Instant,
+}
+
+impl Drop for LockHold<'_> {
+    fn drop(&mut self) {
+        let held = self.start.elapsed().as_secs_f64();
+        self.metrics
+            .hold_seconds
+            .with_label_values(&[self.lock])
+            .inc_by(held);
+        self.metrics
+            .last_hold_seconds
+            .with_label_values(&[self.lock])
+            .set(held);
+    }
+}
+
 /// Resources required for snapshot operations
 #[derive(Clone)]
 struct SnapshotResources {
@@ -37,6 +660,13 @@ struct SnapshotResources {
     instances_rx: tokio::sync::watch::Receiver<Vec<dynamo_runtime::component::Instance>>,
     get_workers_tx: mpsc::Sender<GetWorkersRequest>,
     snapshot_tx: mpsc::Sender<DumpRequest>,
+    /// etcd key holding the unix timestamp (ms) of the last successful snapshot in the cluster
+    timestamp_key: String,
+    /// Purge requests to the consumers of the extra event subjects, whose events the snapshot
+    /// covers as well
+    extra_purge_txs: Vec<mpsc::Sender<PurgeRequest>>,
+    lock_metrics: Arc<RouterLockMetrics>,
+    snapshot_format: SnapshotFormat,
 }
 
 impl SnapshotResources {
@@ -48,7 +678,11 @@ impl SnapshotResources {
         remove_worker_tx: &mpsc::Sender<WorkerId>,
     ) -> anyhow::Result<()> {
         // Try to acquire write lock (non-blocking)
-        let Some(_write_guard) = self.rwlock.try_write_lock(etcd_client).await else {
+        let write_guard = self.rwlock.try_write_lock(etcd_client).await;
+        let _hold = self
+            .lock_metrics
+            .attempt(LOCK_SNAPSHOT, write_guard.is_some());
+        let Some(_write_guard) = write_guard else {
             tracing::debug!(
                 "Could not acquire write lock for snapshot (readers active or lock held)"
             );
@@ -97,8 +731,18 @@ impl SnapshotResources {
             }
         }
 
-        // First, purge acknowledged messages from the stream
+        // First, purge acknowledged messages from the streams
         nats_queue.purge_acknowledged().await?;
+        for purge_tx in &self.extra_purge_txs {
+            let (resp_tx, resp_rx) = oneshot::channel();
+            purge_tx
+                .send(PurgeRequest { resp: resp_tx })
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to send purge request: {e:?}"))?;
+            resp_rx
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to receive purge response: {e:?}"))??;
+        }
 
         // Now request a snapshot from the indexer (which reflects the post-purge state)
         let (resp_tx, resp_rx) = oneshot::channel();
@@ -121,39 +765,205 @@ impl SnapshotResources {
             self.bucket_name
         ))?;
 
+        let snapshot = encode_snapshot(&events, self.snapshot_format)?;
+        let snapshot_bytes = snapshot.len();
         self.nats_client
-            .object_store_upload_data(&events, &url)
+            .object_store_upload_bytes(snapshot, &url)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to upload snapshot: {e:?}"))?;
 
         tracing::info!(
-            "Successfully performed snapshot of radix tree with {} events to bucket {} in {}ms",
+            "Successfully performed snapshot of radix tree with {} events ({snapshot_bytes} bytes, {:?}) to bucket {} in {}ms",
             events.len(),
+            self.snapshot_format,
             self.bucket_name,
             start_time.elapsed().as_millis()
         );
 
+        // Record the success so every router replica can tell how stale the snapshot is. Written
+        // without a lease so that it outlives this replica
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        if let Err(e) = etcd_client
+            .kv_put(&self.timestamp_key, now_ms.to_string(), Some(0))
+            .await
+        {
+            tracing::warn!("Failed to record snapshot timestamp in etcd: {e:?}");
+        }
+
         Ok(())
     }
 }
 
+/// Raises an error when no router in the cluster has completed a snapshot within the staleness bound.
+///
+/// The last successful snapshot time is read from etcd, where it is written by whichever router
+/// replica performed the snapshot. If no snapshot was recorded yet, the time this watchdog was
+/// created is used instead.
+struct SnapshotWatchdog {
+    timestamp_key: String,
+    staleness_bound: Duration,
+    started_at: SystemTime,
+    last_alert: Option<SystemTime>,
+}
+
+impl SnapshotWatchdog {
+    fn new(timestamp_key: String, staleness_bound: Duration) -> Self {
+        Self {
+            timestamp_key,
+            staleness_bound,
+            started_at: SystemTime::now(),
+            last_alert: None,
+        }
+    }
+
+    /// Check the snapshot staleness, alerting at most once per staleness bound
+    async fn check(&mut self, etcd_client: &EtcdClient) {
+        let last_success = match etcd_client.kv_get(self.timestamp_key.as_str(), None).await {
+            Ok(kvs) => kvs
+                .first()
+                .and_then(|kv| std::str::from_utf8(kv.value()).ok()?.parse::<u64>().ok())
+                .map(|ms| UNIX_EPOCH + Duration::from_millis(ms)),
+            Err(e) => {
+                tracing::warn!("Failed to read snapshot timestamp from etcd: {e:?}");
+                None
+            }
+        };
+
+        let Some(staleness) = self.alert_staleness(last_success, SystemTime::now()) else {
+            return;
+        };
+        tracing::error!(
+            staleness_secs = staleness.as_secs(),
+            staleness_bound_secs = self.staleness_bound.as_secs(),
+            "No successful KV router snapshot in the cluster within the staleness bound; \
+            the KV event stream will keep growing until a snapshot succeeds"
+        );
+    }
+
+    /// The staleness to alert about at `now`, if the last snapshot is older than the staleness
+    /// bound and no alert was raised within the bound
+    fn alert_staleness(
+        &mut self,
+        last_success: Option<SystemTime>,
+        now: SystemTime,
+    ) -> Option<Duration> {
+        let reference = last_success.unwrap_or(self.started_at);
+        let staleness = now.duration_since(reference).ok()?;
+        if staleness <= self.staleness_bound {
+            return None;
+        }
+        if self.last_alert.is_some_and(|alerted| {
+            now.duration_since(alerted).unwrap_or_default() < self.staleness_bound
+        }) {
+            return None;
+        }
+
+        self.last_alert = Some(now);
+        Some(staleness)
+    }
+}
+
+/// Options of [`start_kv_router_background`]
+#[derive(Clone, Builder)]
+pub struct KvRouterBackgroundConfig {
+    /// UUID of the router, naming its durable consumer of the KV event stream
+    #[builder(setter(into))]
+    pub consumer_uuid: String,
+
+    /// Number of pending messages in the KV event stream above which a snapshot of the radix tree
+    /// is uploaded and the stream purged (default: None, no snapshots)
+    #[builder(default)]
+    pub snapshot_threshold: Option<u32>,
+
+    /// Whether to start from an empty radix tree instead of the latest snapshot
+    #[builder(default)]
+    pub reset_states: bool,
+
+    /// Alert when no snapshot succeeded within this many seconds while the stream is growing
+    #[builder(default)]
+    pub snapshot_staleness_secs: Option<u64>,
+
+    /// When set, the snapshot threshold adapts to the growth rate of the stream: a snapshot is
+    /// taken once the stream would exceed `snapshot_threshold` within this many seconds at its
+    /// recent growth rate (default: None, fixed threshold)
+    #[builder(default)]
+    pub adaptive_snapshot_horizon_secs: Option<f64>,
+
+    /// Format in which snapshots are uploaded. Snapshots are downloaded whatever their format
+    /// (default: JSON)
+    #[builder(default)]
+    pub snapshot_format: SnapshotFormat,
+
+    #[builder(default)]
+    pub event_counters: WorkerEventCounters,
+
+    #[builder(default)]
+    pub effective_snapshot_threshold: EffectiveSnapshotThreshold,
+
+    /// Subjects of the component, besides [`KV_EVENT_SUBJECT`], on which some workers publish KV
+    /// events. Each is consumed from its own stream, under the same durable consumer name, into
+    /// the same indexer. Snapshot thresholds only look at the primary stream, but every stream
+    /// is purged with it (default: none)
+    #[builder(default)]
+    pub extra_event_subjects: Vec<String>,
+
+    /// When set, only the events of these workers are forwarded to the indexer, e.g. for a
+    /// debugging router modeling a few workers of interest. Its radix tree being partial, such a
+    /// router takes no part in snapshots (default: None, every worker)
+    #[builder(default)]
+    pub worker_filter: Option<HashSet<WorkerId>>,
+
+    /// Cancelled once the background task has taken its final snapshot, stopped consuming events
+    /// and removed its consumers, e.g. to stop the indexer only then (default: None)
+    #[builder(default)]
+    pub stopped: Option<CancellationToken>,
+}
+
+impl KvRouterBackgroundConfig {
+    pub fn builder() -> KvRouterBackgroundConfigBuilder {
+        KvRouterBackgroundConfigBuilder::default()
+    }
+}
+
 /// Start a unified background task for event consumption and optional snapshot management
-#[allow(clippy::too_many_arguments)]
 pub async fn start_kv_router_background(
     component: Component,
-    consumer_uuid: String,
     kv_events_tx: mpsc::Sender<RouterEvent>,
     remove_worker_tx: mpsc::Sender<WorkerId>,
     maybe_get_workers_tx: Option<mpsc::Sender<GetWorkersRequest>>,
     maybe_snapshot_tx: Option<mpsc::Sender<DumpRequest>>,
     cancellation_token: CancellationToken,
-    router_snapshot_threshold: Option<u32>,
-    router_reset_states: bool,
+    config: KvRouterBackgroundConfig,
 ) -> Result<()> {
+    let KvRouterBackgroundConfig {
+        consumer_uuid,
+        snapshot_threshold: router_snapshot_threshold,
+        reset_states: router_reset_states,
+        snapshot_staleness_secs: router_snapshot_staleness_secs,
+        adaptive_snapshot_horizon_secs,
+        snapshot_format,
+        event_counters,
+        effective_snapshot_threshold,
+        extra_event_subjects,
+        worker_filter,
+        stopped,
+    } = config;
+    // Also reports the task stopped if it fails to start
+    let stopped = stopped.map(CancellationToken::drop_guard);
+    let identity = RouterIdentity::new(&component, &consumer_uuid);
+    tracing::info!(
+        router_uuid = %identity.router_uuid,
+        consumer_name = %identity.consumer_name,
+        stream_name = %identity.stream_name,
+        bucket_name = %identity.bucket_name,
+        "Starting KV router background task"
+    );
+
     // Set up NATS connections
-    let stream_name = Slug::slugify(&format!("{}.{}", component.subject(), KV_EVENT_SUBJECT))
-        .to_string()
-        .replace("_", "-");
+    let stream_name = identity.stream_name;
     let nats_server =
         std::env::var("NATS_SERVER").unwrap_or_else(|_| "nats://localhost:4222".to_string());
 
@@ -178,14 +988,13 @@ pub async fn start_kv_router_background(
         .etcd_client()
         .ok_or_else(|| anyhow::anyhow!("etcd client not available"))?;
 
-    // Create bucket name for snapshots/state
-    let bucket_name = Slug::slugify(&format!("{}-{RADIX_STATE_BUCKET}", component.subject()))
-        .to_string()
-        .replace("_", "-");
+    // Bucket name for snapshots/state
+    let bucket_name = identity.bucket_name;
 
     // Create RWLock for snapshot coordination
     let lock_prefix = format!("{}/{}", ROUTER_SNAPSHOT_LOCK, component.subject());
     let snapshot_rwlock = DistributedRWLock::new(lock_prefix);
+    let lock_metrics = RouterLockMetrics::from_component(&component);
 
     // Handle initial state based on router_reset_states flag
     if router_reset_states {
@@ -202,24 +1011,27 @@ pub async fn start_kv_router_background(
         ))?;
 
         // Acquire read lock with default timeout
-        if let Ok(_read_guard) = snapshot_rwlock
+        let read_guard = snapshot_rwlock
             .read_lock_with_wait(&etcd_client, &consumer_uuid, None)
-            .await
-        {
+            .await;
+        let _hold = lock_metrics.attempt(LOCK_SNAPSHOT_READ, read_guard.is_ok());
+        if let Ok(_read_guard) = read_guard {
             tracing::debug!("Acquired read lock for snapshot download");
 
             // Download snapshot while holding read lock
-            match nats_client
-                .object_store_download_data::<Vec<RouterEvent>>(&url)
-                .await
-            {
+            match download_snapshot(&nats_client, &url).await {
                 Ok(events) => {
                     tracing::info!(
                         "Successfully downloaded {} events from object store",
                         events.len()
                     );
-                    // Send all events to the indexer
+                    // Send all events to the indexer. The live stream may replay some of them, as
+                    // delivery is at-least-once: replays are not deduplicated but tolerated by
+                    // the indexer
                     for event in events {
+                        if !forwards(worker_filter.as_ref(), event.worker_id()) {
+                            continue;
+                        }
                         if let Err(e) = kv_events_tx.send(event).await {
                             tracing::warn!("Failed to send initial event to indexer: {e:?}");
                         }
@@ -240,6 +1052,29 @@ pub async fn start_kv_router_background(
     // Cleanup orphaned consumers on startup
     cleanup_orphaned_consumers(&mut nats_queue, &etcd_client, &component, &consumer_uuid).await;
 
+    // The consumers of the extra subjects stop with the primary consumer, after its final
+    // snapshot purged their streams as well
+    let extras_cancellation_token = CancellationToken::new();
+    let stop_extras = extras_cancellation_token.clone().drop_guard();
+    let mut extra_purge_txs = Vec::with_capacity(extra_event_subjects.len());
+    for event_subject in &extra_event_subjects {
+        extra_purge_txs.push(
+            start_extra_event_consumer(
+                &component,
+                event_subject,
+                &consumer_uuid,
+                &nats_server,
+                router_reset_states,
+                &etcd_client,
+                kv_events_tx.clone(),
+                event_counters.clone(),
+                worker_filter.clone(),
+                extras_cancellation_token.clone(),
+            )
+            .await?,
+        );
+    }
+
     // Watch for router deletions to clean up orphaned consumers
     let (_prefix_str, _watcher, mut router_replicas_rx) = etcd_client
         .kv_get_and_watch_prefix(&format!("{}/", KV_ROUTERS_ROOT_PATH))
@@ -262,11 +1097,19 @@ pub async fn start_kv_router_background(
         }
     };
 
+    // A partial radix tree must neither be uploaded nor let the stream be purged
+    if let Some(worker_filter) = &worker_filter {
+        tracing::info!(
+            "Only indexing the KV events of workers {worker_filter:?}, not taking part in snapshots"
+        );
+    }
+
     // Only set up snapshot-related resources if snapshot_tx, get_workers_tx, and threshold are provided
-    let snapshot_resources = if let (Some(get_workers_tx), Some(snapshot_tx), Some(_)) = (
+    let snapshot_resources = if let (Some(get_workers_tx), Some(snapshot_tx), Some(_), None) = (
         maybe_get_workers_tx,
         maybe_snapshot_tx,
         router_snapshot_threshold,
+        &worker_filter,
     ) {
         Some(SnapshotResources {
             nats_client,
@@ -275,12 +1118,64 @@ pub async fn start_kv_router_background(
             instances_rx,
             get_workers_tx,
             snapshot_tx,
+            timestamp_key: format!("{}/{}", ROUTER_SNAPSHOT_TIMESTAMP, component.subject()),
+            extra_purge_txs,
+            lock_metrics: lock_metrics.clone(),
+            snapshot_format,
         })
     } else {
         None
     };
 
+    // The watchdog is only meaningful when this router takes part in snapshotting. It runs on its
+    // own task so that a snapshot attempt hanging in the loop below does not silence it, and
+    // checks while the stream was last seen above the snapshot threshold
+    let stream_over_threshold = Arc::new(AtomicBool::new(false));
+    let watchdog_cancellation_token = CancellationToken::new();
+    let stop_watchdog = watchdog_cancellation_token.clone().drop_guard();
+    if let Some(mut watchdog) = snapshot_resources
+        .as_ref()
+        .zip(router_snapshot_staleness_secs)
+        .map(|(resources, secs)| {
+            SnapshotWatchdog::new(resources.timestamp_key.clone(), Duration::from_secs(secs))
+        })
+    {
+        let etcd_client = etcd_client.clone();
+        let stream_over_threshold = stream_over_threshold.clone();
+        tokio::spawn(async move {
+            let mut check_interval = tokio::time::interval(Duration::from_secs(1));
+            check_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                tokio::select! {
+                    _ = watchdog_cancellation_token.cancelled() => break,
+                    _ = check_interval.tick() => {
+                        if stream_over_threshold.load(Ordering::Relaxed) {
+                            watchdog.check(&etcd_client).await;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    let mut adaptive_threshold = router_snapshot_threshold
+        .zip(adaptive_snapshot_horizon_secs)
+        .map(|(threshold, secs)| {
+            AdaptiveSnapshotThreshold::new(threshold as u64, Duration::from_secs_f64(secs))
+        });
+    if let Some(threshold) = router_snapshot_threshold {
+        effective_snapshot_threshold.set(threshold as u64);
+    }
+
     tokio::spawn(async move {
+        // Dropped last, once the primary consumer is removed
+        let _stopped = stopped;
+        let _stop_extras = stop_extras;
+        let _stop_watchdog = stop_watchdog;
+        let mut dequeue_timeout = DequeueTimeout::new(MIN_DEQUEUE_TIMEOUT, MAX_DEQUEUE_TIMEOUT);
+        let mut consecutive_dequeue_errors: u32 = 0;
+        let mut dequeue_errors =
+            DequeueErrorLog::new(DEQUEUE_ERROR_LOG_INTERVAL, DEQUEUE_OUTAGE_ALERT_AFTER);
         let mut check_interval = tokio::time::interval(Duration::from_secs(1));
         check_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
@@ -290,6 +1185,16 @@ pub async fn start_kv_router_background(
 
                 _ = cancellation_token.cancelled() => {
                     tracing::debug!("KV Router background task received cancellation signal");
+                    // Leave a snapshot of every event acknowledged so far for the next router
+                    if let Some(resources) = snapshot_resources.as_ref() {
+                        match resources
+                            .purge_then_snapshot(&etcd_client, &mut nats_queue, &remove_worker_tx)
+                            .await
+                        {
+                            Ok(_) => tracing::info!("Performed final purge and snapshot"),
+                            Err(e) => tracing::debug!("Could not perform final snapshot: {e:?}"),
+                        }
+                    }
                     // Clean up the queue and remove the durable consumer
                     // TODO: durable consumer cannot cleanup if ungraceful shutdown (crash)
                     if let Err(e) = nats_queue.shutdown(None).await {
@@ -319,28 +1224,29 @@ pub async fn start_kv_router_background(
                     };
 
                     tracing::info!("Generate endpoint instance deleted, removing worker {worker_id}");
+                    event_counters.remove_worker(worker_id);
                     if let Err(e) = remove_worker_tx.send(worker_id).await {
                         tracing::warn!("Failed to send worker removal for worker {worker_id}: {e}");
                     }
                 }
 
                 // Handle event consumption
-                result = nats_queue.dequeue_task(None) => {
+                result = nats_queue.dequeue_task(Some(dequeue_timeout.get())) => {
+                    dequeue_timeout.record(matches!(result, Ok(Some(_))));
+                    if result.is_ok() {
+                        consecutive_dequeue_errors = 0;
+                        dequeue_errors.recovered(&stream_name);
+                    }
                     match result {
                         Ok(Some(bytes)) => {
-                            let event: RouterEvent = match serde_json::from_slice(&bytes) {
-                                Ok(event) => event,
-                                Err(e) => {
-                                    tracing::warn!("Failed to deserialize RouterEvent: {e:?}");
-                                    continue;
-                                }
-                            };
-
-                            // Forward the RouterEvent to the indexer
-                            if let Err(e) = kv_events_tx.send(event).await {
-                                tracing::warn!(
-                                    "failed to send kv event to indexer; shutting down: {e:?}"
-                                );
+                            if !forward_event(
+                                &bytes,
+                                &event_counters,
+                                worker_filter.as_ref(),
+                                &kv_events_tx,
+                            )
+                            .await
+                            {
                                 break;
                             }
                         },
@@ -348,8 +1254,21 @@ pub async fn start_kv_router_background(
                             tracing::trace!("Dequeue timeout, continuing");
                         },
                         Err(e) => {
-                            tracing::error!("Failed to dequeue task: {e:?}");
-                            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                            consecutive_dequeue_errors += 1;
+                            dequeue_errors.error(&stream_name, consecutive_dequeue_errors, &e);
+
+                            // Likely a NATS outage: re-establish the connection, resuming the
+                            // durable consumer from its last acknowledged event
+                            if consecutive_dequeue_errors >= DEQUEUE_ERRORS_BEFORE_RECONNECT {
+                                match nats_queue.reconnect().await {
+                                    Ok(()) => tracing::info!(
+                                        "Reconnected to NATS, resuming KV event consumption"
+                                    ),
+                                    Err(e) => tracing::warn!("Failed to reconnect to NATS: {e}"),
+                                }
+                            }
+                            tokio::time::sleep(dequeue_error_backoff(consecutive_dequeue_errors))
+                                .await;
                         }
                     }
                 }
@@ -367,7 +1286,12 @@ pub async fn start_kv_router_background(
                     };
 
                     // Guard clause: skip if message count is too low
-                    let threshold = router_snapshot_threshold.unwrap_or(u32::MAX) as u64;
+                    let threshold = match adaptive_threshold.as_mut() {
+                        Some(adaptive) => adaptive.observe(message_count, Instant::now()),
+                        None => router_snapshot_threshold.unwrap_or(u32::MAX) as u64,
+                    };
+                    effective_snapshot_threshold.set(threshold);
+                    stream_over_threshold.store(message_count > threshold, Ordering::Relaxed);
                     if message_count <= threshold {
                         continue;
                     }
@@ -420,7 +1344,9 @@ pub async fn start_kv_router_background(
                     let cleanup_rwlock = DistributedRWLock::new(cleanup_lock_name);
 
                     // Try to acquire cleanup write lock (non-blocking) before deleting consumer
-                    if let Some(_cleanup_guard) = cleanup_rwlock.try_write_lock(&etcd_client).await {
+                    let cleanup_guard = cleanup_rwlock.try_write_lock(&etcd_client).await;
+                    let _hold = lock_metrics.attempt(LOCK_CLEANUP, cleanup_guard.is_some());
+                    if let Some(_cleanup_guard) = cleanup_guard {
                         tracing::debug!(
                             "Acquired cleanup lock for deleting consumer: {consumer_to_delete}"
                         );
@@ -451,6 +1377,177 @@ pub async fn start_kv_router_background(
     Ok(())
 }
 
+/// Parse a comma-separated list of extra KV event subjects, skipping blanks, duplicates and
+/// [`KV_EVENT_SUBJECT`] itself, which is always consumed
+pub fn parse_extra_event_subjects(value: &str) -> Vec<String> {
+    let mut subjects: Vec<String> = Vec::new();
+    for subject in value.split(',').map(str::trim) {
+        if subject.is_empty()
+            || subject == KV_EVENT_SUBJECT
+            || subjects.iter().any(|s| s == subject)
+        {
+            continue;
+        }
+        subjects.push(subject.to_string());
+    }
+    subjects
+}
+
+/// Download the snapshot of the radix tree, in whichever format it was written
+async fn download_snapshot(
+    nats_client: &dynamo_runtime::transports::nats::Client,
+    url: &url::Url,
+) -> Result<Vec<RouterEvent>> {
+    let bytes = nats_client.object_store_download_bytes(url).await?;
+    match decode_snapshot(&bytes)? {
+        Some(events) => Ok(events),
+        // Written by an older router, before snapshots had an envelope
+        None => nats_client.object_store_download_data(url).await,
+    }
+}
+
+/// Parse a comma-separated list of worker ids to index the events of, skipping blanks. None if
+/// the list is empty, so that every worker is indexed.
+pub fn parse_worker_filter(value: &str) -> Result<Option<HashSet<WorkerId>>> {
+    let mut worker_ids = HashSet::new();
+    for worker_id in value.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        let parsed = worker_id
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid worker id {worker_id:?} in filter: {e}"))?;
+        worker_ids.insert(parsed);
+    }
+    Ok((!worker_ids.is_empty()).then_some(worker_ids))
+}
+
+/// Whether the events of `worker_id` pass the worker filter of the router, if any
+fn forwards(worker_filter: Option<&HashSet<WorkerId>>, worker_id: WorkerId) -> bool {
+    worker_filter.is_none_or(|worker_ids| worker_ids.contains(&worker_id))
+}
+
+/// Request to purge the acknowledged messages of an extra event stream
+struct PurgeRequest {
+    resp: oneshot::Sender<anyhow::Result<()>>,
+}
+
+/// Decode a KV event from the stream and forward it to the indexer, unless the worker filter
+/// drops it. Returns false once the indexer is gone.
+async fn forward_event(
+    bytes: &[u8],
+    event_counters: &WorkerEventCounters,
+    worker_filter: Option<&HashSet<WorkerId>>,
+    kv_events_tx: &mpsc::Sender<RouterEvent>,
+) -> bool {
+    let event: RouterEvent = match serde_json::from_slice(bytes) {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!("Failed to deserialize RouterEvent: {e:?}");
+            return true;
+        }
+    };
+
+    if !forwards(worker_filter, event.worker_id()) {
+        return true;
+    }
+    event_counters.record_at(event.worker_id(), Instant::now());
+
+    // Forward the RouterEvent to the indexer
+    if let Err(e) = kv_events_tx.send(event).await {
+        tracing::warn!("failed to send kv event to indexer; shutting down: {e:?}");
+        return false;
+    }
+    true
+}
+
+/// Consume the KV events published on an extra subject of the component into the indexer,
+/// alongside the primary stream. Returns the channel through which the snapshot logic of the
+/// primary stream purges this stream.
+#[allow(clippy::too_many_arguments)]
+async fn start_extra_event_consumer(
+    component: &Component,
+    event_subject: &str,
+    consumer_uuid: &str,
+    nats_server: &str,
+    reset_states: bool,
+    etcd_client: &EtcdClient,
+    kv_events_tx: mpsc::Sender<RouterEvent>,
+    event_counters: WorkerEventCounters,
+    worker_filter: Option<HashSet<WorkerId>>,
+    cancellation_token: CancellationToken,
+) -> Result<mpsc::Sender<PurgeRequest>> {
+    let stream_name = kv_event_stream_name_for(component, event_subject);
+    tracing::info!("Consuming extra KV event subject {event_subject} from stream {stream_name}");
+
+    let mut nats_queue = NatsQueue::new_with_consumer(
+        stream_name.clone(),
+        nats_server.to_string(),
+        std::time::Duration::from_secs(60), // 1 minute timeout
+        consumer_uuid.to_string(),
+    );
+    nats_queue.connect_with_reset(reset_states).await?;
+    cleanup_orphaned_consumers(&mut nats_queue, etcd_client, component, consumer_uuid).await;
+
+    let (purge_tx, mut purge_rx) = mpsc::channel::<PurgeRequest>(1);
+    tokio::spawn(async move {
+        let mut dequeue_timeout = DequeueTimeout::new(MIN_DEQUEUE_TIMEOUT, MAX_DEQUEUE_TIMEOUT);
+        let mut consecutive_dequeue_errors: u32 = 0;
+        let mut dequeue_errors =
+            DequeueErrorLog::new(DEQUEUE_ERROR_LOG_INTERVAL, DEQUEUE_OUTAGE_ALERT_AFTER);
+
+        loop {
+            tokio::select! {
+                biased;
+
+                _ = cancellation_token.cancelled() => break,
+
+                Some(request) = purge_rx.recv() => {
+                    let _ = request.resp.send(nats_queue.purge_acknowledged().await);
+                }
+
+                result = nats_queue.dequeue_task(Some(dequeue_timeout.get())) => {
+                    dequeue_timeout.record(matches!(result, Ok(Some(_))));
+                    if result.is_ok() {
+                        consecutive_dequeue_errors = 0;
+                        dequeue_errors.recovered(&stream_name);
+                    }
+                    match result {
+                        Ok(Some(bytes)) => {
+                            if !forward_event(
+                                &bytes,
+                                &event_counters,
+                                worker_filter.as_ref(),
+                                &kv_events_tx,
+                            )
+                            .await
+                            {
+                                break;
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            consecutive_dequeue_errors += 1;
+                            dequeue_errors.error(&stream_name, consecutive_dequeue_errors, &e);
+                            if consecutive_dequeue_errors >= DEQUEUE_ERRORS_BEFORE_RECONNECT
+                                && let Err(e) = nats_queue.reconnect().await
+                            {
+                                tracing::warn!("Failed to reconnect to NATS: {e}");
+                            }
+                            tokio::time::sleep(dequeue_error_backoff(consecutive_dequeue_errors))
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Remove the durable consumer of this router from the stream
+        if let Err(e) = nats_queue.shutdown(None).await {
+            tracing::warn!("Failed to shutdown NatsQueue of stream {stream_name}: {e}");
+        }
+    });
+
+    Ok(purge_tx)
+}
+
 /// Cleanup orphaned NATS consumers that no longer have corresponding etcd router entries
 async fn cleanup_orphaned_consumers(
     nats_queue: &mut NatsQueue,
@@ -462,21 +1559,10 @@ async fn cleanup_orphaned_consumers(
         return;
     };
 
-    let router_prefix = format!("{}/{}/", KV_ROUTERS_ROOT_PATH, component.path());
-    let Ok(router_entries) = etcd_client.kv_get_prefix(&router_prefix).await else {
+    let Ok(active_uuids) = active_router_uuids(etcd_client, component).await else {
         return;
     };
 
-    let active_uuids: HashSet<String> = router_entries
-        .iter()
-        .filter_map(|kv| {
-            String::from_utf8_lossy(kv.key())
-                .split('/')
-                .next_back()
-                .map(str::to_string)
-        })
-        .collect();
-
     for consumer in consumers {
         if consumer == consumer_uuid {
             // Never delete myself (extra/redundant safeguard)
@@ -488,3 +1574,222 @@ async fn cleanup_orphaned_consumers(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dequeue_error_backoff() {
+        assert_eq!(dequeue_error_backoff(1), Duration::from_millis(100));
+        assert_eq!(dequeue_error_backoff(2), Duration::from_millis(200));
+        assert_eq!(dequeue_error_backoff(4), Duration::from_millis(800));
+        assert_eq!(dequeue_error_backoff(100), MAX_DEQUEUE_ERROR_BACKOFF);
+    }
+
+    #[test]
+    fn test_dequeue_error_log() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut log = DequeueErrorLog::new(Duration::from_secs(10), Duration::from_secs(60));
+        let logged = |suppressed| {
+            Some(DequeueErrorReport {
+                suppressed,
+                outage: None,
+            })
+        };
+
+        // One log per interval, with the count of the failures in between
+        assert_eq!(log.report(at(0)), logged(0));
+        assert_eq!(log.report(at(1)), None);
+        assert_eq!(log.report(at(5)), None);
+        assert_eq!(log.report(at(10)), logged(2));
+
+        // A persistent outage is escalated once, even within the interval
+        assert_eq!(log.report(at(55)), logged(0));
+        assert_eq!(
+            log.report(at(60)),
+            Some(DequeueErrorReport {
+                suppressed: 0,
+                outage: Some(Duration::from_secs(60)),
+            })
+        );
+        assert_eq!(log.report(at(61)), None);
+        assert_eq!(log.report(at(120)), logged(1));
+
+        // A success ends the outage
+        log.recovered("test");
+        assert_eq!(log.report(at(121)), logged(0));
+        assert_eq!(log.report(at(150)), logged(0));
+        assert_eq!(
+            log.report(at(181)).unwrap().outage,
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_watchdog_alerts() {
+        let mut watchdog = SnapshotWatchdog::new("key".to_string(), Duration::from_secs(600));
+        let start = watchdog.started_at;
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Without any recorded snapshot, staleness counts from the watchdog's creation
+        assert_eq!(watchdog.alert_staleness(None, at(600)), None);
+        assert_eq!(
+            watchdog.alert_staleness(None, at(601)),
+            Some(Duration::from_secs(601))
+        );
+
+        // Alerts are throttled to one per staleness bound
+        assert_eq!(watchdog.alert_staleness(None, at(700)), None);
+        assert_eq!(watchdog.alert_staleness(None, at(1200)), None);
+        assert_eq!(
+            watchdog.alert_staleness(None, at(1201)),
+            Some(Duration::from_secs(1201))
+        );
+
+        // A recent snapshot by any replica silences it, an old one does not
+        assert_eq!(watchdog.alert_staleness(Some(at(1500)), at(2000)), None);
+        assert_eq!(
+            watchdog.alert_staleness(Some(at(1000)), at(2000)),
+            Some(Duration::from_secs(1000))
+        );
+
+        // A timestamp from the future (clock skew between replicas) is never stale
+        assert_eq!(watchdog.alert_staleness(Some(at(5000)), at(4000)), None);
+    }
+
+    #[test]
+    fn test_adaptive_snapshot_threshold() {
+        let start = Instant::now();
+        let mut adaptive = AdaptiveSnapshotThreshold::new(1000, Duration::from_secs(10));
+
+        // No growth rate yet, then a quiet stream: the configured threshold applies
+        assert_eq!(adaptive.observe(100, start), 1000);
+        assert_eq!(adaptive.observe(101, start + Duration::from_secs(1)), 990);
+
+        // A burst lowers the threshold, down to the floor
+        let burst = adaptive.observe(301, start + Duration::from_secs(2));
+        assert!(burst < 500, "burst threshold {burst}");
+        assert_eq!(
+            adaptive.observe(10_301, start + Duration::from_secs(3)),
+            100
+        );
+
+        // A purge is not mistaken for negative growth
+        assert_eq!(adaptive.observe(0, start + Duration::from_secs(4)), 100);
+        assert_eq!(adaptive.growth_rate.map(|rate| rate > 1000.0), Some(true));
+
+        let effective = EffectiveSnapshotThreshold::default();
+        assert_eq!(effective.get(), None);
+        effective.clone().set(100);
+        assert_eq!(effective.get(), Some(100));
+    }
+
+    #[test]
+    fn test_dequeue_timeout_backs_off_while_idle() {
+        let min = Duration::from_millis(100);
+        let mut timeout = DequeueTimeout::new(min, Duration::from_millis(350));
+        assert_eq!(timeout.get(), min);
+
+        timeout.record(false);
+        assert_eq!(timeout.get(), Duration::from_millis(200));
+        timeout.record(false);
+        assert_eq!(timeout.get(), Duration::from_millis(350));
+        timeout.record(false);
+        assert_eq!(timeout.get(), Duration::from_millis(350));
+
+        timeout.record(true);
+        assert_eq!(timeout.get(), min);
+    }
+
+    #[test]
+    fn test_worker_event_counters() {
+        let counters = WorkerEventCounters::default();
+        let start = Instant::now();
+        for _ in 0..50 {
+            counters.record_at(1, start);
+        }
+        counters.record_at(2, start);
+
+        // No complete window yet, so no rate
+        let stats = counters.snapshot_at(start + Duration::from_secs(1));
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].total_events, 50);
+        assert_eq!(stats[0].events_per_sec, 0.0);
+
+        // After a full window the rate reflects the events received during it
+        let stats = counters.snapshot_at(start + EVENT_RATE_WINDOW);
+        assert_eq!(stats[0].events_per_sec, 5.0);
+        assert_eq!(stats[1].events_per_sec, 0.1);
+        assert_eq!(
+            stats[1].secs_since_last_event,
+            EVENT_RATE_WINDOW.as_secs_f64()
+        );
+
+        // A worker that went silent drops to zero after the next window
+        let stats = counters.snapshot_at(start + EVENT_RATE_WINDOW * 2);
+        assert_eq!(stats[0].events_per_sec, 0.0);
+        assert_eq!(stats[0].total_events, 50);
+
+        counters.remove_worker(1);
+        assert_eq!(counters.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_extra_event_subjects() {
+        assert!(parse_extra_event_subjects("").is_empty());
+        assert_eq!(
+            parse_extra_event_subjects(
+                " sglang_kv_events, ,kv_events,trtllm_kv_events,sglang_kv_events"
+            ),
+            vec![
+                "sglang_kv_events".to_string(),
+                "trtllm_kv_events".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_worker_filter() {
+        assert_eq!(parse_worker_filter(" , ").unwrap(), None);
+        let filter = parse_worker_filter("7, 42,7").unwrap().unwrap();
+        assert_eq!(filter, HashSet::from([7, 42]));
+        assert!(forwards(Some(&filter), 42));
+        assert!(!forwards(Some(&filter), 8));
+        assert!(forwards(None, 8));
+        assert!(parse_worker_filter("7,worker-8").is_err());
+    }
+
+    #[test]
+    fn test_router_lock_metrics() {
+        let metrics = RouterLockMetrics::new_unregistered();
+        assert!(metrics.attempt(LOCK_CLEANUP, false).is_none());
+        drop(metrics.attempt(LOCK_SNAPSHOT, true).unwrap());
+
+        let attempts = |lock, result| metrics.attempts.with_label_values(&[lock, result]).get();
+        assert_eq!(attempts(LOCK_CLEANUP, "unavailable"), 1);
+        assert_eq!(attempts(LOCK_CLEANUP, "acquired"), 0);
+        assert_eq!(attempts(LOCK_SNAPSHOT, "acquired"), 1);
+
+        let held = metrics
+            .hold_seconds
+            .with_label_values(&[LOCK_SNAPSHOT])
+            .get();
+        assert!(held >= 0.0);
+        assert_eq!(
+            metrics
+                .last_hold_seconds
+                .with_label_values(&[LOCK_SNAPSHOT])
+                .get(),
+            held
+        );
+        assert_eq!(
+            metrics
+                .hold_seconds
+                .with_label_values(&[LOCK_CLEANUP])
+                .get(),
+            0.0
+        );
+    }
+}
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
 use crate::protocols::tensor;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ModelRuntimeConfig {
     pub total_kv_blocks: Option<u64>,
 
@@ -19,6 +19,30 @@ pub struct ModelRuntimeConfig {
 
     pub reasoning_parser: Option<String>,
 
+    /// Maximum number of tokens, prompt included, a request may have on this worker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_context_length: Option<u64>,
+
+    /// Maximum number of requests per second the router may dispatch to this worker, overriding
+    /// the router-wide limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_requests_per_second: Option<u32>,
+
+    /// Prefill throughput of this worker in tokens per second, from which the router estimates
+    /// the time to first token of the requests it routes here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefill_tokens_per_second: Option<u64>,
+
+    /// Fraction in (0, 1] of the speculated tokens accepted, for workers running speculative
+    /// decoding. The router weighs the decode load of this worker by its inverse, since rejected
+    /// speculations are wasted decode work
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spec_decode_acceptance_rate: Option<f64>,
+
+    /// Phase of disaggregated serving this worker takes part in
+    #[serde(default)]
+    pub disaggregation_role: DisaggregationRole,
+
     /// Total number of data parallel ranks for this worker (1 if DP not enabled)
     #[serde(default = "default_data_parallel_size")]
     pub data_parallel_size: u32,
@@ -38,6 +62,17 @@ pub struct ModelRuntimeConfig {
     pub tensor_model_config: Option<tensor::TensorModelConfig>,
 }
 
+/// Phase of disaggregated prefill/decode serving a worker handles.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DisaggregationRole {
+    /// Serves both prefill and decode
+    #[default]
+    Aggregated,
+    Prefill,
+    Decode,
+}
+
 const fn default_data_parallel_size() -> u32 {
     1
 }
@@ -50,6 +85,11 @@ impl Default for ModelRuntimeConfig {
             max_num_batched_tokens: None,
             tool_call_parser: None,
             reasoning_parser: None,
+            max_context_length: None,
+            max_requests_per_second: None,
+            prefill_tokens_per_second: None,
+            spec_decode_acceptance_rate: None,
+            disaggregation_role: DisaggregationRole::default(),
             data_parallel_size: default_data_parallel_size(),
             runtime_data: HashMap::new(),
             tensor_model_config: None,
@@ -28,7 +28,9 @@ use crate::protocols::common::preprocessor::PreprocessedRequestBuilder;
 use crate::tokenizers::Encoding;
 
 use dynamo_parsers::{ReasoningParser, ReasoningParserType};
-use dynamo_runtime::engine::{AsyncEngine, AsyncEngineContextProvider, ResponseStream};
+use dynamo_runtime::engine::{
+    AsyncEngine, AsyncEngineContextProvider, CancellationReason, ResponseStream,
+};
 use dynamo_runtime::pipeline::{
     AsyncEngineContext, Error, ManyOut, Operator, SingleIn, async_trait,
 };
@@ -212,9 +214,10 @@ impl OpenAIPreprocessor {
         builder.annotations(request.annotations().unwrap_or_default());
         builder.mdc_sum(Some(self.mdcsum.clone()));
         builder.estimated_prefix_hit_num_blocks(None);
-        // Extract backend_instance_id from nvext if present
+        // Extract backend_instance_id and bypass_routing from nvext if present
         if let Some(nvext) = request.nvext() {
             builder.backend_instance_id(nvext.backend_instance_id);
+            builder.bypass_routing(nvext.bypass_routing.unwrap_or(false));
         }
 
         Ok(builder)
@@ -517,7 +520,9 @@ impl OpenAIPreprocessor {
                                     e
                                 );
                                 inner.cancelled = true;
-                                inner.context.stop_generating();
+                                inner.context.stop_generating_with_reason(
+                                    CancellationReason::Error(e.to_string()),
+                                );
                             })
                             .map_err(|e| e.to_string())
                     });
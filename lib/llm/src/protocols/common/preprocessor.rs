@@ -56,6 +56,12 @@ pub struct PreprocessedRequest {
     #[builder(default)]
     pub router_config_override: Option<RouterConfigOverride>,
 
+    /// Route the request round robin, without KV-aware routing nor tracking it in the router
+    /// state, e.g. for health checks and warmup requests
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bypass_routing: bool,
+
     /// Disaggregated execution parameters (for prefill/decode separation)
     #[builder(default)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -41,6 +41,12 @@ pub struct NvExt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub backend_instance_id: Option<i64>,
 
+    /// Skip KV-aware routing and send the request to the next worker in round robin, without
+    /// tracking it in the router state, e.g. for health checks and warmup requests
+    #[builder(default, setter(strip_option))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bypass_routing: Option<bool>,
+
     /// Pre-tokenized data to use instead of tokenizing the prompt
     /// If provided along with backend_instance_id, these tokens will be used directly
     /// and tokenization will be skipped.
@@ -96,6 +102,7 @@ mod tests {
         assert_eq!(nv_ext.use_raw_prompt, None);
         assert_eq!(nv_ext.annotations, None);
         assert_eq!(nv_ext.backend_instance_id, None);
+        assert_eq!(nv_ext.bypass_routing, None);
         assert_eq!(nv_ext.token_data, None);
         assert_eq!(nv_ext.max_thinking_tokens, None);
     }
@@ -107,6 +114,7 @@ mod tests {
             .greed_sampling(true)
             .use_raw_prompt(true)
             .backend_instance_id(42)
+            .bypass_routing(true)
             .token_data(vec![1, 2, 3, 4])
             .max_thinking_tokens(1024)
             .build()
@@ -115,6 +123,7 @@ mod tests {
         assert_eq!(nv_ext.greed_sampling, Some(true));
         assert_eq!(nv_ext.use_raw_prompt, Some(true));
         assert_eq!(nv_ext.backend_instance_id, Some(42));
+        assert_eq!(nv_ext.bypass_routing, Some(true));
         assert_eq!(nv_ext.token_data, Some(vec![1, 2, 3, 4]));
         assert_eq!(nv_ext.max_thinking_tokens, Some(1024));
         // Validate the built struct
@@ -21,6 +21,27 @@ where
     event: T,
 }
 
+/// When a [`Recorder`] moves on to a new file. Each limit left unset is not checked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecorderRotation {
+    /// Maximum number of lines per file
+    pub max_lines: Option<usize>,
+    /// Maximum size of a file in bytes
+    pub max_bytes: Option<u64>,
+    /// Maximum time a file is written to, checked when an event is recorded
+    pub max_age: Option<Duration>,
+    /// Number of most recent files kept, older ones are deleted on rotation
+    pub max_files: Option<usize>,
+}
+
+impl RecorderRotation {
+    fn is_due(&self, lines: usize, bytes: u64, opened_at: Instant) -> bool {
+        self.max_lines.is_some_and(|max| lines >= max)
+            || self.max_bytes.is_some_and(|max| bytes >= max)
+            || self.max_age.is_some_and(|max| opened_at.elapsed() >= max)
+    }
+}
+
 /// A generic recorder for events that streams directly to a JSONL file
 #[derive(Debug)]
 pub struct Recorder<T> {
@@ -60,6 +81,22 @@ where
         max_lines_per_file: Option<usize>,
         max_count: Option<usize>,
         max_time: Option<f64>,
+    ) -> io::Result<Self> {
+        let rotation = RecorderRotation {
+            max_lines: max_lines_per_file,
+            ..Default::default()
+        };
+        Self::new_with_rotation(token, output_path, rotation, max_count, max_time).await
+    }
+
+    /// Create a new Recorder that streams events to a JSONL file, moving on to a new file, named
+    /// after `output_path` with an increasing index, whenever `rotation` is due
+    pub async fn new_with_rotation<P: AsRef<Path>>(
+        token: CancellationToken,
+        output_path: P,
+        rotation: RecorderRotation,
+        max_count: Option<usize>,
+        max_time: Option<f64>,
     ) -> io::Result<Self> {
         let (event_tx, mut event_rx) = mpsc::channel::<T>(2048);
         let event_count = Arc::new(Mutex::new(0));
@@ -91,6 +128,8 @@ where
             let start_time = start_time;
             let mut writer = BufWriter::with_capacity(32768, file);
             let mut line_count = 0;
+            let mut byte_count: u64 = 0;
+            let mut opened_at = Instant::now();
             let mut file_index = 0;
             let base_path = file_path.clone();
 
@@ -168,10 +207,10 @@ where
 
                         // Increment line count
                         line_count += 1;
+                        byte_count += json.len() as u64 + 1;
 
                         // Check if we need to rotate to a new file
-                        if let Some(max_lines) = max_lines_per_file
-                            && line_count >= max_lines {
+                        if rotation.is_due(line_count, byte_count, opened_at) {
                                 // Flush the current file
                                 if let Err(e) = writer.flush().await {
                                     tracing::error!("Failed to flush file before rotation: {}", e);
@@ -192,7 +231,21 @@ where
                                     Ok(new_file) => {
                                         writer = BufWriter::with_capacity(32768, new_file);
                                         line_count = 0;
+                                        byte_count = 0;
+                                        opened_at = Instant::now();
                                         tracing::info!("Rotated to new file: {}", new_path.display());
+
+                                        // Delete the oldest file beyond the ones to keep
+                                        if let Some(max_files) = rotation.max_files
+                                            && file_index >= max_files.max(1) {
+                                                let expired = match file_index - max_files.max(1) {
+                                                    0 => base_path.clone(),
+                                                    index => create_rotated_path(&base_path, index),
+                                                };
+                                                if let Err(e) = fs::remove_file(&expired).await {
+                                                    tracing::warn!("Failed to delete rotated file {}: {}", expired.display(), e);
+                                                }
+                                            }
                                     },
                                     Err(e) => {
                                         tracing::error!("Failed to open rotated file {}: {}", new_path.display(), e);
@@ -11,6 +11,7 @@ use rayon::prelude::*;
 use std::ops::Range;
 
 pub mod blocks;
+pub mod hasher;
 
 /// A token is represented as a 32-bit unsigned integer.
 pub type Token = u32;
@@ -0,0 +1,158 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pluggable hashing of token blocks into [`BlockHash`]es and [`SequenceHash`]es.
+//!
+//! The KV router matches requests against the blocks reported by workers purely by hash, so the
+//! router and every worker publishing KV events must agree on how those hashes are computed. A
+//! [`SequenceHasher`] carries an [`algorithm_id`](SequenceHasher::algorithm_id) which is attached
+//! to published KV events, allowing the indexer to reject (and report) events produced by a
+//! different algorithm instead of silently never matching them.
+
+use std::borrow::Cow;
+use std::fmt::Debug;
+
+use super::{BlockHash, SequenceHash, Token};
+
+/// Algorithm id of [`Xxh3SequenceHasher`] with the default KV router seed.
+pub const DEFAULT_SEQUENCE_HASHER_ID: &str = "xxh3-64-le-seed1337";
+
+/// Seed used by the KV router for block and sequence hashes.
+pub const DEFAULT_SEQUENCE_HASHER_SEED: u64 = 1337;
+
+/// Computes block and sequence hashes for tokens.
+///
+/// Implementations must be deterministic across processes and languages: two hashers with the
+/// same [`algorithm_id`](SequenceHasher::algorithm_id) must produce identical hashes for identical
+/// inputs.
+pub trait SequenceHasher: Debug + Send + Sync {
+    /// A stable identifier of the algorithm (and any parameters such as the seed), so that two
+    /// hashers producing different hashes never share an id.
+    fn algorithm_id(&self) -> Cow<'_, str>;
+
+    /// Hash the tokens of a single full block.
+    fn hash_block(&self, tokens: &[Token]) -> BlockHash;
+
+    /// Combine the parent's sequence hash with the current block hash.
+    fn hash_sequence(&self, parent: SequenceHash, block_hash: BlockHash) -> SequenceHash;
+
+    /// Hash every full block of `tokens`, ignoring a trailing partial block.
+    fn block_hashes(&self, tokens: &[Token], block_size: usize) -> Vec<BlockHash> {
+        tokens
+            .chunks_exact(block_size)
+            .map(|chunk| self.hash_block(chunk))
+            .collect()
+    }
+
+    /// Compute rolling sequence hashes, where the first block's sequence hash is its block hash.
+    fn sequence_hashes(&self, block_hashes: &[BlockHash]) -> Vec<SequenceHash> {
+        let mut sequence_hashes: Vec<SequenceHash> = Vec::with_capacity(block_hashes.len());
+        for &block_hash in block_hashes {
+            let sequence_hash = match sequence_hashes.last() {
+                Some(&parent) => self.hash_sequence(parent, block_hash),
+                None => block_hash,
+            };
+            sequence_hashes.push(sequence_hash);
+        }
+        sequence_hashes
+    }
+}
+
+/// The default hasher: XXH3-64 over the little-endian bytes of the tokens.
+#[derive(Debug, Clone, Copy)]
+pub struct Xxh3SequenceHasher {
+    seed: u64,
+}
+
+impl Xxh3SequenceHasher {
+    pub const fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl Default for Xxh3SequenceHasher {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEQUENCE_HASHER_SEED)
+    }
+}
+
+/// Shared instance of the default hasher.
+pub static DEFAULT_SEQUENCE_HASHER: Xxh3SequenceHasher =
+    Xxh3SequenceHasher::new(DEFAULT_SEQUENCE_HASHER_SEED);
+
+impl SequenceHasher for Xxh3SequenceHasher {
+    fn algorithm_id(&self) -> Cow<'_, str> {
+        if self.seed == DEFAULT_SEQUENCE_HASHER_SEED {
+            Cow::Borrowed(DEFAULT_SEQUENCE_HASHER_ID)
+        } else {
+            Cow::Owned(format!("xxh3-64-le-seed{}", self.seed))
+        }
+    }
+
+    fn hash_block(&self, tokens: &[Token]) -> BlockHash {
+        let bytes: Vec<u8> = tokens.iter().flat_map(|t| t.to_le_bytes()).collect();
+        xxhash_rust::xxh3::xxh3_64_with_seed(&bytes, self.seed)
+    }
+
+    fn hash_sequence(&self, parent: SequenceHash, block_hash: BlockHash) -> SequenceHash {
+        let bytes: Vec<u8> = [parent, block_hash]
+            .iter()
+            .flat_map(|h| h.to_le_bytes())
+            .collect();
+        xxhash_rust::xxh3::xxh3_64_with_seed(&bytes, self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_block_is_ignored() {
+        let hasher = Xxh3SequenceHasher::default();
+        let tokens: Vec<Token> = (0..10).collect();
+        let hashes = hasher.block_hashes(&tokens, 4);
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0], hasher.hash_block(&tokens[0..4]));
+        assert_eq!(hashes[1], hasher.hash_block(&tokens[4..8]));
+    }
+
+    #[test]
+    fn test_sequence_hashes_chain() {
+        let hasher = Xxh3SequenceHasher::default();
+        let block_hashes = vec![11, 22, 33];
+        let seq = hasher.sequence_hashes(&block_hashes);
+        assert_eq!(seq[0], 11);
+        assert_eq!(seq[1], hasher.hash_sequence(11, 22));
+        assert_eq!(seq[2], hasher.hash_sequence(seq[1], 33));
+
+        // Same blocks under a different prefix must not share a sequence hash
+        let other = hasher.sequence_hashes(&[99, 22]);
+        assert_ne!(other[1], seq[1]);
+    }
+
+    #[test]
+    fn test_algorithm_id_reflects_seed() {
+        assert_eq!(
+            Xxh3SequenceHasher::default().algorithm_id(),
+            DEFAULT_SEQUENCE_HASHER_ID
+        );
+        assert_ne!(
+            Xxh3SequenceHasher::new(0).algorithm_id(),
+            DEFAULT_SEQUENCE_HASHER_ID
+        );
+        // Two custom seeds are told apart too
+        assert_ne!(
+            Xxh3SequenceHasher::new(0).algorithm_id(),
+            Xxh3SequenceHasher::new(1).algorithm_id()
+        );
+        assert_eq!(
+            Xxh3SequenceHasher::new(DEFAULT_SEQUENCE_HASHER_SEED).algorithm_id(),
+            DEFAULT_SEQUENCE_HASHER.algorithm_id()
+        );
+        assert_ne!(
+            Xxh3SequenceHasher::new(0).hash_block(&[1, 2, 3, 4]),
+            DEFAULT_SEQUENCE_HASHER.hash_block(&[1, 2, 3, 4])
+        );
+    }
+}
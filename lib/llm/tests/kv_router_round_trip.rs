@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Round trip of a request through the KV router: the scheduler picks a worker, the
+//! [`AddressedPushRouter`] dispatches the request to it and the worker streams its response back.
+//! The request plane is mocked in-process, handing the request messages straight to the ingress
+//! of the workers, while the responses go through a real TCP response server.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use tokio::sync::watch;
+
+use dynamo_llm::kv_router::{
+    indexer::OverlapScores,
+    protocols::WorkerId,
+    scheduler::{KvScheduler, KvSchedulerConfig, KvSchedulerError},
+};
+use dynamo_runtime::component::{Instance, TransportType};
+use dynamo_runtime::engine::{AsyncEngine, AsyncEngineContextProvider, ResponseStream};
+use dynamo_runtime::pipeline::{
+    AddressedPushRouter, AddressedRequest, Error, ManyOut, RequestTransport, SingleIn,
+    context::Context,
+    network::{
+        Ingress, PushWorkHandler,
+        tcp::server::{ServerOptions, TcpStreamServer},
+    },
+};
+use dynamo_runtime::protocols::annotated::Annotated;
+use dynamo_runtime::{DistributedRuntime, Runtime};
+
+/// Number of data frames streamed by a worker before its final frame
+const RESPONSE_FRAMES: usize = 3;
+
+/// A worker answering every prompt with [`RESPONSE_FRAMES`] items tagged with its id
+struct MockWorker {
+    worker_id: WorkerId,
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<String>, ManyOut<Annotated<String>>, Error> for MockWorker {
+    async fn generate(&self, request: SingleIn<String>) -> Result<ManyOut<Annotated<String>>> {
+        let (prompt, context) = request.transfer(());
+        let worker_id = self.worker_id;
+        let items = (0..RESPONSE_FRAMES)
+            .map(move |i| Annotated::from_data(format!("{prompt} {i} from worker {worker_id}")));
+        Ok(ResponseStream::new(
+            Box::pin(futures::stream::iter(items)),
+            context.context(),
+        ))
+    }
+}
+
+/// Request plane delivering the request messages to the ingress of the worker at their address,
+/// in-process
+struct InProcessTransport {
+    workers: HashMap<String, Arc<dyn PushWorkHandler>>,
+}
+
+#[async_trait]
+impl RequestTransport for InProcessTransport {
+    async fn request(&self, address: String, _headers: HeaderMap, payload: Bytes) -> Result<()> {
+        let worker = self
+            .workers
+            .get(&address)
+            .ok_or_else(|| anyhow::anyhow!("no worker listening at {address}"))?
+            .clone();
+        // Like the request plane, acknowledge once the worker received the request, while it
+        // streams the response
+        tokio::spawn(async move {
+            if let Err(e) = worker.handle_payload(payload).await {
+                tracing::error!("Mock worker failed to handle the request: {e}");
+            }
+        });
+        Ok(())
+    }
+}
+
+fn address(worker_id: WorkerId) -> String {
+    format!("worker-{worker_id}")
+}
+
+fn instance(worker_id: WorkerId) -> Instance {
+    Instance {
+        component: "backend".to_string(),
+        endpoint: "generate".to_string(),
+        namespace: "test".to_string(),
+        instance_id: worker_id,
+        transport: TransportType::NatsTcp(address(worker_id)),
+    }
+}
+
+#[tokio::test]
+#[ignore = "Requires NATS for the component of the scheduler"]
+async fn test_schedule_dispatch_respond_round_trip() -> Result<()> {
+    dynamo_runtime::logging::init();
+
+    let runtime = Runtime::from_current()?;
+    let distributed = DistributedRuntime::from_settings_without_discovery(runtime.clone()).await?;
+    let component = distributed
+        .namespace("test_kv_router_round_trip")?
+        .component("router")?
+        .service_builder()
+        .create()
+        .await?;
+
+    let worker_ids: [WorkerId; 2] = [1, 2];
+    let (_instances_tx, instances_rx) =
+        watch::channel(worker_ids.iter().map(|id| instance(*id)).collect());
+    let (_configs_tx, configs_rx) = watch::channel(HashMap::new());
+    let scheduler = KvScheduler::start(
+        component,
+        4,
+        instances_rx,
+        configs_rx,
+        None,
+        None,
+        KvSchedulerConfig::builder()
+            .router_uuid("test-router")
+            .build()?,
+    )
+    .await?;
+
+    let mut workers: HashMap<String, Arc<dyn PushWorkHandler>> = HashMap::new();
+    for worker_id in worker_ids {
+        let ingress = Ingress::<SingleIn<String>, ManyOut<Annotated<String>>>::for_engine(
+            Arc::new(MockWorker { worker_id }),
+        )?;
+        workers.insert(address(worker_id), ingress);
+    }
+    let router = AddressedPushRouter::with_request_transport(
+        Arc::new(InProcessTransport { workers }),
+        TcpStreamServer::new(ServerOptions::default()).await?,
+        Default::default(),
+    )?;
+
+    // The scheduler reserves a slot on the worker it picks
+    let request_id = "round-trip".to_string();
+    let worker = scheduler
+        .schedule(
+            Some(request_id.clone()),
+            16,
+            None,
+            OverlapScores::new(),
+            None,
+            true,
+        )
+        .await?;
+    assert_eq!(
+        scheduler.active_requests(),
+        vec![(request_id.clone(), worker.worker_id)]
+    );
+
+    // The request is dispatched to that worker, whose frames all reach the caller and whose
+    // final frame ends the stream
+    let request = Context::with_id(
+        AddressedRequest::new("hello".to_string(), address(worker.worker_id)),
+        request_id.clone(),
+    );
+    let stream: ManyOut<Annotated<String>> = router.generate(request).await?;
+    let items: Vec<Annotated<String>> =
+        tokio::time::timeout(Duration::from_secs(5), stream.collect()).await?;
+    let expected: Vec<String> = (0..RESPONSE_FRAMES)
+        .map(|i| format!("hello {i} from worker {}", worker.worker_id))
+        .collect();
+    assert!(items.iter().all(|item| !item.is_error()), "{items:?}");
+    assert_eq!(
+        items
+            .into_iter()
+            .filter_map(|item| item.data)
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    // Freeing the request releases its slot
+    scheduler.free(&request_id).await?;
+    assert!(scheduler.active_requests().is_empty());
+
+    // A repeated free is reported as such, and an id never scheduled as unknown
+    assert!(matches!(
+        scheduler.free(&request_id).await,
+        Err(KvSchedulerError::AlreadyFreed(_))
+    ));
+    assert!(matches!(
+        scheduler.mark_prefill_completed("never-scheduled").await,
+        Err(KvSchedulerError::UnknownRequest(_))
+    ));
+
+    distributed.shutdown();
+    Ok(())
+}
@@ -94,6 +94,34 @@ impl<T: Data> From<EngineStream<T>> for DataStream<T> {
     }
 }
 
+/// Why a stream was cancelled, recorded on its [`AsyncEngineContext`] so that consumers of the
+/// stream can tell how it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancellationReason {
+    /// The caller asked to stop generating
+    Requested,
+    /// The request ran past its deadline
+    DeadlineExceeded,
+    /// The client went away before the stream completed
+    ClientDisconnected,
+    /// The stage driving this stream was cancelled, e.g. a router cancelling a remote request
+    UpstreamAborted,
+    /// A stage failed and cancelled the stream
+    Error(String),
+}
+
+impl std::fmt::Display for CancellationReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CancellationReason::Requested => write!(f, "requested"),
+            CancellationReason::DeadlineExceeded => write!(f, "deadline exceeded"),
+            CancellationReason::ClientDisconnected => write!(f, "client disconnected"),
+            CancellationReason::UpstreamAborted => write!(f, "upstream aborted"),
+            CancellationReason::Error(err) => write!(f, "error: {err}"),
+        }
+    }
+}
+
 // The Controller and the Context when https://github.com/rust-lang/rust/issues/65991 becomes stable
 pub trait AsyncEngineController: Send + Sync {}
 
@@ -148,6 +176,23 @@ pub trait AsyncEngineContext: Send + Sync + Debug {
     /// specific and may not be supported by all engines.
     fn kill(&self);
 
+    /// [`AsyncEngineContext::stop_generating`], recording why the stream is being stopped. Only
+    /// the first recorded reason is kept. Implementations which do not track reasons just stop.
+    fn stop_generating_with_reason(&self, _reason: CancellationReason) {
+        self.stop_generating();
+    }
+
+    /// [`AsyncEngineContext::kill`], recording why the stream is being killed.
+    /// See [`AsyncEngineContext::stop_generating_with_reason`].
+    fn kill_with_reason(&self, _reason: CancellationReason) {
+        self.kill();
+    }
+
+    /// Why the stream was cancelled, if it was cancelled with a reason.
+    fn cancellation_reason(&self) -> Option<CancellationReason> {
+        None
+    }
+
     /// Links child AsyncEngineContext to this AsyncEngineContext. If the `stop_generating`, `stop`
     /// or `kill` on this AsyncEngineContext is called, the same method is called on all linked
     /// child AsyncEngineContext, in the order they are linked, and then the method on this
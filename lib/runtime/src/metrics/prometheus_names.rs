@@ -206,6 +206,12 @@ pub mod work_handler {
     }
 }
 
+/// Egress router Prometheus metric names
+pub mod egress_router {
+    /// Total number of response frames received after the final frame of a stream
+    pub const POST_COMPLETION_FRAMES_TOTAL: &str = "post_completion_frames_total";
+}
+
 /// NATS client metrics. DistributedRuntime contains a NATS client shared by all children)
 pub mod nats_client {
     /// Macro to generate NATS client metric names with the prefix
@@ -372,6 +378,51 @@ pub const KVSTATS_METRICS: &[&str] = &[
 pub mod kvrouter {
     /// Number of KV cache events applied to the index (including status)
     pub const KV_CACHE_EVENTS_APPLIED: &str = "kv_cache_events_applied";
+
+    /// Number of active requests tracked by the router's scheduler
+    pub const TRACKED_REQUESTS: &str = "tracked_requests";
+
+    /// Number of tracked requests evicted because the tracked requests cap was exceeded
+    pub const TRACKED_REQUESTS_EVICTED: &str = "tracked_requests_evicted";
+
+    /// Relative spread of the worker logits of the last routing decision
+    pub const LOGIT_SPREAD: &str = "logit_spread";
+
+    /// Number of decision windows in which the worker logits barely differed
+    pub const DEGENERATE_ROUTING_WINDOWS: &str = "degenerate_routing_windows";
+
+    /// Number of blocks in the radix tree of the indexer, summed over workers
+    pub const INDEXER_TREE_BLOCKS: &str = "indexer_tree_blocks";
+
+    /// Number of blocks evicted to keep the radix tree under its size bound
+    pub const INDEXER_TREE_BLOCKS_EVICTED: &str = "indexer_tree_blocks_evicted";
+
+    /// Number of KV events waiting to be applied by the indexer
+    pub const INDEXER_EVENT_QUEUE_DEPTH: &str = "indexer_event_queue_depth";
+
+    /// Number of KV events dropped because the indexer could not keep up
+    pub const INDEXER_EVENTS_DROPPED: &str = "indexer_events_dropped";
+
+    /// Number of attempts to acquire a router lock, by lock and result
+    pub const LOCK_ACQUISITION_ATTEMPTS: &str = "lock_acquisition_attempts";
+
+    /// Total time a router lock was held by this router
+    pub const LOCK_HOLD_SECONDS: &str = "lock_hold_seconds";
+
+    /// Duration of the last hold of a router lock by this router
+    pub const LOCK_LAST_HOLD_SECONDS: &str = "lock_last_hold_seconds";
+
+    /// Whether routing is degraded to load only because the scheduler queue is deep (0 or 1)
+    pub const ROUTING_DEGRADED: &str = "routing_degraded";
+
+    /// Number of requests routed without overlap data because routing was degraded
+    pub const DEGRADED_ROUTING_REQUESTS: &str = "degraded_routing_requests";
+
+    /// Longest time a request waited in the scheduler queue before being dequeued
+    pub const MAX_QUEUE_WAIT_SECONDS: &str = "max_queue_wait_seconds";
+
+    /// Time spent in each phase of a routing decision, labelled by phase
+    pub const DECISION_PHASE_SECONDS: &str = "decision_phase_seconds";
 }
 
 // Shared regex patterns for Prometheus sanitization
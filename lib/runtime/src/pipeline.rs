@@ -14,13 +14,21 @@ pub use nodes::{
 pub mod context;
 pub mod error;
 pub mod network;
-pub use network::egress::addressed_router::{AddressedPushRouter, AddressedRequest};
-pub use network::egress::push_router::{PushRouter, RouterMode, WorkerLoadMonitor};
+pub use network::egress::addressed_router::{
+    AddressedPushRouter, AddressedPushRouterOptions, AddressedRequest, PostCompletionPolicy,
+    RequestTransport,
+};
+pub use network::egress::push_router::{
+    PushRouter, RoundRobinStats, RouterMode, WorkerLoadMonitor,
+};
+pub use network::egress::sse::SseAddressedPushRouter;
+pub use network::{ResponseDelivery, StreamReconnect};
 pub mod registry;
 
 pub use crate::engine::{
-    self as engine, AsyncEngine, AsyncEngineContext, AsyncEngineContextProvider, Data, DataStream,
-    Engine, EngineStream, EngineUnary, ResponseStream, async_trait,
+    self as engine, AsyncEngine, AsyncEngineContext, AsyncEngineContextProvider,
+    CancellationReason, Data, DataStream, Engine, EngineStream, EngineUnary, ResponseStream,
+    async_trait,
 };
 pub use anyhow::Error;
 pub use context::Context;
@@ -4,7 +4,7 @@
 use std::ops::{Deref, DerefMut};
 use std::sync::{Arc, Mutex};
 
-use super::{AsyncEngineContext, AsyncEngineContextProvider, Data};
+use super::{AsyncEngineContext, AsyncEngineContextProvider, CancellationReason, Data};
 use crate::engine::AsyncEngineController;
 use async_trait::async_trait;
 
@@ -265,6 +265,18 @@ impl AsyncEngineContext for StreamContext {
         self.controller.stop_generating();
     }
 
+    fn stop_generating_with_reason(&self, reason: CancellationReason) {
+        self.controller.stop_generating_with_reason(reason);
+    }
+
+    fn kill_with_reason(&self, reason: CancellationReason) {
+        self.controller.kill_with_reason(reason);
+    }
+
+    fn cancellation_reason(&self) -> Option<CancellationReason> {
+        self.controller.cancellation_reason()
+    }
+
     fn is_stopped(&self) -> bool {
         self.controller.is_stopped()
     }
@@ -316,6 +328,7 @@ pub struct Controller {
     tx: Sender<State>,
     rx: Receiver<State>,
     child_context: Mutex<Vec<Arc<dyn AsyncEngineContext>>>,
+    reason: Mutex<Option<CancellationReason>>,
 }
 
 impl Controller {
@@ -326,12 +339,31 @@ impl Controller {
             tx,
             rx,
             child_context: Mutex::new(Vec::new()),
+            reason: Mutex::new(None),
         }
     }
 
     pub fn id(&self) -> &str {
         &self.id
     }
+
+    /// Record the cancellation reason unless one was already recorded
+    fn record_reason(&self, reason: &CancellationReason) {
+        self.reason
+            .lock()
+            .expect("Failed to lock cancellation reason")
+            .get_or_insert_with(|| reason.clone());
+    }
+
+    fn children(&self) -> Vec<Arc<dyn AsyncEngineContext>> {
+        // Clone child Arcs to avoid deadlock if parent is accidentally linked under child
+        self.child_context
+            .lock()
+            .expect("Failed to lock child context")
+            .iter()
+            .cloned()
+            .collect()
+    }
 }
 
 impl Default for Controller {
@@ -422,6 +454,31 @@ impl AsyncEngineContext for Controller {
         let _ = self.tx.send(State::Killed);
     }
 
+    fn stop_generating_with_reason(&self, reason: CancellationReason) {
+        self.record_reason(&reason);
+        for child in self.children() {
+            child.stop_generating_with_reason(reason.clone());
+        }
+
+        let _ = self.tx.send(State::Stopped);
+    }
+
+    fn kill_with_reason(&self, reason: CancellationReason) {
+        self.record_reason(&reason);
+        for child in self.children() {
+            child.kill_with_reason(reason.clone());
+        }
+
+        let _ = self.tx.send(State::Killed);
+    }
+
+    fn cancellation_reason(&self) -> Option<CancellationReason> {
+        self.reason
+            .lock()
+            .expect("Failed to lock cancellation reason")
+            .clone()
+    }
+
     fn link_child(&self, child: Arc<dyn AsyncEngineContext>) {
         self.child_context
             .lock()
@@ -465,6 +522,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cancellation_reason_propagates_to_children() {
+        let parent = Controller::default();
+        let child = Arc::new(Controller::default());
+        parent.link_child(child.clone());
+        assert_eq!(parent.cancellation_reason(), None);
+
+        parent.stop_generating_with_reason(CancellationReason::Requested);
+        parent.kill_with_reason(CancellationReason::ClientDisconnected);
+
+        assert!(parent.is_killed());
+        assert!(child.is_killed());
+        // The first reason wins
+        assert_eq!(
+            parent.cancellation_reason(),
+            Some(CancellationReason::Requested)
+        );
+        assert_eq!(
+            child.cancellation_reason(),
+            Some(CancellationReason::Requested)
+        );
+    }
+
     #[test]
     fn test_insert_and_get() {
         let mut ctx = Context::new(Input {
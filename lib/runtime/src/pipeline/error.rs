@@ -72,6 +72,11 @@ pub enum PipelineError {
     #[error("Failed to establish a streaming connection: {0}")]
     ConnectionFailed(String),
 
+    /// The response transport could not register the streams of a request, e.g. because its
+    /// server is shutting down; no request was sent.
+    #[error("Failed to register the response stream: {0}")]
+    RegistrationFailed(String),
+
     #[error("Generate Error: {0}")]
     GenerateError(Error),
 
@@ -123,6 +128,11 @@ pub enum PipelineError {
     /// All instances are busy and cannot handle new requests
     #[error("Service temporarily unavailable: {0}")]
     ServiceOverloaded(String),
+
+    /// No response frame arrived within the idle timeout while the stream was still open and
+    /// the final frame had not been received; the remote worker is assumed to have stalled.
+    #[error("Response stream idle for longer than {0:?}")]
+    IdleTimeout(std::time::Duration),
 }
 
 #[derive(Debug, thiserror::Error)]
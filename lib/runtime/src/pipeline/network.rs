@@ -14,7 +14,9 @@ use std::sync::{Arc, OnceLock};
 use anyhow::Result;
 use async_trait::async_trait;
 use bytes::Bytes;
-use codec::{TwoPartCodec, TwoPartMessage, TwoPartMessageType};
+use codec::{
+    DataCompression, MAX_DECOMPRESSED_DATA_SIZE, TwoPartCodec, TwoPartMessage, TwoPartMessageType,
+};
 use derive_builder::Builder;
 use futures::StreamExt;
 // io::Cursor, TryStreamExt
@@ -113,7 +115,7 @@ impl PendingConnections {
 /// be associated with a stream of responses.
 #[async_trait::async_trait]
 pub trait ResponseService {
-    async fn register(&self, options: StreamOptions) -> PendingConnections;
+    async fn register(&self, options: StreamOptions) -> Result<PendingConnections, PipelineError>;
 }
 
 // #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -224,9 +226,58 @@ pub struct StreamOptions {
     #[builder(default = "8")]
     pub send_buffer_count: usize,
 
-    /// The number of messages to buffer before blocking
-    #[builder(default = "8")]
+    /// The number of response messages to buffer before blocking the sender, i.e. how far a
+    /// fast worker may run ahead of a slow consumer of the response stream
+    #[builder(default = "64")]
     pub recv_buffer_count: usize,
+
+    /// How the worker writes response frames to the socket
+    #[builder(default)]
+    pub response_delivery: ResponseDelivery,
+
+    /// Whether the response stream survives a transient disconnect of its socket. `None` fails
+    /// the stream when the socket drops.
+    #[builder(default)]
+    pub reconnect: Option<StreamReconnect>,
+}
+
+/// How the sender of a response stream writes frames to its socket.
+///
+/// [`ResponseDelivery::LowLatency`] disables Nagle's algorithm and flushes every frame as soon as
+/// it is produced, so each token reaches the router without waiting for more data; the cost is a
+/// packet and a syscall per frame. [`ResponseDelivery::Throughput`] lets the kernel coalesce small
+/// writes and only flushes once the sender has no more frames queued, trading per-token latency
+/// for fewer packets when frames are produced in bursts, e.g. for batch or non-streaming requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseDelivery {
+    #[default]
+    LowLatency,
+    Throughput,
+}
+
+impl ResponseDelivery {
+    /// Whether Nagle's algorithm is disabled on the socket
+    pub fn nodelay(&self) -> bool {
+        *self == ResponseDelivery::LowLatency
+    }
+}
+
+/// Lets a response stream survive a transient disconnect of its socket, instead of failing a
+/// request the worker is still generating.
+///
+/// The worker keeps the last `buffer_count` data frames it sent. When the socket drops, the
+/// router waits for the worker to connect again with the subject of the stream, which identifies
+/// it, and replies with the sequence to resume from; the worker then replays the frames from that
+/// sequence on. Frames are numbered in the order they are sent and TCP delivers them in order, so
+/// the sequence is the number of data frames the router received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamReconnect {
+    /// Number of most recent data frames the worker keeps to replay
+    pub buffer_count: usize,
+
+    /// How long the router waits for the worker to connect again, in milliseconds
+    pub timeout_ms: u64,
 }
 
 impl StreamOptions {
@@ -271,6 +322,9 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+    /// Compression of the data part of the message
+    #[serde(default, skip_serializing_if = "DataCompression::is_none")]
+    data_compression: DataCompression,
 }
 
 pub struct Ingress<Req: PipelineIO, Resp: PipelineIO> {
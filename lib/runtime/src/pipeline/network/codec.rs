@@ -15,7 +15,9 @@ use tokio_util::{
 
 mod two_part;
 
-pub use two_part::{TwoPartCodec, TwoPartMessage, TwoPartMessageType};
+pub use two_part::{
+    DataCompression, MAX_DECOMPRESSED_DATA_SIZE, TwoPartCodec, TwoPartMessage, TwoPartMessageType,
+};
 
 // // Custom codec that reads a u64 length header and the message of that length
 // #[derive(Default)]
@@ -1,7 +1,11 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::io::{Read, Write};
+
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
 use tokio_util::codec::{Decoder, Encoder};
 use xxhash_rust::xxh3::xxh3_64;
 
@@ -17,6 +21,10 @@ impl TwoPartCodec {
         TwoPartCodec { max_message_size }
     }
 
+    pub fn max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
     /// Encodes a `TwoPartMessage` into `Bytes`, enforcing `max_message_size`.
     pub fn encode_message(&self, msg: TwoPartMessage) -> Result<Bytes, TwoPartCodecError> {
         let mut buf = BytesMut::new();
@@ -146,6 +154,63 @@ impl Encoder<TwoPartMessage> for TwoPartCodec {
     }
 }
 
+/// Upper bound on the decompressed size of the data part of a [`TwoPartMessage`], used when the
+/// codec has no `max_message_size` of its own.
+pub const MAX_DECOMPRESSED_DATA_SIZE: usize = 256 * 1024 * 1024;
+
+/// Compression of the data part of a [`TwoPartMessage`]. The header is never compressed, so that
+/// it can tell the receiver how to decode the data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataCompression {
+    #[default]
+    None,
+    Gzip,
+}
+
+impl DataCompression {
+    pub fn is_none(&self) -> bool {
+        *self == DataCompression::None
+    }
+
+    /// Compress the data part of a message
+    pub fn compress(&self, data: Bytes) -> Result<Bytes, TwoPartCodecError> {
+        match self {
+            DataCompression::None => Ok(data),
+            DataCompression::Gzip => {
+                let mut encoder =
+                    GzEncoder::new(Vec::with_capacity(data.len() / 4), Compression::fast());
+                encoder.write_all(&data)?;
+                Ok(encoder.finish()?.into())
+            }
+        }
+    }
+
+    /// Decompress the data part of a message, failing if it inflates beyond `max_size` bytes
+    pub fn decompress(
+        &self,
+        data: Bytes,
+        max_size: Option<usize>,
+    ) -> Result<Bytes, TwoPartCodecError> {
+        match self {
+            DataCompression::None => Ok(data),
+            DataCompression::Gzip => {
+                let limit = max_size.map_or(u64::MAX, |max| max as u64 + 1);
+                let mut decompressed = Vec::with_capacity(data.len() * 4);
+                GzDecoder::new(&data[..])
+                    .take(limit)
+                    .read_to_end(&mut decompressed)?;
+                if let Some(max) = max_size
+                    && decompressed.len() > max
+                {
+                    return Err(TwoPartCodecError::MessageTooLarge(decompressed.len(), max));
+                }
+                Ok(decompressed.into())
+            }
+        }
+    }
+}
+
 pub enum TwoPartMessageType {
     HeaderOnly(Bytes),
     DataOnly(Bytes),
@@ -258,6 +323,47 @@ mod tests {
         assert_eq!(decoded.data, data);
     }
 
+    /// Compressing the data part of a long prompt, as the egress router does.
+    #[test]
+    fn test_data_compression_long_prompt() {
+        // A 100k token prompt of pseudo-random token ids, serialized the way requests are
+        let mut state: u64 = 42;
+        let token_ids: Vec<u32> = (0..100_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                ((state >> 33) % 128_000) as u32
+            })
+            .collect();
+        let data = Bytes::from(
+            serde_json::to_vec(&serde_json::json!({ "token_ids": token_ids })).unwrap(),
+        );
+
+        let compressed = DataCompression::Gzip.compress(data.clone()).unwrap();
+        let ratio = compressed.len() as f64 / data.len() as f64;
+        assert!(ratio < 0.6, "compression ratio {ratio}");
+
+        let codec = TwoPartCodec::new(None);
+        let encoded = codec
+            .encode_message(TwoPartMessage::from_parts(
+                Bytes::from("header"),
+                compressed,
+            ))
+            .unwrap();
+        let decoded = codec.decode_message(encoded).unwrap();
+        assert_eq!(decoded.header, Bytes::from("header"));
+        assert_eq!(
+            DataCompression::Gzip
+                .decompress(decoded.data.clone(), None)
+                .unwrap(),
+            data
+        );
+        assert!(matches!(
+            DataCompression::Gzip.decompress(decoded.data, Some(data.len() - 1)),
+            Err(TwoPartCodecError::MessageTooLarge(_, _))
+        ));
+        assert_eq!(DataCompression::None.compress(data.clone()).unwrap(), data);
+    }
+
     /// Test encoding and decoding of a message with only header.
     #[test]
     fn test_message_with_only_header() {
@@ -3,5 +3,6 @@
 
 pub mod addressed_router;
 pub mod push_router;
+pub mod sse;
 
 use super::*;
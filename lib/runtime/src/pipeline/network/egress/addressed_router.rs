@@ -1,6 +1,12 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicBool, Ordering},
+};
+use std::time::Duration;
+
 use async_nats::client::Client;
 use async_nats::{HeaderMap, HeaderValue};
 use tracing as log;
@@ -9,8 +15,9 @@ use super::*;
 use crate::logging::DistributedTraceContext;
 use crate::logging::get_distributed_tracing_context;
 use crate::logging::inject_otel_context_into_nats_headers;
+use crate::metrics::{MetricsRegistry, prometheus_names::egress_router};
 use crate::{Result, protocols::maybe_error::MaybeError};
-use tokio_stream::{StreamExt, StreamNotifyClose, wrappers::ReceiverStream};
+use tokio_stream::StreamExt;
 use tracing::Instrument;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +40,9 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+    /// Compression of the data part of the message
+    #[serde(default, skip_serializing_if = "DataCompression::is_none")]
+    data_compression: DataCompression,
 }
 
 pub struct AddressedRequest<T> {
@@ -50,26 +60,309 @@ impl<T> AddressedRequest<T> {
     }
 }
 
+/// How a response stream handles frames received after the final frame, which only a buggy
+/// worker sends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PostCompletionPolicy {
+    /// Log and drop the extra frames
+    Lenient,
+    /// Emit an error item and end the stream
+    #[default]
+    Strict,
+}
+
+/// Options of an [`AddressedPushRouter`]
+#[derive(Clone, Default)]
+pub struct AddressedPushRouterOptions {
+    /// Maximum time to wait between two response frames before failing the stream with
+    /// [`PipelineError::IdleTimeout`]. `None` waits indefinitely.
+    pub idle_timeout: Option<Duration>,
+
+    /// Request bodies of at least this many bytes are gzip-compressed. The control message tells
+    /// the worker to decompress, so workers must be recent enough to understand it. `None` never
+    /// compresses.
+    pub compress_data_above: Option<usize>,
+
+    pub post_completion: PostCompletionPolicy,
+
+    /// How workers write response frames: flushed one by one for per-token latency (default),
+    /// or coalesced for throughput
+    pub response_delivery: ResponseDelivery,
+
+    /// Lets response streams survive a transient disconnect, with workers replaying the frames
+    /// the router missed. `None` fails the stream when its socket drops.
+    pub response_reconnect: Option<StreamReconnect>,
+
+    /// Counter of the frames received after the final frame. An unregistered counter is used if
+    /// `None`.
+    pub post_completion_frames: Option<IntCounter>,
+}
+
+/// Request plane of an [`AddressedPushRouter`]: delivers the request messages to the worker
+/// listening at an address. Implemented by the NATS client; tests may deliver messages in-process.
+#[async_trait]
+pub trait RequestTransport: Send + Sync {
+    /// Deliver `payload` to the worker at `address`, returning once the worker accepted it
+    async fn request(&self, address: String, headers: HeaderMap, payload: Bytes) -> Result<()>;
+}
+
+#[async_trait]
+impl RequestTransport for Client {
+    async fn request(&self, address: String, headers: HeaderMap, payload: Bytes) -> Result<()> {
+        // we might need to add a timeout on this if there is no subscriber to the subject; however, I think nats
+        // will handle this for us
+        self.request_with_headers(address, headers, payload).await?;
+        Ok(())
+    }
+}
+
+static POST_COMPLETION_FRAMES: OnceLock<IntCounter> = OnceLock::new();
+
+/// The counter of frames received after the final frame, registered with the first registry
+/// asking for it and shared by every router of the process to avoid duplicate registration.
+pub fn post_completion_frames_counter(registry: &impl MetricsRegistry) -> IntCounter {
+    POST_COMPLETION_FRAMES
+        .get_or_init(|| {
+            registry
+                .create_intcounter(
+                    egress_router::POST_COMPLETION_FRAMES_TOTAL,
+                    "Number of response frames received after the final frame of a stream",
+                    &[],
+                )
+                .unwrap_or_else(|e| {
+                    tracing::warn!(
+                        "Failed to register post-completion frames counter: {e}. Using an unregistered counter as fallback."
+                    );
+                    unregistered_post_completion_frames()
+                })
+        })
+        .clone()
+}
+
+pub(crate) fn unregistered_post_completion_frames() -> IntCounter {
+    IntCounter::new(
+        egress_router::POST_COMPLETION_FRAMES_TOTAL,
+        "Number of response frames received after the final frame of a stream",
+    )
+    .unwrap()
+}
+
 pub struct AddressedPushRouter {
-    // todo: generalize with a generic
-    req_transport: Client,
+    req_transport: Arc<dyn RequestTransport>,
 
     // todo: generalize with a generic
     resp_transport: Arc<tcp::server::TcpStreamServer>,
+
+    /// Maximum time to wait between two response frames before failing the stream with
+    /// [`PipelineError::IdleTimeout`]. `None` waits indefinitely.
+    idle_timeout: Option<Duration>,
+
+    /// Request bodies of at least this many bytes are gzip-compressed. `None` never compresses.
+    compress_data_above: Option<usize>,
+
+    post_completion: PostCompletionPolicy,
+
+    post_completion_frames: IntCounter,
+
+    response_delivery: ResponseDelivery,
+
+    response_reconnect: Option<StreamReconnect>,
 }
 
 impl AddressedPushRouter {
     pub fn new(
         req_transport: Client,
         resp_transport: Arc<tcp::server::TcpStreamServer>,
+    ) -> Result<Arc<Self>> {
+        Self::with_idle_timeout(req_transport, resp_transport, None)
+    }
+
+    /// Like [`AddressedPushRouter::new`], but terminates a response stream if no frame arrives
+    /// within `idle_timeout`. This catches workers which stall mid-stream, and is independent of
+    /// any handshake or total-duration timeout.
+    pub fn with_idle_timeout(
+        req_transport: Client,
+        resp_transport: Arc<tcp::server::TcpStreamServer>,
+        idle_timeout: Option<Duration>,
+    ) -> Result<Arc<Self>> {
+        Self::with_options(
+            req_transport,
+            resp_transport,
+            AddressedPushRouterOptions {
+                idle_timeout,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_options(
+        req_transport: Client,
+        resp_transport: Arc<tcp::server::TcpStreamServer>,
+        options: AddressedPushRouterOptions,
+    ) -> Result<Arc<Self>> {
+        Self::with_request_transport(Arc::new(req_transport), resp_transport, options)
+    }
+
+    /// Like [`AddressedPushRouter::with_options`], but sends the requests over `req_transport`
+    /// instead of NATS
+    pub fn with_request_transport(
+        req_transport: Arc<dyn RequestTransport>,
+        resp_transport: Arc<tcp::server::TcpStreamServer>,
+        options: AddressedPushRouterOptions,
     ) -> Result<Arc<Self>> {
         Ok(Arc::new(Self {
             req_transport,
             resp_transport,
+            idle_timeout: options.idle_timeout,
+            compress_data_above: options.compress_data_above,
+            post_completion: options.post_completion,
+            response_delivery: options.response_delivery,
+            response_reconnect: options.response_reconnect,
+            post_completion_frames: options
+                .post_completion_frames
+                .unwrap_or_else(unregistered_post_completion_frames),
         }))
     }
 }
 
+/// Turn the raw response channel into a stream of frames, where `Ok(None)` marks the sender
+/// closing the channel and `Err(PipelineError::IdleTimeout)` marks the idle timeout elapsing.
+/// The stream ends after either of those.
+pub(crate) fn response_frames(
+    rx: tokio::sync::mpsc::Receiver<Bytes>,
+    idle_timeout: Option<Duration>,
+) -> impl futures::Stream<Item = Result<Option<Bytes>, PipelineError>> + Send {
+    futures::stream::unfold(Some(rx), move |rx| async move {
+        let mut rx = rx?;
+        let frame = match idle_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, rx.recv()).await {
+                Ok(frame) => frame,
+                Err(_) => return Some((Err(PipelineError::IdleTimeout(timeout)), None)),
+            },
+            None => rx.recv().await,
+        };
+        match frame {
+            Some(bytes) => Some((Ok(Some(bytes)), Some(rx))),
+            None => Some((Ok(None), None)),
+        }
+    })
+}
+
+/// Decode the response frames of a request into items, ending the stream on the final frame.
+///
+/// A correct worker sends nothing after the final frame. Frames which arrive anyway are counted
+/// in `post_completion_frames` and handled according to `post_completion`.
+pub(crate) fn decode_response_frames<U>(
+    frames: impl futures::Stream<Item = Result<Option<Bytes>, PipelineError>> + Send + 'static,
+    engine_ctx: Arc<dyn AsyncEngineContext>,
+    post_completion: PostCompletionPolicy,
+    post_completion_frames: IntCounter,
+) -> impl futures::Stream<Item = U> + Send
+where
+    U: Data + for<'de> Deserialize<'de> + MaybeError,
+{
+    // TODO: Detect end-of-stream using Server-Sent Events (SSE)
+    let mut is_complete_final = false;
+    let terminated = Arc::new(AtomicBool::new(false));
+    let terminated_ = terminated.clone();
+    let items = Box::pin(frames.filter_map(move |res| {
+        let res = match res {
+            Ok(res) => res,
+            Err(err) => {
+                if is_complete_final {
+                    return None;
+                }
+                if engine_ctx.is_stopped() {
+                    log::debug!(
+                        request_id = engine_ctx.id(),
+                        reason = ?engine_ctx.cancellation_reason(),
+                        "Response stream of cancelled request ended"
+                    );
+                    return None;
+                }
+                log::warn!(request_id = engine_ctx.id(), %err, "Response stream stalled");
+                return Some(U::from_err(err.into()));
+            }
+        };
+        if let Some(res_bytes) = res {
+            if is_complete_final {
+                post_completion_frames.inc();
+                return match post_completion {
+                    PostCompletionPolicy::Lenient => {
+                        log::warn!(
+                            request_id = engine_ctx.id(),
+                            "Dropping response received after generation ended"
+                        );
+                        None
+                    }
+                    PostCompletionPolicy::Strict => {
+                        terminated_.store(true, Ordering::Relaxed);
+                        Some(U::from_err(
+                            Error::msg(
+                                "Response received after generation ended - this should never happen",
+                            )
+                            .into(),
+                        ))
+                    }
+                };
+            }
+            match serde_json::from_slice::<NetworkStreamWrapper<U>>(&res_bytes) {
+                Ok(item) => {
+                    is_complete_final = item.complete_final;
+                    if let Some(data) = item.data {
+                        Some(data)
+                    } else if is_complete_final {
+                        None
+                    } else {
+                        Some(U::from_err(
+                            Error::msg("Empty response received - this should never happen")
+                                .into(),
+                        ))
+                    }
+                }
+                Err(err) => {
+                    // legacy log print
+                    let json_str = String::from_utf8_lossy(&res_bytes);
+                    log::warn!(%err, %json_str, "Failed deserializing JSON to response");
+
+                    Some(U::from_err(Error::new(err).into()))
+                }
+            }
+        } else if is_complete_final {
+            // end of stream
+            None
+        } else if engine_ctx.is_stopped() {
+            // Gracefully end the stream if 'stop_generating()' was called. Do NOT check for
+            // 'is_killed()' here because it implies the stream ended abnormally which should be
+            // handled by the error branch below. The reason, if any, stays recorded on the
+            // context returned with the stream.
+            log::debug!(
+                request_id = engine_ctx.id(),
+                reason = ?engine_ctx.cancellation_reason(),
+                "Request cancelled and then trying to read a response"
+            );
+            None
+        } else {
+            // stream ended unexpectedly
+            log::debug!("{STREAM_ERR_MSG}");
+            Some(U::from_err(Error::msg(STREAM_ERR_MSG).into()))
+        }
+    }));
+
+    // In strict mode the stream ends right after the post-completion error, without waiting for
+    // another frame
+    futures::stream::unfold(items, move |mut items| {
+        let terminated = terminated.clone();
+        async move {
+            if terminated.load(Ordering::Relaxed) {
+                return None;
+            }
+            let item = items.next().await?;
+            Some((item, items))
+        }
+    })
+}
+
 #[async_trait]
 impl<T, U> AsyncEngine<SingleIn<AddressedRequest<T>>, ManyOut<U>, Error> for AddressedPushRouter
 where
@@ -81,25 +374,37 @@ where
         let (addressed_request, context) = request.transfer(());
         let (request, address) = addressed_request.into_parts();
         let engine_ctx = context.context();
-        let engine_ctx_ = engine_ctx.clone();
 
         // registration options for the data plane in a singe in / many out configuration
         let options = StreamOptions::builder()
             .context(engine_ctx.clone())
             .enable_request_stream(false)
             .enable_response_stream(true)
+            .response_delivery(self.response_delivery)
+            .reconnect(self.response_reconnect)
             .build()
             .unwrap();
 
-        // register our needs with the data plane
+        // register our needs with the data plane; a failure is reported to the caller before
+        // anything is sent to the worker
         // todo - generalize this with a generic data plane object which hides the specific transports
-        let pending_connections: PendingConnections = self.resp_transport.register(options).await;
+        let pending_connections: PendingConnections =
+            match self.resp_transport.register(options).await {
+                Ok(pending_connections) => pending_connections,
+                Err(e) => {
+                    log::warn!(request_id, "failed to register the response stream: {e}");
+                    return Err(e.into());
+                }
+            };
 
         // validate and unwrap the RegisteredStream object
         let pending_response_stream = match pending_connections.into_parts() {
             (None, Some(recv_stream)) => recv_stream,
             _ => {
-                panic!("Invalid data plane registration for a SingleIn/ManyOut transport");
+                return Err(PipelineError::RegistrationFailed(
+                    "Invalid data plane registration for a SingleIn/ManyOut transport".to_string(),
+                )
+                .into());
             }
         };
 
@@ -110,27 +415,35 @@ where
         // used to issue the request on the
         // todo -- this object should be automatically created by the register call, and achieved by to the two into_parts()
         // calls. all the information here is provided by the [`StreamOptions`] object and/or the dataplane object
+        let data = Bytes::from(serde_json::to_vec(&request)?);
+        let data_compression = match self.compress_data_above {
+            Some(min_size) if data.len() >= min_size => DataCompression::Gzip,
+            _ => DataCompression::None,
+        };
+        let uncompressed_len = data.len();
+        let data = data_compression.compress(data)?;
+
         let control_message = RequestControlMessage {
             id: engine_ctx.id().to_string(),
             request_type: RequestType::SingleIn,
             response_type: ResponseType::ManyOut,
             connection_info,
+            data_compression,
         };
 
         // next build the two part message where we package the connection info and the request into
         // a single Vec<u8> that can be sent over the wire.
         // --- package this up in the WorkQueuePublisher ---
         let ctrl = serde_json::to_vec(&control_message)?;
-        let data = serde_json::to_vec(&request)?;
 
         log::trace!(
             request_id,
-            "packaging two-part message; ctrl: {} bytes, data: {} bytes",
+            "packaging two-part message; ctrl: {} bytes, data: {} bytes ({uncompressed_len} bytes uncompressed)",
             ctrl.len(),
             data.len()
         );
 
-        let msg = TwoPartMessage::from_parts(ctrl.into(), data.into());
+        let msg = TwoPartMessage::from_parts(ctrl.into(), data);
 
         // the request plane / work queue should provide a two part message codec that can be used
         // or it should take a two part message directly
@@ -140,7 +453,7 @@ where
 
         // TRANSPORT ABSTRACT REQUIRED - END HERE
 
-        log::trace!(request_id, "enqueueing two-part message to nats");
+        log::trace!(request_id, "enqueueing two-part message");
 
         // Insert Trace Context into Headers
         // Enables span to be created in push_endpoint before
@@ -161,11 +474,8 @@ where
             }
         }
 
-        // we might need to add a timeout on this if there is no subscriber to the subject; however, I think nats
-        // will handle this for us
-        let _response = self
-            .req_transport
-            .request_with_headers(address.to_string(), headers, buffer)
+        self.req_transport
+            .request(address.to_string(), headers, buffer)
             .await?;
 
         log::trace!(request_id, "awaiting transport handshake");
@@ -174,59 +484,87 @@ where
             .map_err(|_| PipelineError::DetachedStreamReceiver)?
             .map_err(PipelineError::ConnectionFailed)?;
 
-        // TODO: Detect end-of-stream using Server-Sent Events (SSE)
-        let mut is_complete_final = false;
-        let stream = tokio_stream::StreamNotifyClose::new(
-            tokio_stream::wrappers::ReceiverStream::new(response_stream.rx),
-        )
-        .filter_map(move |res| {
-            if let Some(res_bytes) = res {
-                if is_complete_final {
-                    return Some(U::from_err(
-                        Error::msg(
-                            "Response received after generation ended - this should never happen",
-                        )
-                        .into(),
-                    ));
-                }
-                match serde_json::from_slice::<NetworkStreamWrapper<U>>(&res_bytes) {
-                    Ok(item) => {
-                        is_complete_final = item.complete_final;
-                        if let Some(data) = item.data {
-                            Some(data)
-                        } else if is_complete_final {
-                            None
-                        } else {
-                            Some(U::from_err(
-                                Error::msg("Empty response received - this should never happen")
-                                    .into(),
-                            ))
-                        }
-                    }
-                    Err(err) => {
-                        // legacy log print
-                        let json_str = String::from_utf8_lossy(&res_bytes);
-                        log::warn!(%err, %json_str, "Failed deserializing JSON to response");
+        let frames = response_frames(response_stream.rx, self.idle_timeout);
+        let stream = decode_response_frames(
+            frames,
+            engine_ctx.clone(),
+            self.post_completion,
+            self.post_completion_frames.clone(),
+        );
+        Ok(ResponseStream::new(Box::pin(stream), engine_ctx))
+    }
+}
 
-                        Some(U::from_err(Error::new(err).into()))
-                    }
-                }
-            } else if is_complete_final {
-                // end of stream
-                None
-            } else if engine_ctx_.is_stopped() {
-                // Gracefully end the stream if 'stop_generating()' was called. Do NOT check for
-                // 'is_killed()' here because it implies the stream ended abnormally which should be
-                // handled by the error branch below.
-                log::debug!("Request cancelled and then trying to read a response");
-                None
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Context;
+    use crate::protocols::annotated::Annotated;
+
+    #[tokio::test]
+    async fn test_response_frames_idle_timeout() {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        tx.send(Bytes::from_static(b"frame")).await.unwrap();
+
+        // Keep the sender alive so the stream stalls instead of closing
+        let frames: Vec<_> = response_frames(rx, Some(Duration::from_millis(50)))
+            .collect()
+            .await;
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(frames[0], Ok(Some(_))));
+        assert!(matches!(frames[1], Err(PipelineError::IdleTimeout(_))));
+        drop(tx);
+    }
+
+    fn frame(data: Option<&str>, complete_final: bool) -> Result<Option<Bytes>, PipelineError> {
+        let wrapper = NetworkStreamWrapper {
+            data: data.map(|data| Annotated::from_data(data.to_string())),
+            complete_final,
+        };
+        Ok(Some(serde_json::to_vec(&wrapper).unwrap().into()))
+    }
+
+    #[tokio::test]
+    async fn test_post_completion_frames() {
+        for (policy, expected_items) in [
+            (PostCompletionPolicy::Lenient, 1),
+            (PostCompletionPolicy::Strict, 2),
+        ] {
+            let frames = futures::stream::iter(vec![
+                frame(Some("a"), false),
+                frame(None, true),
+                frame(Some("late"), false),
+                frame(Some("later"), false),
+                Ok(None),
+            ]);
+            let counter = unregistered_post_completion_frames();
+            let items: Vec<Annotated<String>> =
+                decode_response_frames(frames, Context::new(()).context(), policy, counter.clone())
+                    .collect()
+                    .await;
+
+            assert_eq!(items.len(), expected_items, "{policy:?}");
+            assert_eq!(items[0].data.as_deref(), Some("a"));
+            if policy == PostCompletionPolicy::Strict {
+                // The stream ends on the first extra frame
+                assert!(items[1].is_error());
+                assert_eq!(counter.get(), 1);
             } else {
-                // stream ended unexpectedly
-                log::debug!("{STREAM_ERR_MSG}");
-                Some(U::from_err(Error::msg(STREAM_ERR_MSG).into()))
+                assert_eq!(counter.get(), 2);
             }
-        });
+        }
+    }
 
-        Ok(ResponseStream::new(Box::pin(stream), engine_ctx))
+    #[tokio::test]
+    async fn test_response_frames_close() {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        tx.send(Bytes::from_static(b"frame")).await.unwrap();
+        drop(tx);
+
+        let frames: Vec<_> = response_frames(rx, Some(Duration::from_secs(60)))
+            .collect()
+            .await;
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(frames[1], Ok(None)));
     }
 }
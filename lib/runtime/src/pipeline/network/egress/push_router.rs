@@ -8,6 +8,9 @@ use crate::{
     pipeline::{
         AddressedPushRouter, AddressedRequest, Error, ManyOut, SingleIn,
         error::{PipelineError, PipelineErrorExt},
+        network::egress::addressed_router::{
+            AddressedPushRouterOptions, post_completion_frames_counter,
+        },
     },
     protocols::maybe_error::MaybeError,
     traits::DistributedRuntimeProvider,
@@ -19,10 +22,11 @@ use async_trait::async_trait;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     future::Future,
     marker::PhantomData,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicU64, Ordering},
     },
 };
@@ -55,8 +59,8 @@ where
     /// dynamo-llm's KV Routing does this.
     router_mode: RouterMode,
 
-    /// Number of round robin requests handled. Used to decide which server is next.
-    round_robin_counter: Arc<AtomicU64>,
+    /// Round robin position and per-instance dispatch counts. Shared by clones of this router.
+    round_robin: Arc<RoundRobinState>,
 
     /// The next step in the chain. PushRouter (this object) picks an instances,
     /// addresses it, then passes it to AddressedPushRouter which does the network traffic.
@@ -72,6 +76,53 @@ where
     _phantom: PhantomData<(T, U)>,
 }
 
+/// Point-in-time view of the round robin state of a [`PushRouter`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoundRobinStats {
+    /// Number of round robin requests handled since the last reset
+    pub counter: u64,
+    /// Round robin requests dispatched to each instance since the last reset
+    pub dispatched: HashMap<i64, u64>,
+}
+
+#[derive(Debug, Default)]
+struct RoundRobinState {
+    /// Number of round robin requests handled. Used to decide which server is next.
+    counter: AtomicU64,
+    dispatched: Mutex<HashMap<i64, u64>>,
+}
+
+impl RoundRobinState {
+    fn next(&self) -> usize {
+        self.counter.fetch_add(1, Ordering::Relaxed) as usize
+    }
+
+    fn record(&self, instance_id: i64) {
+        *self
+            .dispatched
+            .lock()
+            .unwrap()
+            .entry(instance_id)
+            .or_default() += 1;
+    }
+
+    fn stats(&self) -> RoundRobinStats {
+        RoundRobinStats {
+            counter: self.counter.load(Ordering::Relaxed),
+            dispatched: self.dispatched.lock().unwrap().clone(),
+        }
+    }
+
+    /// Swap in fresh state, returning the previous one. The lock is only held for the swap.
+    fn reset(&self) -> RoundRobinStats {
+        let dispatched = std::mem::take(&mut *self.dispatched.lock().unwrap());
+        RoundRobinStats {
+            counter: self.counter.swap(0, Ordering::Relaxed),
+            dispatched,
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub enum RouterMode {
     #[default]
@@ -89,9 +140,13 @@ impl RouterMode {
 }
 
 async fn addressed_router(endpoint: &Endpoint) -> anyhow::Result<Arc<AddressedPushRouter>> {
-    AddressedPushRouter::new(
+    AddressedPushRouter::with_options(
         endpoint.drt().nats_client.client().clone(),
         endpoint.drt().tcp_server().await?,
+        AddressedPushRouterOptions {
+            post_completion_frames: Some(post_completion_frames_counter(endpoint)),
+            ..Default::default()
+        },
     )
 }
 
@@ -125,7 +180,7 @@ where
             client: client.clone(),
             addressed,
             router_mode,
-            round_robin_counter: Arc::new(AtomicU64::new(0)),
+            round_robin: Arc::new(RoundRobinState::default()),
             busy_threshold,
             _phantom: PhantomData,
         };
@@ -135,7 +190,7 @@ where
 
     /// Issue a request to the next available instance in a round-robin fashion
     pub async fn round_robin(&self, request: SingleIn<T>) -> anyhow::Result<ManyOut<U>> {
-        let counter = self.round_robin_counter.fetch_add(1, Ordering::Relaxed) as usize;
+        let counter = self.round_robin.next();
 
         let instance_id = {
             let instance_ids = self.client.instance_ids_avail();
@@ -149,11 +204,23 @@ where
             instance_ids[counter % count]
         };
         tracing::trace!("round robin router selected {instance_id}");
+        self.round_robin.record(instance_id);
 
         self.generate_with_fault_detection(instance_id, request)
             .await
     }
 
+    /// The round robin counter and the number of requests it dispatched to each instance
+    pub fn round_robin_stats(&self) -> RoundRobinStats {
+        self.round_robin.stats()
+    }
+
+    /// Restart round robin from the first instance and clear the per-instance counts, e.g. after
+    /// a config change or to clear a skewed distribution. Returns the state before the reset.
+    pub fn reset_round_robin(&self) -> RoundRobinStats {
+        self.round_robin.reset()
+    }
+
     /// Issue a request to a random endpoint
     pub async fn random(&self, request: SingleIn<T>) -> anyhow::Result<ManyOut<U>> {
         let instance_id = {
@@ -278,3 +345,25 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_robin_state_reset() {
+        let state = RoundRobinState::default();
+        for instance_id in [1, 2, 1] {
+            state.next();
+            state.record(instance_id);
+        }
+
+        let stats = state.stats();
+        assert_eq!(stats.counter, 3);
+        assert_eq!(stats.dispatched, HashMap::from([(1, 2), (2, 1)]));
+
+        assert_eq!(state.reset(), stats);
+        assert_eq!(state.stats(), RoundRobinStats::default());
+        assert_eq!(state.next(), 0);
+    }
+}
@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Server-Sent Events framing for egress response streams.
+//!
+//! [`SseAddressedPushRouter`] wraps an [`AddressedPushRouter`] and re-encodes each decoded response
+//! item as an SSE frame, so an HTTP handler can forward the bytes directly instead of reformatting
+//! the typed stream. Successful items become `data:` frames holding the JSON encoding of the item,
+//! errors become `event: error` frames, and the stream is always terminated by `data: [DONE]`.
+//!
+//! The typed [`AddressedPushRouter`] output remains the default; this is an opt-in adapter.
+
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use futures::StreamExt;
+
+use super::*;
+use crate::pipeline::{AddressedPushRouter, AddressedRequest};
+use crate::{Result, protocols::maybe_error::MaybeError};
+
+/// The SSE data payload which terminates a stream.
+pub const SSE_DONE: &str = "[DONE]";
+
+/// The SSE event name used for error frames.
+pub const SSE_ERROR_EVENT: &str = "error";
+
+/// An [`AddressedPushRouter`] adapter which emits SSE-framed bytes instead of decoded items.
+///
+/// `U` is the response type the remote endpoint produces; it is decoded as usual and then
+/// re-serialized into a single SSE frame per item.
+pub struct SseAddressedPushRouter<U> {
+    inner: Arc<AddressedPushRouter>,
+    _response: PhantomData<fn() -> U>,
+}
+
+impl<U> SseAddressedPushRouter<U> {
+    pub fn new(inner: Arc<AddressedPushRouter>) -> Arc<Self> {
+        Arc::new(Self {
+            inner,
+            _response: PhantomData,
+        })
+    }
+}
+
+#[async_trait]
+impl<T, U> AsyncEngine<SingleIn<AddressedRequest<T>>, ManyOut<Bytes>, Error>
+    for SseAddressedPushRouter<U>
+where
+    T: Data + Serialize,
+    U: Data + Serialize + for<'de> Deserialize<'de> + MaybeError,
+{
+    async fn generate(
+        &self,
+        request: SingleIn<AddressedRequest<T>>,
+    ) -> Result<ManyOut<Bytes>, Error> {
+        let stream = <AddressedPushRouter as AsyncEngine<
+            SingleIn<AddressedRequest<T>>,
+            ManyOut<U>,
+            Error,
+        >>::generate(self.inner.as_ref(), request)
+        .await?;
+        Ok(into_sse_stream(stream))
+    }
+}
+
+/// Convert a typed response stream into SSE frames, appending the `[DONE]` terminator.
+pub fn into_sse_stream<U>(stream: ManyOut<U>) -> ManyOut<Bytes>
+where
+    U: Data + Serialize + MaybeError,
+{
+    let ctx = stream.context();
+    let frames = stream
+        .map(|item| encode_sse_item(&item))
+        .chain(futures::stream::once(async { sse_frame(None, SSE_DONE) }));
+    ResponseStream::new(Box::pin(frames), ctx)
+}
+
+/// Encode a single response item as an SSE frame.
+pub fn encode_sse_item<U: Serialize + MaybeError>(item: &U) -> Bytes {
+    if let Some(err) = item.err() {
+        return sse_frame(Some(SSE_ERROR_EVENT), &err.to_string());
+    }
+    match serde_json::to_string(item) {
+        Ok(json) => sse_frame(None, &json),
+        Err(err) => sse_frame(
+            Some(SSE_ERROR_EVENT),
+            &format!("Failed serializing response: {err}"),
+        ),
+    }
+}
+
+/// Build an SSE frame. Multi-line data is split across multiple `data:` fields per the SSE spec.
+pub fn sse_frame(event: Option<&str>, data: &str) -> Bytes {
+    let mut frame = String::with_capacity(data.len() + 16);
+    if let Some(event) = event {
+        frame.push_str("event: ");
+        frame.push_str(event);
+        frame.push('\n');
+    }
+    for line in data.split('\n') {
+        frame.push_str("data: ");
+        frame.push_str(line.strip_suffix('\r').unwrap_or(line));
+        frame.push('\n');
+    }
+    frame.push('\n');
+    Bytes::from(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Context;
+    use crate::protocols::annotated::Annotated;
+
+    #[test]
+    fn test_sse_frame() {
+        assert_eq!(sse_frame(None, "{\"a\":1}"), "data: {\"a\":1}\n\n");
+        assert_eq!(
+            sse_frame(Some("error"), "line one\r\nline two"),
+            "event: error\ndata: line one\ndata: line two\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_into_sse_stream() {
+        let items = vec![
+            Annotated::from_data("hello".to_string()),
+            Annotated::<String>::from_error("boom".to_string()),
+        ];
+        let ctx = Context::new(()).context();
+        let typed: ManyOut<Annotated<String>> =
+            ResponseStream::new(Box::pin(futures::stream::iter(items)), ctx);
+
+        let frames: Vec<Bytes> = into_sse_stream(typed).collect().await;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], "data: {\"data\":\"hello\"}\n\n");
+        assert_eq!(frames[1], "event: error\ndata: boom\n\n");
+        assert_eq!(frames[2], "data: [DONE]\n\n");
+    }
+}
@@ -150,9 +150,8 @@ where
         });
 
         // decode the control message and the request
-        let msg = TwoPartCodec::default()
-            .decode_message(payload)?
-            .into_message_type();
+        let codec = TwoPartCodec::default();
+        let msg = codec.decode_message(payload)?.into_message_type();
 
         // we must have a header and a body
         // it will be held by this closure as a Some(permit)
@@ -177,6 +176,12 @@ where
                         )));
                     }
                 };
+                let max_size = codec
+                    .max_message_size()
+                    .unwrap_or(MAX_DECOMPRESSED_DATA_SIZE);
+                let data = control_msg
+                    .data_compression
+                    .decompress(data, Some(max_size))?;
                 let request: T = serde_json::from_slice(&data)?;
                 (control_msg, request)
             }
@@ -25,8 +25,8 @@ use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)]
 use super::{
-    ConnectionInfo, PendingConnections, RegisteredStream, ResponseService, StreamOptions,
-    StreamReceiver, StreamSender, StreamType, codec::TwoPartCodec,
+    ConnectionInfo, PendingConnections, RegisteredStream, ResponseDelivery, ResponseService,
+    StreamOptions, StreamReceiver, StreamReconnect, StreamSender, StreamType, codec::TwoPartCodec,
 };
 
 const TCP_TRANSPORT: &str = "tcp_server";
@@ -37,6 +37,12 @@ pub struct TcpStreamConnectionInfo {
     pub subject: String,
     pub context: String,
     pub stream_type: StreamType,
+    /// Absent when sent by an older router, which always expects low-latency delivery
+    #[serde(default)]
+    pub delivery: ResponseDelivery,
+    /// Absent when the stream fails on a disconnect, and when sent by an older router
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnect: Option<StreamReconnect>,
 }
 
 impl From<TcpStreamConnectionInfo> for ConnectionInfo {
@@ -78,14 +84,35 @@ impl TryFrom<ConnectionInfo> for TcpStreamConnectionInfo {
 struct CallHomeHandshake {
     subject: String,
     stream_type: StreamType,
+    /// Set when the socket connects again a stream which lost its previous socket
+    #[serde(default)]
+    resume: bool,
+}
+
+/// Reply of the server to a resuming [`CallHomeHandshake`]: the sequence of the first data frame
+/// the server did not receive, from which the client replays the frames it kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResumeFrom {
+    sequence: u64,
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures::StreamExt;
+
     use crate::engine::AsyncEngineContextProvider;
 
     use super::*;
-    use crate::pipeline::Context;
+    use crate::pipeline::network::NetworkStreamWrapper;
+    use crate::pipeline::network::egress::addressed_router::{
+        decode_response_frames, response_frames, unregistered_post_completion_frames,
+    };
+    use crate::pipeline::{Context, PostCompletionPolicy};
+    use crate::protocols::annotated::Annotated;
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     struct TestMessage {
@@ -109,7 +136,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let pending_connection = server.register(options).await;
+        let pending_connection = server.register(options).await.unwrap();
 
         let connection_info = pending_connection
             .recv_stream
@@ -174,4 +201,165 @@ mod tests {
 
         // assert!(data.is_none());
     }
+
+    /// Forwards connections to a target address, so that a test can drop them like a network
+    /// fault would
+    struct TcpProxy {
+        address: String,
+        accept_task: tokio::task::JoinHandle<()>,
+        connections: Arc<Mutex<Vec<tokio::task::AbortHandle>>>,
+    }
+
+    impl TcpProxy {
+        async fn start(target: String) -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let connections = Arc::new(Mutex::new(Vec::new()));
+            let accepted = connections.clone();
+            let accept_task = tokio::spawn(async move {
+                while let Ok((mut inbound, _)) = listener.accept().await {
+                    let Ok(mut outbound) = tokio::net::TcpStream::connect(&target).await else {
+                        continue;
+                    };
+                    let forward = tokio::spawn(async move {
+                        let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                    });
+                    accepted.lock().unwrap().push(forward.abort_handle());
+                }
+            });
+            Self {
+                address,
+                accept_task,
+                connections,
+            }
+        }
+
+        /// Close both ends of every forwarded connection
+        fn drop_connections(&self) {
+            for connection in self.connections.lock().unwrap().drain(..) {
+                connection.abort();
+            }
+        }
+
+        /// Stop accepting connections, then drop the forwarded ones
+        fn shutdown(&self) {
+            self.accept_task.abort();
+            self.drop_connections();
+        }
+    }
+
+    /// Register a resumable response stream, and connect a worker to it through a proxy
+    async fn connect_through_proxy(
+        reconnect: StreamReconnect,
+    ) -> (
+        Arc<server::TcpStreamServer>,
+        TcpProxy,
+        StreamSender,
+        StreamReceiver,
+        Context<()>,
+    ) {
+        let server = server::TcpStreamServer::new(server::ServerOptions::default())
+            .await
+            .unwrap();
+        let context = Context::new(());
+        let options = StreamOptions::builder()
+            .context(context.context())
+            .enable_request_stream(false)
+            .enable_response_stream(true)
+            .reconnect(Some(reconnect))
+            .build()
+            .unwrap();
+        let pending_connection = server.register(options).await.unwrap();
+        let (connection_info, stream_provider) =
+            pending_connection.recv_stream.unwrap().into_parts();
+
+        let mut info = TcpStreamConnectionInfo::try_from(connection_info).unwrap();
+        let proxy = TcpProxy::start(info.address.clone()).await;
+        info.address = proxy.address.clone();
+
+        let mut send_stream =
+            client::TcpClient::create_response_stream(context.context(), info.into())
+                .await
+                .unwrap();
+        send_stream.send_prologue(None).await.unwrap();
+        let recv_stream = stream_provider.await.unwrap().unwrap();
+
+        (server, proxy, send_stream, recv_stream, context)
+    }
+
+    #[tokio::test]
+    async fn test_response_stream_resumes_after_disconnect() {
+        let (_server, proxy, send_stream, mut recv_stream, _context) =
+            connect_through_proxy(StreamReconnect {
+                buffer_count: 64,
+                timeout_ms: 5_000,
+            })
+            .await;
+        let frame = |i: u32| Bytes::from(i.to_string());
+
+        for i in 0..10 {
+            send_stream.send(frame(i)).await.unwrap();
+        }
+        for i in 0..10 {
+            assert_eq!(recv_stream.rx.recv().await.unwrap(), frame(i));
+        }
+
+        // The worker only notices the drop on a write after the first one following it, which
+        // may still succeed; the frames written meanwhile are replayed
+        proxy.drop_connections();
+        for i in 10..20 {
+            send_stream.send(frame(i)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        drop(send_stream);
+
+        let mut resumed = Vec::new();
+        while let Some(data) = tokio::time::timeout(Duration::from_secs(10), recv_stream.rx.recv())
+            .await
+            .expect("the stream should resume and complete")
+        {
+            resumed.push(data);
+        }
+        assert_eq!(resumed, (10..20).map(frame).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_response_stream_fails_when_worker_does_not_reconnect() {
+        let (_server, proxy, send_stream, recv_stream, context) =
+            connect_through_proxy(StreamReconnect {
+                buffer_count: 64,
+                timeout_ms: 200,
+            })
+            .await;
+
+        for data in ["a", "b"] {
+            let wrapper = NetworkStreamWrapper {
+                data: Some(Annotated::from_data(data.to_string())),
+                complete_final: false,
+            };
+            send_stream
+                .send(serde_json::to_vec(&wrapper).unwrap().into())
+                .await
+                .unwrap();
+        }
+        let mut items = Box::pin(decode_response_frames::<Annotated<String>>(
+            response_frames(recv_stream.rx, None),
+            context.context(),
+            PostCompletionPolicy::Lenient,
+            unregistered_post_completion_frames(),
+        ));
+        for data in ["a", "b"] {
+            assert_eq!(items.next().await.unwrap().data.as_deref(), Some(data));
+        }
+
+        // The worker can no longer reach the server, so the stream is not resumed
+        proxy.shutdown();
+        let item = tokio::time::timeout(Duration::from_secs(10), items.next())
+            .await
+            .expect("the stream should fail once the reconnect timeout elapses")
+            .unwrap();
+        assert!(item.is_error());
+        assert!(items.next().await.is_none());
+        drop(send_stream);
+    }
 }
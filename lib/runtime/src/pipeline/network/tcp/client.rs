@@ -1,6 +1,7 @@
 // SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 use futures::{SinkExt, StreamExt};
@@ -12,10 +13,10 @@ use tokio::{
 };
 use tokio_util::codec::{FramedRead, FramedWrite};
 
-use super::{CallHomeHandshake, ControlMessage, TcpStreamConnectionInfo};
-use crate::engine::AsyncEngineContext;
+use super::{CallHomeHandshake, ControlMessage, ResumeFrom, TcpStreamConnectionInfo};
+use crate::engine::{AsyncEngineContext, CancellationReason};
 use crate::pipeline::network::{
-    ConnectionInfo, ResponseStreamPrologue, StreamSender,
+    ConnectionInfo, ResponseDelivery, ResponseStreamPrologue, StreamSender,
     codec::{TwoPartCodec, TwoPartMessage},
     tcp::StreamType,
 };
@@ -39,13 +40,13 @@ impl TcpClient {
         TcpClient { worker_id }
     }
 
-    async fn connect(address: &str) -> std::io::Result<TcpStream> {
+    async fn connect(address: &str, nodelay: bool) -> std::io::Result<TcpStream> {
         // try to connect to the address; retry with linear backoff if AddrNotAvailable
         let backoff = std::time::Duration::from_millis(200);
         loop {
             match TcpStream::connect(address).await {
                 Ok(socket) => {
-                    socket.set_nodelay(true)?;
+                    socket.set_nodelay(nodelay)?;
                     return Ok(socket);
                 }
                 Err(e) => {
@@ -83,25 +84,17 @@ impl TcpClient {
             ));
         }
 
-        let stream = TcpClient::connect(&info.address).await?;
+        let stream = TcpClient::connect(&info.address, info.delivery.nodelay()).await?;
         let (read_half, write_half) = tokio::io::split(stream);
 
         let framed_reader = FramedRead::new(read_half, TwoPartCodec::default());
         let mut framed_writer = FramedWrite::new(write_half, TwoPartCodec::default());
 
-        // this is a oneshot channel that will be used to signal when the stream is closed
-        // when the stream sender is dropped, the bytes_rx will be closed and the forwarder task will exit
-        // the forwarder task will capture the alive_rx half of the oneshot channel; this will close the alive channel
-        // so the holder of the alive_tx half will be notified that the stream is closed; the alive_tx channel will be
-        // captured by the monitor task
-        let (alive_tx, alive_rx) = tokio::sync::oneshot::channel::<()>();
-
-        let reader_task = tokio::spawn(handle_reader(framed_reader, context.clone(), alive_tx));
-
         // transport specific handshake message
         let handshake = CallHomeHandshake {
-            subject: info.subject,
+            subject: info.subject.clone(),
             stream_type: StreamType::Response,
+            resume: false,
         };
 
         let handshake_bytes = match serde_json::to_vec(&handshake) {
@@ -123,55 +116,13 @@ impl TcpClient {
         // set up the channel to send bytes to the transport layer
         let (bytes_tx, bytes_rx) = tokio::sync::mpsc::channel(64);
 
-        // forwards the bytes send from this stream to the transport layer; hold the alive_rx half of the oneshot channel
-
-        let writer_task = tokio::spawn(handle_writer(framed_writer, bytes_rx, alive_rx, context));
-
-        tokio::spawn(async move {
-            // await both tasks
-            let (reader, writer) = tokio::join!(reader_task, writer_task);
-
-            match (reader, writer) {
-                (Ok(reader), Ok(writer)) => {
-                    let reader = reader.into_inner();
-
-                    let writer = match writer {
-                        Ok(writer) => writer.into_inner(),
-                        Err(e) => {
-                            tracing::error!("failed to join writer task: {:?}", e);
-                            return Err(e);
-                        }
-                    };
-
-                    let mut stream = reader.unsplit(writer);
-
-                    // await the tcp server to shutdown the socket connection
-                    // set a timeout for the server shutdown
-                    let mut buf = vec![0u8; 1024];
-                    let deadline = Instant::now() + Duration::from_secs(10);
-                    loop {
-                        let n = time::timeout_at(deadline, stream.read(&mut buf))
-                            .await
-                            .inspect_err(|_| {
-                                tracing::debug!("server did not close socket within the deadline");
-                            })?
-                            .inspect_err(|e| {
-                                tracing::debug!("failed to read from stream: {:?}", e);
-                            })?;
-                        if n == 0 {
-                            // Server has closed (FIN)
-                            break;
-                        }
-                    }
-
-                    Ok(())
-                }
-                _ => {
-                    tracing::error!("failed to join reader and writer tasks");
-                    anyhow::bail!("failed to join reader and writer tasks");
-                }
-            }
-        });
+        tokio::spawn(drive_response_stream(
+            framed_reader,
+            framed_writer,
+            bytes_rx,
+            context,
+            info,
+        ));
 
         // set up the prologue for the stream
         // this might have transport specific metadata in the future
@@ -187,11 +138,225 @@ impl TcpClient {
     }
 }
 
+type FramedReader = FramedRead<ReadHalf<TcpStream>, TwoPartCodec>;
+type FramedWriter = FramedWrite<WriteHalf<TcpStream>, TwoPartCodec>;
+
+/// Interval between two attempts to connect again a response stream which lost its socket
+const RECONNECT_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Write the frames of a response stream until it ends, then await the server closing the socket.
+/// If the stream may reconnect and its socket drops, connect again and replay the frames the
+/// server did not receive.
+async fn drive_response_stream(
+    mut framed_reader: FramedReader,
+    mut framed_writer: FramedWriter,
+    mut bytes_rx: tokio::sync::mpsc::Receiver<TwoPartMessage>,
+    context: Arc<dyn AsyncEngineContext>,
+    info: TcpStreamConnectionInfo,
+) -> Result<()> {
+    let mut replay = info
+        .reconnect
+        .map(|reconnect| ReplayBuffer::new(reconnect.buffer_count));
+
+    let (reader, writer) = loop {
+        // this is a oneshot channel that will be used to signal when the stream is closed
+        // when the stream sender is dropped, the bytes_rx will be closed and the forwarder task will exit
+        // the forwarder task will capture the alive_rx half of the oneshot channel; this will close the alive channel
+        // so the holder of the alive_tx half will be notified that the stream is closed; the alive_tx channel will be
+        // captured by the monitor task
+        let (alive_tx, alive_rx) = tokio::sync::oneshot::channel::<()>();
+
+        let reader_task = tokio::spawn(handle_reader(
+            framed_reader,
+            context.clone(),
+            alive_tx,
+            replay.is_some(),
+        ));
+
+        // forwards the bytes send from this stream to the transport layer; hold the alive_rx half of the oneshot channel
+        let writer = handle_writer(
+            framed_writer,
+            &mut bytes_rx,
+            alive_rx,
+            context.clone(),
+            info.delivery,
+            replay.as_mut(),
+        )
+        .await;
+
+        match (writer, &replay, info.reconnect) {
+            (Ok(Some(writer)), _, _) => match reader_task.await {
+                Ok(reader) => break (reader, writer),
+                Err(e) => {
+                    tracing::error!("failed to join reader task: {:?}", e);
+                    anyhow::bail!("failed to join reader and writer tasks");
+                }
+            },
+            (Ok(None), Some(replay), Some(reconnect)) => {
+                // the reader of the dropped socket is of no use anymore
+                reader_task.abort();
+                let deadline = Instant::now() + Duration::from_millis(reconnect.timeout_ms);
+                (framed_reader, framed_writer) = loop {
+                    match resume_response_stream(&info, replay).await {
+                        Ok(connection) => break connection,
+                        Err(e) if Instant::now() < deadline => {
+                            tracing::debug!(
+                                request_id = context.id(),
+                                "failed to resume the response stream: {e}; retrying"
+                            );
+                            time::sleep(RECONNECT_BACKOFF).await;
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                request_id = context.id(),
+                                "failed to resume the response stream: {e}"
+                            );
+                            return Err(e);
+                        }
+                    }
+                };
+            }
+            (Ok(None), _, _) => unreachable!("only a resumable stream drops its socket"),
+            (Err(e), _, _) => {
+                tracing::error!("failed to write the response stream: {:?}", e);
+                return Err(e);
+            }
+        }
+    };
+
+    let mut stream = reader.into_inner().unsplit(writer.into_inner());
+
+    // await the tcp server to shutdown the socket connection
+    // set a timeout for the server shutdown
+    let mut buf = vec![0u8; 1024];
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let n = time::timeout_at(deadline, stream.read(&mut buf))
+            .await
+            .inspect_err(|_| {
+                tracing::debug!("server did not close socket within the deadline");
+            })?
+            .inspect_err(|e| {
+                tracing::debug!("failed to read from stream: {:?}", e);
+            })?;
+        if n == 0 {
+            // Server has closed (FIN)
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Connect again to the server of a response stream which lost its socket, and replay the
+/// frames the server did not receive
+async fn resume_response_stream(
+    info: &TcpStreamConnectionInfo,
+    replay: &ReplayBuffer,
+) -> Result<(FramedReader, FramedWriter)> {
+    let stream = TcpClient::connect(&info.address, info.delivery.nodelay()).await?;
+    let (read_half, write_half) = tokio::io::split(stream);
+
+    let mut framed_reader = FramedRead::new(read_half, TwoPartCodec::default());
+    let mut framed_writer = FramedWrite::new(write_half, TwoPartCodec::default());
+
+    let handshake = CallHomeHandshake {
+        subject: info.subject.clone(),
+        stream_type: StreamType::Response,
+        resume: true,
+    };
+    let handshake_bytes = serde_json::to_vec(&handshake)?;
+    framed_writer
+        .send(TwoPartMessage::from_header(handshake_bytes.into()))
+        .await
+        .map_err(|e| error!("failed to send handshake: {:?}", e))?;
+
+    // the server replies with the sequence to resume from, or closes the socket if the stream
+    // is not awaiting a reconnect
+    let reply = framed_reader
+        .next()
+        .await
+        .ok_or(error!("Connection closed without a ResumeFrom"))??;
+    let resume: ResumeFrom = match reply.header() {
+        Some(header) => serde_json::from_slice(header)
+            .map_err(|e| error!("Failed to deserialize ResumeFrom: {e}"))?,
+        None => return Err(error!("Expected ResumeFrom, got DataMessage")),
+    };
+
+    let frames = replay.since(resume.sequence).ok_or(error!(
+        "cannot resume the response stream from frame {}; {} frames were sent and only the last {} are kept",
+        resume.sequence,
+        replay.sent,
+        replay.frames.len()
+    ))?;
+    for frame in frames {
+        framed_writer
+            .feed(frame.clone())
+            .await
+            .map_err(|e| error!("failed to replay frame: {:?}", e))?;
+    }
+    framed_writer
+        .flush()
+        .await
+        .map_err(|e| error!("failed to replay frames: {:?}", e))?;
+
+    tracing::debug!(
+        sequence = resume.sequence,
+        sent = replay.sent,
+        "response stream resumed on a new connection"
+    );
+    Ok((framed_reader, framed_writer))
+}
+
+/// The most recent data frames sent on a response stream which may reconnect, numbered in the
+/// order they were sent
+struct ReplayBuffer {
+    frames: VecDeque<TwoPartMessage>,
+    capacity: usize,
+    /// Number of data frames sent so far, i.e. the sequence of the next one
+    sent: u64,
+}
+
+impl ReplayBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            sent: 0,
+        }
+    }
+
+    /// Keep `msg` if it is a data frame, evicting the oldest frame once full. Header-only
+    /// messages are not numbered, matching the frames the server counts.
+    fn record(&mut self, msg: &TwoPartMessage) {
+        if msg.data().is_none() {
+            return;
+        }
+        self.frames.push_back(msg.clone());
+        if self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+        self.sent += 1;
+    }
+
+    /// The frames from `sequence` on, or None if some of them were already evicted
+    fn since(&self, sequence: u64) -> Option<impl Iterator<Item = &TwoPartMessage>> {
+        let first = self.sent - self.frames.len() as u64;
+        if sequence < first || sequence > self.sent {
+            return None;
+        }
+        Some(self.frames.iter().skip((sequence - first) as usize))
+    }
+}
+
+/// Apply the control messages of the server until the writer is done. Unless `resumable`, a
+/// socket error is fatal.
 async fn handle_reader(
-    framed_reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
+    framed_reader: FramedReader,
     context: Arc<dyn AsyncEngineContext>,
     alive_tx: tokio::sync::oneshot::Sender<()>,
-) -> FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec> {
+    resumable: bool,
+) -> FramedReader {
     let mut framed_reader = framed_reader;
     let mut alive_tx = alive_tx;
     loop {
@@ -211,10 +376,14 @@ async fn handle_reader(
 
                                 match msg {
                                     ControlMessage::Stop => {
-                                        context.stop();
+                                        context.stop_generating_with_reason(
+                                            CancellationReason::UpstreamAborted,
+                                        );
                                     }
                                     ControlMessage::Kill => {
-                                        context.kill();
+                                        context.kill_with_reason(
+                                            CancellationReason::UpstreamAborted,
+                                        );
                                     }
                                     ControlMessage::Sentinel => {
                                         // TODO(#171) - address fatal errors
@@ -227,6 +396,10 @@ async fn handle_reader(
                            }
                         }
                     }
+                    Some(Err(e)) if resumable => {
+                        tracing::debug!("tcp stream failed: {e}; the writer will reconnect");
+                        break;
+                    }
                     Some(Err(_)) => {
                         // TODO(#171) - address fatal errors
                         // in this case the binary representation of the message is invalid
@@ -246,12 +419,16 @@ async fn handle_reader(
     framed_reader
 }
 
+/// Write the frames of `bytes_rx` to the socket, then the sentinel. Returns None if the socket
+/// dropped and `replay` is set, i.e. the stream may resume on a new connection.
 async fn handle_writer(
-    mut framed_writer: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
-    mut bytes_rx: tokio::sync::mpsc::Receiver<TwoPartMessage>,
+    mut framed_writer: FramedWriter,
+    bytes_rx: &mut tokio::sync::mpsc::Receiver<TwoPartMessage>,
     alive_rx: tokio::sync::oneshot::Receiver<()>,
     context: Arc<dyn AsyncEngineContext>,
-) -> Result<FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>> {
+    delivery: ResponseDelivery,
+    mut replay: Option<&mut ReplayBuffer>,
+) -> Result<Option<FramedWriter>> {
     loop {
         let msg = tokio::select! {
             biased;
@@ -277,11 +454,26 @@ async fn handle_writer(
             }
         };
 
-        if let Err(e) = framed_writer.send(msg).await {
+        if let Some(replay) = replay.as_deref_mut() {
+            replay.record(&msg);
+        }
+
+        let written = match delivery {
+            ResponseDelivery::LowLatency => framed_writer.send(msg).await,
+            // Only flush once the queued frames are written, letting them share packets
+            ResponseDelivery::Throughput => match framed_writer.feed(msg).await {
+                Ok(()) if bytes_rx.is_empty() => framed_writer.flush().await,
+                result => result,
+            },
+        };
+        if let Err(e) = written {
             tracing::trace!(
                 "failed to send message to network; possible disconnect: {:?}",
                 e
             );
+            if replay.is_some() {
+                return Ok(None);
+            }
             break;
         }
     }
@@ -289,8 +481,43 @@ async fn handle_writer(
     // send sentinel message
     let message = serde_json::to_vec(&ControlMessage::Sentinel)?;
     let msg = TwoPartMessage::from_header(message.into());
-    framed_writer.send(msg).await?;
+    if let Err(e) = framed_writer.send(msg).await {
+        if replay.is_some() {
+            return Ok(None);
+        }
+        return Err(e.into());
+    }
 
     drop(alive_rx);
-    Ok(framed_writer)
+    Ok(Some(framed_writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_buffer() {
+        let mut replay = ReplayBuffer::new(2);
+        let frame = |i: u8| TwoPartMessage::from_data(vec![i].into());
+
+        // header-only messages, e.g. the prologue, are not numbered
+        replay.record(&TwoPartMessage::from_header(b"{}".to_vec().into()));
+        for i in 0..3 {
+            replay.record(&frame(i));
+        }
+        assert_eq!(replay.sent, 3);
+
+        let since = |sequence| {
+            replay
+                .since(sequence)
+                .map(|frames| frames.map(|f| f.data[0]).collect::<Vec<_>>())
+        };
+        assert_eq!(since(1), Some(vec![1, 2]));
+        assert_eq!(since(2), Some(vec![2]));
+        assert_eq!(since(3), Some(vec![]));
+        // frame 0 was evicted, and frame 4 was never sent
+        assert_eq!(since(0), None);
+        assert_eq!(since(4), None);
+    }
 }
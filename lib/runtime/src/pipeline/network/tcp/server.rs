@@ -44,8 +44,9 @@ use tokio::{
 use tokio_util::codec::{FramedRead, FramedWrite};
 
 use super::{
-    CallHomeHandshake, ControlMessage, PendingConnections, RegisteredStream, StreamOptions,
-    StreamReceiver, StreamSender, TcpStreamConnectionInfo, TwoPartCodec,
+    CallHomeHandshake, ControlMessage, PendingConnections, RegisteredStream, ResumeFrom,
+    StreamOptions, StreamReceiver, StreamReconnect, StreamSender, TcpStreamConnectionInfo,
+    TwoPartCodec,
 };
 use crate::engine::AsyncEngineContext;
 use crate::pipeline::{
@@ -100,8 +101,18 @@ struct RequestedSendConnection {
 struct RequestedRecvConnection {
     context: Arc<dyn AsyncEngineContext>,
     connection: oneshot::Sender<Result<StreamReceiver, String>>,
+    buffer_count: usize,
+    reconnect: Option<StreamReconnect>,
 }
 
+type FramedConnection = (
+    FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
+    FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
+);
+
+/// How long the response buffer may stay full before the consumer is reported as slow.
+const SLOW_CONSUMER_THRESHOLD: time::Duration = time::Duration::from_secs(1);
+
 // /// When registering a new TcpStream on the server, the registration method will return a [`Connections`] object.
 // /// This [`Connections`] object will have two [`oneshot::Receiver`] objects, one for the [`TcpStreamSender`] and one for the [`TcpStreamReceiver`].
 // /// The [`Connections`] object can be awaited to get the [`TcpStreamSender`] and [`TcpStreamReceiver`] objects; these objects will
@@ -122,6 +133,8 @@ struct RequestedRecvConnection {
 struct State {
     tx_subjects: HashMap<String, RequestedSendConnection>,
     rx_subjects: HashMap<String, RequestedRecvConnection>,
+    /// Response streams which lost their socket, awaiting the worker to connect again
+    resuming: HashMap<String, oneshot::Sender<FramedConnection>>,
     handle: Option<tokio::task::JoinHandle<Result<()>>>,
 }
 
@@ -223,12 +236,28 @@ impl ResponseService for TcpStreamServer {
     ///
     /// the registration probably needs to be done in one-go, so we should use a builder object for
     /// requesting a receiver and optional sender
-    async fn register(&self, options: StreamOptions) -> PendingConnections {
+    ///
+    /// Fails with [`PipelineError::RegistrationFailed`] once the listener has stopped, since no
+    /// connection could ever complete the registered streams.
+    async fn register(&self, options: StreamOptions) -> Result<PendingConnections, PipelineError> {
         // oneshot channels to pass back the sender and receiver objects
 
         let address = format!("{}:{}", self.local_ip, self.local_port);
         tracing::debug!("Registering new TcpStream on {}", address);
 
+        let listening = self
+            .state
+            .lock()
+            .await
+            .handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished());
+        if !listening {
+            return Err(PipelineError::RegistrationFailed(format!(
+                "TcpStreamServer on {address} is no longer accepting connections"
+            )));
+        }
+
         let send_stream = if options.enable_request_stream {
             let sender_subject = uuid::Uuid::new_v4().to_string();
 
@@ -250,6 +279,8 @@ impl ResponseService for TcpStreamServer {
                     subject: sender_subject.clone(),
                     context: options.context.id().to_string(),
                     stream_type: StreamType::Request,
+                    delivery: options.response_delivery,
+                    reconnect: options.reconnect,
                 }
                 .into(),
                 stream_provider: pending_sender_rx,
@@ -267,6 +298,8 @@ impl ResponseService for TcpStreamServer {
             let connection_info = RequestedRecvConnection {
                 context: options.context.clone(),
                 connection: pending_recver_tx,
+                buffer_count: options.recv_buffer_count.max(1),
+                reconnect: options.reconnect,
             };
 
             let mut state = self.state.lock().await;
@@ -280,6 +313,8 @@ impl ResponseService for TcpStreamServer {
                     subject: receiver_subject.clone(),
                     context: options.context.id().to_string(),
                     stream_type: StreamType::Response,
+                    delivery: options.response_delivery,
+                    reconnect: options.reconnect,
                 }
                 .into(),
                 stream_provider: pending_recver_rx,
@@ -290,10 +325,10 @@ impl ResponseService for TcpStreamServer {
             None
         };
 
-        PendingConnections {
+        Ok(PendingConnections {
             send_stream,
             recv_stream,
-        }
+        })
     }
 }
 
@@ -411,6 +446,9 @@ async fn tcp_listener(
         // branch here to handle sender stream or receiver stream
         match handshake.stream_type {
             StreamType::Request => process_request_stream().await,
+            StreamType::Response if handshake.resume => {
+                resume_response_stream(handshake.subject, state, framed_reader, framed_writer).await
+            }
             StreamType::Response => {
                 process_response_stream(handshake.subject, state, framed_reader, framed_writer)
                     .await
@@ -426,7 +464,7 @@ async fn tcp_listener(
         subject: String,
         state: Arc<Mutex<State>>,
         mut reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
-        writer: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
+        mut writer: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
     ) -> Result<()> {
         let response_stream = state
             .lock().await
@@ -438,6 +476,8 @@ async fn tcp_listener(
         let RequestedRecvConnection {
             context,
             connection,
+            buffer_count,
+            reconnect,
         } = response_stream;
 
         // the [`Prologue`]
@@ -470,8 +510,7 @@ async fn tcp_listener(
             return Err(error!("Received error prologue: {}", error));
         }
 
-        // we need to know the buffer size from the registration options; add this to the RequestRecvConnection object
-        let (response_tx, response_rx) = mpsc::channel(64);
+        let (response_tx, response_rx) = mpsc::channel(buffer_count);
 
         if connection
             .send(Ok(crate::pipeline::network::StreamReceiver {
@@ -484,36 +523,126 @@ async fn tcp_listener(
             ));
         }
 
-        let (control_tx, control_rx) = mpsc::channel::<ControlMessage>(1);
-
-        // sender task
-        // issues control messages to the sender and when finished shuts down the socket
-        // this should be the last task to finish and must
-        let send_task = tokio::spawn(network_send_handler(writer, control_rx));
+        let mut response_tx = response_tx;
+        let mut received = 0;
+        loop {
+            let (control_tx, control_rx) = mpsc::channel::<ControlMessage>(1);
+
+            // sender task
+            // issues control messages to the sender and when finished shuts down the socket
+            // this should be the last task to finish and must
+            let send_task = tokio::spawn(network_send_handler(writer, control_rx));
+
+            // forward task
+            let recv_task = tokio::spawn(network_receive_handler(
+                reader,
+                response_tx,
+                control_tx,
+                context.clone(),
+                received,
+                reconnect.is_some(),
+            ));
 
-        // forward task
-        let recv_task = tokio::spawn(network_receive_handler(
-            reader,
-            response_tx,
-            control_tx,
-            context.clone(),
-        ));
+            // check the results of each of the tasks
+            let (monitor_result, forward_result) = tokio::join!(send_task, recv_task);
+
+            monitor_result?;
+            let (
+                ConnectionEnd::Dropped {
+                    response_tx: dropped_tx,
+                    received: dropped_received,
+                },
+                Some(reconnect),
+            ) = (forward_result?, reconnect)
+            else {
+                return Ok(());
+            };
+            response_tx = dropped_tx;
+            received = dropped_received;
+
+            (reader, writer) = await_reconnect(
+                &subject,
+                &state,
+                reconnect,
+                &response_tx,
+                &context,
+                received,
+            )
+            .await?;
+        }
+    }
 
-        // check the results of each of the tasks
-        let (monitor_result, forward_result) = tokio::join!(send_task, recv_task);
+    /// Hand the socket of a worker connecting again to the response stream awaiting it
+    async fn resume_response_stream(
+        subject: String,
+        state: Arc<Mutex<State>>,
+        reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
+        writer: FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, TwoPartCodec>,
+    ) -> Result<()> {
+        let resume = state.lock().await.resuming.remove(&subject).ok_or(error!(
+            "Subject not found: {subject}; no response stream awaits a reconnect"
+        ))?;
+        resume
+            .send((reader, writer))
+            .map_err(|_| error!("Response stream {subject} stopped awaiting a reconnect"))
+    }
 
-        monitor_result?;
-        forward_result?;
+    /// Wait for the worker of a response stream which lost its socket to connect again, then
+    /// tell it to resume from the `received` frame. Fails if the worker does not connect again
+    /// within the timeout, or if the request is killed or its consumer goes away meanwhile.
+    async fn await_reconnect(
+        subject: &str,
+        state: &Mutex<State>,
+        reconnect: StreamReconnect,
+        response_tx: &mpsc::Sender<Bytes>,
+        context: &Arc<dyn AsyncEngineContext>,
+        received: u64,
+    ) -> Result<FramedConnection> {
+        let (resume_tx, resume_rx) = oneshot::channel();
+        state
+            .lock()
+            .await
+            .resuming
+            .insert(subject.to_string(), resume_tx);
+
+        let timeout = time::Duration::from_millis(reconnect.timeout_ms);
+        let connection = tokio::select! {
+            connection = resume_rx => connection.ok(),
+            _ = time::sleep(timeout) => None,
+            _ = context.killed() => None,
+            _ = response_tx.closed() => None,
+        };
+        state.lock().await.resuming.remove(subject);
 
-        Ok(())
+        let Some((reader, mut writer)) = connection else {
+            return Err(error!(
+                "Response stream {subject} was not resumed; the worker did not connect again within {timeout:?}"
+            ));
+        };
+        let resume = serde_json::to_vec(&ResumeFrom { sequence: received })?;
+        writer
+            .send(TwoPartMessage::from_header(resume.into()))
+            .await
+            .map_err(|e| error!("failed to send the resume sequence: {e}"))?;
+        tracing::debug!(
+            request_id = context.id(),
+            received,
+            "response stream resumed on a new connection"
+        );
+        Ok((reader, writer))
     }
 
+    /// Forward the frames of one connection of a response stream. `received` counts the data
+    /// frames forwarded over the previous connections of the stream. Unless `resumable`, a socket
+    /// closing before the sentinel ends the stream as if it completed.
     async fn network_receive_handler(
         mut framed_reader: FramedRead<tokio::io::ReadHalf<tokio::net::TcpStream>, TwoPartCodec>,
         response_tx: mpsc::Sender<Bytes>,
         control_tx: mpsc::Sender<ControlMessage>,
         context: Arc<dyn AsyncEngineContext>,
-    ) {
+        mut received: u64,
+        resumable: bool,
+    ) -> ConnectionEnd {
         // loop over reading the tcp stream and checking if the writer is closed
         let mut can_stop = true;
         loop {
@@ -559,17 +688,27 @@ async fn tcp_listener(
                                 }
                             }
 
-                            if !data.is_empty()
-                                && let Err(err) = response_tx.send(data).await {
+                            if !data.is_empty() {
+                                if let Err(err) = forward_response(&response_tx, data, context.id()).await {
                                     tracing::debug!("forwarding body/data message to response channel failed: {}", err);
                                     control_tx.send(ControlMessage::Kill).await.expect("the control channel should not be closed");
                                     break;
-                                };
+                                }
+                                received += 1;
+                            }
+                        }
+                        Some(Err(err)) if resumable => {
+                            tracing::debug!(request_id = context.id(), received, "tcp stream failed: {err}; awaiting a reconnect");
+                            return ConnectionEnd::Dropped { response_tx, received };
                         }
                         Some(Err(_)) => {
                             // TODO(#171) - address fatal errors
                             panic!("invalid message issued over socket; this should never happen");
                         }
+                        None if resumable => {
+                            tracing::debug!(request_id = context.id(), received, "tcp stream was closed by client before the sentinel; awaiting a reconnect");
+                            return ConnectionEnd::Dropped { response_tx, received };
+                        }
                         None => {
                             // this is allowed but we try to avoid it
                             // the logic is that the client will tell us when its is done and the server
@@ -584,6 +723,39 @@ async fn tcp_listener(
 
             }
         }
+        ConnectionEnd::Closed
+    }
+
+    /// Forward a response frame to the consumer, reporting consumers which keep the response
+    /// buffer full for longer than [`SLOW_CONSUMER_THRESHOLD`]: in that case the worker is
+    /// producing faster than the client reads, rather than the worker being slow.
+    async fn forward_response(
+        response_tx: &mpsc::Sender<Bytes>,
+        data: Bytes,
+        request_id: &str,
+    ) -> Result<(), mpsc::error::SendError<Bytes>> {
+        let data = match response_tx.try_send(data) {
+            Ok(()) => return Ok(()),
+            Err(mpsc::error::TrySendError::Closed(data)) => {
+                return Err(mpsc::error::SendError(data));
+            }
+            Err(mpsc::error::TrySendError::Full(data)) => data,
+        };
+
+        let start = time::Instant::now();
+        let result = response_tx.send(data).await;
+        let blocked = start.elapsed();
+        if blocked >= SLOW_CONSUMER_THRESHOLD {
+            tracing::warn!(
+                request_id,
+                blocked_ms = blocked.as_millis() as u64,
+                buffer_count = response_tx.max_capacity(),
+                "response buffer stayed full; the consumer is reading responses slower than the worker produces them"
+            );
+        } else {
+            tracing::trace!(request_id, "response buffer full, waited {blocked:?}");
+        }
+        result
     }
 
     async fn network_send_handler(
@@ -620,6 +792,18 @@ async fn tcp_listener(
     }
 }
 
+/// How a connection of a response stream ended
+enum ConnectionEnd {
+    /// The stream completed, or was aborted
+    Closed,
+    /// The socket of a resumable stream dropped before the sentinel; the stream goes on over the
+    /// next connection of the worker, from the `received` frame
+    Dropped {
+        response_tx: mpsc::Sender<Bytes>,
+        received: u64,
+    },
+}
+
 enum ControlAction {
     Continue,
     Shutdown,
@@ -647,6 +831,7 @@ mod tests {
     use super::*;
     use crate::engine::AsyncEngineContextProvider;
     use crate::pipeline::Context;
+    use crate::pipeline::network::ResponseDelivery;
 
     // Mock resolver that always fails to simulate the fallback scenario
     struct FailingIpResolver;
@@ -684,7 +869,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let pending_connection = server.register(stream_options).await;
+        let pending_connection = server.register(stream_options).await.unwrap();
 
         // Verify connection info is available and valid
         let connection_info = pending_connection
@@ -696,6 +881,7 @@ mod tests {
 
         let tcp_info: TcpStreamConnectionInfo = connection_info.try_into().unwrap();
         let socket_addr = tcp_info.address.parse::<std::net::SocketAddr>().unwrap();
+        assert_eq!(tcp_info.delivery, ResponseDelivery::LowLatency);
 
         // Should have a valid port assigned
         assert!(
@@ -734,7 +920,7 @@ mod tests {
             .build()
             .unwrap();
 
-        let pending_connection = server.register(stream_options).await;
+        let pending_connection = server.register(stream_options).await.unwrap();
         let connection_info = pending_connection
             .recv_stream
             .as_ref()
@@ -765,4 +951,37 @@ mod tests {
         // The server should work with the fallback IP
         assert!(socket_addr.port() > 0, "Server should have a valid port");
     }
+
+    #[tokio::test]
+    async fn test_register_fails_once_listener_stopped() {
+        let server = TcpStreamServer::new(ServerOptions::default())
+            .await
+            .unwrap();
+
+        // Stop the listener, as a server shutting down would
+        let listener = server
+            .state
+            .lock()
+            .await
+            .handle
+            .as_ref()
+            .unwrap()
+            .abort_handle();
+        listener.abort();
+        while !listener.is_finished() {
+            tokio::task::yield_now().await;
+        }
+
+        let context = Context::new(());
+        let stream_options = StreamOptions::builder()
+            .context(context.context())
+            .enable_request_stream(false)
+            .enable_response_stream(true)
+            .build()
+            .unwrap();
+
+        let result = server.register(stream_options).await;
+        assert!(matches!(result, Err(PipelineError::RegistrationFailed(_))));
+        assert!(server.state.lock().await.rx_subjects.is_empty());
+    }
 }
@@ -221,6 +221,15 @@ impl Client {
         let binary_data = bincode::serialize(data)
             .map_err(|e| anyhow::anyhow!("Failed to serialize data with bincode: {e}"))?;
 
+        self.object_store_upload_bytes(binary_data, nats_url).await
+    }
+
+    /// Upload raw bytes to NATS object store, e.g. data serialized by the caller
+    pub async fn object_store_upload_bytes(
+        &self,
+        binary_data: Vec<u8>,
+        nats_url: &Url,
+    ) -> anyhow::Result<()> {
         let (bucket_name, key) = url_to_bucket_and_key(nats_url)?;
         let bucket = self.get_or_create_bucket(&bucket_name, true).await?;
 
@@ -243,6 +252,17 @@ impl Client {
     where
         T: DeserializeOwned,
     {
+        let buffer = self.object_store_download_bytes(nats_url).await?;
+
+        // Deserialize from bincode
+        let data = bincode::deserialize(&buffer)
+            .map_err(|e| anyhow::anyhow!("Failed to deserialize data with bincode: {e}"))?;
+
+        Ok(data)
+    }
+
+    /// Download the raw bytes of an object from NATS object store
+    pub async fn object_store_download_bytes(&self, nats_url: &Url) -> anyhow::Result<Vec<u8>> {
         let (bucket_name, key) = url_to_bucket_and_key(nats_url)?;
         let bucket = self.get_or_create_bucket(&bucket_name, false).await?;
 
@@ -259,11 +279,7 @@ impl Client {
             .map_err(|e| anyhow::anyhow!("Failed reading object data: {e}"))?;
         tracing::debug!("Downloaded {} bytes from {bucket_name}/{key}", buffer.len());
 
-        // Deserialize from bincode
-        let data = bincode::deserialize(&buffer)
-            .map_err(|e| anyhow::anyhow!("Failed to deserialize data with bincode: {e}"))?;
-
-        Ok(data)
+        Ok(buffer)
     }
 }
 
@@ -561,7 +577,18 @@ impl NatsQueue {
                     ..Default::default()
                 };
 
-                let subscriber = stream.create_consumer(consumer_config).await?;
+                // Reuse the durable consumer if the server still has it, so consumption resumes
+                // from its last acknowledged message
+                let subscriber = match stream.get_consumer(consumer_name).await {
+                    Ok(subscriber) => subscriber,
+                    Err(e) => {
+                        log::info!(
+                            "Creating durable consumer {consumer_name} on stream {} ({e})",
+                            self.stream_name
+                        );
+                        stream.create_consumer(consumer_config).await?
+                    }
+                };
                 self.subscriber = Some(subscriber);
             }
 
@@ -579,6 +606,14 @@ impl NatsQueue {
         Ok(())
     }
 
+    /// Drop the current connection and connect again without resetting the stream, e.g. after a
+    /// NATS server restart. The durable consumer is looked up by name so its position is kept; it
+    /// is only recreated if the server lost it.
+    pub async fn reconnect(&mut self) -> Result<()> {
+        self.close().await?;
+        self.connect().await
+    }
+
     /// Close the connection when done
     pub async fn close(&mut self) -> Result<()> {
         self.subscriber = None;